@@ -3,13 +3,13 @@ use std::str::FromStr;
 use lb::{
     blocking::Lb,
     model::api::{
-        AccountFilter, AccountIdentifier, AdminSetUserTierInfo, AppStoreAccountState,
+        AccountFilter, AccountIdentifier, AccountState, AdminSetUserTierInfo, AppStoreAccountState,
         GooglePlayAccountState, StripeAccountState,
     },
 };
 use libsecp256k1::PublicKey;
 
-use crate::{error::Error, Res, SetUserTier};
+use crate::{error::Error, CliAccountState, Res, SetUserTier};
 
 pub fn list(
     lb: &Lb, premium: bool, app_store_premium: bool, google_play_premium: bool,
@@ -119,3 +119,21 @@ pub fn set_user_tier(lb: &Lb, premium_info: SetUserTier) -> Res<()> {
 
     Ok(())
 }
+
+pub fn set_data_cap(lb: &Lb, username: Option<String>, cap: Option<u64>) -> Res<()> {
+    lb.admin_set_data_cap(username.as_deref(), cap)?;
+
+    Ok(())
+}
+
+pub fn set_state(lb: &Lb, state: CliAccountState) -> Res<()> {
+    let (username, state) = match state {
+        CliAccountState::Active { username } => (username, AccountState::Active),
+        CliAccountState::ReadOnly { username } => (username, AccountState::ReadOnly),
+        CliAccountState::Suspended { username } => (username, AccountState::Suspended),
+    };
+    lb.admin_set_account_state(&username, state)?;
+    println!("{username} is now {state:?}");
+
+    Ok(())
+}
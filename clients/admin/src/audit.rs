@@ -0,0 +1,61 @@
+use std::thread;
+use std::time::{Duration as StdDuration, SystemTime, UNIX_EPOCH};
+
+use basic_human_duration::ChronoHumanDuration;
+use lb::blocking::Lb;
+use lb::model::api::{AdminGetAuditLogRequest, AuditEntry, AuditOutcome};
+use time::Duration;
+
+use crate::Res;
+
+pub fn log(
+    core: &Lb, actor: Option<String>, action: Option<String>, target: Option<String>, follow: bool,
+) -> Res<()> {
+    let mut filter = AdminGetAuditLogRequest { actor, action, target, ..Default::default() };
+
+    loop {
+        let entries = core.admin_get_audit_log(filter.clone())?;
+        for entry in &entries {
+            print_entry(entry);
+        }
+        if let Some(last) = entries.last() {
+            filter.after = Some(last.seq);
+        }
+
+        if !follow {
+            return Ok(());
+        }
+        // only wait once caught up, there may be more than a page to print
+        if entries.is_empty() {
+            thread::sleep(StdDuration::from_secs(2));
+        }
+    }
+}
+
+fn print_entry(entry: &AuditEntry) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64;
+    let ago = Duration::milliseconds(now - entry.timestamp as i64).format_human();
+    let actor = match &entry.actor_username {
+        Some(username) => username.clone(),
+        None => base64::encode(entry.actor.serialize_compressed()),
+    };
+    let outcome = match &entry.outcome {
+        AuditOutcome::Succeeded => "succeeded".to_string(),
+        AuditOutcome::Rejected(err) => format!("rejected: {err}"),
+        AuditOutcome::Failed(err) => format!("failed: {err}"),
+    };
+
+    println!(
+        "{}\t{}\t{}\t{}\t{}\t{}",
+        entry.seq,
+        ago,
+        actor,
+        entry.action,
+        entry.target.as_deref().unwrap_or("-"),
+        outcome
+    );
+    println!("\t{}", entry.details);
+}
@@ -1,8 +1,10 @@
 mod account;
+mod audit;
 mod disappear;
 mod error;
 mod indexes;
 mod info;
+mod repair;
 mod validate;
 
 use clap::{Parser, Subcommand};
@@ -13,6 +15,7 @@ use lb::Uuid;
 
 use crate::error::Error;
 use crate::indexes::CliIndex;
+use crate::repair::CliRepair;
 
 #[derive(Debug, PartialEq, Eq, Parser)]
 pub enum Admin {
@@ -62,12 +65,67 @@ pub enum Admin {
     #[command(subcommand)]
     RebuildIndex(CliIndex),
 
+    /// Fixes problems found by validating the server and prints what changed
+    Repair {
+        #[command(subcommand)]
+        repair: CliRepair,
+
+        /// Print what would change without changing anything
+        #[structopt(long)]
+        dry_run: bool,
+    },
+
     /// Prints information about a file as it appears on the server
     FileInfo { id: Uuid },
 
     /// Manually set a user's tier and their subscription information
     #[command(subcommand)]
     SetUserTier(SetUserTier),
+
+    /// Set how many bytes a user may store, or the default for every user if no username is given.
+    /// Without a cap, the override is removed.
+    SetDataCap {
+        #[structopt(short, long)]
+        username: Option<String>,
+
+        #[structopt(short, long)]
+        cap: Option<u64>,
+    },
+
+    /// Restrict a user's account without deleting anything, or lift the restriction
+    #[command(subcommand)]
+    SetAccountState(CliAccountState),
+
+    /// Print the journal of admin actions and account deletions, oldest first
+    AuditLog {
+        /// Only actions taken by this user
+        #[structopt(long)]
+        actor: Option<String>,
+
+        /// Only requests to this route, like admin-disappear-file
+        #[structopt(long)]
+        action: Option<String>,
+
+        /// Only actions aimed at this username or file id
+        #[structopt(long)]
+        target: Option<String>,
+
+        /// Keep printing new entries as they're journaled
+        #[structopt(short, long)]
+        follow: bool,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum CliAccountState {
+    /// No restrictions
+    Active { username: String },
+
+    /// The user can download their files but not change them
+    ReadOnly { username: String },
+
+    /// The user can only download their files
+    Suspended { username: String },
 }
 
 #[derive(Debug, PartialEq, Eq, Subcommand)]
@@ -119,7 +177,13 @@ pub fn main() {
         Admin::ValidateServer => validate::server(&core),
         Admin::FileInfo { id } => info::file(&core, id),
         Admin::RebuildIndex(index) => indexes::rebuild(&core, index),
+        Admin::Repair { repair, dry_run } => repair::server(&core, repair, dry_run),
         Admin::SetUserTier(info) => account::set_user_tier(&core, info),
+        Admin::SetDataCap { username, cap } => account::set_data_cap(&core, username, cap),
+        Admin::SetAccountState(state) => account::set_state(&core, state),
+        Admin::AuditLog { actor, action, target, follow } => {
+            audit::log(&core, actor, action, target, follow)
+        }
     };
 
     if result.is_err() {
@@ -0,0 +1,47 @@
+use crate::Res;
+use clap::Subcommand;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::Confirm;
+use lb::blocking::Lb;
+use lb::model::api::{AdminRepairServer, ServerRepair};
+
+#[derive(Debug, PartialEq, Eq, Subcommand)]
+pub enum CliRepair {
+    /// Set each document's size from its stored contents
+    Sizes,
+    /// Delete stored contents that no file refers to
+    OrphanedDocuments,
+    /// Bring owned_files, shared_files, and file_children in line with the file metadata
+    Indexes,
+    /// Disappear links to files their owner can no longer access
+    BrokenShares,
+}
+
+pub fn server(lb: &Lb, repair: CliRepair, dry_run: bool) -> Res<()> {
+    let repair = match repair {
+        CliRepair::Sizes => ServerRepair::Sizes,
+        CliRepair::OrphanedDocuments => ServerRepair::OrphanedDocuments,
+        CliRepair::Indexes => ServerRepair::Indexes,
+        CliRepair::BrokenShares => ServerRepair::BrokenShares,
+    };
+
+    if !dry_run {
+        let maybe_confirm = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Are you sure you want to repair {:?}?", repair))
+            .interact_opt()?;
+        if !maybe_confirm.unwrap_or(false) {
+            return Ok(());
+        }
+    }
+
+    let changes = lb.admin_repair_server(repair, dry_run)?;
+    if changes == AdminRepairServer::default() {
+        println!("Nothing to repair");
+    } else if dry_run {
+        println!("Would change: {:#?}", changes);
+    } else {
+        println!("Changed: {:#?}", changes);
+    }
+
+    Ok(())
+}
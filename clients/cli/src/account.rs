@@ -1,30 +1,36 @@
-use std::{io, str::FromStr};
-
-use cli_rs::cli_error::{CliError, CliResult};
+use std::{io, path::PathBuf, str::FromStr};
 
 use is_terminal::IsTerminal;
 use lb_rs::model::{
-    api::{PaymentMethod, PaymentPlatform, StripeAccountTier},
+    api::{PaymentMethod, PaymentPlatform, StripeAccountTier, SubscriptionInfo},
     work_unit::WorkUnit,
 };
+use serde::Serialize;
 
-use crate::{core, ensure_account, input};
+use crate::{
+    core, ensure_account,
+    error::{CmdErr, CmdResult},
+    input,
+    output::{self, Format},
+};
 
 #[tokio::main]
-pub async fn new(username: String, api_url: ApiUrl) -> CliResult<()> {
+pub async fn new(username: String, api_url: ApiUrl) -> CmdResult<()> {
     let lb = core().await?;
-    println!("generating keys and checking for username availability...");
-    lb.create_account(&username, &api_url.0, true).await?;
-    println!("account created!");
+    output::status("generating keys and checking for username availability...");
+    let account = lb.create_account(&username, &api_url.0, true).await?;
+    output::print(&AccountInfo { username: account.username, api_url: account.api_url }, |_| {
+        println!("account created!")
+    });
 
     Ok(())
 }
 
 #[tokio::main]
-pub async fn import() -> CliResult<()> {
+pub async fn import() -> CmdResult<()> {
     let lb = &core().await?;
     if io::stdin().is_terminal() {
-        return Err(CliError::from("to import an existing lockbook account, pipe your account string into this command, e.g.:\npbpaste | lockbook account import".to_string()));
+        return Err(CmdErr::from("to import an existing lockbook account, pipe your account string into this command, e.g.:\npbpaste | lockbook account import".to_string()));
     }
 
     let mut account_string = String::new();
@@ -33,22 +39,30 @@ pub async fn import() -> CliResult<()> {
         .expect("failed to read from stdin");
     account_string.retain(|c| !c.is_whitespace());
 
-    println!("importing account...");
-    lb.import_account(&account_string, None).await?;
+    output::status("importing account...");
+    let account = lb.import_account(&account_string, None).await?;
 
-    println!("account imported! next, try to sync by running: lockbook sync");
+    output::print(&AccountInfo { username: account.username, api_url: account.api_url }, |_| {
+        println!("account imported! next, try to sync by running: lockbook sync")
+    });
 
     Ok(())
 }
 
 #[tokio::main]
-pub async fn export(skip_check: bool) -> CliResult<()> {
+pub async fn export(skip_check: bool) -> CmdResult<()> {
     let lb = &core().await?;
     ensure_account(lb)?;
 
     let should_ask = !skip_check;
     let mut should_show = false;
 
+    if should_ask && output::format() != Format::Human {
+        return Err(CmdErr::from(
+            "pass --skip-check to reveal the private key without being asked",
+        ));
+    }
+
     if should_ask {
         let answer: String = input::std_in(
             "your private key is about to be visible. do you want to proceed? [y/n]: ",
@@ -61,17 +75,61 @@ pub async fn export(skip_check: bool) -> CliResult<()> {
     }
 
     if should_show {
-        println!("{}", lb.export_account_private_key()?);
+        let account_string = lb.export_account_private_key()?;
+        output::print(&AccountString { account_string }, |key| println!("{}", key.account_string));
     }
 
     Ok(())
 }
 
 #[tokio::main]
-pub async fn subscribe() -> Result<(), CliError> {
+pub async fn takeout(dest: PathBuf) -> CmdResult<()> {
     let lb = &core().await?;
     ensure_account(lb)?;
 
+    output::status("downloading your account...");
+    let summary = lb.takeout(&dest).await?;
+    output::print(&summary, |summary| {
+        println!(
+            "wrote {} files and {} documents to {}",
+            summary.files,
+            summary.documents,
+            dest.display()
+        )
+    });
+
+    Ok(())
+}
+
+#[tokio::main]
+pub async fn restore(archive: PathBuf) -> CmdResult<()> {
+    let lb = &core().await?;
+    if io::stdin().is_terminal() {
+        return Err(CmdErr::from("to restore from a takeout archive, pipe your account string into this command, e.g.:\npbpaste | lockbook account restore archive".to_string()));
+    }
+
+    let mut account_string = String::new();
+    io::stdin()
+        .read_line(&mut account_string)
+        .expect("failed to read from stdin");
+    account_string.retain(|c| !c.is_whitespace());
+
+    output::status("restoring account...");
+    let account = lb.import_takeout(&account_string, &archive).await?;
+
+    output::print(&AccountInfo { username: account.username, api_url: account.api_url }, |_| {
+        println!("account restored! your files are available offline, run lockbook sync to catch up with the server")
+    });
+
+    Ok(())
+}
+
+#[tokio::main]
+pub async fn subscribe() -> CmdResult<()> {
+    let lb = &core().await?;
+    ensure_account(lb)?;
+    output::interactive("subscribe")?;
+
     println!("checking for existing payment methods...");
     let existing_card =
         lb.get_subscription_info()
@@ -110,9 +168,10 @@ pub async fn subscribe() -> Result<(), CliError> {
 }
 
 #[tokio::main]
-pub async fn unsubscribe() -> Result<(), CliError> {
+pub async fn unsubscribe() -> CmdResult<()> {
     let lb = &core().await?;
     ensure_account(lb)?;
+    output::interactive("unsubscribe")?;
 
     let answer: String =
         input::std_in("are you sure you would like to cancel your subscription? [y/n]: ")?;
@@ -124,12 +183,11 @@ pub async fn unsubscribe() -> Result<(), CliError> {
 }
 
 #[tokio::main]
-pub async fn status() -> Result<(), CliError> {
+pub async fn status() -> CmdResult<()> {
     let lb = &core().await?;
     ensure_account(lb)?;
 
     let last_synced = lb.get_last_synced_human().await?;
-    println!("files last synced: {last_synced}");
 
     let lb_status = lb.calculate_work().await?;
     let local = lb_status
@@ -148,34 +206,73 @@ pub async fn status() -> Result<(), CliError> {
             WorkUnit::LocalChange(_) => None,
         })
         .count();
-    println!("files ready to push: {local}");
-    println!("files ready to pull: {server}");
 
     let cap = lb.get_usage().await?;
     let pct = (cap.server_usage.exact * 100) / cap.data_cap.exact;
 
-    if let Some(info) = lb.get_subscription_info().await? {
-        match info.payment_platform {
-            PaymentPlatform::Stripe { card_last_4_digits } => {
-                println!("type: Stripe, *{}", card_last_4_digits)
-            }
-            PaymentPlatform::GooglePlay { account_state } => {
-                println!("type: Google Play");
-                println!("state: {:?}", account_state);
-            }
-            PaymentPlatform::AppStore { account_state } => {
-                println!("type: App Store");
-                println!("state: {:?}", account_state);
+    let status = AccountStatus {
+        username: lb.get_account()?.username.clone(),
+        last_synced,
+        ready_to_push: local,
+        ready_to_pull: server,
+        subscription: lb.get_subscription_info().await?,
+        server_usage: cap.server_usage.exact,
+        data_cap: cap.data_cap.exact,
+    };
+
+    output::print(&status, |status| {
+        println!("files last synced: {}", status.last_synced);
+        println!("files ready to push: {}", status.ready_to_push);
+        println!("files ready to pull: {}", status.ready_to_pull);
+
+        if let Some(info) = &status.subscription {
+            match &info.payment_platform {
+                PaymentPlatform::Stripe { card_last_4_digits } => {
+                    println!("type: Stripe, *{}", card_last_4_digits)
+                }
+                PaymentPlatform::GooglePlay { account_state } => {
+                    println!("type: Google Play");
+                    println!("state: {:?}", account_state);
+                }
+                PaymentPlatform::AppStore { account_state } => {
+                    println!("type: App Store");
+                    println!("state: {:?}", account_state);
+                }
             }
+            println!("renews on: {}", info.period_end);
+        } else {
+            println!("trial tier");
         }
-        println!("renews on: {}", info.period_end);
-    } else {
-        println!("trial tier");
-    }
-    println!("data cap: {}, {}% utilized", cap.data_cap.readable, pct);
+        println!("data cap: {}, {}% utilized", cap.data_cap.readable, pct);
+    });
     Ok(())
 }
 
+#[derive(Serialize)]
+struct AccountInfo {
+    username: String,
+    api_url: String,
+}
+
+#[derive(Serialize)]
+struct AccountString {
+    account_string: String,
+}
+
+#[derive(Serialize)]
+struct AccountStatus {
+    username: String,
+    last_synced: String,
+    ready_to_push: usize,
+    ready_to_pull: usize,
+    /// absent on the trial tier
+    subscription: Option<SubscriptionInfo>,
+    /// bytes
+    server_usage: u64,
+    /// bytes
+    data_cap: u64,
+}
+
 #[derive(Clone)]
 pub struct ApiUrl(String);
 
@@ -1,59 +1,83 @@
-use cli_rs::cli_error::{CliError, CliResult};
+use serde::Serialize;
 
-use crate::{core, ensure_account, input::FileInput};
+use crate::{
+    core, ensure_account,
+    error::{CmdErr, CmdResult},
+    input::FileInput,
+    output::{self, FileInfo},
+};
 
 #[tokio::main]
-pub async fn validate() -> CliResult<()> {
+pub async fn validate() -> CmdResult<()> {
     let lb = core().await?;
     ensure_account(&lb)?;
 
     let warnings = lb
         .test_repo_integrity()
         .await
-        .map_err(|err| CliError::from(format!("validating: {:?}", err)))?;
+        .map_err(|err| CmdErr { msg: format!("validating: {:?}", err), kind: Some(err.kind) })?;
     if warnings.is_empty() {
         return Ok(());
     }
-    for w in &warnings {
-        eprintln!("{:#?}", w);
-    }
-    Err(CliError::from(format!("{} warnings found", warnings.len())))
+    output::print(&warnings, |warnings| {
+        for w in warnings {
+            eprintln!("{:#?}", w);
+        }
+    });
+    Err(CmdErr::from(format!("{} warnings found", warnings.len())))
 }
 
 #[tokio::main]
-pub async fn info(target: FileInput) -> Result<(), CliError> {
+pub async fn info(target: FileInput) -> CmdResult<()> {
     let lb = &core().await?;
     ensure_account(lb)?;
 
     let f = target.find(lb).await?;
-    println!("{:#?}", f);
+    output::print(&FileInfo::new(lb, &f).await?, |_| println!("{:#?}", f));
     Ok(())
 }
 
 #[tokio::main]
-pub async fn whoami() -> Result<(), CliError> {
+pub async fn whoami() -> CmdResult<()> {
     let lb = &core().await?;
     ensure_account(lb)?;
 
-    println!("{}", lb.get_account()?.username);
+    let username = lb.get_account()?.username.clone();
+    output::print(&WhoAmI { username }, |whoami| println!("{}", whoami.username));
     Ok(())
 }
 
 #[tokio::main]
-pub async fn whereami() -> Result<(), CliError> {
+pub async fn whereami() -> CmdResult<()> {
     let lb = &core().await?;
     ensure_account(lb)?;
 
     let account = lb.get_account()?;
     let config = &lb.config;
-    println!("Server: {}", account.api_url);
-    println!("Core: {}", config.writeable_path);
+    let whereami =
+        WhereAmI { server: account.api_url.clone(), core: config.writeable_path.clone() };
+    output::print(&whereami, |whereami| {
+        println!("Server: {}", whereami.server);
+        println!("Core: {}", whereami.core);
+    });
     Ok(())
 }
 
 #[tokio::main]
-pub async fn debug_info() -> Result<(), CliError> {
+pub async fn debug_info() -> CmdResult<()> {
     let lb = &core().await?;
+    // already json
     println!("{}", lb.debug_info("None Provided".to_string()).await?);
     Ok(())
 }
+
+#[derive(Serialize)]
+struct WhoAmI {
+    username: String,
+}
+
+#[derive(Serialize)]
+struct WhereAmI {
+    server: String,
+    core: String,
+}
@@ -6,19 +6,22 @@ use std::{
     str::FromStr,
 };
 
-use cli_rs::{
-    cli_error::{CliError, CliResult},
-    flag::Flag,
-};
+use cli_rs::{cli_error::CliResult, flag::Flag};
 use hotwatch::{Event, EventKind, Hotwatch};
 use lb_rs::{Lb, Uuid};
 
-use crate::{core, ensure_account_and_root, input::FileInput};
+use crate::{
+    core, ensure_account_and_root,
+    error::{CmdErr, CmdResult},
+    input::FileInput,
+    output,
+};
 
 #[tokio::main]
-pub async fn edit(editor: Editor, target: FileInput) -> CliResult<()> {
+pub async fn edit(editor: Editor, target: FileInput) -> CmdResult<()> {
     let lb = &core().await?;
     ensure_account_and_root(lb).await?;
+    output::interactive("edit")?;
 
     let f = target.find(lb).await?;
 
@@ -28,7 +31,7 @@ pub async fn edit(editor: Editor, target: FileInput) -> CliResult<()> {
     temp_file_path.push(f.name);
 
     let mut file_handle = fs::File::create(&temp_file_path).map_err(|err| {
-        CliError::from(format!("couldn't open temporary file for writing: {:#?}", err))
+        CmdErr::from(format!("couldn't open temporary file for writing: {:#?}", err))
     })?;
     file_handle.write_all(&file_content)?;
     file_handle.sync_all()?;
@@ -55,11 +58,11 @@ pub async fn edit(editor: Editor, target: FileInput) -> CliResult<()> {
     Ok(())
 }
 
-fn create_tmp_dir() -> Result<PathBuf, CliError> {
+fn create_tmp_dir() -> CmdResult<PathBuf> {
     let mut dir = std::env::temp_dir();
     dir.push(Uuid::new_v4().to_string());
     fs::create_dir(&dir).map_err(|err| {
-        CliError::from(format!("couldn't open temporary file for writing: {:#?}", err))
+        CmdErr::from(format!("couldn't open temporary file for writing: {:#?}", err))
     })?;
     Ok(dir)
 }
@@ -207,12 +210,10 @@ fn set_up_auto_save<P: AsRef<Path>>(core: &Lb, id: Uuid, path: P) -> Option<Hotw
     }
 }
 
-async fn save_temp_file_contents<P: AsRef<Path>>(
-    lb: Lb, id: Uuid, path: P,
-) -> Result<(), CliError> {
+async fn save_temp_file_contents<P: AsRef<Path>>(lb: Lb, id: Uuid, path: P) -> CmdResult<()> {
     let secret = fs::read_to_string(&path)
         .map_err(|err| {
-            CliError::from(format!(
+            CmdErr::from(format!(
                 "could not read from temporary file, not deleting {}, err: {:#?}",
                 path.as_ref().display(),
                 err
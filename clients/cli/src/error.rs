@@ -0,0 +1,139 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    io,
+};
+
+use lb_rs::model::errors::{LbErr, LbErrKind};
+
+pub type CmdResult<T> = Result<T, CmdErr>;
+
+/// Why a command failed. Unlike a [cli_rs::cli_error::CliError] it remembers the [LbErrKind] when
+/// lb is the reason, which `--json` reports and the exit code is derived from.
+#[derive(Debug)]
+pub struct CmdErr {
+    pub msg: String,
+    pub kind: Option<LbErrKind>,
+}
+
+impl CmdErr {
+    /// An lb error with a friendlier message than lb's
+    pub fn lb(kind: LbErrKind, msg: impl Into<String>) -> Self {
+        Self { msg: msg.into(), kind: Some(kind) }
+    }
+
+    /// The variant name of the lb error kind, e.g. `FileNonexistent`
+    pub fn kind_name(&self) -> Option<String> {
+        let kind = format!("{:?}", self.kind.as_ref()?);
+        Some(
+            kind.split(['(', ' ', '{'])
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+        )
+    }
+
+    /// The process exits with one of these, grouped so scripts can branch on what went wrong
+    /// without knowing every kind. docs/guides/cli-scripting.md lists them for users.
+    pub fn exit_code(&self) -> i32 {
+        use LbErrKind::*;
+
+        match &self.kind {
+            None | Some(Unexpected(_)) => 1,
+            Some(AccountNonexistent | RootNonexistent) => 3,
+            Some(
+                FileNonexistent
+                | FileParentNonexistent
+                | ShareNonexistent
+                | PublicationNonexistent
+                | ConflictNonexistent
+                | DocumentVersionNonexistent
+                | UsernameNotFound,
+            ) => 4,
+            Some(
+                AccountExists
+                | DiskPathTaken
+                | ShareAlreadyExists
+                | UsernameTaken
+                | ReReadRequired
+                | AlreadySyncing
+                | ExistingRequestPending,
+            ) => 5,
+            Some(
+                AccountStringCorrupted
+                | DiskPathInvalid
+                | DrawingInvalid
+                | FileNameContainsSlash
+                | FileNameTooLong
+                | FileNameEmpty
+                | FileNotDocument
+                | KeyPhraseInvalid
+                | PathContainsEmptyFileName
+                | RootModificationInvalid
+                | TakeoutCorrupted
+                | UsernameInvalid
+                | UsernamePublicKeyMismatch
+                | InvalidAuthDetails
+                | Validation(_)
+                | Diff(_),
+            ) => 6,
+            Some(InsufficientPermission | AccountReadOnly | AccountSuspended) => 7,
+            Some(ServerUnreachable | ServerDisabled | RateLimited | TryAgain) => 8,
+            Some(ClientUpdateRequired) => 9,
+            Some(
+                UsageIsOverDataCap
+                | UsageIsOverFreeTierDataCap
+                | PublicationTooLarge
+                | TooManyPublications,
+            ) => 10,
+            Some(
+                AlreadyCanceled
+                | AlreadyPremium
+                | AppStoreAccountAlreadyLinked
+                | CannotCancelSubscriptionForAppStore
+                | CardDecline
+                | CardExpired
+                | CardInsufficientFunds
+                | CardInvalidCvc
+                | CardInvalidExpMonth
+                | CardInvalidExpYear
+                | CardInvalidNumber
+                | CardNotSupported
+                | CurrentUsageIsMoreThanNewTier
+                | InvalidPurchaseToken
+                | NotPremium
+                | OldCardDoesNotExist,
+            ) => 11,
+            Some(Sign(_) | Crypto(_)) => 12,
+        }
+    }
+}
+
+impl Display for CmdErr {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+impl From<LbErr> for CmdErr {
+    fn from(err: LbErr) -> Self {
+        Self { msg: err.to_string(), kind: Some(err.kind) }
+    }
+}
+
+impl From<String> for CmdErr {
+    fn from(msg: String) -> Self {
+        Self { msg, kind: None }
+    }
+}
+
+impl From<&str> for CmdErr {
+    fn from(msg: &str) -> Self {
+        Self::from(msg.to_string())
+    }
+}
+
+impl From<io::Error> for CmdErr {
+    fn from(err: io::Error) -> Self {
+        Self::from(err.to_string())
+    }
+}
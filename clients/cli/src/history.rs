@@ -0,0 +1,61 @@
+use serde::Serialize;
+
+use crate::{
+    core, ensure_account_and_root,
+    error::{CmdErr, CmdResult},
+    input::FileInput,
+    output::{self, FileInfo},
+};
+
+#[tokio::main]
+pub async fn log(target: FileInput) -> CmdResult<()> {
+    let lb = &core().await?;
+    ensure_account_and_root(lb).await?;
+
+    let id = target.find(lb).await?.id;
+    let history = lb.get_document_history(id).await?;
+    output::print(&history, |history| {
+        for v in history.iter().rev() {
+            let when = lb.get_timestamp_human_string(v.last_modified as i64);
+            let unsynced = if v.synced { "" } else { "  (unsynced)" };
+            println!("{:>4}  {}  {when}{unsynced}", v.version, v.last_modified_by);
+        }
+    });
+    Ok(())
+}
+
+/// `version` is 0 when the flag wasn't passed, versions count from 1
+#[tokio::main]
+pub async fn diff(version: usize, target: FileInput) -> CmdResult<()> {
+    let lb = &core().await?;
+    ensure_account_and_root(lb).await?;
+
+    let id = target.find(lb).await?.id;
+    let version = if version == 0 { None } else { Some(version) };
+    let diff = lb.diff_document(id, version).await?;
+    output::print(&Diff { diff }, |diff| print!("{}", diff.diff));
+    Ok(())
+}
+
+#[tokio::main]
+pub async fn restore(version: usize, target: FileInput) -> CmdResult<()> {
+    let lb = &core().await?;
+    ensure_account_and_root(lb).await?;
+
+    if version == 0 {
+        return Err(CmdErr::from("pass the --version to restore, see lockbook log"));
+    }
+
+    let id = target.find(lb).await?.id;
+    lb.restore_document_version(id, version).await?;
+
+    let restored = lb.get_file_by_id(id).await?;
+    output::print(&FileInfo::new(lb, &restored).await?, |_| {});
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Diff {
+    /// unified diff, empty when nothing changed
+    diff: String,
+}
@@ -1,17 +1,34 @@
 use std::{
-    cell::Cell,
+    cell::{Cell, RefCell},
     fs,
     io::{self, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
 };
 
-use cli_rs::cli_error::CliResult;
-use lb_rs::service::import_export::ImportStatus;
+use hotwatch::{Event, Hotwatch};
+use lb_rs::{
+    service::{
+        import_export::{ExportFileInfo, ImportStatus},
+        importers::ImportFormat,
+        mirror::{MirrorReport, MIRROR_STATE_FILE},
+        render::Theme,
+    },
+    Lb, Uuid,
+};
+use serde::Serialize;
+use tokio::sync::mpsc;
 
-use crate::{core, ensure_account_and_root, input::FileInput};
+use crate::{
+    core, ensure_account_and_root,
+    error::{CmdErr, CmdResult},
+    input::FileInput,
+    output::{self, Format},
+};
 
 #[tokio::main]
-pub async fn copy(disk: PathBuf, parent: FileInput) -> CliResult<()> {
+pub async fn copy(disk: PathBuf, parent: FileInput) -> CmdResult<()> {
     let lb = &core().await?;
     ensure_account_and_root(lb).await?;
 
@@ -19,42 +36,300 @@ pub async fn copy(disk: PathBuf, parent: FileInput) -> CliResult<()> {
 
     let total = Cell::new(0);
     let nth_file = Cell::new(0);
-    let update_status = move |status: ImportStatus| match status {
+    let current = RefCell::new(String::new());
+    let imported = RefCell::new(vec![]);
+    let update_status = |status: ImportStatus| match status {
         ImportStatus::CalculatedTotal(n_files) => total.set(n_files),
         ImportStatus::StartingItem(disk_path) => {
             nth_file.set(nth_file.get() + 1);
-            print!("({}/{}) importing: {}... ", nth_file.get(), total.get(), disk_path);
-            io::stdout().flush().unwrap();
+            if output::format() == Format::Human {
+                print!("({}/{}) importing: {}... ", nth_file.get(), total.get(), disk_path);
+                io::stdout().flush().unwrap();
+            }
+            *current.borrow_mut() = disk_path;
+        }
+        ImportStatus::FinishedItem(meta) => {
+            let item = Imported { disk_path: current.take(), id: meta.id };
+            output::update(&item, |_| println!("done."));
+            imported.borrow_mut().push(item);
         }
-        ImportStatus::FinishedItem(_meta) => println!("done."),
+        // only reported by importers for other apps' formats
+        ImportStatus::Unconverted(_) => {}
     };
 
     lb.import_files(&[disk], parent, &update_status).await?;
 
+    output::print(&imported.into_inner(), |_| {});
     Ok(())
 }
 
+/// `--from`, which cli-rs needs a default for even though the import can't go ahead without it
+#[derive(Clone, Copy, Default)]
+pub struct ImportFrom(Option<ImportFormat>);
+
+impl FromStr for ImportFrom {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(Some(s.parse()?)))
+    }
+}
+
 #[tokio::main]
-pub async fn export(target: FileInput, dest: PathBuf) -> CliResult<()> {
+pub async fn import(from: ImportFrom, disk: PathBuf, parent: FileInput) -> CmdResult<()> {
+    let lb = &core().await?;
+    ensure_account_and_root(lb).await?;
+
+    let Some(format) = from.0 else {
+        return Err(CmdErr::from("--from is required: obsidian, notion, evernote or bear"));
+    };
+    let parent = parent.find(lb).await?.id;
+
+    let total = Cell::new(0);
+    let nth_file = Cell::new(0usize);
+    let folder = Cell::new(None);
+    let unconverted = RefCell::new(vec![]);
+    let update_status = |status: ImportStatus| match status {
+        ImportStatus::CalculatedTotal(n_files) => total.set(n_files),
+        ImportStatus::StartingItem(path) => {
+            nth_file.set(nth_file.get() + 1);
+            if output::format() == Format::Human {
+                print!("({}/{}) importing: {}... ", nth_file.get(), total.get(), path);
+                io::stdout().flush().unwrap();
+            }
+        }
+        ImportStatus::FinishedItem(meta) => {
+            // the first item is the folder everything else is created in
+            if folder.get().is_none() {
+                folder.set(Some(meta.id));
+            }
+            output::status("done.");
+        }
+        ImportStatus::Unconverted(item) => {
+            let item = NotConverted {
+                source: item.source.to_string_lossy().to_string(),
+                detail: item.detail,
+            };
+            output::update(&item, |item| {
+                println!("not converted: {}: {}", item.source, item.detail)
+            });
+            unconverted.borrow_mut().push(item);
+        }
+    };
+
+    lb.import_from(format, &disk, parent, &update_status)
+        .await?;
+
+    let imported = Import {
+        folder: folder.get().unwrap_or_default(),
+        files: nth_file.get().saturating_sub(1),
+        unconverted: unconverted.into_inner(),
+    };
+    output::print(&imported, |imported| {
+        if !imported.unconverted.is_empty() {
+            println!(
+                "{} things couldn't be converted, see the notes above",
+                imported.unconverted.len()
+            );
+        }
+    });
+    Ok(())
+}
+
+/// `--format` of `export`
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum ExportFormat {
+    /// the files as lockbook stores them
+    #[default]
+    Files,
+    Html,
+    Pdf,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "files" => Ok(Self::Files),
+            "html" => Ok(Self::Html),
+            "pdf" => Ok(Self::Pdf),
+            other => Err(format!("unknown format {other}, expected files, html or pdf")),
+        }
+    }
+}
+
+#[tokio::main]
+pub async fn export(
+    format: ExportFormat, theme: Theme, target: FileInput, dest: PathBuf,
+) -> CmdResult<()> {
     let lb = &core().await?;
     ensure_account_and_root(lb).await?;
 
     let target_file = target.find(lb).await?;
 
-    println!("exporting '{}'...", target_file.name);
-    if !dest.exists() {
-        fs::create_dir(&dest)?;
-    }
-
-    // todo this is possibly ugly
-    lb.export_file(
-        target_file.id,
-        dest,
-        false,
-        &Some(|i| {
-            println!("{:?}", i);
-        }),
-    )
-    .await?;
+    output::status(format!("exporting '{}'...", target_file.name));
+
+    let exported = RefCell::new(vec![]);
+    let update_status = Some(|i: ExportFileInfo| {
+        let item = Exported {
+            lockbook_path: i.lockbook_path.clone(),
+            disk_path: i.disk_path.to_string_lossy().to_string(),
+        };
+        output::update(&item, |_| println!("{:?}", i));
+        exported.borrow_mut().push(item);
+    });
+
+    match format {
+        ExportFormat::Files => {
+            if !dest.exists() {
+                fs::create_dir(&dest)?;
+            }
+            // todo this is possibly ugly
+            lb.export_file(target_file.id, dest, false, &update_status)
+                .await?;
+        }
+        ExportFormat::Html => {
+            lb.export_site(target_file.id, dest, theme, &update_status)
+                .await?;
+        }
+        ExportFormat::Pdf => {
+            // exporting into a folder names the pdf after the document
+            let dest = if dest.is_dir() {
+                let stem = target_file
+                    .name
+                    .rsplit_once('.')
+                    .map(|(stem, _)| stem)
+                    .filter(|stem| !stem.is_empty())
+                    .unwrap_or(&target_file.name);
+                dest.join(format!("{stem}.pdf"))
+            } else {
+                dest
+            };
+            for warning in lb.export_pdf(target_file.id, dest.clone(), theme).await? {
+                eprintln!("warning: {warning}");
+            }
+            if let Some(update_status) = &update_status {
+                update_status(ExportFileInfo {
+                    disk_path: dest,
+                    lockbook_path: lb.get_path_by_id(target_file.id).await?,
+                });
+            }
+        }
+    }
+
+    output::print(&exported.into_inner(), |_| {});
     Ok(())
 }
+
+#[tokio::main]
+pub async fn mirror(watch: bool, target: FileInput, dest: PathBuf) -> CmdResult<()> {
+    let lb = &core().await?;
+    ensure_account_and_root(lb).await?;
+
+    let target_file = target.find(lb).await?;
+    if !target_file.is_folder() {
+        return Err(CmdErr::from(format!("'{}' is not a folder", target_file.name)));
+    }
+    if watch && output::format() == Format::Json {
+        return Err(CmdErr::from(
+            "--watch never finishes, use --jsonl to get each pass as it happens",
+        ));
+    }
+
+    output::status(format!("mirroring '{}' to {}...", target_file.name, dest.display()));
+    if !watch {
+        let report = mirror_pass(lb, target_file.id, &dest).await?;
+        output::print(&report, print_mirror_report);
+        return Ok(());
+    }
+
+    // passes run when something changes on disk, or every so often to pick up remote changes
+    fs::create_dir_all(&dest)?;
+    let (changed, mut changes) = mpsc::unbounded_channel();
+    let mut watcher = Hotwatch::new_with_custom_delay(Duration::from_secs(1))
+        .map_err(|err| CmdErr::from(format!("file watcher failed to initialize: {err:#?}")))?;
+    watcher
+        .watch(&dest, move |event: Event| {
+            // passes write the state file, which would otherwise set off the next pass
+            let own_state = event.paths.iter().all(|path| {
+                path.file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with(MIRROR_STATE_FILE))
+            });
+            if !own_state {
+                let _ = changed.send(());
+            }
+        })
+        .map_err(|err| CmdErr::from(format!("file watcher failed to watch: {err:#?}")))?;
+
+    loop {
+        match mirror_pass(lb, target_file.id, &dest).await {
+            Ok(report) if !report.is_empty() => output::update(&report, print_mirror_report),
+            Ok(_) => {}
+            // likely a dropped connection or a file that was busy; the next pass will retry
+            Err(err) => eprintln!("mirror pass failed: {}", err.msg),
+        }
+        tokio::select! {
+            _ = changes.recv() => {}
+            _ = tokio::time::sleep(Duration::from_secs(30)) => {}
+        }
+        while changes.try_recv().is_ok() {}
+    }
+}
+
+/// Mirrors against what's on the server rather than whatever was last synced, and uploads
+/// whatever the mirror pushed
+async fn mirror_pass(lb: &Lb, id: Uuid, dest: &Path) -> CmdResult<MirrorReport> {
+    lb.sync(None).await?;
+    let report = lb.mirror(id, dest).await?;
+    if !report.is_empty() {
+        lb.sync(None).await?;
+    }
+    Ok(report)
+}
+
+fn print_mirror_report(report: &MirrorReport) {
+    for path in &report.pulled {
+        println!("pulled: {path}");
+    }
+    for path in &report.pushed {
+        println!("pushed: {path}");
+    }
+    for path in &report.deleted_locally {
+        println!("deleted locally: {path}");
+    }
+    for path in &report.deleted_remotely {
+        println!("deleted in lockbook: {path}");
+    }
+    for path in &report.conflicts {
+        println!("conflict: {path} (your copy was kept alongside it)");
+    }
+}
+
+#[derive(Serialize)]
+struct Imported {
+    disk_path: String,
+    id: Uuid,
+}
+
+#[derive(Serialize)]
+struct Import {
+    /// the folder the import was created in
+    folder: Uuid,
+    /// files and folders created inside it
+    files: usize,
+    unconverted: Vec<NotConverted>,
+}
+
+#[derive(Serialize)]
+struct NotConverted {
+    /// the file in the export it's from
+    source: String,
+    detail: String,
+}
+
+#[derive(Serialize)]
+struct Exported {
+    lockbook_path: String,
+    disk_path: String,
+}
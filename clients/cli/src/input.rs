@@ -4,10 +4,10 @@ use std::{
     str::FromStr,
 };
 
-use cli_rs::cli_error::{CliError, CliResult};
+use cli_rs::cli_error::CliResult;
 use lb_rs::{model::file::File, model::path_ops::Filter, Lb, Uuid};
 
-use crate::core;
+use crate::{core, error::CmdResult};
 
 #[derive(Clone, Debug)]
 pub enum FileInput {
@@ -25,7 +25,7 @@ impl Display for FileInput {
 }
 
 impl FileInput {
-    pub async fn find(&self, lb: &Lb) -> CliResult<File> {
+    pub async fn find(&self, lb: &Lb) -> CmdResult<File> {
         let f = match self {
             FileInput::Id(id) => lb.get_file_by_id(*id).await?,
             FileInput::Path(path) => lb.get_by_path(path).await?,
@@ -48,7 +48,7 @@ impl FromStr for FileInput {
 
 #[tokio::main]
 pub async fn file_completor(prompt: &str, filter: Option<Filter>) -> CliResult<Vec<String>> {
-    let lb = &core().await?;
+    let lb = &core().await.map_err(|err| err.msg)?;
     if !prompt.is_empty() && prompt.chars().all(|c| c == '-' || c.is_ascii_hexdigit()) {
         return id_completor(lb, prompt, filter).await;
     }
@@ -100,7 +100,7 @@ pub async fn id_completor(lb: &Lb, prompt: &str, filter: Option<Filter>) -> CliR
         .collect())
 }
 
-pub fn std_in<T>(prompt: impl Display) -> Result<T, CliError>
+pub fn std_in<T>(prompt: impl Display) -> CmdResult<T>
 where
     T: FromStr,
     <T as FromStr>::Err: Debug,
@@ -1,20 +1,22 @@
-use crate::{core, ensure_account, input};
-use cli_rs::cli_error::CliResult;
+use crate::error::CmdResult;
+use crate::{core, ensure_account, input, output};
 use fs_extra::dir::CopyOptions;
 use lb_fs::fs_impl::Drive;
+use lb_fs::mount::{Backend, MountPoint};
 use lb_rs::model::core_config::Config;
 
 #[tokio::main]
-pub async fn mount() -> CliResult<()> {
+pub async fn mount(backend: Backend, mount_point: MountPoint) -> CmdResult<()> {
     let lb = &core().await?;
     ensure_account(lb)?;
+    output::interactive("fs")?;
     warning()?;
     copy_data()?;
-    Drive::mount().await?;
+    Drive::mount(backend, mount_point).await?;
     Ok(())
 }
 
-fn warning() -> CliResult<()> {
+fn warning() -> CmdResult<()> {
     let answer: String = input::std_in(WARNING)?;
     if answer != "y" && answer != "Y" {
         return Err("Aborted.".into());
@@ -23,7 +25,7 @@ fn warning() -> CliResult<()> {
     Ok(())
 }
 
-fn copy_data() -> CliResult<()> {
+fn copy_data() -> CmdResult<()> {
     let current_path = Config::writeable_path("cli");
     let target_path = format!("{}/.lockbook/drive", std::env::var("HOME").unwrap());
 
@@ -44,8 +46,9 @@ This version will cp your your CLI's data directory and create a dedicated one f
 iterations will be more tightly integrated into host programs. lb-fs will sync changes to our server
 on startup and then every 5 minutes.
 
-This command will not return and print out logs from the NFS server. Once the server starts it will
-mount a virtual file system to /tmp/lockbook. Ctrl-C'ing this process will shut down the server and
+This command will not return and print out logs from the drive. Once it starts it will mount a
+virtual file system to /tmp/lockbook, or wherever --mount-point says. On linux this is a FUSE mount,
+elsewhere (or with --backend nfs) it's an NFS server. Ctrl-C'ing or terminating this process will
 unmount the file system. For now, a clean umount is critical to not requiring a restart.
 
 Press Y to proceed.
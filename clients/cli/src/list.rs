@@ -1,16 +1,24 @@
-use std::{cmp::Ordering, path::Path};
+use std::{cmp::Ordering, collections::HashMap, path::Path};
 
-use cli_rs::cli_error::CliResult;
-use lb_rs::{model::file::File, Lb, Uuid};
+use lb_rs::{
+    model::{file::File, usage::bytes_to_human},
+    Lb, Uuid,
+};
+use serde::Serialize;
 
-use crate::{core, ensure_account_and_root, input::FileInput};
+use crate::{
+    core, ensure_account_and_root,
+    error::CmdResult,
+    input::FileInput,
+    output::{self, FileInfo, Format},
+};
 
 const ID_PREFIX_LEN: usize = 8;
 
 #[tokio::main]
 pub async fn list(
     long: bool, recursive: bool, mut paths: bool, target: FileInput,
-) -> CliResult<()> {
+) -> CmdResult<()> {
     let lb = &core().await?;
     ensure_account_and_root(lb).await?;
 
@@ -27,6 +35,16 @@ pub async fn list(
         let _ = files.swap_remove(pos);
     }
 
+    if output::format() != Format::Human {
+        let mut infos = Vec::with_capacity(files.len());
+        for f in &files {
+            infos.push(FileInfo::new(lb, f).await?);
+        }
+        infos.sort_by(|a, b| a.path.cmp(&b.path));
+        output::print(&infos, |_| {});
+        return Ok(());
+    }
+
     if recursive {
         paths = true
     };
@@ -45,6 +63,96 @@ pub async fn list(
     Ok(())
 }
 
+#[tokio::main]
+pub async fn tree(target: FileInput) -> CmdResult<()> {
+    let lb = &core().await?;
+    ensure_account_and_root(lb).await?;
+
+    let target = target.find(lb).await?;
+    let files = lb.get_and_get_children_recursively(&target.id).await?;
+    let usage = lb.get_uncompressed_usage_breakdown().await?;
+
+    let mut children: HashMap<Uuid, Vec<&File>> = HashMap::new();
+    for file in &files {
+        // the root is its own parent
+        if file.id != target.id && file.id != file.parent {
+            children.entry(file.parent).or_default().push(file);
+        }
+    }
+    for siblings in children.values_mut() {
+        siblings.sort_by(|a, b| b.is_folder().cmp(&a.is_folder()).then(a.name.cmp(&b.name)));
+    }
+
+    let tree = Tree { children, usage };
+    let path = lb.get_path_by_id(target.id).await?;
+    output::print(&tree.node(&target, path), |root| {
+        println!("{} ({})", root.path, bytes_to_human(root.size as u64));
+        tree.print(target.id, "");
+    });
+    Ok(())
+}
+
+struct Tree<'a> {
+    children: HashMap<Uuid, Vec<&'a File>>,
+    usage: HashMap<Uuid, usize>,
+}
+
+impl Tree<'_> {
+    /// a document's size, or the size of everything in a folder
+    fn size(&self, file: &File) -> usize {
+        let own = self.usage.get(&file.id).copied().unwrap_or_default();
+        let children = self
+            .children
+            .get(&file.id)
+            .map(|children| children.iter().map(|child| self.size(child)).sum::<usize>())
+            .unwrap_or_default();
+        own + children
+    }
+
+    fn node(&self, file: &File, path: String) -> TreeNode {
+        let children = self
+            .children
+            .get(&file.id)
+            .map(|children| {
+                children
+                    .iter()
+                    .map(|child| {
+                        let slash = if child.is_folder() { "/" } else { "" };
+                        self.node(child, format!("{path}{}{slash}", child.name))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        TreeNode { id: file.id, name: file.name.clone(), path, size: self.size(file), children }
+    }
+
+    fn print(&self, parent: Uuid, prefix: &str) {
+        let Some(children) = self.children.get(&parent) else {
+            return;
+        };
+        for (i, child) in children.iter().enumerate() {
+            let last = i == children.len() - 1;
+            let branch = if last { "└── " } else { "├── " };
+            let slash = if child.is_folder() { "/" } else { "" };
+            let size = bytes_to_human(self.size(child) as u64);
+            println!("{prefix}{branch}{}{slash} ({size})", child.name);
+
+            let continuation = if last { "    " } else { "│   " };
+            self.print(child.id, &format!("{prefix}{continuation}"));
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct TreeNode {
+    id: Uuid,
+    name: String,
+    path: String,
+    /// bytes, including everything below a folder
+    size: usize,
+    children: Vec<TreeNode>,
+}
+
 struct LsConfig {
     my_name: String,
     w_id: usize,
@@ -99,7 +207,7 @@ fn node_string(node: &FileNode, cfg: &LsConfig) -> String {
 
 async fn get_children(
     lb: &Lb, files: &[File], parent: Uuid, cfg: &mut LsConfig,
-) -> CliResult<Vec<FileNode>> {
+) -> CmdResult<Vec<FileNode>> {
     let mut children = Vec::new();
     for f in files {
         if f.parent == parent {
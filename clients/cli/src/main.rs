@@ -1,10 +1,14 @@
 mod account;
 mod debug;
 mod edit;
+mod error;
+mod history;
 mod imex;
 mod input;
 mod lb_fs;
 mod list;
+mod output;
+mod search;
 mod share;
 mod stream;
 
@@ -14,17 +18,20 @@ use std::path::PathBuf;
 use account::ApiUrl;
 use cli_rs::{
     arg::Arg,
-    cli_error::{CliError, CliResult, Exit},
+    cli_error::{CliResult, Exit},
     command::Command,
     flag::Flag,
     parser::Cmd,
 };
 
+use error::{CmdErr, CmdResult};
 use input::FileInput;
+use ::lb_fs::mount::{Backend, MountPoint};
+use output::{FileInfo, Format, SyncSummary, SyncUpdate};
 use lb_rs::{
     model::path_ops::Filter,
     model::{core_config::Config, errors::LbErrKind},
-    service::sync::SyncProgress,
+    service::{render::Theme, sync::SyncProgress},
     Lb, Uuid,
 };
 
@@ -40,30 +47,46 @@ fn run() -> CliResult<()> {
                         .input(Arg::str("username").description("your desired username."))
                         .input(Flag::<ApiUrl>::new("api_url")
                             .description("location of the lockbook server you're trying to use. If not provided will check the API_URL env var, and then fall back to https://api.prod.lockbook.net"))
-                        .handler(|username, api_url| {
-                            account::new(username.get(), api_url.get())
-                        })
+                        .input(output::json_flag())
+                        .handler(|username, api_url, json| output::run(json.get(), || account::new(username.get(), api_url.get())))
                 )
                 .subcommand(
                     Command::name("import").description("import an existing account by piping in the account string")
-                        .handler(account::import)
+                        .input(output::json_flag())
+                        .handler(|json| output::run(json.get(), account::import))
                 )
                 .subcommand(
                     Command::name("export").description("reveal your account's private key")
                         .input(Flag::bool("skip-check").description("don't ask for confirmation to reveal the private key"))
-                        .handler(|skip_check| account::export(skip_check.get()))
+                        .input(output::json_flag())
+                        .handler(|skip_check, json| output::run(json.get(), || account::export(skip_check.get())))
+                )
+                .subcommand(
+                    Command::name("takeout").description("download an encrypted archive of everything your account owns, for offline backups")
+                        .input(Arg::<PathBuf>::name("dest").description("where to write the archive, must not exist yet"))
+                        .input(output::json_flag())
+                        .handler(|dest, json| output::run(json.get(), || account::takeout(dest.get())))
+                )
+                .subcommand(
+                    Command::name("restore").description("set up this lockbook from a takeout archive by piping in the account string")
+                        .input(Arg::<PathBuf>::name("archive").description("path of the archive on disk"))
+                        .input(output::json_flag())
+                        .handler(|archive, json| output::run(json.get(), || account::restore(archive.get())))
                 )
                 .subcommand(
                     Command::name("subscribe").description("start a monthly subscription for massively increased storage")
-                        .handler(account::subscribe)
+                        .input(output::json_flag())
+                        .handler(|json| output::run(json.get(), account::subscribe))
                 )
                 .subcommand(
                     Command::name("unsubscribe").description("cancel an existing subscription")
-                        .handler(account::unsubscribe)
+                        .input(output::json_flag())
+                        .handler(|json| output::run(json.get(), account::unsubscribe))
                 )
                 .subcommand(
                     Command::name("status").description("show your account status")
-                        .handler(account::status)
+                        .input(output::json_flag())
+                        .handler(|json| output::run(json.get(), account::status))
                 )
         )
         .subcommand(
@@ -72,31 +95,38 @@ fn run() -> CliResult<()> {
                 .input(Arg::<FileInput>::name("dest")
                        .description("the path or id of a folder within lockbook to place the file.")
                        .completor(|prompt| input::file_completor(prompt, Some(Filter::FoldersOnly))))
-                .handler(|disk, parent| imex::copy(disk.get(), parent.get()))
+                .input(output::json_flag())
+                .input(output::jsonl_flag())
+                .handler(|disk, parent, json, jsonl| output::run_streaming(json.get(), jsonl.get(), || imex::copy(disk.get(), parent.get())))
         )
         .subcommand(
             Command::name("debug").description("investigative commands")
                 .subcommand(
                     Command::name("validate").description("helps find invalid states within your lockbook")
-                        .handler(debug::validate)
+                        .input(output::json_flag())
+                        .handler(|json| output::run(json.get(), debug::validate))
                 )
                 .subcommand(
                     Command::name("info").description("print metadata associated with a file")
                         .input(Arg::<FileInput>::name("target").description("id or path of file to debug")
                             .completor(|prompt| input::file_completor(prompt, None)))
-                        .handler(|target| debug::info(target.get()))
+                        .input(output::json_flag())
+                        .handler(|target, json| output::run(json.get(), || debug::info(target.get())))
                 )
                 .subcommand(
                     Command::name("whoami").description("print who is logged into this lockbook")
-                        .handler(debug::whoami)
+                        .input(output::json_flag())
+                        .handler(|json| output::run(json.get(), debug::whoami))
                 )
                 .subcommand(
                     Command::name("whereami").description("print information about where this lockbook is stored and it's server url")
-                        .handler(debug::whereami)
+                        .input(output::json_flag())
+                        .handler(|json| output::run(json.get(), debug::whereami))
                 )
                 .subcommand(
                     Command::name("debuginfo").description("retrieve the debug-info string to help a lockbook engineer diagnose a problem")
-                        .handler(debug::debug_info)
+                        .input(output::json_flag())
+                        .handler(|json| output::run(json.get(), debug::debug_info))
                 )
         )
         .subcommand(
@@ -104,26 +134,64 @@ fn run() -> CliResult<()> {
                 .input(Flag::bool("force"))
                 .input(Arg::<FileInput>::name("target").description("path of id of file to delete")
                             .completor(|prompt| input::file_completor(prompt, None)))
-                .handler(|force, target| delete(force.get(), target.get()))
+                .input(output::json_flag())
+                .handler(|force, target, json| output::run(json.get(), || delete(force.get(), target.get())))
+        )
+        .subcommand(
+            Command::name("diff").description("show a document's unsynced changes, or its changes since a version, as a unified diff")
+                .input(Flag::<usize>::new("version").description("version to compare against, see lockbook log"))
+                .input(Arg::<FileInput>::name("target").description("lockbook document path or ID")
+                            .completor(|prompt| input::file_completor(prompt, None)))
+                .input(output::json_flag())
+                .handler(|version, target, json| output::run(json.get(), || history::diff(version.get(), target.get())))
         )
         .subcommand(
             Command::name("edit").description("edit a document")
                 .input(edit::editor_flag())
                 .input(Arg::<FileInput>::name("target").description("path or id of file to edit")
                             .completor(|prompt| input::file_completor(prompt, None)))
-                .handler(|editor, target| edit::edit(editor.get(), target.get()))
+                .input(output::json_flag())
+                .handler(|editor, target, json| output::run(json.get(), || edit::edit(editor.get(), target.get())))
         )
         .subcommand(
             Command::name("export").description("export a lockbook file to your file system")
+                .input(Flag::<imex::ExportFormat>::new("format").description("files (default) copies files as they are, html renders a folder as a static site, pdf renders a document"))
+                .input(Flag::<Theme>::new("theme").description("colors for html and pdf exports: light (default) or dark"))
                 .input(Arg::<FileInput>::name("target")
                             .completor(|prompt| input::file_completor(prompt, None)))
                 .input(Arg::<PathBuf>::name("dest"))
-                .handler(|target, dest| imex::export(target.get(), dest.get()))
+                .input(output::json_flag())
+                .input(output::jsonl_flag())
+                .handler(|format, theme, target, dest, json, jsonl| output::run_streaming(json.get(), jsonl.get(), || imex::export(format.get(), theme.get(), target.get(), dest.get())))
         )
         .subcommand(
             Command::name("fs")
-                .description("use your lockbook files with your local filesystem by mounting an NFS drive to /tmp/lockbook")
-                .handler(lb_fs::mount)
+                .description("use your lockbook files with your local filesystem by mounting a drive to /tmp/lockbook")
+                .input(Flag::<Backend>::new("backend").description("nfs or fuse, defaults to fuse on linux where nfs needs root"))
+                .input(Flag::<MountPoint>::new("mount-point").description("where to mount the drive, defaults to /tmp/lockbook"))
+                .input(output::json_flag())
+                .handler(|backend, mount_point, json| output::run(json.get(), || lb_fs::mount(backend.get(), mount_point.get())))
+        )
+        .subcommand(
+            Command::name("grep").description("print every line matching a regex in a document or the documents in a folder, as path:line: match")
+                .input(Flag::bool("ignore-case").description("match regardless of case"))
+                .input(Arg::str("regex"))
+                .input(Arg::<FileInput>::name("target").description("lockbook file path or ID of the document or folder to search")
+                            .completor(|prompt| input::file_completor(prompt, None))
+                            .default(FileInput::Path("/".to_string())))
+                .input(output::json_flag())
+                .handler(|ignore_case, regex, target, json| output::run(json.get(), || search::grep(ignore_case.get(), regex.get(), target.get())))
+        )
+        .subcommand(
+            Command::name("import").description("import notes exported from obsidian, notion, evernote or bear into a new lockbook folder")
+                .input(Flag::<imex::ImportFrom>::new("from").description("the app the export is from: obsidian, notion, evernote or bear"))
+                .input(Arg::<PathBuf>::name("disk-path").description("path of the export on disk"))
+                .input(Arg::<FileInput>::name("dest")
+                       .description("the path or id of a folder within lockbook to create the import in")
+                       .completor(|prompt| input::file_completor(prompt, Some(Filter::FoldersOnly))))
+                .input(output::json_flag())
+                .input(output::jsonl_flag())
+                .handler(|from, disk, parent, json, jsonl| output::run_streaming(json.get(), jsonl.get(), || imex::import(from.get(), disk.get(), parent.get())))
         )
         .subcommand(
             Command::name("list").description("list files and file information")
@@ -133,7 +201,25 @@ fn run() -> CliResult<()> {
                 .input(Arg::<FileInput>::name("target").description("file path location whose files will be listed")
                             .completor(|prompt| input::file_completor(prompt, Some(Filter::FoldersOnly)))
                             .default(FileInput::Path("/".to_string())))
-                .handler(|long, recur, paths, target| list::list(long.get(), recur.get(), paths.get(), target.get()))
+                .input(output::json_flag())
+                .handler(|long, recur, paths, target, json| output::run(json.get(), || list::list(long.get(), recur.get(), paths.get(), target.get())))
+        )
+        .subcommand(
+            Command::name("log").description("list the versions of a document this device knows about, newest first")
+                .input(Arg::<FileInput>::name("target").description("lockbook document path or ID")
+                            .completor(|prompt| input::file_completor(prompt, None)))
+                .input(output::json_flag())
+                .handler(|target, json| output::run(json.get(), || history::log(target.get())))
+        )
+        .subcommand(
+            Command::name("mirror").description("keep a lockbook folder and a folder on your file system in sync with each other")
+                .input(Flag::bool("watch").description("keep mirroring every few seconds until interrupted"))
+                .input(Arg::<FileInput>::name("target").description("lockbook folder path or ID to mirror")
+                            .completor(|prompt| input::file_completor(prompt, Some(Filter::FoldersOnly))))
+                .input(Arg::<PathBuf>::name("dest").description("folder on your file system to mirror it to"))
+                .input(output::json_flag())
+                .input(output::jsonl_flag())
+                .handler(|watch, target, dest, json, jsonl| output::run_streaming(json.get(), jsonl.get(), || imex::mirror(watch.get(), target.get(), dest.get())))
         )
         .subcommand(
             Command::name("move").description("move a file to a new parent")
@@ -141,13 +227,15 @@ fn run() -> CliResult<()> {
                             .completor(|prompt| input::file_completor(prompt, None)))
                 .input(Arg::<FileInput>::name("dest").description("lockbook file path or ID of the new parent folder")
                             .completor(|prompt| input::file_completor(prompt, Some(Filter::FoldersOnly))))
-                .handler(|src, dst| move_file(src.get(), dst.get()))
+                .input(output::json_flag())
+                .handler(|src, dst, json| output::run(json.get(), || move_file(src.get(), dst.get())))
         )
         .subcommand(
             Command::name("new").description("create a new file at the given path or do nothing if it exists")
                 .input(Arg::<FileInput>::name("path").description("create a new file at the given path or do nothing if it exists")
                             .completor(|prompt| input::file_completor(prompt, Some(Filter::FoldersOnly))))
-                .handler(|target| create_file(target.get()))
+                .input(output::json_flag())
+                .handler(|target, json| output::run(json.get(), || create_file(target.get())))
         )
         .subcommand(
             Command::name("stream").description("interact with stdout and stdin")
@@ -156,7 +244,8 @@ fn run() -> CliResult<()> {
                         .description("print a document to stdout")
                         .input(Arg::<FileInput>::name("target").description("lockbook file path or ID")
                             .completor(|prompt| input::file_completor(prompt, None)))
-                        .handler(|target| stream::stdout(target.get()))
+                        .input(output::json_flag())
+                        .handler(|target, json| output::run(json.get(), || stream::stdout(target.get())))
                 )
                 .subcommand(
                     Command::name("in")
@@ -164,7 +253,8 @@ fn run() -> CliResult<()> {
                         .input(Arg::<FileInput>::name("target").description("lockbook file path or ID")
                             .completor(|prompt| input::file_completor(prompt, None)))
                         .input(Flag::bool("append").description("don't overwrite the specified lb file, append to it"))
-                        .handler(|target, append| stream::stdin(target.get(), append.get()))
+                        .input(output::json_flag())
+                        .handler(|target, append, json| output::run(json.get(), || stream::stdin(target.get(), append.get())))
                 )
         )
         .subcommand(
@@ -172,7 +262,22 @@ fn run() -> CliResult<()> {
                 .input(Arg::<FileInput>::name("target").description("lockbook file path or ID of file to rename")
                             .completor(|prompt| input::file_completor(prompt, None)))
                 .input(Arg::str("new_name"))
-                .handler(|target, new_name| rename(target.get(), new_name.get()))
+                .input(output::json_flag())
+                .handler(|target, new_name, json| output::run(json.get(), || rename(target.get(), new_name.get())))
+        )
+        .subcommand(
+            Command::name("restore").description("make an old version of a document current again")
+                .input(Flag::<usize>::new("version").description("version to restore, see lockbook log"))
+                .input(Arg::<FileInput>::name("target").description("lockbook document path or ID")
+                            .completor(|prompt| input::file_completor(prompt, None)))
+                .input(output::json_flag())
+                .handler(|version, target, json| output::run(json.get(), || history::restore(version.get(), target.get())))
+        )
+        .subcommand(
+            Command::name("search").description("search file paths and document contents")
+                .input(Arg::str("query"))
+                .input(output::json_flag())
+                .handler(|query, json| output::run(json.get(), || search::search(query.get())))
         )
         .subcommand(
             Command::name("share").description("sharing related commands")
@@ -182,11 +287,28 @@ fn run() -> CliResult<()> {
                             .completor(|prompt| input::file_completor(prompt, None)))
                         .input(Arg::str("username"))
                         .input(Flag::bool("read-only"))
-                        .handler(|target, username, ro| share::new(target.get(), username.get(), ro.get()))
+                        .input(output::json_flag())
+                        .handler(|target, username, ro, json| output::run(json.get(), || share::new(target.get(), username.get(), ro.get())))
+                )
+                .subcommand(
+                    Command::name("publish").description("publish a copy of a file that anyone with the printed link can read, publishing again replaces the old link")
+                        .input(Arg::<FileInput>::name("target").description("lockbook file path or ID of the document or folder to publish")
+                            .completor(|prompt| input::file_completor(prompt, None)))
+                        .input(Flag::<u64>::new("days").description("take the link down after this many days, 0 keeps it up until it's unpublished"))
+                        .input(output::json_flag())
+                        .handler(|target, days, json| output::run(json.get(), || share::publish(target.get(), days.get())))
+                )
+                .subcommand(
+                    Command::name("unpublish").description("take down a file's publish link")
+                        .input(Arg::<FileInput>::name("target").description("lockbook file path or ID of the published file")
+                            .completor(|prompt| input::file_completor(prompt, None)))
+                        .input(output::json_flag())
+                        .handler(|target, json| output::run(json.get(), || share::unpublish(target.get())))
                 )
                 .subcommand(
                     Command::name("pending").description("list pending shares")
-                        .handler(share::pending)
+                        .input(output::json_flag())
+                        .handler(|json| output::run(json.get(), share::pending))
                 )
                 .subcommand(
                     Command::name("accept").description("accept a pending share by adding it to your file tree")
@@ -194,18 +316,30 @@ fn run() -> CliResult<()> {
                                     .completor(share::pending_share_completor))
                         .input(Arg::<FileInput>::name("target").description("lockbook file path or ID of the folder you want to place this shared file")
                             .completor(|prompt| input::file_completor(prompt, Some(Filter::FoldersOnly))))
-                        .handler(|id, dest| share::accept(&id.get(), dest.get()))
+                        .input(output::json_flag())
+                        .handler(|id, dest, json| output::run(json.get(), || share::accept(&id.get(), dest.get())))
                 )
                 .subcommand(
                     Command::name("delete").description("delete a pending share")
                         .input(Arg::<Uuid>::name("share-id").description("ID of pending share to delete")
                                .completor(share::pending_share_completor))
-                        .handler(|target| share::delete(target.get()))
+                        .input(output::json_flag())
+                        .handler(|target, json| output::run(json.get(), || share::delete(target.get())))
                 )
         )
         .subcommand(
             Command::name("sync").description("sync your local changes back to lockbook servers") // todo also back
-                .handler(sync)
+                .input(output::json_flag())
+                .input(output::jsonl_flag())
+                .handler(|json, jsonl| output::run_streaming(json.get(), jsonl.get(), sync))
+        )
+        .subcommand(
+            Command::name("tree").description("show a folder's contents as a tree, with sizes")
+                .input(Arg::<FileInput>::name("target").description("lockbook folder path or ID to show")
+                            .completor(|prompt| input::file_completor(prompt, Some(Filter::FoldersOnly)))
+                            .default(FileInput::Path("/".to_string())))
+                .input(output::json_flag())
+                .handler(|target, json| output::run(json.get(), || list::tree(target.get())))
         )
         .with_completions()
         .parse();
@@ -217,33 +351,37 @@ fn main() {
     run().exit();
 }
 
-pub async fn core() -> CliResult<Lb> {
-    Lb::init(Config::cli_config("cli"))
-        .await
-        .map_err(|err| CliError::from(err.to_string()))
+pub async fn core() -> CmdResult<Lb> {
+    Ok(Lb::init(Config::cli_config("cli")).await?)
 }
 
 #[tokio::main]
-async fn sync() -> CliResult<()> {
+async fn sync() -> CmdResult<()> {
     let lb = &core().await?;
     ensure_account(lb)?;
 
-    println!("syncing...");
-    lb.sync(Some(Box::new(|sp: SyncProgress| {
-        println!("{sp}");
-    })))
-    .await?;
+    output::status("syncing...");
+    let status = lb
+        .sync_now(Some(Box::new(|sp: SyncProgress| {
+            output::update(&SyncUpdate::from(sp.clone()), |_| println!("{sp}"));
+        })))
+        .await?;
+    output::print(&SyncSummary::from(&status), |_| {});
     Ok(())
 }
 
 #[tokio::main]
-async fn delete(force: bool, target: FileInput) -> Result<(), CliError> {
+async fn delete(force: bool, target: FileInput) -> CmdResult<()> {
     let lb = &core().await?;
     ensure_account_and_root(lb).await?;
 
     let f = target.find(lb).await?;
 
     if !force {
+        if output::format() != Format::Human {
+            return Err(CmdErr::from("pass --force to delete without being asked to confirm"));
+        }
+
         let mut phrase = format!("delete '{target}'");
 
         if f.is_folder() {
@@ -272,62 +410,67 @@ async fn delete(force: bool, target: FileInput) -> Result<(), CliError> {
 }
 
 #[tokio::main]
-async fn move_file(src: FileInput, dest: FileInput) -> CliResult<()> {
+async fn move_file(src: FileInput, dest: FileInput) -> CmdResult<()> {
     let lb = &core().await?;
     ensure_account_and_root(lb).await?;
 
     let src = src.find(lb).await?;
     let dest = dest.find(lb).await?;
     lb.move_file(&src.id, &dest.id).await?;
+
+    let moved = lb.get_file_by_id(src.id).await?;
+    output::print(&FileInfo::new(lb, &moved).await?, |_| {});
     Ok(())
 }
 
 #[tokio::main]
-async fn create_file(path: FileInput) -> CliResult<()> {
+async fn create_file(path: FileInput) -> CmdResult<()> {
     let lb = &core().await?;
     ensure_account_and_root(lb).await?;
 
     let FileInput::Path(path) = path else {
-        return Err(CliError::from("cannot create a file using ids"));
+        return Err(CmdErr::from("cannot create a file using ids"));
     };
 
-    match lb.get_by_path(&path).await {
-        Ok(_f) => Ok(()),
+    let file = match lb.get_by_path(&path).await {
+        Ok(f) => f,
         Err(err) => match err.kind {
-            LbErrKind::FileNonexistent => match lb.create_at_path(&path).await {
-                Ok(_f) => Ok(()),
-                Err(err) => Err(err.into()),
-            },
-            _ => Err(err.into()),
+            LbErrKind::FileNonexistent => lb.create_at_path(&path).await?,
+            _ => return Err(err.into()),
         },
-    }
+    };
+    output::print(&FileInfo::new(lb, &file).await?, |_| {});
+    Ok(())
 }
 
 #[tokio::main]
-async fn rename(target: FileInput, new_name: String) -> Result<(), CliError> {
+async fn rename(target: FileInput, new_name: String) -> CmdResult<()> {
     let lb = &core().await?;
     ensure_account_and_root(lb).await?;
 
     let id = target.find(lb).await?.id;
     lb.rename_file(&id, &new_name).await?;
+
+    let renamed = lb.get_file_by_id(id).await?;
+    output::print(&FileInfo::new(lb, &renamed).await?, |_| {});
     Ok(())
 }
 
-fn ensure_account(lb: &Lb) -> CliResult<()> {
+fn ensure_account(lb: &Lb) -> CmdResult<()> {
     if let Err(e) = lb.get_account() {
         if e.kind == LbErrKind::AccountNonexistent {
-            return Err(CliError::from("no account found, run lockbook account import"));
+            return Err(CmdErr::lb(e.kind, "no account found, run lockbook account import"));
         }
     }
 
     Ok(())
 }
 
-async fn ensure_account_and_root(lb: &Lb) -> CliResult<()> {
+async fn ensure_account_and_root(lb: &Lb) -> CmdResult<()> {
     ensure_account(lb)?;
     if let Err(e) = lb.root().await {
         if e.kind == LbErrKind::RootNonexistent {
-            return Err(CliError::from("no root found, have you synced yet?"));
+            return Err(CmdErr::lb(e.kind, "no root found, have you synced yet?"));
         }
     }
 
@@ -0,0 +1,214 @@
+//! How commands print. Every command takes `--json`, which swaps its text for a single json value
+//! on stdout, and commands that report progress as they go also take `--jsonl`, which prints each
+//! update as a json object on its own line. The shapes below are what scripts get and change only
+//! by gaining fields.
+
+use std::{process, sync::OnceLock};
+
+use cli_rs::{cli_error::CliResult, flag::Flag};
+use lb_rs::{
+    model::file::{File, Share, ShareMode},
+    model::file_metadata::FileType,
+    model::work_unit::WorkUnit,
+    service::sync::{SyncProgress, SyncStatus},
+    Lb, Uuid,
+};
+use serde::Serialize;
+
+use crate::error::{CmdErr, CmdResult};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Human,
+    Json,
+    JsonLines,
+}
+
+static FORMAT: OnceLock<Format> = OnceLock::new();
+
+pub fn format() -> Format {
+    FORMAT.get().copied().unwrap_or_default()
+}
+
+pub fn json_flag() -> Flag<'static, bool> {
+    Flag::bool("json").description("print json instead of text")
+}
+
+pub fn jsonl_flag() -> Flag<'static, bool> {
+    Flag::bool("jsonl").description("print each update as a line of json as it happens")
+}
+
+/// Runs a command in the format its flags asked for. Errors are reported here rather than handed
+/// back to cli-rs so the exit code can say what went wrong.
+pub fn run(json: bool, cmd: impl FnOnce() -> CmdResult<()>) -> CliResult<()> {
+    run_streaming(json, false, cmd)
+}
+
+pub fn run_streaming(
+    json: bool, jsonl: bool, cmd: impl FnOnce() -> CmdResult<()>,
+) -> CliResult<()> {
+    let format = match (json, jsonl) {
+        (_, true) => Format::JsonLines,
+        (true, false) => Format::Json,
+        (false, false) => Format::Human,
+    };
+    let _ = FORMAT.set(format);
+
+    if let Err(err) = cmd() {
+        match format {
+            Format::Human => eprintln!("{}", err.msg),
+            Format::Json | Format::JsonLines => {
+                let err = ErrorOutput {
+                    error: ErrorInfo {
+                        kind: err.kind_name(),
+                        message: err.msg.clone(),
+                        exit_code: err.exit_code(),
+                    },
+                };
+                eprintln!("{}", to_json(&err));
+            }
+        }
+        process::exit(err.exit_code());
+    }
+    Ok(())
+}
+
+/// Prints a command's result: `value` as json, or whatever `human` prints
+pub fn print<T: Serialize>(value: &T, human: impl FnOnce(&T)) {
+    match format() {
+        Format::Human => human(value),
+        Format::Json | Format::JsonLines => println!("{}", to_json(value)),
+    }
+}
+
+/// Prints one of a stream of updates. `--json` leaves these out and prints only the result.
+pub fn update<T: Serialize>(value: &T, human: impl FnOnce(&T)) {
+    match format() {
+        Format::Human => human(value),
+        Format::Json => {}
+        Format::JsonLines => println!("{}", to_json(value)),
+    }
+}
+
+/// For commands that ask questions, which can't be answered by a script reading json
+pub fn interactive(command: &str) -> CmdResult<()> {
+    if format() != Format::Human {
+        return Err(CmdErr::from(format!("{command} asks questions, run it without --json")));
+    }
+    Ok(())
+}
+
+/// Narration like "syncing..." that only makes sense to a person
+pub fn status(msg: impl AsRef<str>) {
+    if format() == Format::Human {
+        println!("{}", msg.as_ref());
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> String {
+    // everything printed is plain structs with string keys, which can't fail to serialize
+    serde_json::to_string(value).unwrap()
+}
+
+#[derive(Serialize)]
+struct ErrorOutput {
+    error: ErrorInfo,
+}
+
+#[derive(Serialize)]
+struct ErrorInfo {
+    /// the lb error kind, absent when lb isn't why the command failed
+    kind: Option<String>,
+    message: String,
+    exit_code: i32,
+}
+
+#[derive(Serialize)]
+pub struct FileInfo {
+    pub id: Uuid,
+    pub parent: Uuid,
+    pub name: String,
+    pub path: String,
+    /// `document`, `folder` or `link`
+    pub file_type: &'static str,
+    pub last_modified: u64,
+    pub shares: Vec<ShareInfo>,
+}
+
+impl FileInfo {
+    pub async fn new(lb: &Lb, file: &File) -> CmdResult<Self> {
+        Ok(Self {
+            id: file.id,
+            parent: file.parent,
+            name: file.name.clone(),
+            path: lb.get_path_by_id(file.id).await?,
+            file_type: match file.file_type {
+                FileType::Document => "document",
+                FileType::Folder => "folder",
+                FileType::Link { .. } => "link",
+            },
+            last_modified: file.last_modified,
+            shares: file.shares.iter().map(ShareInfo::from).collect(),
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct ShareInfo {
+    pub shared_by: String,
+    pub shared_with: String,
+    /// `read` or `write`
+    pub mode: &'static str,
+}
+
+impl From<&Share> for ShareInfo {
+    fn from(share: &Share) -> Self {
+        Self {
+            shared_by: share.shared_by.clone(),
+            shared_with: share.shared_with.clone(),
+            mode: match share.mode {
+                ShareMode::Read => "read",
+                ShareMode::Write => "write",
+            },
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct SyncUpdate {
+    pub progress: usize,
+    pub total: usize,
+    pub file: Option<Uuid>,
+    pub msg: String,
+}
+
+impl From<SyncProgress> for SyncUpdate {
+    fn from(progress: SyncProgress) -> Self {
+        Self {
+            progress: progress.progress,
+            total: progress.total,
+            file: progress.file_being_processed,
+            msg: progress.msg,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct SyncSummary {
+    /// local changes sent to the server
+    pub pushed: usize,
+    /// changes from the server applied locally
+    pub pulled: usize,
+}
+
+impl From<&SyncStatus> for SyncSummary {
+    fn from(status: &SyncStatus) -> Self {
+        let pushed = status
+            .work_units
+            .iter()
+            .filter(|wu| matches!(wu, WorkUnit::LocalChange(_)))
+            .count();
+        Self { pushed, pulled: status.work_units.len() - pushed }
+    }
+}
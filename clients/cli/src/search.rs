@@ -0,0 +1,81 @@
+use lb_rs::service::search::{SearchConfig, SearchResult};
+use regex::RegexBuilder;
+use serde::Serialize;
+
+use crate::{
+    core, ensure_account_and_root,
+    error::{CmdErr, CmdResult},
+    input::FileInput,
+    output,
+};
+
+#[tokio::main]
+pub async fn search(query: String) -> CmdResult<()> {
+    let lb = &core().await?;
+    ensure_account_and_root(lb).await?;
+
+    let results = lb.search(&query, SearchConfig::PathsAndDocuments).await?;
+
+    output::print(&results, |results| {
+        for result in results {
+            match result {
+                SearchResult::PathMatch { path, .. } => println!("{path}"),
+                SearchResult::DocumentMatch { path, content_matches, .. } => {
+                    println!("{path}");
+                    for content_match in content_matches {
+                        println!("    {}", content_match.paragraph.replace('\n', " "));
+                    }
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+#[tokio::main]
+pub async fn grep(ignore_case: bool, pattern: String, target: FileInput) -> CmdResult<()> {
+    let lb = &core().await?;
+    ensure_account_and_root(lb).await?;
+
+    let regex = RegexBuilder::new(&pattern)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|err| CmdErr::from(format!("invalid regex: {err}")))?;
+
+    let target = target.find(lb).await?;
+    let mut docs = vec![];
+    for file in lb.get_and_get_children_recursively(&target.id).await? {
+        if file.is_document() {
+            docs.push((lb.get_path_by_id(file.id).await?, file.id));
+        }
+    }
+    docs.sort();
+
+    let mut matches = vec![];
+    for (path, id) in docs {
+        // binary documents like images can't match a line of text
+        let Ok(content) = String::from_utf8(lb.read_document(id, false).await?) else {
+            continue;
+        };
+        for (line_number, line) in content.lines().enumerate() {
+            if regex.is_match(line) {
+                let found =
+                    GrepMatch { path: path.clone(), line: line_number + 1, text: line.to_string() };
+                output::update(&found, |found| {
+                    println!("{}:{}: {}", found.path, found.line, found.text)
+                });
+                matches.push(found);
+            }
+        }
+    }
+
+    output::print(&matches, |_| {});
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct GrepMatch {
+    path: String,
+    line: usize,
+    text: String,
+}
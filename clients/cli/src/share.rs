@@ -1,5 +1,7 @@
+use std::time::Duration;
+
 use crate::core;
-use cli_rs::cli_error::{CliError, CliResult};
+use cli_rs::cli_error::CliResult;
 use lb_rs::{
     model::{
         file::{File, ShareMode},
@@ -8,36 +10,71 @@ use lb_rs::{
     Uuid,
 };
 
-use crate::{ensure_account_and_root, input::FileInput};
+use serde::Serialize;
+
+use crate::{
+    ensure_account_and_root,
+    error::{CmdErr, CmdResult},
+    input::FileInput,
+    output::{self, FileInfo},
+};
 
 #[tokio::main]
-pub async fn new(target: FileInput, username: String, read_only: bool) -> CliResult<()> {
+pub async fn new(target: FileInput, username: String, read_only: bool) -> CmdResult<()> {
     let lb = &core().await?;
     ensure_account_and_root(lb).await?;
 
     let id = target.find(lb).await?.id;
     let mode = if read_only { ShareMode::Read } else { ShareMode::Write };
     lb.share_file(id, &username, mode).await?;
-    println!("done!\nfile '{}' will be shared next time you sync.", id);
+
+    let shared = lb.get_file_by_id(id).await?;
+    output::print(&FileInfo::new(lb, &shared).await?, |_| {
+        println!("done!\nfile '{}' will be shared next time you sync.", id)
+    });
     Ok(())
 }
 
 #[tokio::main]
-pub async fn pending() -> CliResult<()> {
+pub async fn publish(target: FileInput, days: u64) -> CmdResult<()> {
+    let lb = &core().await?;
+    ensure_account_and_root(lb).await?;
+
+    let id = target.find(lb).await?.id;
+    let expires_in = (days > 0).then(|| Duration::from_secs(days * 24 * 60 * 60));
+    let link = lb.publish(id, expires_in).await?;
+    output::print(&PublishLink { link }, |published| println!("{}", published.link));
+    Ok(())
+}
+
+#[tokio::main]
+pub async fn unpublish(target: FileInput) -> CmdResult<()> {
+    let lb = &core().await?;
+    ensure_account_and_root(lb).await?;
+
+    let id = target.find(lb).await?.id;
+    lb.unpublish(id).await?;
+    Ok(())
+}
+
+#[tokio::main]
+pub async fn pending() -> CmdResult<()> {
     let lb = &core().await?;
     ensure_account_and_root(lb).await?;
 
     let pending_shares = to_share_infos(lb.get_pending_shares().await?);
-    if pending_shares.is_empty() {
-        println!("no pending shares.");
-        return Ok(());
-    }
-    print_share_infos(&pending_shares);
+    output::print(&pending_shares, |pending_shares| {
+        if pending_shares.is_empty() {
+            println!("no pending shares.");
+        } else {
+            print_share_infos(pending_shares);
+        }
+    });
     Ok(())
 }
 
 #[tokio::main]
-pub async fn accept(target: &Uuid, dest: FileInput) -> CliResult<()> {
+pub async fn accept(target: &Uuid, dest: FileInput) -> CmdResult<()> {
     let lb = &core().await?;
     ensure_account_and_root(lb).await?;
 
@@ -46,18 +83,20 @@ pub async fn accept(target: &Uuid, dest: FileInput) -> CliResult<()> {
         .await?
         .into_iter()
         .find(|f| f.id == *target)
-        .ok_or_else(|| CliError::from(format!("Could not find {target} in pending shares")))?;
+        .ok_or_else(|| CmdErr::from(format!("Could not find {target} in pending shares")))?;
     let parent = dest.find(lb).await?;
 
-    lb.create_file(&share.name, &parent.id, FileType::Link { target: share.id })
+    let link = lb
+        .create_file(&share.name, &parent.id, FileType::Link { target: share.id })
         .await
-        .map_err(|err| CliError::from(format!("{:?}", err)))?;
+        .map_err(|err| CmdErr { msg: format!("{:?}", err), kind: Some(err.kind) })?;
 
+    output::print(&FileInfo::new(lb, &link).await?, |_| {});
     Ok(())
 }
 
 #[tokio::main]
-pub async fn delete(target: Uuid) -> Result<(), CliError> {
+pub async fn delete(target: Uuid) -> CmdResult<()> {
     let lb = &core().await?;
     ensure_account_and_root(lb).await?;
 
@@ -66,7 +105,7 @@ pub async fn delete(target: Uuid) -> Result<(), CliError> {
         .await?
         .into_iter()
         .find(|f| f.id == target)
-        .ok_or_else(|| CliError::from(format!("Could not find {target} in pending shares")))?;
+        .ok_or_else(|| CmdErr::from(format!("Could not find {target} in pending shares")))?;
     lb.reject_share(&share.id).await?;
     Ok(())
 }
@@ -96,6 +135,7 @@ fn to_share_infos(files: Vec<File>) -> Vec<ShareInfo> {
     infos
 }
 
+#[derive(Serialize)]
 struct ShareInfo {
     id: Uuid,
     mode: String,
@@ -160,9 +200,14 @@ fn share_infos_table(infos: &[ShareInfo]) -> String {
     ret
 }
 
+#[derive(Serialize)]
+struct PublishLink {
+    link: String,
+}
+
 #[tokio::main]
 pub async fn pending_share_completor(prompt: &str) -> CliResult<Vec<String>> {
-    let lb = &core().await?;
+    let lb = &core().await.map_err(|err| err.msg)?;
     Ok(lb
         .get_pending_shares()
         .await?
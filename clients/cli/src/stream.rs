@@ -1,12 +1,12 @@
 use crate::core;
 use crate::ensure_account_and_root;
+use crate::error::CmdResult;
 use crate::input::FileInput;
-use cli_rs::cli_error::CliResult;
 use std::io;
 use std::io::{Read, Write};
 
 #[tokio::main]
-pub async fn stdin(target: FileInput, append: bool) -> CliResult<()> {
+pub async fn stdin(target: FileInput, append: bool) -> CmdResult<()> {
     let lb = &core().await?;
     ensure_account_and_root(lb).await?;
     let id = target.find(lb).await?.id;
@@ -27,7 +27,7 @@ pub async fn stdin(target: FileInput, append: bool) -> CliResult<()> {
 }
 
 #[tokio::main]
-pub async fn stdout(target: FileInput) -> CliResult<()> {
+pub async fn stdout(target: FileInput) -> CmdResult<()> {
     let lb = &core().await?;
     ensure_account_and_root(lb).await?;
 
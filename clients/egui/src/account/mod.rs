@@ -743,6 +743,9 @@ impl AccountScreen {
                 ImportStatus::FinishedItem(item) => {
                     println!("finished import of {} as lb://{}", item.name, item.id);
                 }
+                ImportStatus::Unconverted(item) => {
+                    println!("not converted: {}: {}", item.source.display(), item.detail);
+                }
             });
 
             let all_metas = core.list_metadatas().unwrap();
@@ -206,7 +206,7 @@ impl super::AccountScreen {
 
         thread::spawn(move || {
             let _lock = sync_lock.lock().unwrap();
-            if let Err(err) = core.sync(None) {
+            if let Err(err) = core.sync_now(None) {
                 eprintln!("error: final sync: {:?}", err);
             }
             update_tx.send(AccountUpdate::FinalSyncAttemptDone).unwrap();
@@ -554,7 +554,7 @@ You can view your key again in the settings."#
                 }
             };
 
-            match core.sync(Some(Box::new(closure))) {
+            match core.sync_now(Some(Box::new(closure))) {
                 Ok(_acct) => {
                     tx.send(Update::ImportSyncDone(None)).unwrap();
                     tx.send(Update::AccountDataLoaded(load_account_data(&core)))
@@ -0,0 +1,39 @@
+use lb::model::errors::LbErr;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Ends the helper; git shows it to the user alongside its own error
+#[derive(Debug)]
+pub struct Error(pub String);
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<String> for Error {
+    fn from(err: String) -> Self {
+        Self(err)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(err: &str) -> Self {
+        Self(err.to_string())
+    }
+}
+
+impl From<LbErr> for Error {
+    fn from(err: LbErr) -> Self {
+        Self(format!("lockbook: {err}"))
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self(err.to_string())
+    }
+}
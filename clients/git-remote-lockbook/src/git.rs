@@ -0,0 +1,97 @@
+//! The git plumbing the helper shells out to. Anything git prints to stdout is captured: stdout is
+//! how the helper talks to git.
+
+use crate::error::Result;
+use std::env;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// The repository's `.git`, which git hands helpers in `GIT_DIR`
+pub fn dir() -> Result<PathBuf> {
+    match env::var_os("GIT_DIR") {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => Ok(PathBuf::from(stdout(run(&["rev-parse", "--git-dir"], None)?)?)),
+    }
+}
+
+/// The object a local ref or revision points at
+pub fn rev_parse(rev: &str) -> Result<String> {
+    stdout(run(&["rev-parse", "--verify", rev], None)?)
+}
+
+pub fn has_object(sha: &str) -> Result<bool> {
+    Ok(run(&["cat-file", "-e", sha], None)?.status.success())
+}
+
+pub fn is_ancestor(ancestor: &str, descendant: &str) -> Result<bool> {
+    Ok(run(&["merge-base", "--is-ancestor", ancestor, descendant], None)?
+        .status
+        .success())
+}
+
+/// A self-contained pack of everything reachable from `include` that isn't reachable from
+/// `exclude`
+pub fn pack_objects(include: &[String], exclude: &[String]) -> Result<Vec<u8>> {
+    let revs = include
+        .iter()
+        .cloned()
+        .chain(exclude.iter().map(|sha| format!("^{sha}")))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let output = run(&["pack-objects", "--stdout", "--revs", "-q"], Some(revs.as_bytes()))?;
+    if !output.status.success() {
+        return Err("git pack-objects failed".into());
+    }
+    Ok(output.stdout)
+}
+
+/// Adds a pack's objects to the repository
+pub fn index_pack(pack: &[u8]) -> Result<()> {
+    if !run(&["index-pack", "--stdin"], Some(pack))?
+        .status
+        .success()
+    {
+        return Err("git index-pack failed".into());
+    }
+    Ok(())
+}
+
+/// How many objects a pack holds, from its header
+pub fn pack_object_count(pack: &[u8]) -> u32 {
+    pack.get(8..12)
+        .map(|count| u32::from_be_bytes(count.try_into().unwrap()))
+        .unwrap_or_default()
+}
+
+/// The checksum git ends every pack with, which also names it in `.git/objects/pack`
+pub fn pack_name(pack: &[u8]) -> String {
+    pack[pack.len().saturating_sub(20)..]
+        .iter()
+        .fold(String::new(), |mut name, byte| {
+            let _ = write!(name, "{byte:02x}");
+            name
+        })
+}
+
+fn run(args: &[&str], input: Option<&[u8]>) -> Result<Output> {
+    let mut child = Command::new("git")
+        .args(args)
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()?;
+    if let Some(input) = input {
+        // dropped once written so git sees the end of its input
+        child.stdin.take().unwrap().write_all(input)?;
+    }
+    Ok(child.wait_with_output()?)
+}
+
+fn stdout(output: Output) -> Result<String> {
+    if !output.status.success() {
+        return Err("git command failed".into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -0,0 +1,140 @@
+//! A git remote helper that stores repositories in lockbook folders, end-to-end encrypted and
+//! readable by anyone the folder is shared with:
+//!
+//! ```sh
+//! git remote add lockbook lockbook::/runbooks
+//! git push lockbook main
+//! git clone lockbook::/runbooks
+//! ```
+//!
+//! It uses the account the lockbook cli is signed into. The folder holds a `refs` document and a
+//! `packs` folder with a pack of new objects for every push. Git runs the helper and talks to it
+//! over stdin and stdout, see gitremote-helpers(7).
+
+mod error;
+mod git;
+mod remote;
+
+use crate::error::Result;
+use crate::remote::{PushSpec, Remote};
+use lb::blocking::Lb;
+use lb::model::core_config::Config;
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("fatal: {err}");
+        process::exit(128);
+    }
+}
+
+fn run() -> Result<()> {
+    // git passes the remote's name, or the url when there's no remote, and then the url
+    let args = env::args().collect::<Vec<_>>();
+    let url = match args.as_slice() {
+        [_, _, url] | [_, url] => url.trim_start_matches("lockbook://"),
+        _ => return Err("usage: git-remote-lockbook <remote> <url>".into()),
+    };
+
+    let lb = Lb::init(Config::cli_config("cli"))?; // use the cli account
+    let remote = Remote::open(lb, url)?;
+
+    let mut lines = io::stdin().lock().lines();
+    let mut out = io::stdout().lock();
+    while let Some(line) = lines.next() {
+        let line = line?;
+        match line
+            .split_once(' ')
+            .map_or(line.as_str(), |(command, _)| command)
+        {
+            // git is done with us
+            "" => return Ok(()),
+            "capabilities" => writeln!(out, "fetch\npush\n")?,
+            "list" => writeln!(out, "{}", remote.refs()?)?,
+            "fetch" => {
+                batch(line, &mut lines)?;
+                remote.fetch()?;
+                writeln!(out)?;
+            }
+            "push" => {
+                let specs = batch(line, &mut lines)?
+                    .iter()
+                    .map(|line| push_spec(line))
+                    .collect::<Result<Vec<_>>>()?;
+                for (dst, result) in remote.push(&specs)? {
+                    match result {
+                        Ok(()) => writeln!(out, "ok {dst}")?,
+                        Err(reason) => writeln!(out, "error {dst} {reason}")?,
+                    }
+                }
+                writeln!(out)?;
+            }
+            _ => return Err(format!("unsupported command: {line}").into()),
+        }
+        out.flush()?;
+    }
+    Ok(())
+}
+
+/// `fetch` and `push` come in batches of lines ending with a blank one
+fn batch(
+    first: String, lines: &mut impl Iterator<Item = io::Result<String>>,
+) -> Result<Vec<String>> {
+    let mut batch = vec![first];
+    for line in lines {
+        let line = line?;
+        if line.is_empty() {
+            break;
+        }
+        batch.push(line);
+    }
+    Ok(batch)
+}
+
+/// `push [+]<src>:<dst>`
+fn push_spec(line: &str) -> Result<PushSpec> {
+    let spec = line.trim_start_matches("push ");
+    let (force, spec) = match spec.strip_prefix('+') {
+        Some(spec) => (true, spec),
+        None => (false, spec),
+    };
+    let (src, dst) = spec
+        .split_once(':')
+        .ok_or_else(|| format!("malformed push: {line}"))?;
+    Ok(PushSpec { src: src.to_string(), dst: dst.to_string(), force })
+}
+
+#[cfg(test)]
+mod test {
+    use super::push_spec;
+
+    #[test]
+    fn push_specs() {
+        let spec = push_spec("push refs/heads/main:refs/heads/main").unwrap();
+        assert_eq!(
+            (spec.src.as_str(), spec.dst.as_str(), spec.force),
+            ("refs/heads/main", "refs/heads/main", false)
+        );
+
+        let spec = push_spec("push +refs/heads/topic:refs/heads/main").unwrap();
+        assert_eq!(
+            (spec.src.as_str(), spec.dst.as_str(), spec.force),
+            ("refs/heads/topic", "refs/heads/main", true)
+        );
+    }
+
+    #[test]
+    fn push_spec_deletions() {
+        let spec = push_spec("push :refs/heads/old").unwrap();
+        assert!(spec.src.is_empty());
+        assert_eq!(spec.dst, "refs/heads/old");
+        assert!(!spec.force);
+    }
+
+    #[test]
+    fn push_spec_needs_a_destination() {
+        assert!(push_spec("push refs/heads/main").is_err());
+    }
+}
@@ -0,0 +1,363 @@
+use crate::error::{Error, Result};
+use crate::git;
+use lb::blocking::Lb;
+use lb::model::errors::LbErrKind;
+use lb::model::file::File;
+use lb::model::file_metadata::DocumentHmac;
+use lb::model::filename::NameComponents;
+use lb::service::conflicts::ConflictResolution;
+use lb::Uuid;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::PathBuf;
+
+/// The refs document, in the format `list` answers git with
+const REFS: &str = "refs";
+
+/// One pack per push, named by its checksum
+const PACKS: &str = "packs";
+
+/// The default branch of a repository whose first push didn't include the current branch
+const DEFAULT_BRANCHES: [&str; 2] = ["refs/heads/main", "refs/heads/master"];
+
+/// A git repository stored in a lockbook folder
+pub struct Remote {
+    lb: Lb,
+
+    /// the folder's path, ending in `/`
+    path: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Refs {
+    /// the ref `HEAD` points at
+    pub head: Option<String>,
+    /// ref name to object id
+    pub refs: BTreeMap<String, String>,
+}
+
+struct RefsDocument {
+    id: Uuid,
+    hmac: Option<DocumentHmac>,
+    refs: Refs,
+}
+
+/// A ref's outcome, `Err` holding the reason git shows for rejecting it
+pub type PushResult = std::result::Result<(), String>;
+
+/// `[+]<src>:<dst>`, one line of a push batch. `src` is empty for deletions.
+pub struct PushSpec {
+    pub src: String,
+    pub dst: String,
+    pub force: bool,
+}
+
+impl Remote {
+    /// Syncs so what git sees is what's on the server, not what this device last saw
+    pub fn open(lb: Lb, path: &str) -> Result<Self> {
+        lb.sync(None)?;
+        let path = format!("/{}/", path.trim_matches('/'));
+        Ok(Self { lb, path })
+    }
+
+    pub fn refs(&self) -> Result<Refs> {
+        Ok(self
+            .refs_document()?
+            .map(|doc| doc.refs)
+            .unwrap_or_default())
+    }
+
+    /// Adds every pack this repository hasn't seen yet to it. Git checks it has what it asked for
+    /// afterwards, so there's no need to work out which packs hold the wanted objects.
+    pub fn fetch(&self) -> Result<()> {
+        let Some(packs) = self.find(&format!("{}{PACKS}/", self.path))? else {
+            return Ok(());
+        };
+
+        let mut fetched = self.fetched()?;
+        for pack in self.lb.get_children(&packs.id)? {
+            if fetched.contains(&pack.name) {
+                continue;
+            }
+            git::index_pack(&self.lb.read_document(pack.id, false)?)?;
+            fetched.insert(pack.name);
+            self.save_fetched(&fetched)?;
+        }
+        Ok(())
+    }
+
+    /// Uploads the objects the pushed refs need in one pack, then updates the refs document
+    /// unless someone else updated it first
+    pub fn push(&self, specs: &[PushSpec]) -> Result<Vec<(String, PushResult)>> {
+        let doc = self.refs_document()?;
+        let old = doc.as_ref().map(|doc| doc.refs.clone()).unwrap_or_default();
+        let mut new = old.clone();
+
+        let mut results = vec![];
+        let mut pushed = vec![];
+        for spec in specs {
+            let result = self.update_ref(&old, &mut new, spec);
+            if result.is_ok() {
+                pushed.push(spec.dst.clone());
+            }
+            results.push((spec.dst.clone(), result));
+        }
+        if pushed.is_empty() {
+            return Ok(results);
+        }
+
+        let include = pushed
+            .iter()
+            .filter_map(|dst| new.refs.get(dst).cloned())
+            .collect::<Vec<_>>();
+        let mut exclude = vec![];
+        for sha in old.refs.values() {
+            if git::has_object(sha)? {
+                exclude.push(sha.clone());
+            }
+        }
+        if !include.is_empty() {
+            self.upload_pack(&include, &exclude)?;
+        }
+
+        if new
+            .head
+            .as_ref()
+            .map_or(true, |head| !new.refs.contains_key(head))
+        {
+            new.head = DEFAULT_BRANCHES
+                .iter()
+                .map(|branch| branch.to_string())
+                .chain(pushed.iter().cloned())
+                .find(|branch| branch.starts_with("refs/heads/") && new.refs.contains_key(branch));
+        }
+
+        let (id, written) = match doc {
+            Some(doc) => (doc.id, self.lb.safe_write(doc.id, doc.hmac, new.to_string().into())),
+            None => {
+                let id = self.lb.create_at_path(&format!("{}{REFS}", self.path))?.id;
+                (id, self.lb.safe_write(id, None, new.to_string().into()))
+            }
+        };
+        match written {
+            Ok(_) => {}
+            Err(err) if err.kind == LbErrKind::ReReadRequired => {
+                return Ok(reject(results, "fetch first"));
+            }
+            Err(err) => return Err(err.into()),
+        }
+
+        // a push that raced ours reached the server first, and sync doesn't fail because of it:
+        // it either merges the two refs documents or keeps theirs and saves ours as a copy
+        self.lb.sync(None)?;
+        if self.undo_lost_push(id, &new)? {
+            return Ok(reject(results, "fetch first"));
+        }
+        let synced = self.refs()?;
+        for (dst, result) in &mut results {
+            if result.is_ok() && synced.refs.get(dst) != new.refs.get(dst) {
+                *result = Err("fetch first".to_string());
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Puts the refs document back the way the push that beat ours left it, so our refs don't
+    /// end up mixed into theirs. Returns whether there was anything to undo.
+    fn undo_lost_push(&self, refs: Uuid, ours: &Refs) -> Result<bool> {
+        let mut undone = false;
+        if self
+            .lb
+            .list_conflicts()?
+            .iter()
+            .any(|conflict| conflict.id == refs)
+        {
+            self.lb
+                .resolve_conflict(refs, ConflictResolution::KeepRemote)?;
+            undone = true;
+        }
+
+        let folder = self.lb.get_file_by_id(refs)?.parent;
+        for file in self.lb.get_children(&folder)? {
+            let name = NameComponents::from(&file.name);
+            if !file.is_document() || name.name != REFS || name.variant.is_none() {
+                continue;
+            }
+            let content = self.lb.read_document(file.id, false)?;
+            if String::from_utf8_lossy(&content)
+                .parse::<Refs>()
+                .ok()
+                .as_ref()
+                == Some(ours)
+            {
+                self.lb.delete_file(&file.id)?;
+                undone = true;
+            }
+        }
+
+        if undone {
+            self.lb.sync(None)?;
+        }
+        Ok(undone)
+    }
+
+    fn update_ref(&self, old: &Refs, new: &mut Refs, spec: &PushSpec) -> PushResult {
+        if spec.src.is_empty() {
+            new.refs.remove(&spec.dst);
+            return Ok(());
+        }
+
+        let sha = git::rev_parse(&spec.src).map_err(|err| err.to_string())?;
+        if let Some(old_sha) = old.refs.get(&spec.dst) {
+            if !spec.force && old_sha != &sha {
+                let known = git::has_object(old_sha).map_err(|err| err.to_string())?;
+                if !known {
+                    return Err("fetch first".to_string());
+                }
+                if !git::is_ancestor(old_sha, &sha).map_err(|err| err.to_string())? {
+                    return Err("non-fast-forward".to_string());
+                }
+            }
+        }
+        new.refs.insert(spec.dst.clone(), sha);
+        Ok(())
+    }
+
+    fn upload_pack(&self, include: &[String], exclude: &[String]) -> Result<()> {
+        let pack = git::pack_objects(include, exclude)?;
+        if git::pack_object_count(&pack) == 0 {
+            return Ok(());
+        }
+
+        let name = format!("{}.pack", git::pack_name(&pack));
+        let path = format!("{}{PACKS}/{name}", self.path);
+        if self.find(&path)?.is_none() {
+            let file = self.lb.create_at_path(&path)?;
+            self.lb.write_document(file.id, &pack)?;
+        }
+
+        // this repository already has everything in it
+        let mut fetched = self.fetched()?;
+        fetched.insert(name);
+        self.save_fetched(&fetched)
+    }
+
+    fn refs_document(&self) -> Result<Option<RefsDocument>> {
+        let Some(file) = self.find(&format!("{}{REFS}", self.path))? else {
+            return Ok(None);
+        };
+        let (hmac, content) = self.lb.read_document_with_hmac(file.id, false)?;
+        let refs = String::from_utf8_lossy(&content).parse()?;
+        Ok(Some(RefsDocument { id: file.id, hmac, refs }))
+    }
+
+    fn find(&self, path: &str) -> Result<Option<File>> {
+        match self.lb.get_by_path(path) {
+            Ok(file) => Ok(Some(file)),
+            Err(err) if err.kind == LbErrKind::FileNonexistent => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// The packs this repository has already indexed, recorded in `.git` by the folder's id
+    fn fetched(&self) -> Result<HashSet<String>> {
+        match fs::read_to_string(self.fetched_path()?) {
+            Ok(fetched) => Ok(fetched.lines().map(String::from).collect()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(HashSet::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn save_fetched(&self, fetched: &HashSet<String>) -> Result<()> {
+        let path = self.fetched_path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut fetched = fetched.iter().cloned().collect::<Vec<_>>();
+        fetched.sort();
+        fs::write(path, fetched.join("\n"))?;
+        Ok(())
+    }
+
+    fn fetched_path(&self) -> Result<PathBuf> {
+        let folder = self
+            .find(&self.path)?
+            .ok_or_else(|| Error(format!("{} does not exist in lockbook", self.path)))?;
+        Ok(git::dir()?.join("lockbook").join(folder.id.to_string()))
+    }
+}
+
+fn reject(results: Vec<(String, PushResult)>, reason: &str) -> Vec<(String, PushResult)> {
+    results
+        .into_iter()
+        .map(|(dst, result)| (dst, result.and(Err(reason.to_string()))))
+        .collect()
+}
+
+impl Display for Refs {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if let Some(head) = &self.head {
+            writeln!(f, "@{head} HEAD")?;
+        }
+        for (name, sha) in &self.refs {
+            writeln!(f, "{sha} {name}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Refs {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut refs = Refs::default();
+        for line in s.lines().filter(|line| !line.is_empty()) {
+            let (value, name) = line
+                .split_once(' ')
+                .ok_or_else(|| Error(format!("malformed refs document line: {line}")))?;
+            match (value.strip_prefix('@'), name) {
+                (Some(target), "HEAD") => refs.head = Some(target.to_string()),
+                _ => {
+                    refs.refs.insert(name.to_string(), value.to_string());
+                }
+            }
+        }
+        Ok(refs)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Refs;
+
+    #[test]
+    fn refs_round_trip() {
+        let text = "@refs/heads/main HEAD\n\
+                    1111111111111111111111111111111111111111 refs/heads/main\n\
+                    2222222222222222222222222222222222222222 refs/tags/v1\n";
+        let refs = text.parse::<Refs>().unwrap();
+
+        assert_eq!(refs.head.as_deref(), Some("refs/heads/main"));
+        assert_eq!(refs.refs.len(), 2);
+        assert_eq!(refs.refs["refs/tags/v1"], "2222222222222222222222222222222222222222");
+        assert_eq!(refs.to_string(), text);
+    }
+
+    #[test]
+    fn refs_skip_blank_lines() {
+        let refs = "\n1111111111111111111111111111111111111111 refs/heads/main\n\n"
+            .parse::<Refs>()
+            .unwrap();
+
+        assert_eq!(refs.head, None);
+        assert_eq!(refs.refs.len(), 1);
+        assert_eq!("".parse::<Refs>().unwrap(), Refs::default());
+    }
+
+    #[test]
+    fn refs_reject_malformed_lines() {
+        assert!("refs/heads/main".parse::<Refs>().is_err());
+    }
+}
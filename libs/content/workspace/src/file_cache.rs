@@ -5,6 +5,7 @@ use lb_rs::blocking::Lb;
 use lb_rs::model::errors::LbResult;
 use lb_rs::model::file::File;
 use lb_rs::model::file_metadata::FileType;
+use lb_rs::service::events::Event;
 use lb_rs::service::usage::UsageMetrics;
 use lb_rs::Uuid;
 
@@ -26,6 +27,34 @@ impl FileCache {
             usage: lb.get_usage()?,
         })
     }
+
+    /// Applies a change to the cached files without re-listing them. Returns false if the event
+    /// doesn't describe the change precisely enough and the cache needs a full refresh.
+    pub fn apply(&mut self, lb: &Lb, event: &Event) -> bool {
+        match event {
+            Event::MetadataChanged(_) => return false,
+            Event::ConflictDetected(_) => {}
+            Event::FileDeleted(id) => {
+                let mut removed: Vec<Uuid> =
+                    self.files.descendents(*id).iter().map(|f| f.id).collect();
+                removed.push(*id);
+                self.files.retain(|f| !removed.contains(&f.id));
+            }
+            Event::FileCreated(id)
+            | Event::FileMoved { id, .. }
+            | Event::FileRenamed { id, .. }
+            | Event::DocumentWritten(id)
+            | Event::ShareAdded(id)
+            | Event::ShareRemoved(id) => match lb.get_file_by_id(*id) {
+                Ok(file) => match self.files.iter_mut().find(|f| f.id == *id) {
+                    Some(cached) => *cached = file,
+                    None => self.files.push(file),
+                },
+                Err(_) => self.files.retain(|f| f.id != *id),
+            },
+        }
+        true
+    }
 }
 
 impl Debug for FileCache {
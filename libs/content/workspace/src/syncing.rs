@@ -1,6 +1,8 @@
-use lb_rs::model::errors::LbErrKind;
+use lb_rs::model::errors::{LbErr, LbErrKind, LbResult};
 use lb_rs::model::work_unit::WorkUnit;
 use lb_rs::service::sync::SyncStatus;
+use lb_rs::service::sync_scheduler::BackgroundSyncStatus;
+use std::time::Instant;
 use tracing::{debug, error};
 
 use crate::task_manager::{CompletedSync, CompletedSyncStatusUpdate};
@@ -9,16 +11,35 @@ use crate::workspace::Workspace;
 impl Workspace {
     pub fn sync_done(&mut self, outcome: CompletedSync) {
         let CompletedSync { status_result, timing } = outcome;
+        self.handle_sync_result(status_result, timing.completed_at);
+    }
+
+    /// Handles syncs lb performed on its own schedule. Syncs we requested are reported to lb as
+    /// well, but they complete before we hear about them and are skipped here.
+    pub fn background_sync_done(&mut self, status: BackgroundSyncStatus) {
+        let (Some(result), Some(completed_at)) = (status.last_sync, status.last_sync_at) else {
+            return;
+        };
+        if self
+            .last_sync_completed
+            .map(|last| last >= completed_at)
+            .unwrap_or_default()
+        {
+            return;
+        }
+
+        self.handle_sync_result(result.map_err(LbErr::from), completed_at);
+    }
 
+    fn handle_sync_result(&mut self, status_result: LbResult<SyncStatus>, completed_at: Instant) {
         self.out.status_updated = true;
-        self.last_sync_completed = Some(timing.completed_at);
+        self.last_sync_completed = Some(completed_at);
         match status_result {
             Ok(done) => {
                 self.status.sync_error = None;
                 self.status.sync_message = None;
 
                 self.tasks.queue_sync_status_update();
-                self.tasks.queue_file_cache_refresh();
                 self.refresh_files(&done);
                 self.out.sync_done = Some(done);
             }
@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 
-use crate::tab::markdown_editor::appearance::{GRAY, YELLOW};
+use crate::tab::markdown_editor::appearance::{
+    BLUE, BROWN, GRAY, GREEN, INDIGO, ORANGE, PINK, PURPLE, TEAL, YELLOW,
+};
 use crate::tab::markdown_editor::bounds::RangesExt;
 use crate::tab::markdown_editor::images::ImageState;
 use crate::tab::markdown_editor::input::{Event, Location, Region};
@@ -465,6 +467,33 @@ impl Editor {
         }
     }
 
+    pub fn draw_collaborators(&self, ui: &mut Ui) {
+        let Some(collab) = &self.collab else { return };
+        let palette = [BLUE, GREEN, ORANGE, PURPLE, PINK, TEAL, INDIGO, BROWN];
+        let end = self.buffer.current.segs.last_cursor_position();
+
+        for presence in collab.presence() {
+            let color_idx = presence
+                .username
+                .bytes()
+                .fold(0usize, |acc, b| acc.wrapping_mul(31).wrapping_add(b as usize));
+            let color = palette[color_idx % palette.len()].get(self.appearance.current_theme);
+
+            // presence reflects the latest collaborative view which may be a frame ahead of us
+            let offset = presence.selection.1.min(end);
+            let line = cursor::line(offset, &self.galleys, &self.bounds.text, &self.appearance);
+            ui.painter()
+                .line_segment(line, Stroke { width: 2.0, color });
+            ui.painter().text(
+                line[0],
+                Align2::LEFT_BOTTOM,
+                &presence.username,
+                FontId::proportional(10.0),
+                color,
+            );
+        }
+    }
+
     pub fn draw_debug(&self, ui: &mut Ui) {
         for galley in &self.galleys.galleys {
             let galley_rect = galley.galley.rect.translate(galley.text_location.to_vec2());
@@ -7,6 +7,7 @@ use lb_rs::blocking::Lb;
 use lb_rs::model::file_metadata::DocumentHmac;
 use lb_rs::model::text::buffer::Buffer;
 use lb_rs::model::text::offset_types::{DocCharOffset, RangeExt as _};
+use lb_rs::service::collab::{CollabSession, CollabStatus};
 use lb_rs::Uuid;
 
 use crate::tab::markdown_editor;
@@ -67,6 +68,9 @@ pub struct Editor {
     pub find: Find,
     pub event: EventState,
 
+    /// Set when editing a shared document with collaborators
+    pub collab: Option<CollabSession>,
+
     pub virtual_keyboard_shown: bool,
 }
 
@@ -97,6 +101,8 @@ impl Editor {
             find: Default::default(),
             event: Default::default(),
 
+            collab: None,
+
             virtual_keyboard_shown: false,
         }
     }
@@ -109,6 +115,24 @@ impl Editor {
         self.buffer.reload(text)
     }
 
+    /// Whether changes are currently exchanged through a collaborative editing session rather than
+    /// through saving and syncing
+    pub fn collab_active(&self) -> bool {
+        self.collab
+            .as_ref()
+            .map(|collab| collab.status() == CollabStatus::Joined)
+            .unwrap_or_default()
+    }
+
+    /// Whether this editor should save its document. In a collaborative session, one participant
+    /// saves on behalf of everyone.
+    pub fn persists(&self) -> bool {
+        self.collab
+            .as_ref()
+            .map(|collab| collab.persists())
+            .unwrap_or(true)
+    }
+
     pub fn id(&self) -> Id {
         Id::new(self.file_id)
     }
@@ -257,15 +281,35 @@ impl Editor {
         let prior_suggested_title = self.get_suggested_title();
         let prior_selection = self.buffer.current.selection;
 
+        // collaborators' changes are merged the same way as changes made outside the editor
+        let mut collab_updated = false;
+        if let Some(collab) = &self.collab {
+            if let Some(text) = collab.take_view() {
+                self.buffer.reload(text);
+                collab_updated = self.buffer.update().into();
+            }
+        }
+
         // process events
         let text_updated = if self.initialized {
             self.process_events(ui.ctx())
         } else {
             self.initialized = true;
             true
-        };
+        } || collab_updated;
         let selection_updated = prior_selection != self.buffer.current.selection;
 
+        if let Some(collab) = &self.collab {
+            if text_updated && collab.edit(&self.buffer.current.text) {
+                // what collaborators have is the new reference point for merging their changes
+                self.buffer
+                    .saved(self.buffer.current.seq, self.buffer.current.text.clone());
+            }
+            if text_updated || selection_updated {
+                collab.set_selection(self.buffer.current.selection);
+            }
+        }
+
         // recalculate dependent state
         if text_updated {
             self.ast = ast::calc(&self.buffer);
@@ -327,6 +371,7 @@ impl Editor {
 
         // draw
         self.draw_text(ui);
+        self.draw_collaborators(ui);
         if self.focused(ui.ctx()) && !cfg!(target_os = "ios") {
             self.draw_cursor(ui, touch_mode);
         }
@@ -751,7 +751,7 @@ impl TaskManager {
                 sender.send(p).unwrap();
                 ctx.request_repaint();
             };
-            self.core.sync(Some(Box::new(progress_closure)))
+            self.core.sync_now(Some(Box::new(progress_closure)))
         };
 
         {
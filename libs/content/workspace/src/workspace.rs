@@ -6,6 +6,9 @@ use lb_rs::model::file_metadata::FileType;
 use lb_rs::model::filename::NameComponents;
 use lb_rs::model::svg;
 use lb_rs::model::svg::buffer::Buffer;
+use lb_rs::service::collab::CollabSession;
+use lb_rs::service::events::{Event, Receiver, TryRecvError};
+use lb_rs::service::sync_scheduler::SyncStatusWatch;
 use lb_rs::Uuid;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
@@ -36,6 +39,8 @@ pub struct Workspace {
     // Files and task status
     pub tasks: TaskManager,
     pub files: Option<FileCache>,
+    pub file_events: Receiver<Event>,
+    pub background_sync: SyncStatusWatch,
     pub last_save_all: Option<Instant>,
     pub last_sync_completed: Option<Instant>,
     pub last_sync_status_refresh_completed: Option<Instant>,
@@ -69,6 +74,8 @@ impl Workspace {
 
             tasks: TaskManager::new(core.clone(), ctx.clone()),
             files: None,
+            file_events: core.subscribe(),
+            background_sync: core.background_sync_status(),
             last_sync_completed: Default::default(),
             last_save_all: Default::default(),
             last_sync_status_refresh_completed: Default::default(),
@@ -223,7 +230,8 @@ impl Workspace {
     pub fn save_tab(&mut self, i: usize) {
         if let Some(tab) = self.tabs.get_mut(i) {
             if let Some(id) = tab.id() {
-                if tab.is_dirty(&self.tasks) {
+                let persists = tab.markdown().map(|md| md.persists()).unwrap_or(true);
+                if persists && tab.is_dirty(&self.tasks) {
                     self.tasks.queue_save(SaveRequest { id });
                 }
             }
@@ -359,18 +367,22 @@ impl Workspace {
                             }
                         } else if ext == "md" || ext == "txt" {
                             if tab_created {
-                                tab.content =
-                                    ContentState::Open(TabContent::Markdown(Markdown::new(
-                                        core.clone(),
-                                        &String::from_utf8_lossy(&bytes),
-                                        id,
-                                        maybe_hmac,
-                                        is_new_file,
-                                        ext != "md",
-                                    )));
+                                let mut md = Markdown::new(
+                                    core.clone(),
+                                    &String::from_utf8_lossy(&bytes),
+                                    id,
+                                    maybe_hmac,
+                                    is_new_file,
+                                    ext != "md",
+                                );
+                                md.collab = join_collab_if_shared(&core, &ctx, id);
+                                tab.content = ContentState::Open(TabContent::Markdown(md));
                             } else {
                                 let md = tab.markdown_mut().unwrap();
-                                md.reload(String::from_utf8_lossy(&bytes).into());
+                                // collaborators' changes already arrived through the session
+                                if !md.collab_active() {
+                                    md.reload(String::from_utf8_lossy(&bytes).into());
+                                }
                                 md.hmac = maybe_hmac;
                             }
                         } else {
@@ -402,7 +414,6 @@ impl Workspace {
                         timing: CompletedTiming { queued_at: _, started_at, completed_at: _ },
                     } = save;
 
-                    let mut sync = false;
                     if let Some(tab) = self.get_mut_tab_by_id(id) {
                         match new_hmac_result {
                             Ok(hmac) => {
@@ -410,7 +421,10 @@ impl Workspace {
                                 if let Some(md) = tab.markdown_mut() {
                                     if let TabSaveContent::String(content) = content {
                                         md.hmac = Some(hmac);
-                                        md.buffer.saved(seq, content);
+                                        // a collaborative session tracks its own reference point
+                                        if !md.collab_active() {
+                                            md.buffer.saved(seq, content);
+                                        }
                                     }
                                 } else if let Some(svg) = tab.svg_mut() {
                                     if let TabSaveContent::Svg(content) = content {
@@ -418,7 +432,6 @@ impl Workspace {
                                         svg.opened_content = content;
                                     }
                                 }
-                                sync = true;
                             }
                             Err(err) => {
                                 if err.kind == LbErrKind::ReReadRequired {
@@ -432,9 +445,6 @@ impl Workspace {
                             }
                         }
                     }
-                    if sync {
-                        self.tasks.queue_sync();
-                    }
                     self.tasks.queue_file_cache_refresh();
                 }
             }
@@ -447,6 +457,13 @@ impl Workspace {
         }
         start.warn_after("processing completed sync", Duration::from_millis(100));
 
+        let start = Instant::now();
+        if self.background_sync.has_changed().unwrap_or_default() {
+            let status = self.background_sync.borrow_and_update().clone();
+            self.background_sync_done(status);
+        }
+        start.warn_after("processing background sync", Duration::from_millis(100));
+
         let start = Instant::now();
         if let Some(update) = completed_sync_status_update {
             self.sync_status_update_done(update)
@@ -462,6 +479,26 @@ impl Workspace {
         }
         start.warn_after("processing completed file cache refresh", Duration::from_millis(100));
 
+        let start = Instant::now();
+        let mut refresh_file_cache = false;
+        loop {
+            match self.file_events.try_recv() {
+                Ok(event) => {
+                    if let Some(files) = &mut self.files {
+                        if !files.apply(&self.core, &event) {
+                            refresh_file_cache = true;
+                        }
+                    }
+                }
+                Err(TryRecvError::Lagged(_)) => refresh_file_cache = true,
+                Err(_) => break,
+            }
+        }
+        if refresh_file_cache {
+            self.tasks.queue_file_cache_refresh();
+        }
+        start.warn_after("processing file events", Duration::from_millis(100));
+
         let start = Instant::now();
         {
             let tasks = self.tasks.tasks.lock().unwrap();
@@ -514,27 +551,21 @@ impl Workspace {
         }
         start.warn_after("processing auto save", Duration::from_millis(100));
 
+        // syncs themselves are scheduled by lb, we just tell it how eager to be
         let start = Instant::now();
         if self.cfg.get_auto_sync() {
-            if let Some(last_sync) = self.tasks.sync_queued_at().or(self.last_sync_completed) {
-                let focused = self.ctx.input(|i| i.focused);
-                let user_active = self.user_last_seen.elapsed() < Duration::from_secs(60);
-                let sync_period = if user_active && focused {
-                    Duration::from_secs(5)
-                } else {
-                    Duration::from_secs(5 * 60)
-                };
-
-                let instant_of_next_sync = last_sync + sync_period;
-                if instant_of_next_sync < now {
-                    self.tasks.queue_sync();
-                } else {
-                    let duration_until_next_sync = instant_of_next_sync - now;
-                    self.ctx.request_repaint_after(duration_until_next_sync);
-                }
+            let focused = self.ctx.input(|i| i.focused);
+            let user_active = self.user_last_seen.elapsed() < Duration::from_secs(60);
+            let sync_period = if user_active && focused {
+                Duration::from_secs(5)
             } else {
-                self.tasks.queue_sync();
-            }
+                Duration::from_secs(5 * 60)
+            };
+
+            self.core.set_sync_interval(sync_period);
+            self.core.resume_sync();
+        } else {
+            self.core.pause_sync();
         }
         start.warn_after("processing auto sync", Duration::from_millis(100));
 
@@ -641,7 +672,6 @@ impl Workspace {
         }
 
         self.out.file_renamed = Some((id, new_name));
-        self.tasks.queue_file_cache_refresh();
         self.ctx.request_repaint();
     }
 
@@ -650,7 +680,6 @@ impl Workspace {
         match self.core.move_file(&id, &new_parent) {
             Ok(()) => {
                 self.out.file_moved = Some((id, new_parent));
-                self.tasks.queue_file_cache_refresh();
                 self.ctx.request_repaint();
             }
             Err(LbErr { kind, .. }) => {
@@ -779,6 +808,32 @@ impl WsPersistentStore {
     }
 }
 
+/// Documents in shared folders are edited together in real time
+fn join_collab_if_shared(core: &Lb, ctx: &Context, id: Uuid) -> Option<CollabSession> {
+    let mut file = core.get_file_by_id(id).ok()?;
+    loop {
+        if !file.shares.is_empty() {
+            break;
+        }
+        if file.parent == file.id {
+            return None;
+        }
+        file = core.get_file_by_id(file.parent).ok()?;
+    }
+
+    match core.join_collab(id) {
+        Ok(collab) => {
+            let ctx = ctx.clone();
+            collab.set_waker(move || ctx.request_repaint());
+            Some(collab)
+        }
+        Err(err) => {
+            warn!(?err, "could not join collaborative session");
+            None
+        }
+    }
+}
+
 trait InstantExt {
     fn warn_after(self, work: &str, duration: Duration);
 }
@@ -0,0 +1,134 @@
+use crate::fs_impl::Drive;
+use lb_rs::model::errors::LbErrKind;
+use lb_rs::Uuid;
+use nfsserve::nfs::{fileid3, nfsstat3};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+
+/// how long a document has to go without writes before it's written to lb
+pub const WRITE_BACK_DELAY: Duration = Duration::from_secs(2);
+
+/// how long an untouched document stays decrypted in memory
+pub const EVICT_AFTER: Duration = Duration::from_secs(60);
+
+pub type OpenDocuments = HashMap<fileid3, OpenDocument>;
+
+/// A decrypted copy of a document that's being read or written through the drive. NFS reads and
+/// writes arrive in chunks of at most a few hundred KB, so without this each chunk would decrypt
+/// (and for writes, re-encrypt) the whole document.
+pub struct OpenDocument {
+    pub id: Uuid,
+    pub content: Vec<u8>,
+
+    /// has writes lb hasn't seen yet
+    pub dirty: bool,
+    pub last_write: Instant,
+    pub last_access: Instant,
+}
+
+impl OpenDocument {
+    /// copies as much of `content` starting at `offset` as `count` allows, and whether that
+    /// reached the end
+    pub fn read(&mut self, offset: usize, count: usize) -> (Vec<u8>, bool) {
+        self.last_access = Instant::now();
+
+        if offset >= self.content.len() {
+            return (vec![], true);
+        }
+        let end = offset.saturating_add(count).min(self.content.len());
+        (self.content[offset..end].to_vec(), end == self.content.len())
+    }
+
+    pub fn write(&mut self, offset: usize, buffer: &[u8]) {
+        let end = offset + buffer.len();
+        if end > self.content.len() {
+            self.content.resize(end, 0);
+        }
+        self.content[offset..end].copy_from_slice(buffer);
+        self.touch();
+    }
+
+    pub fn truncate(&mut self, size: usize) {
+        self.content.resize(size, 0);
+        self.touch();
+    }
+
+    fn touch(&mut self) {
+        self.dirty = true;
+        self.last_write = Instant::now();
+        self.last_access = self.last_write;
+    }
+}
+
+impl Drive {
+    /// The open copy of a document, decrypting it if nobody has it open. A document deleted
+    /// since the kernel looked it up is a stale handle.
+    pub async fn open_document<'a>(
+        &self, open: &'a mut OpenDocuments, fileid: fileid3, id: Uuid,
+    ) -> Result<&'a mut OpenDocument, nfsstat3> {
+        match open.entry(fileid) {
+            Entry::Occupied(doc) => Ok(doc.into_mut()),
+            Entry::Vacant(entry) => {
+                let content = self.lb.read_document(id, false).await.map_err(|err| {
+                    error!("failed to open {id}: {:?}", err);
+                    match err.kind {
+                        LbErrKind::FileNonexistent => nfsstat3::NFS3ERR_STALE,
+                        _ => nfsstat3::NFS3ERR_IO,
+                    }
+                })?;
+                let now = Instant::now();
+                Ok(entry.insert(OpenDocument {
+                    id,
+                    content,
+                    dirty: false,
+                    last_write: now,
+                    last_access: now,
+                }))
+            }
+        }
+    }
+
+    /// Writes a document's pending changes to lb
+    pub async fn flush(&self, fileid: fileid3) {
+        let mut open = self.open.lock().await;
+        if let Some(doc) = open.get_mut(&fileid) {
+            self.write_back(doc).await;
+        }
+    }
+
+    /// Writes every pending change to lb, for before unmounting
+    pub async fn flush_all(&self) {
+        let mut open = self.open.lock().await;
+        for doc in open.values_mut() {
+            self.write_back(doc).await;
+        }
+    }
+
+    /// Writes documents nobody has written to in a while and forgets documents nobody has touched
+    /// in a while. Runs periodically for as long as the drive is mounted.
+    pub async fn flush_idle(&self) {
+        let mut open = self.open.lock().await;
+        for doc in open.values_mut() {
+            if doc.dirty && doc.last_write.elapsed() >= WRITE_BACK_DELAY {
+                self.write_back(doc).await;
+            }
+        }
+        open.retain(|_, doc| doc.dirty || doc.last_access.elapsed() < EVICT_AFTER);
+    }
+
+    async fn write_back(&self, doc: &mut OpenDocument) {
+        if !doc.dirty {
+            return;
+        }
+        match self.lb.write_document(doc.id, &doc.content).await {
+            Ok(()) => {
+                info!("wrote back {}, |{}|", doc.id, doc.content.len());
+                doc.dirty = false;
+            }
+            // stays dirty, we'll try again next time around
+            Err(err) => error!("failed to write back {}: {:?}", doc.id, err),
+        }
+    }
+}
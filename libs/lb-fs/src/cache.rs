@@ -1,8 +1,13 @@
 use crate::fs_impl::Drive;
-use lb_rs::model::{file::File, work_unit::WorkUnit};
-use nfsserve::nfs::{fattr3, ftype3, nfstime3};
+use crate::utils::chop;
+use lb_rs::model::file::File;
+use lb_rs::model::file_metadata::FileType;
+use lb_rs::service::events::Event;
+use lb_rs::Uuid;
+use nfsserve::nfs::{fattr3, fileid3, ftype3, nfstime3};
+use std::collections::HashMap;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tracing::info;
+use tracing::{info, instrument, warn};
 
 pub struct FileEntry {
     pub file: File,
@@ -11,10 +16,20 @@ pub struct FileEntry {
 
 impl FileEntry {
     pub fn from_file(file: File, size: u64) -> Self {
-        let ftype = if file.is_folder() { ftype3::NF3DIR } else { ftype3::NF3REG };
+        let ftype = if file.is_folder() {
+            ftype3::NF3DIR
+        } else if matches!(file.file_type, FileType::Link { .. }) {
+            ftype3::NF3LNK
+        } else {
+            ftype3::NF3REG
+        };
 
         // todo this deserves some scrutiny and cross platform testing
-        let mode = if file.is_folder() { 0o755 } else { 0o644 };
+        let mode = match ftype {
+            ftype3::NF3DIR => 0o755,
+            ftype3::NF3LNK => 0o777,
+            _ => 0o644,
+        };
 
         let fileid = file.id.as_u64_pair().0;
         // intereREADDIR3resstingly a number of key read operations rely on this being correct
@@ -55,11 +70,15 @@ impl FileEntry {
 }
 
 impl Drive {
-    // todo: probably need a variant of this that is more suitable post sync cache updates
     pub async fn prepare_caches(&self) {
         info!("performing startup sync");
-        self.lb.sync(Self::progress()).await.unwrap();
+        self.lb.sync_now(Self::progress()).await.unwrap();
+
+        self.load_all().await;
+    }
 
+    /// Rebuilds the cache from scratch, for startup and for when we've missed events
+    pub async fn load_all(&self) {
         info!("preparing cache, are you release build?");
         let sizes = self.lb.get_uncompressed_usage_breakdown().await.unwrap();
         let files = self.lb.list_metadatas().await.unwrap();
@@ -74,28 +93,108 @@ impl Drive {
         info!("cache ready");
     }
 
-    pub async fn sync(&self) {
-        let status = self.lb.sync(None).await.unwrap();
+    /// Brings the cache in line with a change made by sync, another lb user, or the drive itself
+    #[instrument(skip(self))]
+    pub async fn apply_event(&self, event: Event) {
+        match event {
+            Event::DocumentWritten(id) | Event::ConflictDetected(id) => {
+                self.document_written(id).await
+            }
+            Event::FileDeleted(id) => self.forget(id).await,
+            Event::MetadataChanged(id) | Event::FileCreated(id) => self.refresh(id, true).await,
+            Event::FileMoved { id, .. }
+            | Event::FileRenamed { id, .. }
+            | Event::ShareAdded(id)
+            | Event::ShareRemoved(id) => self.refresh(id, false).await,
+        }
+    }
+
+    async fn refresh(&self, id: Uuid, recursive: bool) {
+        let files = if recursive {
+            self.lb.get_and_get_children_recursively(&id).await
+        } else {
+            self.lb.get_file_by_id(id).await.map(|file| vec![file])
+        };
+        let Ok(files) = files else {
+            // deleted, or never visible to us
+            self.forget(id).await;
+            return;
+        };
+
         let mut data = self.data.lock().await;
+        for file in files {
+            match data.get_mut(&chop(file.id)) {
+                // timestamps and permissions are the drive's to manage
+                Some(entry) => entry.file = file,
+                None => {
+                    let size = if file.is_document() {
+                        match self.lb.read_document(file.id, false).await {
+                            Ok(content) => content.len(),
+                            Err(err) => {
+                                // it'll be picked up by the next event or rebuild
+                                warn!("failed to read {} for the cache: {:?}", file.id, err);
+                                continue;
+                            }
+                        }
+                    } else {
+                        0
+                    };
+                    let entry = FileEntry::from_file(file, size as u64);
+                    data.insert(entry.fattr.fileid, entry);
+                }
+            }
+        }
+    }
 
-        for unit in status.work_units {
-            if let WorkUnit::ServerChange(dirty_id) = unit {
-                let file = self.lb.get_file_by_id(dirty_id).await.unwrap();
-                let size = if file.is_document() {
-                    self.lb.read_document(dirty_id, false).await.unwrap().len()
-                } else {
-                    0
-                };
+    async fn document_written(&self, id: Uuid) {
+        let fileid = chop(id);
+        let mut data = self.data.lock().await;
+        let mut open = self.open.lock().await;
 
-                let mut entry = FileEntry::from_file(file, size as u64);
+        // unwritten local changes win until they're written back and synced; otherwise the open
+        // copy is stale
+        if open.get(&fileid).map(|doc| doc.dirty) == Some(true) {
+            return;
+        }
+        open.remove(&fileid);
 
-                let now = FileEntry::ts_from_u64(FileEntry::now());
+        let Some(entry) = data.get_mut(&fileid) else {
+            return;
+        };
+        let (Ok(file), Ok(content)) =
+            (self.lb.get_file_by_id(id).await, self.lb.read_document(id, false).await)
+        else {
+            return;
+        };
 
-                entry.fattr.mtime = now;
-                entry.fattr.ctime = now;
+        let modified = FileEntry::ts_from_u64(file.last_modified);
+        entry.fattr.size = content.len() as u64;
+        entry.fattr.used = content.len() as u64;
+        entry.fattr.mtime = modified;
+        entry.fattr.ctime = modified;
+        entry.file = file;
+    }
 
-                data.insert(entry.fattr.fileid, entry);
+    /// Drops a deleted file and everything that was in it
+    async fn forget(&self, id: Uuid) {
+        let mut data = self.data.lock().await;
+        let mut open = self.open.lock().await;
+
+        let mut children: HashMap<fileid3, Vec<fileid3>> = HashMap::new();
+        for (fileid, entry) in data.iter() {
+            if entry.file.id != entry.file.parent {
+                children
+                    .entry(chop(entry.file.parent))
+                    .or_default()
+                    .push(*fileid);
             }
         }
+
+        let mut gone = vec![chop(id)];
+        while let Some(fileid) = gone.pop() {
+            data.remove(&fileid);
+            open.remove(&fileid);
+            gone.extend(children.remove(&fileid).unwrap_or_default());
+        }
     }
 }
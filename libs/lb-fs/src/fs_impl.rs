@@ -1,9 +1,17 @@
 use crate::{
+    buffer::OpenDocuments,
     cache::FileEntry,
     utils::{fmt, get_string},
 };
 use async_trait::async_trait;
-use lb_rs::{model::file_metadata::FileType, Lb, Uuid};
+use lb_rs::{
+    model::{
+        errors::{LbErr, LbErrKind},
+        file_metadata::FileType,
+        ValidationFailure,
+    },
+    Lb, Uuid,
+};
 use nfsserve::{
     nfs::{
         fattr3, fileid3, filename3, nfspath3, nfsstat3, nfsstring, sattr3, set_atime, set_gid3,
@@ -11,7 +19,11 @@ use nfsserve::{
     },
     vfs::{DirEntry, NFSFileSystem, ReadDirResult, VFSCapabilities},
 };
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
 use tokio::sync::Mutex;
 use tracing::{info, instrument, warn};
 
@@ -31,6 +43,13 @@ pub struct Drive {
     /// 2. nfs needs to update timestamps to specified values
     /// 3. nfs models properties we don't, like file permission bits
     pub data: Arc<Mutex<HashMap<fileid3, FileEntry>>>,
+
+    /// documents being read or written, see [crate::buffer]. Locked after `data` when both are
+    /// needed.
+    pub open: Arc<Mutex<OpenDocuments>>,
+
+    /// where the drive is mounted, for making sense of absolute symlink targets
+    pub mount_point: PathBuf,
 }
 
 #[async_trait]
@@ -53,26 +72,21 @@ impl NFSFileSystem for Drive {
         let offset = offset as usize;
 
         let mut data = self.data.lock().await;
-        let entry = data.get_mut(&id).unwrap();
-        let id = entry.file.id;
-
-        let mut doc = self.lb.read_document(id, false).await.unwrap();
-        let mut expanded = false;
-        if offset + buffer.len() > doc.len() {
-            doc.resize(offset + buffer.len(), 0);
-            doc[offset..].copy_from_slice(buffer);
-            expanded = true;
-        } else {
-            for (idx, datum) in buffer.iter().enumerate() {
-                doc[offset + idx] = *datum;
-            }
-        }
-        let doc_size = doc.len();
-        self.lb.write_document(id, &doc).await.unwrap();
+        let entry = data.get_mut(&id).ok_or(nfsstat3::NFS3ERR_STALE)?;
 
-        entry.fattr.size = doc_size as u64;
+        // written back to lb once the writes stop, see Drive::flush_idle
+        let mut open = self.open.lock().await;
+        let doc = self.open_document(&mut open, id, entry.file.id).await?;
+        doc.write(offset, buffer);
+        let doc_size = doc.content.len() as u64;
 
-        info!("expanded={expanded}, fattr.size = {}", doc_size);
+        let now = FileEntry::ts_from_u64(FileEntry::now());
+        entry.fattr.size = doc_size;
+        entry.fattr.used = doc_size;
+        entry.fattr.mtime = now;
+        entry.fattr.ctime = now;
+
+        info!("fattr.size = {}", doc_size);
 
         Ok(entry.fattr)
     }
@@ -83,7 +97,14 @@ impl NFSFileSystem for Drive {
         &self, dirid: fileid3, filename: &filename3, attr: sattr3,
     ) -> Result<(fileid3, fattr3), nfsstat3> {
         let filename = get_string(filename);
-        let parent = self.data.lock().await.get(&dirid).unwrap().file.id;
+        let parent = self
+            .data
+            .lock()
+            .await
+            .get(&dirid)
+            .ok_or(nfsstat3::NFS3ERR_STALE)?
+            .file
+            .id;
         let file = self
             .lb
             .create_file(&filename, &parent, FileType::Document)
@@ -105,7 +126,14 @@ impl NFSFileSystem for Drive {
         &self, dirid: fileid3, filename: &filename3,
     ) -> Result<fileid3, nfsstat3> {
         let filename = get_string(filename);
-        let dirid = self.data.lock().await.get(&dirid).unwrap().file.id;
+        let dirid = self
+            .data
+            .lock()
+            .await
+            .get(&dirid)
+            .ok_or(nfsstat3::NFS3ERR_STALE)?
+            .file
+            .id;
         let children = self.lb.get_children(&dirid).await.unwrap();
         for child in children {
             if child.name == filename {
@@ -130,7 +158,14 @@ impl NFSFileSystem for Drive {
 
     #[instrument(skip(self), fields(dirid = fmt(dirid), filename = get_string(filename)))]
     async fn lookup(&self, dirid: fileid3, filename: &filename3) -> Result<fileid3, nfsstat3> {
-        let dir = self.data.lock().await.get(&dirid).unwrap().file.clone();
+        let dir = self
+            .data
+            .lock()
+            .await
+            .get(&dirid)
+            .ok_or(nfsstat3::NFS3ERR_STALE)?
+            .file
+            .clone();
 
         if dir.is_document() {
             info!("NOTDIR");
@@ -165,7 +200,13 @@ impl NFSFileSystem for Drive {
 
     #[instrument(skip(self), fields(id = fmt(id)))]
     async fn getattr(&self, id: fileid3) -> Result<fattr3, nfsstat3> {
-        let file = self.data.lock().await.get(&id).unwrap().fattr;
+        let file = self
+            .data
+            .lock()
+            .await
+            .get(&id)
+            .ok_or(nfsstat3::NFS3ERR_STALE)?
+            .fattr;
         info!("fattr = {:?}", file);
         Ok(file)
     }
@@ -174,15 +215,18 @@ impl NFSFileSystem for Drive {
     async fn setattr(&self, id: fileid3, setattr: sattr3) -> Result<fattr3, nfsstat3> {
         let mut data = self.data.lock().await;
         let now = FileEntry::now();
-        let entry = data.get_mut(&id).unwrap();
+        let entry = data.get_mut(&id).ok_or(nfsstat3::NFS3ERR_STALE)?;
 
         match setattr.size {
             set_size3::Void => {}
             set_size3::size(new) => {
                 if entry.fattr.size != new {
-                    let mut doc = self.lb.read_document(entry.file.id, false).await.unwrap();
-                    doc.resize(new as usize, 0);
-                    self.lb.write_document(entry.file.id, &doc).await.unwrap();
+                    let mut open = self.open.lock().await;
+                    self.open_document(&mut open, id, entry.file.id)
+                        .await?
+                        .truncate(new as usize);
+                    entry.fattr.size = new;
+                    entry.fattr.used = new;
                     entry.fattr.mtime = FileEntry::ts_from_u64(now);
                     entry.fattr.ctime = FileEntry::ts_from_u64(now);
                 }
@@ -244,24 +288,24 @@ impl NFSFileSystem for Drive {
     async fn read(
         &self, id: fileid3, offset: u64, count: u32,
     ) -> Result<(Vec<u8>, bool), nfsstat3> {
-        let offset = offset as usize;
-        let count = count as usize;
-        let id = self.data.lock().await.get(&id).unwrap().file.id;
-
-        let doc = self.lb.read_document(id, false).await.unwrap();
-
-        if offset >= doc.len() {
-            info!("[] EOF");
-            return Ok((vec![], true));
-        }
-
-        if offset + count >= doc.len() {
-            info!("|{}| EOF", doc[offset..].len());
-            return Ok((doc[offset..].to_vec(), true));
-        }
-
-        info!("|{}|", count);
-        return Ok((doc[offset..offset + count].to_vec(), false));
+        let fileid = id;
+        let id = self
+            .data
+            .lock()
+            .await
+            .get(&id)
+            .ok_or(nfsstat3::NFS3ERR_STALE)?
+            .file
+            .id;
+
+        let mut open = self.open.lock().await;
+        let (buffer, eof) = self
+            .open_document(&mut open, fileid, id)
+            .await?
+            .read(offset as usize, count as usize);
+
+        info!("|{}| eof={eof}", buffer.len());
+        Ok((buffer, eof))
     }
 
     /// they will provide a start_after of 0 for no id
@@ -270,7 +314,7 @@ impl NFSFileSystem for Drive {
         &self, dirid: fileid3, start_after: fileid3, max_entries: usize,
     ) -> Result<ReadDirResult, nfsstat3> {
         let data = self.data.lock().await;
-        let dirid = data.get(&dirid).unwrap().file.id;
+        let dirid = data.get(&dirid).ok_or(nfsstat3::NFS3ERR_STALE)?.file.id;
         let mut children = self.lb.get_children(&dirid).await.unwrap();
 
         children.sort_by(|a, b| a.id.cmp(&b.id));
@@ -303,9 +347,14 @@ impl NFSFileSystem for Drive {
         for child in &children[start_index..end_index] {
             let fileid = child.id.as_u64_pair().0;
             let name = nfsstring(child.name.clone().into_bytes());
-            let attr = data.get(&fileid).unwrap().fattr;
-
-            ret.entries.push(DirEntry { fileid, name, attr });
+            // not cached yet if it arrived since the last event we applied; it'll be listed once
+            // it is
+            let Some(entry) = data.get(&fileid) else {
+                continue;
+            };
+
+            ret.entries
+                .push(DirEntry { fileid, name, attr: entry.fattr });
         }
 
         info!("|{}| done={}", ret.entries.len(), ret.end);
@@ -320,7 +369,7 @@ impl NFSFileSystem for Drive {
     #[allow(unused)]
     async fn remove(&self, dirid: fileid3, filename: &filename3) -> Result<(), nfsstat3> {
         let mut data = self.data.lock().await;
-        let dirid = data.get(&dirid).unwrap().file.id;
+        let dirid = data.get(&dirid).ok_or(nfsstat3::NFS3ERR_STALE)?.file.id;
 
         let children = self.lb.get_children(&dirid).await.unwrap();
         let file_name = String::from_utf8(filename.0.clone()).unwrap();
@@ -330,6 +379,7 @@ impl NFSFileSystem for Drive {
                 info!("deleted");
                 self.lb.delete(&child.id).await;
                 data.remove(&child.id.as_u64_pair().0);
+                self.open.lock().await.remove(&child.id.as_u64_pair().0);
                 return Ok(());
             }
         }
@@ -350,8 +400,12 @@ impl NFSFileSystem for Drive {
         let from_filename = String::from_utf8(from_filename.0.clone()).unwrap();
         let to_filename = String::from_utf8(to_filename.0.clone()).unwrap();
 
-        let from_dirid = data.get(&from_dirid).unwrap().file.id;
-        let to_dirid = data.get(&to_dirid).unwrap().file.id;
+        let from_dirid = data
+            .get(&from_dirid)
+            .ok_or(nfsstat3::NFS3ERR_STALE)?
+            .file
+            .id;
+        let to_dirid = data.get(&to_dirid).ok_or(nfsstat3::NFS3ERR_STALE)?.file.id;
 
         let src_children = self.lb.get_children(&from_dirid).await.unwrap();
 
@@ -376,20 +430,28 @@ impl NFSFileSystem for Drive {
             }
         }
 
-        let from_id = from_id.unwrap();
+        let from_id = from_id.ok_or(nfsstat3::NFS3ERR_NOENT)?;
 
         match to_id {
             // we are overwriting a file
             Some(id) => {
                 info!("overwrite {from_id} -> {id}");
+                self.flush(from_id.as_u64_pair().0).await;
+                let mut open = self.open.lock().await;
+                open.remove(&from_id.as_u64_pair().0);
+                open.remove(&id.as_u64_pair().0);
+                drop(open);
+
                 let from_doc = self.lb.read_document(from_id, false).await.unwrap();
                 info!("|{}|", from_doc.len());
                 let doc_len = from_doc.len() as u64;
                 self.lb.write_document(id, &from_doc).await.unwrap();
                 self.lb.delete(&from_id).await.unwrap();
 
-                let mut entry = data.get_mut(&id.as_u64_pair().0).unwrap();
-                entry.fattr.size = doc_len;
+                if let Some(entry) = data.get_mut(&id.as_u64_pair().0) {
+                    entry.fattr.size = doc_len;
+                    entry.fattr.used = doc_len;
+                }
 
                 data.remove(&from_id.as_u64_pair().0);
             }
@@ -406,10 +468,10 @@ impl NFSFileSystem for Drive {
                     self.lb.rename_file(&from_id, &to_filename).await.unwrap();
                 }
 
-                let mut entry = data.get_mut(&from_id.as_u64_pair().0).unwrap();
-
                 let file = self.lb.get_file_by_id(from_id).await.unwrap();
-                entry.file = file;
+                if let Some(entry) = data.get_mut(&from_id.as_u64_pair().0) {
+                    entry.file = file;
+                }
 
                 info!("ok");
             }
@@ -424,7 +486,14 @@ impl NFSFileSystem for Drive {
         &self, dirid: fileid3, dirname: &filename3,
     ) -> Result<(fileid3, fattr3), nfsstat3> {
         let filename = get_string(dirname);
-        let parent = self.data.lock().await.get(&dirid).unwrap().file.id;
+        let parent = self
+            .data
+            .lock()
+            .await
+            .get(&dirid)
+            .ok_or(nfsstat3::NFS3ERR_STALE)?
+            .file
+            .id;
         let file = self
             .lb
             .create_file(&filename, &parent, FileType::Folder)
@@ -440,14 +509,123 @@ impl NFSFileSystem for Drive {
         Ok((id, fattr))
     }
 
+    /// Lockbook links can only point at files someone shared with you, so the target is either
+    /// the id of a pending share or a path (inside the mount) to a shared file
+    #[instrument(skip(self), fields(dirid = fmt(dirid), linkname = get_string(linkname)))]
     async fn symlink(
-        &self, _dirid: fileid3, _linkname: &filename3, _symlink: &nfspath3, _attr: &sattr3,
+        &self, dirid: fileid3, linkname: &filename3, symlink: &nfspath3, _attr: &sattr3,
     ) -> Result<(fileid3, fattr3), nfsstat3> {
-        info!("symlink NOTSUPP");
-        return Err(nfsstat3::NFS3ERR_NOTSUPP);
+        let linkname = get_string(linkname);
+        let target = String::from_utf8(symlink.0.clone()).map_err(|_| nfsstat3::NFS3ERR_INVAL)?;
+        let dir = self
+            .data
+            .lock()
+            .await
+            .get(&dirid)
+            .ok_or(nfsstat3::NFS3ERR_STALE)?
+            .file
+            .id;
+        let dir = self
+            .lb
+            .get_path_by_id(dir)
+            .await
+            .map_err(|_| nfsstat3::NFS3ERR_STALE)?;
+        let target = self.link_target(&dir, &target).await?;
+
+        let file = self
+            .lb
+            .create_link_at_path(&format!("{dir}{linkname}"), target)
+            .await
+            .map_err(|err| {
+                warn!("{:?}", err);
+                link_err(err)
+            })?;
+
+        let entry = FileEntry::from_file(file, 0);
+        let (id, fattr) = (entry.fattr.fileid, entry.fattr);
+        self.data.lock().await.insert(id, entry);
+
+        info!("({}, fattr={:?})", fmt(id), fattr);
+        Ok((id, fattr))
+    }
+
+    /// The target's path inside the mount, or its id if it's a pending share that isn't anywhere
+    /// in the mount yet
+    #[instrument(skip(self), fields(id = fmt(id)))]
+    async fn readlink(&self, id: fileid3) -> Result<nfspath3, nfsstat3> {
+        let file_type = self
+            .data
+            .lock()
+            .await
+            .get(&id)
+            .ok_or(nfsstat3::NFS3ERR_STALE)?
+            .file
+            .file_type;
+        let FileType::Link { target } = file_type else {
+            info!("INVAL");
+            return Err(nfsstat3::NFS3ERR_INVAL);
+        };
+
+        let path = match self.lb.get_path_by_id(target).await {
+            Ok(path) => self
+                .mount_point
+                .join(path.trim_start_matches('/'))
+                .to_string_lossy()
+                .into_owned(),
+            Err(_) => target.to_string(),
+        };
+
+        info!("{path}");
+        Ok(nfsstring(path.into_bytes()))
     }
-    async fn readlink(&self, _id: fileid3) -> Result<nfspath3, nfsstat3> {
-        info!("readklink NOTSUPP");
-        return Err(nfsstat3::NFS3ERR_NOTSUPP);
+}
+
+impl Drive {
+    /// Resolves a symlink target made in the folder at `dir` to the file it names
+    async fn link_target(&self, dir: &str, target: &str) -> Result<Uuid, nfsstat3> {
+        if let Ok(id) = Uuid::parse_str(target) {
+            return Ok(id);
+        }
+
+        let target = Path::new(target);
+        let path = if target.is_absolute() {
+            // lockbook links can't leave lockbook
+            target
+                .strip_prefix(&self.mount_point)
+                .map_err(|_| nfsstat3::NFS3ERR_NOTSUPP)?
+                .to_path_buf()
+        } else {
+            Path::new(dir).join(target)
+        };
+
+        let mut names = vec![];
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => names.push(name.to_string_lossy().to_string()),
+                Component::ParentDir => {
+                    names.pop();
+                }
+                Component::RootDir | Component::CurDir | Component::Prefix(_) => {}
+            }
+        }
+
+        self.lb
+            .get_by_path(&names.join("/"))
+            .await
+            .map(|file| file.id)
+            .map_err(|_| nfsstat3::NFS3ERR_NOENT)
+    }
+}
+
+fn link_err(err: LbErr) -> nfsstat3 {
+    match err.kind {
+        LbErrKind::FileNonexistent | LbErrKind::Validation(ValidationFailure::BrokenLink(_)) => {
+            nfsstat3::NFS3ERR_NOENT
+        }
+        LbErrKind::Validation(ValidationFailure::DuplicateLink { .. })
+        | LbErrKind::Validation(ValidationFailure::PathConflict(_)) => nfsstat3::NFS3ERR_EXIST,
+        LbErrKind::Validation(ValidationFailure::OwnedLink(_))
+        | LbErrKind::Validation(ValidationFailure::SharedLink { .. }) => nfsstat3::NFS3ERR_NOTSUPP,
+        _ => nfsstat3::NFS3ERR_IO,
     }
 }
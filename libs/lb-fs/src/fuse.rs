@@ -0,0 +1,371 @@
+//! The drive over FUSE, which, unlike NFS, mounts without root. Calls the same [NFSFileSystem]
+//! methods the NFS server does, so both backends behave the same and share [Drive]'s caches.
+
+use crate::fs_impl::Drive;
+use fuser::{
+    BackgroundSession, FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyCreate,
+    ReplyData, ReplyDirectory, ReplyEmpty, ReplyEntry, ReplyWrite, Request, TimeOrNow,
+    FUSE_ROOT_ID,
+};
+use nfsserve::nfs::{
+    fattr3, fileid3, ftype3, nfsstat3, nfsstring, nfstime3, sattr3, set_atime, set_gid3, set_mode3,
+    set_mtime, set_size3, set_uid3,
+};
+use nfsserve::vfs::NFSFileSystem;
+use std::ffi::OsStr;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::runtime::Handle;
+
+/// how long the kernel may cache what we tell it about a file
+const TTL: Duration = Duration::from_secs(1);
+
+pub struct FuseDrive {
+    drive: Drive,
+    rt: Handle,
+}
+
+/// Mounts in the background, unmounting when the returned session is dropped
+pub fn mount(drive: Drive, mount_point: &Path) -> io::Result<BackgroundSession> {
+    let fs = FuseDrive { drive, rt: Handle::current() };
+    let options = [
+        MountOption::FSName("lockbook".to_string()),
+        MountOption::DefaultPermissions,
+        MountOption::NoDev,
+        MountOption::NoSuid,
+    ];
+    fuser::spawn_mount2(fs, mount_point, &options)
+}
+
+impl FuseDrive {
+    /// fuse has a fixed inode for the root
+    fn fileid(&self, ino: u64) -> fileid3 {
+        if ino == FUSE_ROOT_ID {
+            self.drive.root_dir()
+        } else {
+            ino
+        }
+    }
+
+    fn ino(&self, fileid: fileid3) -> u64 {
+        if fileid == self.drive.root_dir() {
+            FUSE_ROOT_ID
+        } else {
+            fileid
+        }
+    }
+
+    /// [Drive::remove] deletes a folder along with everything in it, so the checks fuse leaves to
+    /// the filesystem happen here: `unlink` only takes files and `rmdir` only empty folders.
+    fn remove(&self, parent: u64, name: &OsStr, dir: bool) -> Result<(), i32> {
+        let parent = self.fileid(parent);
+        let name = filename(name);
+        self.rt.block_on(async {
+            let id = self.drive.lookup(parent, &name).await.map_err(errno)?;
+            let attr = self.drive.getattr(id).await.map_err(errno)?;
+            let is_dir = matches!(attr.ftype, ftype3::NF3DIR);
+            match (is_dir, dir) {
+                (true, false) => return Err(libc::EISDIR),
+                (false, true) => return Err(libc::ENOTDIR),
+                (true, true) => {
+                    let children = self.drive.readdir(id, 0, 1).await.map_err(errno)?;
+                    if !children.entries.is_empty() {
+                        return Err(libc::ENOTEMPTY);
+                    }
+                }
+                (false, false) => {}
+            }
+            self.drive.remove(parent, &name).await.map_err(errno)
+        })
+    }
+
+    fn attr(&self, fattr: fattr3) -> FileAttr {
+        let kind = match fattr.ftype {
+            ftype3::NF3DIR => FileType::Directory,
+            ftype3::NF3LNK => FileType::Symlink,
+            _ => FileType::RegularFile,
+        };
+        let ctime = time(fattr.ctime);
+
+        FileAttr {
+            ino: self.ino(fattr.fileid),
+            size: fattr.size,
+            blocks: fattr.used.div_ceil(512),
+            atime: time(fattr.atime),
+            mtime: time(fattr.mtime),
+            ctime,
+            crtime: ctime,
+            kind,
+            perm: (fattr.mode & 0o7777) as u16,
+            nlink: fattr.nlink,
+            // the ids nfs reports are placeholders, and with default_permissions the kernel would
+            // hold them against whoever mounted the drive
+            uid: unsafe { libc::getuid() },
+            gid: unsafe { libc::getgid() },
+            rdev: 0,
+            blksize: 4096,
+            flags: 0,
+        }
+    }
+
+    fn entry(&self, fileid: fileid3, reply: ReplyEntry) {
+        match self.rt.block_on(self.drive.getattr(fileid)) {
+            Ok(fattr) => reply.entry(&TTL, &self.attr(fattr), 0),
+            Err(err) => reply.error(errno(err)),
+        }
+    }
+}
+
+impl Filesystem for FuseDrive {
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let parent = self.fileid(parent);
+        match self.rt.block_on(self.drive.lookup(parent, &filename(name))) {
+            Ok(fileid) => self.entry(fileid, reply),
+            Err(err) => reply.error(errno(err)),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+        match self.rt.block_on(self.drive.getattr(self.fileid(ino))) {
+            Ok(fattr) => reply.attr(&TTL, &self.attr(fattr)),
+            Err(err) => reply.error(errno(err)),
+        }
+    }
+
+    fn setattr(
+        &mut self, _req: &Request<'_>, ino: u64, mode: Option<u32>, uid: Option<u32>,
+        gid: Option<u32>, size: Option<u64>, atime: Option<TimeOrNow>, mtime: Option<TimeOrNow>,
+        _ctime: Option<SystemTime>, _fh: Option<u64>, _crtime: Option<SystemTime>,
+        _chgtime: Option<SystemTime>, _bkuptime: Option<SystemTime>, _flags: Option<u32>,
+        reply: ReplyAttr,
+    ) {
+        let setattr = sattr3 {
+            mode: mode.map(set_mode3::mode).unwrap_or(set_mode3::Void),
+            uid: uid.map(set_uid3::uid).unwrap_or(set_uid3::Void),
+            gid: gid.map(set_gid3::gid).unwrap_or(set_gid3::Void),
+            size: size.map(set_size3::size).unwrap_or(set_size3::Void),
+            atime: match atime {
+                None => set_atime::DONT_CHANGE,
+                Some(TimeOrNow::Now) => set_atime::SET_TO_SERVER_TIME,
+                Some(TimeOrNow::SpecificTime(time)) => {
+                    set_atime::SET_TO_CLIENT_TIME(nfs_time(time))
+                }
+            },
+            mtime: match mtime {
+                None => set_mtime::DONT_CHANGE,
+                Some(TimeOrNow::Now) => set_mtime::SET_TO_SERVER_TIME,
+                Some(TimeOrNow::SpecificTime(time)) => {
+                    set_mtime::SET_TO_CLIENT_TIME(nfs_time(time))
+                }
+            },
+        };
+        match self
+            .rt
+            .block_on(self.drive.setattr(self.fileid(ino), setattr))
+        {
+            Ok(fattr) => reply.attr(&TTL, &self.attr(fattr)),
+            Err(err) => reply.error(errno(err)),
+        }
+    }
+
+    fn read(
+        &mut self, _req: &Request<'_>, ino: u64, _fh: u64, offset: i64, size: u32, _flags: i32,
+        _lock_owner: Option<u64>, reply: ReplyData,
+    ) {
+        match self
+            .rt
+            .block_on(self.drive.read(self.fileid(ino), offset as u64, size))
+        {
+            Ok((data, _)) => reply.data(&data),
+            Err(err) => reply.error(errno(err)),
+        }
+    }
+
+    fn write(
+        &mut self, _req: &Request<'_>, ino: u64, _fh: u64, offset: i64, data: &[u8],
+        _write_flags: u32, _flags: i32, _lock_owner: Option<u64>, reply: ReplyWrite,
+    ) {
+        match self
+            .rt
+            .block_on(self.drive.write(self.fileid(ino), offset as u64, data))
+        {
+            Ok(_) => reply.written(data.len() as u32),
+            Err(err) => reply.error(errno(err)),
+        }
+    }
+
+    fn flush(
+        &mut self, _req: &Request<'_>, ino: u64, _fh: u64, _lock_owner: u64, reply: ReplyEmpty,
+    ) {
+        self.rt.block_on(self.drive.flush(self.fileid(ino)));
+        reply.ok();
+    }
+
+    fn fsync(
+        &mut self, _req: &Request<'_>, ino: u64, _fh: u64, _datasync: bool, reply: ReplyEmpty,
+    ) {
+        self.rt.block_on(self.drive.flush(self.fileid(ino)));
+        reply.ok();
+    }
+
+    fn release(
+        &mut self, _req: &Request<'_>, ino: u64, _fh: u64, _flags: i32, _lock_owner: Option<u64>,
+        _flush: bool, reply: ReplyEmpty,
+    ) {
+        self.rt.block_on(self.drive.flush(self.fileid(ino)));
+        reply.ok();
+    }
+
+    fn readdir(
+        &mut self, _req: &Request<'_>, ino: u64, _fh: u64, offset: i64, mut reply: ReplyDirectory,
+    ) {
+        let dirid = self.fileid(ino);
+        let children = match self.rt.block_on(self.drive.readdir(dirid, 0, usize::MAX)) {
+            Ok(children) => children.entries,
+            Err(err) => return reply.error(errno(err)),
+        };
+        let parent = self
+            .rt
+            .block_on(self.drive.lookup(dirid, &filename(OsStr::new(".."))))
+            .unwrap_or(dirid);
+
+        let entries = [
+            (ino, FileType::Directory, String::from(".")),
+            (self.ino(parent), FileType::Directory, String::from("..")),
+        ]
+        .into_iter()
+        .chain(children.into_iter().map(|child| {
+            let kind = self.attr(child.attr).kind;
+            (self.ino(child.fileid), kind, String::from_utf8_lossy(&child.name).to_string())
+        }));
+
+        // offsets tell the kernel where to pick up from next time
+        for (idx, (ino, kind, name)) in entries.enumerate().skip(offset as usize) {
+            if reply.add(ino, idx as i64 + 1, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+
+    fn create(
+        &mut self, req: &Request<'_>, parent: u64, name: &OsStr, mode: u32, umask: u32,
+        _flags: i32, reply: ReplyCreate,
+    ) {
+        let attr = sattr3 {
+            mode: set_mode3::mode(mode & !umask & 0o7777),
+            uid: set_uid3::uid(req.uid()),
+            gid: set_gid3::gid(req.gid()),
+            size: set_size3::Void,
+            atime: set_atime::DONT_CHANGE,
+            mtime: set_mtime::DONT_CHANGE,
+        };
+        let parent = self.fileid(parent);
+        match self
+            .rt
+            .block_on(self.drive.create(parent, &filename(name), attr))
+        {
+            Ok((_, fattr)) => reply.created(&TTL, &self.attr(fattr), 0, 0, 0),
+            Err(err) => reply.error(errno(err)),
+        }
+    }
+
+    fn mkdir(
+        &mut self, _req: &Request<'_>, parent: u64, name: &OsStr, _mode: u32, _umask: u32,
+        reply: ReplyEntry,
+    ) {
+        let parent = self.fileid(parent);
+        match self.rt.block_on(self.drive.mkdir(parent, &filename(name))) {
+            Ok((_, fattr)) => reply.entry(&TTL, &self.attr(fattr), 0),
+            Err(err) => reply.error(errno(err)),
+        }
+    }
+
+    fn symlink(
+        &mut self, _req: &Request<'_>, parent: u64, link_name: &OsStr, target: &Path,
+        reply: ReplyEntry,
+    ) {
+        let parent = self.fileid(parent);
+        let target = filename(target.as_os_str());
+        let attr = sattr3 {
+            mode: set_mode3::Void,
+            uid: set_uid3::Void,
+            gid: set_gid3::Void,
+            size: set_size3::Void,
+            atime: set_atime::DONT_CHANGE,
+            mtime: set_mtime::DONT_CHANGE,
+        };
+        match self.rt.block_on(
+            self.drive
+                .symlink(parent, &filename(link_name), &target, &attr),
+        ) {
+            Ok((_, fattr)) => reply.entry(&TTL, &self.attr(fattr), 0),
+            Err(err) => reply.error(errno(err)),
+        }
+    }
+
+    fn readlink(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyData) {
+        match self.rt.block_on(self.drive.readlink(self.fileid(ino))) {
+            Ok(target) => reply.data(&target.0),
+            Err(err) => reply.error(errno(err)),
+        }
+    }
+
+    fn unlink(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        match self.remove(parent, name, false) {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn rmdir(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        match self.remove(parent, name, true) {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn rename(
+        &mut self, _req: &Request<'_>, parent: u64, name: &OsStr, newparent: u64, newname: &OsStr,
+        _flags: u32, reply: ReplyEmpty,
+    ) {
+        let (parent, newparent) = (self.fileid(parent), self.fileid(newparent));
+        match self.rt.block_on(self.drive.rename(
+            parent,
+            &filename(name),
+            newparent,
+            &filename(newname),
+        )) {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(errno(err)),
+        }
+    }
+}
+
+fn filename(name: &OsStr) -> nfsstring {
+    nfsstring(name.as_bytes().to_vec())
+}
+
+fn time(time: nfstime3) -> SystemTime {
+    UNIX_EPOCH + Duration::new(time.seconds as u64, time.nseconds)
+}
+
+fn nfs_time(time: SystemTime) -> nfstime3 {
+    let time = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    nfstime3 { seconds: time.as_secs() as u32, nseconds: time.subsec_nanos() }
+}
+
+fn errno(err: nfsstat3) -> i32 {
+    match err {
+        nfsstat3::NFS3ERR_NOENT | nfsstat3::NFS3ERR_STALE => libc::ENOENT,
+        nfsstat3::NFS3ERR_EXIST => libc::EEXIST,
+        nfsstat3::NFS3ERR_NOTDIR => libc::ENOTDIR,
+        nfsstat3::NFS3ERR_ISDIR => libc::EISDIR,
+        nfsstat3::NFS3ERR_NOTEMPTY => libc::ENOTEMPTY,
+        nfsstat3::NFS3ERR_NOTSUPP => libc::ENOTSUP,
+        nfsstat3::NFS3ERR_INVAL => libc::EINVAL,
+        _ => libc::EIO,
+    }
+}
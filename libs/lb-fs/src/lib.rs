@@ -1,5 +1,5 @@
 use crate::fs_impl::Drive;
-use crate::mount::{mount, umount};
+use crate::mount::{mount, umount, Backend, MountPoint};
 use cli_rs::cli_error::{CliError, CliResult};
 use lb_rs::model::core_config::Config;
 use lb_rs::service::sync::SyncProgress;
@@ -10,23 +10,31 @@ use std::io::IsTerminal;
 use std::process::exit;
 use std::sync::Arc;
 use std::time::Duration;
-use tracing::info;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
 
+pub mod buffer;
 pub mod cache;
 pub mod fs_impl;
+#[cfg(target_os = "linux")]
+pub mod fuse;
 pub mod logger;
 pub mod mount;
 pub mod utils;
 
 impl Drive {
     pub async fn init() -> Self {
-        let lb = Lb::init(Config::cli_config("drive")).await.unwrap();
+        // background work keeps us synced for as long as we're mounted
+        let config = Config { background_work: true, ..Config::cli_config("drive") };
+        let lb = Lb::init(config).await.unwrap();
 
         let root = lb.root().await.map(|file| file.id).unwrap_or(Uuid::nil());
 
         let data = Arc::default();
+        let open = Arc::default();
+        let mount_point = MountPoint::default().0;
 
-        Self { lb, root, data }
+        Self { lb, root, data, open, mount_point }
     }
 
     pub async fn import() -> CliResult<()> {
@@ -49,46 +57,90 @@ impl Drive {
             .await
             .unwrap();
 
-        drive.lb.sync(Self::progress()).await.unwrap();
+        drive.lb.sync_now(Self::progress()).await.unwrap();
 
         Ok(())
     }
 
-    pub async fn mount() -> CliResult<()> {
-        let drive = Self::init().await;
+    pub async fn mount(backend: Backend, mount_point: MountPoint) -> Result<(), String> {
+        let mount_point = mount_point.0;
+        let mut drive = Self::init().await;
+        drive.mount_point = mount_point.clone();
+        let mut events = drive.lb.subscribe();
         drive.prepare_caches().await;
-        info!("registering sig handler");
 
-        // capture ctrl_c and try to cleanup
+        // keep up with whatever sync and other lb users change
+        let refresher = drive.clone();
         tokio::spawn(async move {
-            tokio::signal::ctrl_c().await.unwrap();
-            umount().await;
-            info!("cleaned up, goodbye!");
-            exit(0);
+            loop {
+                match events.recv().await {
+                    Ok(event) => refresher.apply_event(event).await,
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("missed {missed} events, reloading");
+                        refresher.load_all().await;
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
         });
 
-        // sync periodically in the background
-        let syncer = drive.clone();
+        // write back documents once they stop changing
+        let flusher = drive.clone();
         tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(1));
             loop {
-                info!("will sync in 5 minutes");
-                tokio::time::sleep(Duration::from_secs(300)).await;
-                info!("syncing");
-                syncer.sync().await;
+                interval.tick().await;
+                flusher.flush_idle().await;
             }
         });
 
-        // todo have a better port selection strategy
-        info!("creating server");
-        let listener = NFSTcpListener::bind("127.0.0.1:11111", drive)
-            .await
-            .unwrap();
+        match backend {
+            Backend::Nfs => {
+                info!("registering sig handler");
+
+                // capture ctrl_c and try to cleanup
+                let flusher = drive.clone();
+                let nfs_mount_point = mount_point.clone();
+                tokio::spawn(async move {
+                    shutdown_requested().await;
+                    flusher.flush_all().await;
+                    umount(&nfs_mount_point).await;
+                    info!("cleaned up, goodbye!");
+                    exit(0);
+                });
+
+                // todo have a better port selection strategy
+                info!("creating server");
+                let listener = NFSTcpListener::bind("127.0.0.1:11111", drive)
+                    .await
+                    .unwrap();
+
+                info!("mounting");
+                mount(&mount_point);
+
+                info!("ready");
+                listener.handle_forever().await.unwrap();
+            }
 
-        info!("mounting");
-        mount();
+            #[cfg(target_os = "linux")]
+            Backend::Fuse => {
+                info!("mounting");
+                let session = std::fs::create_dir_all(&mount_point)
+                    .and_then(|_| fuse::mount(drive.clone(), &mount_point))
+                    .map_err(|err| format!("failed to mount {}: {err}", mount_point.display()))?;
+
+                info!("ready");
+                shutdown_requested().await;
+                drive.flush_all().await;
+                session.join();
+                info!("cleaned up, goodbye!");
+            }
 
-        info!("ready");
-        listener.handle_forever().await.unwrap();
+            #[cfg(not(target_os = "linux"))]
+            Backend::Fuse => {
+                return Err("the fuse backend is only available on linux".into());
+            }
+        }
         Ok(())
     }
 
@@ -96,3 +148,19 @@ impl Drive {
         Some(Box::new(|status| println!("{status}")))
     }
 }
+
+/// Resolves on ctrl-c, or when something like systemd or `kill` asks us to stop
+async fn shutdown_requested() {
+    #[cfg(unix)]
+    {
+        let mut terminate =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()).unwrap();
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await.unwrap();
+}
@@ -1,7 +1,8 @@
 use cli_rs::cli_error::CliResult;
-use cli_rs::{command::Command, parser::Cmd};
+use cli_rs::{command::Command, flag::Flag, parser::Cmd};
 use lb_fs::fs_impl::Drive;
 use lb_fs::logger;
+use lb_fs::mount::{Backend, MountPoint};
 
 fn main() {
     logger::init();
@@ -14,8 +15,12 @@ fn main() {
         )
         .subcommand(
             Command::name("mount")
-                .description("start an NFS server and mount it to /tmp/lockbook")
-                .handler(mount),
+                .description("mount your lockbook, to /tmp/lockbook unless a mount point is given")
+                .input(Flag::<Backend>::new("backend").description(
+                    "nfs or fuse, fuse is linux only and the default there because it doesn't need root",
+                ))
+                .input(Flag::<MountPoint>::new("mount-point"))
+                .handler(|backend, mount_point| mount(backend.get(), mount_point.get())),
         )
         .parse();
 }
@@ -27,7 +32,7 @@ async fn import() -> CliResult<()> {
 }
 
 #[tokio::main]
-async fn mount() -> CliResult<()> {
-    Drive::mount().await?;
+async fn mount(backend: Backend, mount_point: MountPoint) -> CliResult<()> {
+    Drive::mount(backend, mount_point).await?;
     Ok(())
 }
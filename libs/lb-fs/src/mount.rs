@@ -1,55 +1,107 @@
+use std::convert::Infallible;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use tokio::process::Command;
 use tracing::info;
 
+/// How the drive is presented to the OS
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// a local NFS server, mounted with the OS's NFS client. Mounting needs root on linux.
+    Nfs,
+
+    /// linux only, mounts as whoever runs lb-fs
+    Fuse,
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        if cfg!(target_os = "linux") {
+            Backend::Fuse
+        } else {
+            Backend::Nfs
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "nfs" => Ok(Backend::Nfs),
+            "fuse" => Ok(Backend::Fuse),
+            other => Err(format!("unknown backend {other}, expected nfs or fuse")),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MountPoint(pub PathBuf);
+
+impl Default for MountPoint {
+    fn default() -> Self {
+        Self(PathBuf::from("/tmp/lockbook"))
+    }
+}
+
+impl FromStr for MountPoint {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(PathBuf::from(s)))
+    }
+}
+
 // see https://github.com/xetdata/nfsserve for more mount examples
 #[cfg(target_os = "macos")]
-pub fn mount() {
-    fs::create_dir_all("/tmp/lockbook").unwrap();
+pub fn mount(mount_point: &Path) {
+    fs::create_dir_all(mount_point).unwrap();
 
     Command::new("mount_nfs")
         .arg("-o")
         .arg("nolocks,vers=3,tcp,rsize=131072,actimeo=120,port=11111,mountport=11111")
         .arg("localhost:/")
-        .arg("/tmp/lockbook")
+        .arg(mount_point)
         .spawn()
         .unwrap();
 }
 
 // see https://github.com/xetdata/nfsserve for more mount examples
 #[cfg(target_os = "linux")]
-pub fn mount() {
-    fs::create_dir_all("/tmp/lockbook").unwrap();
+pub fn mount(mount_point: &Path) {
+    fs::create_dir_all(mount_point).unwrap();
 
     Command::new("sudo")
         .arg("mount.nfs")
         .arg("-o")
         .arg("user,noacl,nolock,vers=3,tcp,wsize=1048576,rsize=131072,actimeo=120,port=11111,mountport=11111")
         .arg("localhost:/")
-        .arg("/tmp/lockbook")
+        .arg(mount_point)
         .spawn()
         .unwrap();
 }
 
 #[cfg(target_os = "windows")]
-pub fn mount() {
-    fs::create_dir_all("/tmp/lockbook").unwrap();
+pub fn mount(mount_point: &Path) {
+    fs::create_dir_all(mount_point).unwrap();
 
     Command::new("mount.exe")
         .arg("-o")
         .arg("anon,nolock,mtype=soft,fileaccess=6,casesensitive,lang=ansi,rsize=128,wsize=128,timeout=60,retry=2")
         .arg("localhost:/")
-        .arg("/tmp/lockbook")
+        .arg(mount_point)
         .spawn()
         .unwrap();
 }
 
 #[cfg(target_os = "linux")]
-pub async fn umount() {
+pub async fn umount(mount_point: &Path) {
     info!("umounting");
     Command::new("sudo")
         .arg("umount")
-        .arg("/tmp/lockbook")
+        .arg(mount_point)
         .spawn()
         .unwrap()
         .wait()
@@ -58,10 +110,10 @@ pub async fn umount() {
 }
 
 #[cfg(target_os = "macos")]
-pub async fn umount() {
+pub async fn umount(mount_point: &Path) {
     info!("umounting");
     Command::new("umount")
-        .arg("/tmp/lockbook")
+        .arg(mount_point)
         .spawn()
         .unwrap()
         .wait()
@@ -70,7 +122,7 @@ pub async fn umount() {
 }
 
 #[cfg(target_os = "windows")]
-pub async fn umount() {
+pub async fn umount(mount_point: &Path) {
     info!("todo");
     todo!()
 }
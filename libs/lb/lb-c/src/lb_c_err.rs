@@ -23,7 +23,9 @@ pub enum LbEC {
     Unexpected,
     AccountExists,
     AccountNonexistent,
+    AccountReadOnly,
     AccountStringCorrupted,
+    AccountSuspended,
     AlreadyCanceled,
     AlreadyPremium,
     AppStoreAccountAlreadyLinked,
@@ -38,9 +40,11 @@ pub enum LbEC {
     CardInvalidNumber,
     CardNotSupported,
     ClientUpdateRequired,
+    ConflictNonexistent,
     CurrentUsageIsMoreThanNewTier,
     DiskPathInvalid,
     DiskPathTaken,
+    DocumentVersionNonexistent,
     DrawingInvalid,
     ExistingRequestPending,
     FileNameContainsSlash,
@@ -65,6 +69,10 @@ pub enum LbEC {
     OldCardDoesNotExist,
     PathContainsEmptyFileName,
     PathTaken,
+    PublicationNonexistent,
+    PublicationTooLarge,
+    TooManyPublications,
+    RateLimited,
     RootModificationInvalid,
     RootNonexistent,
     ReReadRequired,
@@ -72,6 +80,7 @@ pub enum LbEC {
     ServerUnreachable,
     ShareAlreadyExists,
     ShareNonexistent,
+    TakeoutCorrupted,
     TryAgain,
     UsernameInvalid,
     UsernameNotFound,
@@ -99,6 +108,8 @@ impl From<&LbErrKind> for LbEC {
         match value {
             LbErrKind::AccountExists => Self::AccountExists,
             LbErrKind::AccountNonexistent => Self::AccountNonexistent,
+            LbErrKind::AccountReadOnly => Self::AccountReadOnly,
+            LbErrKind::AccountSuspended => Self::AccountSuspended,
             LbErrKind::AccountStringCorrupted => Self::AccountStringCorrupted,
             LbErrKind::AlreadyCanceled => Self::AlreadyCanceled,
             LbErrKind::AlreadyPremium => Self::AlreadyPremium,
@@ -116,9 +127,11 @@ impl From<&LbErrKind> for LbEC {
             LbErrKind::CardInvalidNumber => Self::CardInvalidNumber,
             LbErrKind::CardNotSupported => Self::CardNotSupported,
             LbErrKind::ClientUpdateRequired => Self::ClientUpdateRequired,
+            LbErrKind::ConflictNonexistent => Self::ConflictNonexistent,
             LbErrKind::CurrentUsageIsMoreThanNewTier => Self::CurrentUsageIsMoreThanNewTier,
             LbErrKind::DiskPathInvalid => Self::DiskPathInvalid,
             LbErrKind::DiskPathTaken => Self::DiskPathTaken,
+            LbErrKind::DocumentVersionNonexistent => Self::DocumentVersionNonexistent,
             LbErrKind::DrawingInvalid => Self::DrawingInvalid,
             LbErrKind::ExistingRequestPending => Self::ExistingRequestPending,
             LbErrKind::FileNameContainsSlash => Self::FileNameContainsSlash,
@@ -136,12 +149,17 @@ impl From<&LbErrKind> for LbEC {
             LbErrKind::UsageIsOverFreeTierDataCap => Self::UsageIsOverFreeTierDataCap,
             LbErrKind::OldCardDoesNotExist => Self::OldCardDoesNotExist,
             LbErrKind::PathContainsEmptyFileName => Self::PathContainsEmptyFileName,
+            LbErrKind::PublicationNonexistent => Self::PublicationNonexistent,
+            LbErrKind::PublicationTooLarge => Self::PublicationTooLarge,
+            LbErrKind::TooManyPublications => Self::TooManyPublications,
+            LbErrKind::RateLimited => Self::RateLimited,
             LbErrKind::RootModificationInvalid => Self::RootModificationInvalid,
             LbErrKind::RootNonexistent => Self::RootNonexistent,
             LbErrKind::ServerDisabled => Self::ServerDisabled,
             LbErrKind::ServerUnreachable => Self::ServerUnreachable,
             LbErrKind::ShareAlreadyExists => Self::ShareAlreadyExists,
             LbErrKind::ShareNonexistent => Self::ShareNonexistent,
+            LbErrKind::TakeoutCorrupted => Self::TakeoutCorrupted,
             LbErrKind::TryAgain => Self::TryAgain,
             LbErrKind::UsernameInvalid => Self::UsernameInvalid,
             LbErrKind::UsernameNotFound => Self::UsernameNotFound,
@@ -8,7 +8,9 @@ public class LbError extends Exception {
     public enum LbEC {
         AccountExists,
         AccountNonexistent,
+        AccountReadOnly,
         AccountStringCorrupted,
+        AccountSuspended,
         AlreadyCanceled,
         AlreadyPremium,
         AppStoreAccountAlreadyLinked,
@@ -23,9 +25,11 @@ public class LbError extends Exception {
         CardInvalidNumber,
         CardNotSupported,
         ClientUpdateRequired,
+        ConflictNonexistent,
         CurrentUsageIsMoreThanNewTier,
         DiskPathInvalid,
         DiskPathTaken,
+        DocumentVersionNonexistent,
         DrawingInvalid,
         ExistingRequestPending,
         FileNameContainsSlash,
@@ -50,12 +54,17 @@ public class LbError extends Exception {
         OldCardDoesNotExist,
         PathContainsEmptyFileName,
         PathTaken,
+        PublicationNonexistent,
+        PublicationTooLarge,
+        TooManyPublications,
+        RateLimited,
         RootModificationInvalid,
         RootNonexistent,
         ServerDisabled,
         ServerUnreachable,
         ShareAlreadyExists,
         ShareNonexistent,
+        TakeoutCorrupted,
         TryAgain,
         UsernameInvalid,
         UsernameNotFound,
@@ -59,6 +59,8 @@ pub(crate) fn throw_err<'local>(env: &mut JNIEnv<'local>, err: LbErr) -> JObject
     let name = match err.kind {
         LbErrKind::AccountExists => "AccountExists",
         LbErrKind::AccountNonexistent => "AccountNonexistent",
+        LbErrKind::AccountReadOnly => "AccountReadOnly",
+        LbErrKind::AccountSuspended => "AccountSuspended",
         LbErrKind::AccountStringCorrupted => "AccountStringCorrupted",
         LbErrKind::AlreadyCanceled => "AlreadyCanceled",
         LbErrKind::AlreadyPremium => "AlreadyPremium",
@@ -74,9 +76,11 @@ pub(crate) fn throw_err<'local>(env: &mut JNIEnv<'local>, err: LbErr) -> JObject
         LbErrKind::CardInvalidNumber => "CardInvalidNumber",
         LbErrKind::CardNotSupported => "CardNotSupported",
         LbErrKind::ClientUpdateRequired => "ClientUpdateRequired",
+        LbErrKind::ConflictNonexistent => "ConflictNonexistent",
         LbErrKind::CurrentUsageIsMoreThanNewTier => "CurrentUsageIsMoreThanNewTier",
         LbErrKind::DiskPathInvalid => "DiskPathInvalid",
         LbErrKind::DiskPathTaken => "DiskPathTaken",
+        LbErrKind::DocumentVersionNonexistent => "DocumentVersionNonexistent",
         LbErrKind::DrawingInvalid => "DrawingInvalid",
         LbErrKind::ExistingRequestPending => "ExistingRequestPending",
         LbErrKind::FileNameContainsSlash => "FileNameContainsSlash",
@@ -94,12 +98,17 @@ pub(crate) fn throw_err<'local>(env: &mut JNIEnv<'local>, err: LbErr) -> JObject
         LbErrKind::UsageIsOverFreeTierDataCap => "UsageIsOverFreeTierDataCap",
         LbErrKind::OldCardDoesNotExist => "OldCardDoesNotExist",
         LbErrKind::PathContainsEmptyFileName => "PathContainsEmptyFileName",
+        LbErrKind::PublicationNonexistent => "PublicationNonexistent",
+        LbErrKind::PublicationTooLarge => "PublicationTooLarge",
+        LbErrKind::TooManyPublications => "TooManyPublications",
+        LbErrKind::RateLimited => "RateLimited",
         LbErrKind::RootModificationInvalid => "RootModificationInvalid",
         LbErrKind::RootNonexistent => "RootNonexistent",
         LbErrKind::ServerDisabled => "ServerDisabled",
         LbErrKind::ServerUnreachable => "ServerUnreachable",
         LbErrKind::ShareAlreadyExists => "ShareAlreadyExists",
         LbErrKind::ShareNonexistent => "ShareNonexistent",
+        LbErrKind::TakeoutCorrupted => "TakeoutCorrupted",
         LbErrKind::TryAgain => "TryAgain",
         LbErrKind::UsernameInvalid => "UsernameInvalid",
         LbErrKind::UsernameNotFound => "UsernameNotFound",
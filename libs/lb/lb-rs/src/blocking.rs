@@ -1,4 +1,9 @@
-use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
 use tokio::runtime::Runtime;
 use uuid::Uuid;
@@ -7,9 +12,10 @@ use crate::{
     model::{
         account::{Account, Username},
         api::{
-            AccountFilter, AccountIdentifier, AccountInfo, AdminFileInfoResponse,
-            AdminSetUserTierInfo, AdminValidateAccount, AdminValidateServer, ServerIndex,
-            StripeAccountTier, SubscriptionInfo,
+            AccountFilter, AccountIdentifier, AccountInfo, AccountState, AdminFileInfoResponse,
+            AdminGetAuditLogRequest, AdminRepairServer, AdminSetUserTierInfo, AdminValidateAccount,
+            AdminValidateServer, AuditEntry, ServerIndex, ServerRepair, StripeAccountTier,
+            SubscriptionInfo,
         },
         core_config::Config,
         crypto::DecryptedDocument,
@@ -20,13 +26,25 @@ use crate::{
     },
     service::{
         activity::RankingWeights,
+        collab::CollabSession,
+        conflicts::{Conflict, ConflictResolution, ConflictStrategy},
+        events::{Event, Receiver},
+        history::DocumentVersion,
         import_export::{ExportFileInfo, ImportStatus},
+        mirror::MirrorReport,
         search::{SearchConfig, SearchResult},
         sync::{SyncProgress, SyncStatus},
+        sync_scheduler::SyncStatusWatch,
+        takeout::TakeoutSummary,
         usage::{UsageItemMetric, UsageMetrics},
     },
 };
 
+#[cfg(feature = "importers")]
+use crate::service::importers::ImportFormat;
+#[cfg(feature = "render")]
+use crate::service::render::Theme;
+
 #[derive(Clone)]
 pub struct Lb {
     lb: crate::Lb,
@@ -71,6 +89,10 @@ impl Lb {
         self.lb.config.clone()
     }
 
+    pub fn subscribe(&self) -> Receiver<Event> {
+        self.lb.subscribe()
+    }
+
     pub fn create_file(&self, name: &str, parent: &Uuid, file_type: FileType) -> LbResult<File> {
         self.rt
             .block_on(self.lb.create_file(name, parent, file_type))
@@ -134,10 +156,55 @@ impl Lb {
         self.rt.block_on(self.lb.share_file(id, username, mode))
     }
 
+    pub fn publish(&self, id: Uuid, expires_in: Option<Duration>) -> LbResult<String> {
+        self.rt.block_on(self.lb.publish(id, expires_in))
+    }
+
+    pub fn unpublish(&self, id: Uuid) -> LbResult<()> {
+        self.rt.block_on(self.lb.unpublish(id))
+    }
+
     pub fn get_pending_shares(&self) -> LbResult<Vec<File>> {
         self.rt.block_on(self.lb.get_pending_shares())
     }
 
+    pub fn join_collab(&self, id: Uuid) -> LbResult<CollabSession> {
+        self.rt.block_on(self.lb.join_collab(id))
+    }
+
+    pub fn get_conflict_strategy(&self) -> ConflictStrategy {
+        self.rt.block_on(self.lb.get_conflict_strategy())
+    }
+
+    pub fn set_conflict_strategy(&self, strategy: ConflictStrategy) -> LbResult<()> {
+        self.rt.block_on(self.lb.set_conflict_strategy(strategy))
+    }
+
+    pub fn list_conflicts(&self) -> LbResult<Vec<Conflict>> {
+        self.rt.block_on(self.lb.list_conflicts())
+    }
+
+    pub fn resolve_conflict(&self, id: Uuid, resolution: ConflictResolution) -> LbResult<()> {
+        self.rt.block_on(self.lb.resolve_conflict(id, resolution))
+    }
+
+    pub fn get_document_history(&self, id: Uuid) -> LbResult<Vec<DocumentVersion>> {
+        self.rt.block_on(self.lb.get_document_history(id))
+    }
+
+    pub fn read_document_version(&self, id: Uuid, version: usize) -> LbResult<DecryptedDocument> {
+        self.rt.block_on(self.lb.read_document_version(id, version))
+    }
+
+    pub fn restore_document_version(&self, id: Uuid, version: usize) -> LbResult<()> {
+        self.rt
+            .block_on(self.lb.restore_document_version(id, version))
+    }
+
+    pub fn diff_document(&self, id: Uuid, version: Option<usize>) -> LbResult<String> {
+        self.rt.block_on(self.lb.diff_document(id, version))
+    }
+
     pub fn delete_pending_share(&self, id: &Uuid) -> LbResult<()> {
         self.rt.block_on(async { self.lb.reject_share(id).await })
     }
@@ -179,6 +246,26 @@ impl Lb {
         self.rt.block_on(self.lb.sync(f))
     }
 
+    pub fn sync_now(&self, f: Option<Box<dyn Fn(SyncProgress) + Send>>) -> LbResult<SyncStatus> {
+        self.rt.block_on(self.lb.sync_now(f))
+    }
+
+    pub fn pause_sync(&self) {
+        self.lb.pause_sync()
+    }
+
+    pub fn resume_sync(&self) {
+        self.lb.resume_sync()
+    }
+
+    pub fn set_sync_interval(&self, interval: Duration) {
+        self.lb.set_sync_interval(interval)
+    }
+
+    pub fn background_sync_status(&self) -> SyncStatusWatch {
+        self.lb.background_sync_status()
+    }
+
     pub fn get_last_synced(&self) -> LbResult<i64> {
         self.rt.block_on(async {
             let tx = self.lb.ro_tx().await;
@@ -219,6 +306,18 @@ impl Lb {
             .block_on(self.lb.import_files(sources, dest, update_status))
     }
 
+    #[cfg(feature = "importers")]
+    pub fn import_from<F: Fn(ImportStatus)>(
+        &self, format: ImportFormat, source: &Path, dest: Uuid, update_status: &F,
+    ) -> LbResult<()> {
+        self.rt
+            .block_on(self.lb.import_from(format, source, dest, update_status))
+    }
+
+    pub fn mirror(&self, folder: Uuid, dir: &Path) -> LbResult<MirrorReport> {
+        self.rt.block_on(self.lb.mirror(folder, dir))
+    }
+
     pub fn export_files(
         &self, id: Uuid, dest: PathBuf, edit: bool,
         export_progress: &Option<Box<dyn Fn(ExportFileInfo)>>,
@@ -227,6 +326,28 @@ impl Lb {
             .block_on(self.lb.export_file(id, dest, edit, export_progress))
     }
 
+    #[cfg(feature = "render")]
+    pub fn export_site(
+        &self, id: Uuid, dest: PathBuf, theme: Theme,
+        export_progress: &Option<Box<dyn Fn(ExportFileInfo)>>,
+    ) -> LbResult<()> {
+        self.rt
+            .block_on(self.lb.export_site(id, dest, theme, export_progress))
+    }
+
+    #[cfg(feature = "render")]
+    pub fn export_pdf(&self, id: Uuid, dest: PathBuf, theme: Theme) -> LbResult<Vec<Warning>> {
+        self.rt.block_on(self.lb.export_pdf(id, dest, theme))
+    }
+
+    pub fn takeout(&self, dest: &Path) -> LbResult<TakeoutSummary> {
+        self.rt.block_on(self.lb.takeout(dest))
+    }
+
+    pub fn import_takeout(&self, key: &str, archive: &Path) -> LbResult<Account> {
+        self.rt.block_on(self.lb.import_takeout(key, archive))
+    }
+
     pub fn search_file_paths(&self, input: &str) -> LbResult<Vec<SearchResult>> {
         self.rt
             .block_on(async { self.lb.search(input, SearchConfig::Paths).await })
@@ -307,10 +428,30 @@ impl Lb {
         self.rt.block_on(self.lb.rebuild_index(index))
     }
 
+    pub fn admin_repair_server(
+        &self, repair: ServerRepair, dry_run: bool,
+    ) -> LbResult<AdminRepairServer> {
+        self.rt.block_on(self.lb.repair_server(repair, dry_run))
+    }
+
     pub fn admin_set_user_tier(&self, username: &str, info: AdminSetUserTierInfo) -> LbResult<()> {
         self.rt.block_on(self.lb.set_user_tier(username, info))
     }
 
+    pub fn admin_set_data_cap(&self, username: Option<&str>, cap: Option<u64>) -> LbResult<()> {
+        self.rt.block_on(self.lb.set_data_cap(username, cap))
+    }
+
+    pub fn admin_set_account_state(&self, username: &str, state: AccountState) -> LbResult<()> {
+        self.rt.block_on(self.lb.set_account_state(username, state))
+    }
+
+    pub fn admin_get_audit_log(
+        &self, filter: AdminGetAuditLogRequest,
+    ) -> LbResult<Vec<AuditEntry>> {
+        self.rt.block_on(self.lb.get_audit_log(filter))
+    }
+
     pub fn debug_info(&self, os_info: String) -> String {
         self.rt
             .block_on(self.lb.debug_info(os_info))
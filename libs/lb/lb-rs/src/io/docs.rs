@@ -51,6 +51,15 @@ impl AsyncDocs {
             .ok_or_else(|| LbErrKind::FileNonexistent.into())
     }
 
+    /// How many bytes a document's contents take up, if they're stored
+    pub async fn size(&self, id: Uuid, hmac: DocumentHmac) -> LbResult<Option<u64>> {
+        match fs::metadata(key_path(&self.location, id, hmac)).await {
+            Ok(metadata) => Ok(Some(metadata.len())),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
     pub async fn maybe_get(
         &self, id: Uuid, hmac: Option<DocumentHmac>,
     ) -> LbResult<Option<EncryptedDocument>> {
@@ -12,6 +12,8 @@ use crate::model::account::Account;
 use crate::model::file_metadata::Owner;
 use crate::model::signed_file::SignedFile;
 use crate::service::activity::DocEvent;
+use crate::service::conflicts::{Conflict, ConflictStrategy};
+use crate::service::history::VersionRecord;
 use crate::Lb;
 use db_rs::{Db, List, LookupTable, Single, TxHandle};
 use db_rs_derive::Schema;
@@ -37,6 +39,13 @@ pub struct CoreV3 {
     pub pub_key_lookup: LookupTable<Owner, String>,
 
     pub doc_events: List<DocEvent>,
+
+    /// unresolved text merge conflicts by document id
+    pub conflicts: LookupTable<Uuid, Conflict>,
+    pub conflict_strategy: Single<ConflictStrategy>,
+
+    /// synced versions of each document, oldest first
+    pub doc_versions: LookupTable<Uuid, Vec<VersionRecord>>,
 }
 
 pub struct LbRO<'a> {
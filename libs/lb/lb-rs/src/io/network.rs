@@ -1,3 +1,4 @@
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
 use reqwest::Client;
@@ -19,6 +20,11 @@ impl<E> From<ErrorWrapper<E>> for ApiError<E> {
             ErrorWrapper::ExpiredAuth => ApiError::ExpiredAuth,
             ErrorWrapper::InternalError => ApiError::InternalError,
             ErrorWrapper::BadRequest => ApiError::BadRequest,
+            ErrorWrapper::RateLimited { retry_after_ms } => {
+                ApiError::RateLimited { retry_after_ms }
+            }
+            ErrorWrapper::AccountReadOnly => ApiError::AccountReadOnly,
+            ErrorWrapper::AccountSuspended => ApiError::AccountSuspended,
         }
     }
 }
@@ -32,6 +38,9 @@ pub enum ApiError<E> {
     ExpiredAuth,
     InternalError,
     BadRequest,
+    RateLimited { retry_after_ms: u64 },
+    AccountReadOnly,
+    AccountSuspended,
     Sign(LbErr),
     Serialize(String),
     SendFailed(String),
@@ -45,15 +54,33 @@ pub struct Network {
     pub client: Client,
     pub get_code_version: fn() -> &'static str,
     pub get_time: fn() -> Timestamp,
+
+    /// when the server said we could come back after the last rate limit we gave up waiting out
+    pub rate_limited_until: Arc<Mutex<Option<Instant>>>,
 }
 
 impl Default for Network {
     fn default() -> Self {
-        Self { client: Default::default(), get_code_version, get_time }
+        Self {
+            client: Default::default(),
+            get_code_version,
+            get_time,
+            rate_limited_until: Default::default(),
+        }
     }
 }
 
+/// Rate limits asking for a longer wait than this are surfaced to the caller instead
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(10);
+const MAX_RATE_LIMIT_RETRIES: u32 = 3;
+
 impl Network {
+    /// See [Network::rate_limited_until]. None once that time has passed.
+    pub fn rate_limited_until(&self) -> Option<Instant> {
+        let until = (*self.rate_limited_until.lock().unwrap())?;
+        (until > Instant::now()).then_some(until)
+    }
+
     #[instrument(level = "debug", skip(self, account, request), fields(route=T::ROUTE), err(Debug))]
     pub async fn request<T: Request>(
         &self, account: &Account, request: T,
@@ -74,14 +101,41 @@ impl Network {
             warn!("making network request with {} bytes", serialized_request.len());
         }
 
+        let mut rate_limit_retries = 0;
+        loop {
+            match self
+                .send::<T>(account, &serialized_request, &client_version)
+                .await
+            {
+                Err(ApiError::RateLimited { retry_after_ms })
+                    if rate_limit_retries < MAX_RATE_LIMIT_RETRIES
+                        && Duration::from_millis(retry_after_ms) <= MAX_RATE_LIMIT_WAIT =>
+                {
+                    warn!("network request rate limited; retrying after {}ms", retry_after_ms);
+                    sleep(Duration::from_millis(retry_after_ms)).await;
+                    rate_limit_retries += 1;
+                }
+                Err(ApiError::RateLimited { retry_after_ms }) => {
+                    let until = Instant::now() + Duration::from_millis(retry_after_ms);
+                    *self.rate_limited_until.lock().unwrap() = Some(until);
+                    return Err(ApiError::RateLimited { retry_after_ms });
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn send<T: Request>(
+        &self, account: &Account, serialized_request: &[u8], client_version: &str,
+    ) -> Result<T::Response, ApiError<T::Error>> {
         let mut retries = 0;
         let start = Instant::now();
         let sent = loop {
             match self
                 .client
                 .request(T::METHOD, format!("{}{}", account.api_url, T::ROUTE).as_str())
-                .body(serialized_request.clone())
-                .header("Accept-Version", client_version.clone())
+                .body(serialized_request.to_vec())
+                .header("Accept-Version", client_version)
                 .send()
                 .await
             {
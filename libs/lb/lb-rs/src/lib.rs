@@ -33,6 +33,7 @@ pub struct Lb {
     pub client: Network,
     pub events: EventSubs,
     pub syncing: Arc<AtomicBool>,
+    pub sync_scheduler: SyncScheduler,
 }
 
 impl Lb {
@@ -49,9 +50,12 @@ impl Lb {
         let search = SearchIndex::default();
         let syncing = Arc::default();
         let events = EventSubs::default();
+        let sync_scheduler = SyncScheduler::default();
 
-        let result = Self { config, keychain, db, docs, client, search, syncing, events };
+        let result =
+            Self { config, keychain, db, docs, client, search, syncing, events, sync_scheduler };
         result.setup_search();
+        result.setup_sync_scheduler();
         Ok(result)
     }
 }
@@ -74,6 +78,7 @@ use model::errors::{LbErrKind, LbResult};
 use service::events::EventSubs;
 use service::keychain::Keychain;
 use service::search::SearchIndex;
+use service::sync_scheduler::SyncScheduler;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -4,6 +4,7 @@ use crate::model::crypto::*;
 use crate::model::file_metadata::{DocumentHmac, FileDiff, FileMetadata, Owner};
 use crate::model::server_file::ServerFile;
 use crate::model::signed_file::SignedFile;
+use crate::model::text::collab::CollabMessage;
 use crate::model::ValidationFailure;
 use http::Method;
 use libsecp256k1::PublicKey;
@@ -40,6 +41,25 @@ pub enum ErrorWrapper<E> {
     ExpiredAuth,
     InternalError,
     BadRequest,
+    /// The caller is sending requests too quickly and should wait before trying again
+    RateLimited {
+        retry_after_ms: u64,
+    },
+    /// An admin has made the caller's account read-only, so it can't make changes
+    AccountReadOnly,
+    /// An admin has suspended the caller's account, so it can only download its files
+    AccountSuspended,
+}
+
+/// Set by an admin, for example to freeze an account during an investigation
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum AccountState {
+    #[default]
+    Active,
+    /// Files can be read and synced down but not changed
+    ReadOnly,
+    /// Only downloading files is allowed
+    Suspended,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -235,20 +255,37 @@ impl Request for GetFileIdsRequest {
     const ROUTE: &'static str = "/get-file-ids";
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+/// Files in the caller's tree changed since `since_metadata_version`. With a `page_size`, results
+/// come in pages ordered by version; a file changed while paging shows up again in a later page.
+/// Without one, everything comes back in a single response.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Default)]
 pub struct GetUpdatesRequest {
     pub since_metadata_version: u64,
+
+    /// `next_cursor` of the previous page, absent for the first page
+    #[serde(default)]
+    pub cursor: Option<String>,
+
+    #[serde(default)]
+    pub page_size: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct GetUpdatesResponse {
+    /// pass as `since_metadata_version` next time, taken from the last page
     pub as_of_metadata_version: u64,
     pub file_metadata: Vec<SignedFile>,
+
+    /// present if there are more pages
+    #[serde(default)]
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub enum GetUpdatesError {
     UserNotFound,
+    /// the cursor is malformed or belongs to a request for a different version
+    InvalidCursor,
 }
 
 impl Request for GetUpdatesRequest {
@@ -258,6 +295,211 @@ impl Request for GetUpdatesRequest {
     const ROUTE: &'static str = "/get-updates";
 }
 
+/// A page of everything an account owns, for offline backups: the metadata of every owned file,
+/// deleted or not, and the contents of those documents that still have contents. Pages are
+/// ordered by file id. Admins may take out someone else's account by passing their `username`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Default)]
+pub struct TakeoutRequest {
+    #[serde(default)]
+    pub username: Option<String>,
+
+    /// `next` of the previous page, absent for the first page
+    #[serde(default)]
+    pub after: Option<Uuid>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct TakeoutResponse {
+    pub files: Vec<ServerFile>,
+    pub documents: Vec<TakeoutDocument>,
+
+    /// present if there are more pages
+    #[serde(default)]
+    pub next: Option<Uuid>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct TakeoutDocument {
+    pub id: Uuid,
+    pub hmac: DocumentHmac,
+    pub content: EncryptedDocument,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum TakeoutError {
+    UserNotFound,
+    NotPermissioned,
+}
+
+impl Request for TakeoutRequest {
+    type Response = TakeoutResponse;
+    type Error = TakeoutError;
+    const METHOD: Method = Method::GET;
+    const ROUTE: &'static str = "/takeout";
+}
+
+/// Long-polls the server until any file visible to this account (owned or shared) has a version
+/// at least `since_metadata_version`, or the server gives up waiting.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct AwaitUpdatesRequest {
+    pub since_metadata_version: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct AwaitUpdatesResponse {
+    /// false if the server stopped waiting without seeing an update, clients should simply ask
+    /// again
+    pub updated: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum AwaitUpdatesError {
+    UserNotFound,
+}
+
+impl Request for AwaitUpdatesRequest {
+    type Response = AwaitUpdatesResponse;
+    type Error = AwaitUpdatesError;
+    const METHOD: Method = Method::GET;
+    const ROUTE: &'static str = "/await-updates";
+}
+
+pub type EncryptedCollabMessage = AESEncrypted<CollabMessage>;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct PushCollabRequest {
+    pub id: Uuid,
+    pub message: EncryptedCollabMessage,
+    /// Set when `message` is a snapshot; the snapshot is only accepted as the first message of a
+    /// session, unless it compacts the session
+    pub is_snapshot: bool,
+    /// Set along with `is_snapshot` when the snapshot is of the session's text as of `ack.seq`,
+    /// the session's head. Once every participant has caught up to it, the snapshot replaces the
+    /// session's messages for participants who join later.
+    pub compacts: Option<CollabAck>,
+}
+
+/// How far a participant of a collaborative editing session has caught up: none of the messages
+/// it sends from now on are based on messages sequenced before `seq`
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct CollabAck {
+    pub participant: Uuid,
+    pub seq: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct PushCollabResponse {
+    pub seq: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum PushCollabError {
+    DocumentNotFound,
+    NotPermissioned,
+    /// A snapshot was pushed to a session that already has one
+    SessionStarted,
+    /// A message other than a snapshot was pushed to a session that hasn't started
+    SessionNotStarted,
+    /// The session has grown too large, participants should end it and save their work
+    SessionFull,
+    MessageTooLarge,
+    /// The caller already has as many sessions open as they're allowed
+    TooManySessions,
+    /// A compacting snapshot was pushed before every participant caught up to it
+    ParticipantsBehind,
+}
+
+impl Request for PushCollabRequest {
+    type Response = PushCollabResponse;
+    type Error = PushCollabError;
+    const METHOD: Method = Method::POST;
+    const ROUTE: &'static str = "/push-collab";
+}
+
+/// Returns messages sequenced after `since`, waiting for some to arrive if there are none
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct PullCollabRequest {
+    pub id: Uuid,
+    pub since: u64,
+    pub ack: CollabAck,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct PullCollabResponse {
+    /// Sequence number of the latest message in the session, lower than `since` if the session
+    /// the client was part of has ended or was compacted past where the client caught up to
+    pub head: u64,
+    pub messages: Vec<(u64, EncryptedCollabMessage)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum PullCollabError {
+    DocumentNotFound,
+    NotPermissioned,
+    /// The caller already has as many sessions open as they're allowed
+    TooManySessions,
+}
+
+impl Request for PullCollabRequest {
+    type Response = PullCollabResponse;
+    type Error = PullCollabError;
+    const METHOD: Method = Method::GET;
+    const ROUTE: &'static str = "/pull-collab";
+}
+
+/// A [crate::model::publication::Publication] as JSON, encrypted with a key that's only shared
+/// through the link's fragment
+pub type EncryptedPublication = AESEncrypted<Vec<u8>>;
+
+/// Publishes a copy of a file the caller owns, replacing any earlier publication of it. The copy
+/// doesn't follow later changes to the file; publish again to update it.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct PublishRequest {
+    pub file_id: Uuid,
+    pub content: EncryptedPublication,
+    pub expires_at: Option<UnixTimeMillis>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct PublishResponse {
+    /// the publication is served at `/p/{link_id}`
+    pub link_id: Uuid,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum PublishError {
+    FileNonexistent,
+    NotPermissioned,
+    TooLarge,
+    TooMany,
+    UsageIsOverDataCap,
+}
+
+impl Request for PublishRequest {
+    type Response = PublishResponse;
+    type Error = PublishError;
+    const METHOD: Method = Method::POST;
+    const ROUTE: &'static str = "/publish";
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct UnpublishRequest {
+    pub file_id: Uuid,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum UnpublishError {
+    NotPublished,
+    NotPermissioned,
+}
+
+impl Request for UnpublishRequest {
+    type Response = ();
+    type Error = UnpublishError;
+    const METHOD: Method = Method::DELETE;
+    const ROUTE: &'static str = "/unpublish";
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct NewAccountRequest {
     pub username: Username,
@@ -365,6 +607,7 @@ pub enum UpgradeAccountStripeError {
     InvalidCardCvc,
     ExistingRequestPending,
     UserNotFound,
+    Disabled,
 }
 
 impl Request for UpgradeAccountStripeRequest {
@@ -389,6 +632,7 @@ pub enum UpgradeAccountGooglePlayError {
     InvalidPurchaseToken,
     ExistingRequestPending,
     UserNotFound,
+    Disabled,
 }
 
 impl Request for UpgradeAccountGooglePlayRequest {
@@ -414,6 +658,7 @@ pub enum UpgradeAccountAppStoreError {
     InvalidAuthDetails,
     ExistingRequestPending,
     UserNotFound,
+    Disabled,
 }
 
 impl Request for UpgradeAccountAppStoreRequest {
@@ -437,6 +682,7 @@ pub enum CancelSubscriptionError {
     UserNotFound,
     ExistingRequestPending,
     CannotCancelForAppStore,
+    Disabled,
 }
 
 impl Request for CancelSubscriptionRequest {
@@ -648,6 +894,8 @@ pub struct AccountInfo {
     pub root: Uuid,
     pub payment_platform: Option<PaymentPlatform>,
     pub usage: String,
+    #[serde(default)]
+    pub state: AccountState,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -739,6 +987,52 @@ impl Request for AdminSetUserTierRequest {
     const ROUTE: &'static str = "/admin-set-user-tier";
 }
 
+/// Overrides the data cap of one user, or of every user without an override if no username is
+/// given. A cap of `None` removes the override.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct AdminSetDataCapRequest {
+    pub username: Option<String>,
+    pub cap: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AdminSetDataCapResponse {}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum AdminSetDataCapError {
+    UserNotFound,
+    NotPermissioned,
+}
+
+impl Request for AdminSetDataCapRequest {
+    type Response = AdminSetDataCapResponse;
+    type Error = AdminSetDataCapError;
+    const METHOD: Method = Method::POST;
+    const ROUTE: &'static str = "/admin-set-data-cap";
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AdminSetAccountStateRequest {
+    pub username: String,
+    pub state: AccountState,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AdminSetAccountStateResponse {}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum AdminSetAccountStateError {
+    UserNotFound,
+    NotPermissioned,
+}
+
+impl Request for AdminSetAccountStateRequest {
+    type Response = AdminSetAccountStateResponse;
+    type Error = AdminSetAccountStateError;
+    const METHOD: Method = Method::POST;
+    const ROUTE: &'static str = "/admin-set-account-state";
+}
+
 // number of milliseconds that have elapsed since the unix epoch
 pub type UnixTimeMillis = u64;
 
@@ -766,6 +1060,107 @@ impl Request for AdminRebuildIndexRequest {
     const ROUTE: &'static str = "/admin-rebuild-index";
 }
 
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+pub enum ServerRepair {
+    /// Set each document's size from its stored contents and drop sizes of files without contents
+    Sizes,
+    /// Delete stored contents that no file refers to
+    OrphanedDocuments,
+    /// Bring owned_files, shared_files, and file_children in line with the file metadata
+    Indexes,
+    /// Disappear links to files that were unshared, deleted, or are owned by the link's owner
+    BrokenShares,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AdminRepairServerRequest {
+    pub repair: ServerRepair,
+    /// Report what would change without changing it
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Default)]
+pub struct AdminRepairServer {
+    pub sizes_set: HashMap<Uuid, u64>,
+    pub sizes_removed: HashSet<Uuid>,
+    pub orphaned_documents_deleted: HashMap<Uuid, usize>,
+    pub owned_files_mapped: HashMap<Owner, HashSet<Uuid>>,
+    pub owned_files_unmapped: HashMap<Owner, HashSet<Uuid>>,
+    pub shared_files_mapped: HashMap<Owner, HashSet<Uuid>>,
+    pub shared_files_unmapped: HashMap<Owner, HashSet<Uuid>>,
+    pub file_children_mapped: HashMap<Uuid, HashSet<Uuid>>,
+    pub file_children_unmapped: HashMap<Uuid, HashSet<Uuid>>,
+    pub links_disappeared: HashMap<Owner, HashSet<Uuid>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum AdminRepairServerError {
+    NotPermissioned,
+}
+
+impl Request for AdminRepairServerRequest {
+    type Response = AdminRepairServer;
+    type Error = AdminRepairServerError;
+    const METHOD: Method = Method::POST;
+    const ROUTE: &'static str = "/admin-repair-server";
+}
+
+/// One request to a route that changes accounts or the server on someone's behalf, as the server
+/// journaled it
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct AuditEntry {
+    /// position in the journal, counting from 0
+    pub seq: u64,
+    pub timestamp: UnixTimeMillis,
+    pub actor: PublicKey,
+    pub actor_username: Option<Username>,
+    /// the route that was requested
+    pub action: String,
+    /// the username or file id the action was aimed at, if any
+    pub target: Option<String>,
+    /// the request, as JSON
+    pub details: String,
+    pub outcome: AuditOutcome,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum AuditOutcome {
+    Succeeded,
+    /// the endpoint's error
+    Rejected(String),
+    /// the server failed
+    Failed(String),
+}
+
+/// Journal entries matching every given filter, oldest first
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct AdminGetAuditLogRequest {
+    /// only entries with a greater `seq`, to pick up where an earlier request left off
+    pub after: Option<u64>,
+    pub actor: Option<Username>,
+    pub action: Option<String>,
+    pub target: Option<String>,
+    /// at most this many entries, 100 if absent
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AdminGetAuditLogResponse {
+    pub entries: Vec<AuditEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum AdminGetAuditLogError {
+    NotPermissioned,
+}
+
+impl Request for AdminGetAuditLogRequest {
+    type Response = AdminGetAuditLogResponse;
+    type Error = AdminGetAuditLogError;
+    const METHOD: Method = Method::GET;
+    const ROUTE: &'static str = "/admin-get-audit-log";
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub enum StripeAccountState {
     Ok,
@@ -43,6 +43,12 @@ impl Display for LbErrKind {
         match self {
             LbErrKind::AccountExists => write!(f, "An account already exists"),
             LbErrKind::AccountNonexistent => write!(f, "You need an account to do that"),
+            LbErrKind::AccountReadOnly => {
+                write!(f, "Your account is read-only, please contact support")
+            }
+            LbErrKind::AccountSuspended => {
+                write!(f, "Your account is suspended, please contact support")
+            }
             LbErrKind::AccountStringCorrupted => write!(f, "That account key is invalid"),
             LbErrKind::AlreadyCanceled => write!(f, "Your subscription has already been cancelled"),
             LbErrKind::AlreadyPremium => write!(f, "Your account is already premium"),
@@ -63,11 +69,15 @@ impl Display for LbErrKind {
             LbErrKind::ClientUpdateRequired => {
                 write!(f, "You must update your Lockbook to do that")
             }
+            LbErrKind::ConflictNonexistent => write!(f, "That file has no unresolved conflict"),
             LbErrKind::CurrentUsageIsMoreThanNewTier => {
                 write!(f, "You need to delete some files before downgrading your usage")
             }
             LbErrKind::DiskPathInvalid => write!(f, "That disk path is invalid"),
             LbErrKind::DiskPathTaken => write!(f, "That disk path is not available"),
+            LbErrKind::DocumentVersionNonexistent => {
+                write!(f, "This device doesn't know that version of the document")
+            }
             LbErrKind::DrawingInvalid => write!(f, "That drawing is invalid"),
             LbErrKind::ExistingRequestPending => {
                 write!(f, "Existing billing request in progress, please wait and try again")
@@ -99,6 +109,12 @@ impl Display for LbErrKind {
             LbErrKind::PathContainsEmptyFileName => {
                 write!(f, "That path contains an empty file name")
             }
+            LbErrKind::PublicationNonexistent => write!(f, "That file is not published"),
+            LbErrKind::PublicationTooLarge => write!(f, "That is too large to publish"),
+            LbErrKind::TooManyPublications => {
+                write!(f, "You have too many publications, unpublish some first")
+            }
+            LbErrKind::RateLimited => write!(f, "Too many requests, please try again later"),
             LbErrKind::RootModificationInvalid => write!(f, "You cannot modify your root"),
             LbErrKind::RootNonexistent => write!(f, "Could not find your root file"),
             LbErrKind::ServerDisabled => write!(
@@ -108,6 +124,9 @@ impl Display for LbErrKind {
             LbErrKind::ServerUnreachable => write!(f, "Could not reach server"),
             LbErrKind::ShareAlreadyExists => write!(f, "That share already exists"),
             LbErrKind::ShareNonexistent => write!(f, "That share does not exist"),
+            LbErrKind::TakeoutCorrupted => {
+                write!(f, "That account archive is damaged or incomplete")
+            }
             LbErrKind::TryAgain => write!(f, "Please try again"),
             LbErrKind::UsernameInvalid => write!(f, "That username is invalid"),
             LbErrKind::UsernameNotFound => write!(f, "That username is not found"),
@@ -258,7 +277,9 @@ macro_rules! unexpected_only {
 pub enum LbErrKind {
     AccountExists,
     AccountNonexistent,
+    AccountReadOnly,
     AccountStringCorrupted,
+    AccountSuspended,
     AlreadyCanceled,
     AlreadyPremium,
     AppStoreAccountAlreadyLinked,
@@ -274,9 +295,11 @@ pub enum LbErrKind {
     CardInvalidNumber,
     CardNotSupported,
     ClientUpdateRequired,
+    ConflictNonexistent,
     CurrentUsageIsMoreThanNewTier,
     DiskPathInvalid,
     DiskPathTaken,
+    DocumentVersionNonexistent,
     DrawingInvalid,
     ExistingRequestPending,
     // todo: Group
@@ -296,12 +319,17 @@ pub enum LbErrKind {
     UsageIsOverFreeTierDataCap,
     OldCardDoesNotExist,
     PathContainsEmptyFileName,
+    PublicationNonexistent,
+    PublicationTooLarge,
+    TooManyPublications,
+    RateLimited,
     RootModificationInvalid,
     RootNonexistent,
     ServerDisabled,
     ServerUnreachable,
     ShareAlreadyExists,
     ShareNonexistent,
+    TakeoutCorrupted,
     TryAgain,
     // todo: group username errors
     UsernameInvalid,
@@ -398,6 +426,9 @@ impl From<ApiError<api::NewAccountError>> for LbErr {
     fn from(err: ApiError<api::NewAccountError>) -> Self {
         match err {
             ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+            ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+            ApiError::AccountSuspended => LbErrKind::AccountSuspended,
             ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
             ApiError::Endpoint(api::NewAccountError::UsernameTaken) => LbErrKind::UsernameTaken,
             ApiError::Endpoint(api::NewAccountError::InvalidUsername) => LbErrKind::UsernameInvalid,
@@ -412,6 +443,9 @@ impl From<ApiError<api::GetPublicKeyError>> for LbErr {
     fn from(err: ApiError<api::GetPublicKeyError>) -> Self {
         match err {
             ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+            ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+            ApiError::AccountSuspended => LbErrKind::AccountSuspended,
             ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
             ApiError::Endpoint(api::GetPublicKeyError::UserNotFound) => {
                 LbErrKind::AccountNonexistent
@@ -426,6 +460,9 @@ impl From<ApiError<api::GetUsernameError>> for LbErr {
     fn from(err: ApiError<api::GetUsernameError>) -> Self {
         match err {
             ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+            ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+            ApiError::AccountSuspended => LbErrKind::AccountSuspended,
             ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
             ApiError::Endpoint(api::GetUsernameError::UserNotFound) => {
                 LbErrKind::AccountNonexistent
@@ -440,6 +477,9 @@ impl From<ApiError<api::GetFileIdsError>> for LbErr {
     fn from(e: ApiError<api::GetFileIdsError>) -> Self {
         match e {
             ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+            ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+            ApiError::AccountSuspended => LbErrKind::AccountSuspended,
             ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
             e => core_err_unexpected(e),
         }
@@ -451,7 +491,81 @@ impl From<ApiError<api::GetUpdatesError>> for LbErr {
     fn from(e: ApiError<api::GetUpdatesError>) -> Self {
         match e {
             ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+            ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+            ApiError::AccountSuspended => LbErrKind::AccountSuspended,
+            ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+            e => core_err_unexpected(e),
+        }
+        .into()
+    }
+}
+
+impl From<ApiError<api::TakeoutError>> for LbErr {
+    fn from(e: ApiError<api::TakeoutError>) -> Self {
+        match e {
+            ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+            ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+            ApiError::AccountSuspended => LbErrKind::AccountSuspended,
+            ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+            e => core_err_unexpected(e),
+        }
+        .into()
+    }
+}
+
+impl From<ApiError<api::AwaitUpdatesError>> for LbErr {
+    fn from(err: ApiError<api::AwaitUpdatesError>) -> Self {
+        match err {
+            ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+            ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+            ApiError::AccountSuspended => LbErrKind::AccountSuspended,
+            ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+            ApiError::Endpoint(api::AwaitUpdatesError::UserNotFound) => {
+                LbErrKind::AccountNonexistent
+            }
+            e => core_err_unexpected(e),
+        }
+        .into()
+    }
+}
+
+impl From<ApiError<api::PushCollabError>> for LbErr {
+    fn from(err: ApiError<api::PushCollabError>) -> Self {
+        match err {
+            ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+            ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+            ApiError::AccountSuspended => LbErrKind::AccountSuspended,
+            ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+            ApiError::Endpoint(api::PushCollabError::DocumentNotFound) => {
+                LbErrKind::FileNonexistent
+            }
+            ApiError::Endpoint(api::PushCollabError::NotPermissioned) => {
+                LbErrKind::InsufficientPermission
+            }
+            e => core_err_unexpected(e),
+        }
+        .into()
+    }
+}
+
+impl From<ApiError<api::PullCollabError>> for LbErr {
+    fn from(err: ApiError<api::PullCollabError>) -> Self {
+        match err {
+            ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+            ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+            ApiError::AccountSuspended => LbErrKind::AccountSuspended,
             ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+            ApiError::Endpoint(api::PullCollabError::DocumentNotFound) => {
+                LbErrKind::FileNonexistent
+            }
+            ApiError::Endpoint(api::PullCollabError::NotPermissioned) => {
+                LbErrKind::InsufficientPermission
+            }
             e => core_err_unexpected(e),
         }
         .into()
@@ -462,6 +576,9 @@ impl From<ApiError<api::GetDocumentError>> for LbErr {
     fn from(e: ApiError<api::GetDocumentError>) -> Self {
         match e {
             ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+            ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+            ApiError::AccountSuspended => LbErrKind::AccountSuspended,
             ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
             e => core_err_unexpected(e),
         }
@@ -473,6 +590,9 @@ impl From<ApiError<api::UpsertError>> for LbErr {
     fn from(e: ApiError<api::UpsertError>) -> Self {
         match e {
             ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+            ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+            ApiError::AccountSuspended => LbErrKind::AccountSuspended,
             ApiError::Endpoint(api::UpsertError::UsageIsOverDataCap) => {
                 LbErrKind::UsageIsOverDataCap
             }
@@ -487,6 +607,9 @@ impl From<ApiError<api::ChangeDocError>> for LbErr {
     fn from(e: ApiError<api::ChangeDocError>) -> Self {
         match e {
             ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+            ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+            ApiError::AccountSuspended => LbErrKind::AccountSuspended,
             ApiError::Endpoint(api::ChangeDocError::UsageIsOverDataCap) => {
                 LbErrKind::UsageIsOverDataCap
             }
@@ -501,6 +624,9 @@ impl From<ApiError<api::GetUsageError>> for LbErr {
     fn from(e: ApiError<api::GetUsageError>) -> Self {
         match e {
             ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+            ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+            ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+            ApiError::AccountSuspended => LbErrKind::AccountSuspended,
             ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
             e => core_err_unexpected(e),
         }
@@ -512,6 +638,8 @@ impl From<ApiError<api::GetUsageError>> for LbErr {
 pub enum Warning {
     EmptyFile(Uuid),
     InvalidUTF8(Uuid),
+    /// characters an export couldn't show, which were replaced with `?`
+    UnprintableCharacters(Uuid, Vec<char>),
 }
 
 impl fmt::Display for Warning {
@@ -519,6 +647,10 @@ impl fmt::Display for Warning {
         match self {
             Self::EmptyFile(id) => write!(f, "empty file: {}", id),
             Self::InvalidUTF8(id) => write!(f, "invalid utf8 in file: {}", id),
+            Self::UnprintableCharacters(id, chars) => {
+                let chars = chars.iter().collect::<String>();
+                write!(f, "characters printed as '?' in file: {}: {}", id, chars)
+            }
         }
     }
 }
@@ -22,6 +22,7 @@ pub mod file_metadata;
 pub mod filename;
 pub mod lazy;
 pub mod path_ops;
+pub mod publication;
 pub mod pubkey;
 pub mod secret_filename;
 pub mod server_file;
@@ -235,3 +235,28 @@ fn split_path(path: &str) -> Vec<&str> {
         .filter(|s| !s.is_empty()) // Remove the trailing empty element in the case this is a folder
         .collect::<Vec<&str>>()
 }
+
+/// Decodes the `%xx` escapes of a link destination, such as a space written as `%20`
+#[cfg(any(feature = "importers", feature = "render"))]
+pub(crate) fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,29 @@
+use crate::model::crypto::AESKey;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// What a publish link's page shows once it's decrypted. Serialized as JSON so the page's script
+/// can read it.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct Publication {
+    pub name: String,
+    pub files: Vec<PublishedFile>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct PublishedFile {
+    /// relative to the published folder, or just the name of a published document
+    pub path: String,
+    /// base64
+    pub content: String,
+}
+
+impl PublishedFile {
+    pub fn new(path: String, content: &[u8]) -> Self {
+        Self { path, content: base64::encode(content) }
+    }
+}
+
+pub fn publish_link(api_url: &str, link_id: Uuid, key: &AESKey) -> String {
+    format!("{api_url}/p/{link_id}#{}", base64::encode_config(key, base64::URL_SAFE_NO_PAD))
+}
@@ -4,16 +4,16 @@ use crate::model::file_like::FileLike;
 use crate::model::file_metadata::FileDiff;
 use crate::model::lazy::{LazyStaged1, LazyTree};
 use crate::model::server_file::{IntoServerFile, ServerFile};
-use crate::model::server_tree::ServerTree;
+use crate::model::server_tree::ServerTreeLike;
 use crate::model::signed_file::SignedFile;
 use crate::model::tree_like::TreeLike;
 
-type LazyServerStaged1<'a> = LazyStaged1<ServerTree<'a>, Vec<ServerFile>>;
+type LazyServerStaged1<T> = LazyStaged1<T, Vec<ServerFile>>;
 
-impl<'a> LazyTree<ServerTree<'a>> {
+impl<T: ServerTreeLike> LazyTree<T> {
     /// Validates a diff prior to staging it. Performs individual validations, then validations that
     /// require a tree
-    pub fn stage_diff(self, changes: Vec<FileDiff<SignedFile>>) -> LbResult<LazyServerStaged1<'a>> {
+    pub fn stage_diff(self, changes: Vec<FileDiff<SignedFile>>) -> LbResult<LazyServerStaged1<T>> {
         // Check new.id == old.id
         for change in &changes {
             if let Some(old) = &change.old {
@@ -55,7 +55,7 @@ impl<'a> LazyTree<ServerTree<'a>> {
                 }
                 None => {
                     // if you're claiming this file is new, it must be globally unique
-                    if self.tree.files.maybe_find(change.new.id()).is_some() {
+                    if self.tree.maybe_find_global(change.new.id()).is_some() {
                         return Err(LbErrKind::Diff(DiffError::OldVersionRequired))?;
                     }
                 }
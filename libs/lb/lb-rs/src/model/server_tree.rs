@@ -24,30 +24,90 @@ impl<'a> ServerTree<'a> {
         shared_files: &'a mut LookupSet<Owner, Uuid>, file_children: &'a mut LookupSet<Uuid, Uuid>,
         files: &'a mut LookupTable<Uuid, ServerFile>,
     ) -> LbResult<Self> {
-        let (owned_ids, shared_ids) =
-            match (owned_files.get().get(&owner), shared_files.get().get(&owner)) {
-                (Some(owned_ids), Some(shared_ids)) => (owned_ids.clone(), shared_ids.clone()),
-                _ => {
-                    error!("Tree created for user without owned and shared files {:?}", owner);
-                    (HashSet::new(), HashSet::new())
-                }
-            };
-
-        let mut ids = HashSet::new();
-        ids.extend(owned_ids);
-        ids.extend(shared_ids.clone());
-
-        let mut to_get_descendants = Vec::from_iter(shared_ids);
-        while let Some(id) = to_get_descendants.pop() {
-            let children = file_children.get().get(&id).cloned().unwrap_or_default();
-            ids.extend(children.clone());
-            to_get_descendants.extend(children);
-        }
-
+        let ids = tree_ids(owner, owned_files, shared_files, file_children);
         Ok(Self { ids, owned_files, shared_files, file_children, files })
     }
 }
 
+/// A read-only [ServerTree], for checking a change while others read the db concurrently. Staging
+/// a diff on it validates the diff without applying it.
+pub struct ServerTreeView<'a> {
+    pub ids: HashSet<Uuid>,
+    pub files: &'a LookupTable<Uuid, ServerFile>,
+}
+
+impl<'a> ServerTreeView<'a> {
+    pub fn new(
+        owner: Owner, owned_files: &LookupSet<Owner, Uuid>, shared_files: &LookupSet<Owner, Uuid>,
+        file_children: &LookupSet<Uuid, Uuid>, files: &'a LookupTable<Uuid, ServerFile>,
+    ) -> LbResult<Self> {
+        let ids = tree_ids(owner, owned_files, shared_files, file_children);
+        Ok(Self { ids, files })
+    }
+}
+
+/// Ids of the files `owner` owns, plus the files shared with them and those files' descendants
+fn tree_ids(
+    owner: Owner, owned_files: &LookupSet<Owner, Uuid>, shared_files: &LookupSet<Owner, Uuid>,
+    file_children: &LookupSet<Uuid, Uuid>,
+) -> HashSet<Uuid> {
+    let (owned_ids, shared_ids) =
+        match (owned_files.get().get(&owner), shared_files.get().get(&owner)) {
+            (Some(owned_ids), Some(shared_ids)) => (owned_ids.clone(), shared_ids.clone()),
+            _ => {
+                error!("Tree created for user without owned and shared files {:?}", owner);
+                (HashSet::new(), HashSet::new())
+            }
+        };
+
+    let mut ids = HashSet::new();
+    ids.extend(owned_ids);
+    ids.extend(shared_ids.clone());
+
+    let mut to_get_descendants = Vec::from_iter(shared_ids);
+    while let Some(id) = to_get_descendants.pop() {
+        let children = file_children.get().get(&id).cloned().unwrap_or_default();
+        ids.extend(children.clone());
+        to_get_descendants.extend(children);
+    }
+
+    ids
+}
+
+/// Trees of server files that can tell whether a file exists anywhere on the server, not just in
+/// the tree
+pub trait ServerTreeLike: TreeLike<F = ServerFile> {
+    fn maybe_find_global(&self, id: &Uuid) -> Option<&ServerFile>;
+}
+
+impl ServerTreeLike for ServerTree<'_> {
+    fn maybe_find_global(&self, id: &Uuid) -> Option<&ServerFile> {
+        self.files.maybe_find(id)
+    }
+}
+
+impl ServerTreeLike for ServerTreeView<'_> {
+    fn maybe_find_global(&self, id: &Uuid) -> Option<&ServerFile> {
+        self.files.maybe_find(id)
+    }
+}
+
+impl TreeLike for ServerTreeView<'_> {
+    type F = ServerFile;
+
+    fn ids(&self) -> Vec<Uuid> {
+        self.ids.iter().copied().collect()
+    }
+
+    fn maybe_find(&self, id: &Uuid) -> Option<&Self::F> {
+        if self.ids.contains(id) {
+            self.files.maybe_find(id)
+        } else {
+            None
+        }
+    }
+}
+
 impl TreeLike for ServerTree<'_> {
     type F = ServerFile;
 
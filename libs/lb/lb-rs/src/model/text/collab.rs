@@ -0,0 +1,430 @@
+use super::buffer::adjust_subsequent_range;
+use super::offset_types::{DocCharOffset, RangeExt as _};
+use super::operation_types::Replace;
+use super::unicode_segs::{self, UnicodeSegs};
+use crate::model::errors::{LbErrKind, LbResult};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use unicode_segmentation::UnicodeSegmentation as _;
+use uuid::Uuid;
+
+/// Identifies one participant's session with a document. A user with the document open on two
+/// devices has two sessions.
+pub type SessionId = Uuid;
+
+/// A message relayed between participants of a collaborative editing session. Messages are
+/// encrypted with the document's key; the server only orders and relays them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CollabMessage {
+    /// The document's text at the start of the session, or as of the previous message when the
+    /// session is compacted. Always the first message of a session.
+    Snapshot { text: String },
+
+    /// Replacements made by `author` while looking at the document as of message `parent` plus
+    /// their own edits since. All replacements are based on the same version of the document,
+    /// like the operations queued into a [super::buffer::Buffer] in a single frame.
+    Edit { author: SessionId, parent: u64, ops: Vec<Replace> },
+
+    /// Where `author`'s cursor is, in the same coordinates as an edit with the same `parent`
+    Presence {
+        author: SessionId,
+        username: String,
+        parent: u64,
+        selection: (DocCharOffset, DocCharOffset),
+    },
+
+    /// `author` closed the document
+    Leave { author: SessionId },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Presence {
+    pub username: String,
+    pub selection: (DocCharOffset, DocCharOffset),
+}
+
+/// The text of a document as seen by one participant, including their edits the server hasn't
+/// relayed back yet
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct View {
+    pub text: String,
+    pub presence: HashMap<SessionId, Presence>,
+}
+
+/// One participant's copy of a collaborative editing session.
+///
+/// # Convergence
+/// The server assigns every message a sequence number but can't read, and therefore can't
+/// transform, the edits it relays. Instead each participant replays the sequenced messages with
+/// the same rules: an edit is transformed by every edit sequenced after its `parent` that its
+/// author hadn't seen (edits by other authors), then applied. Because the rules are deterministic
+/// and the order is shared, every participant arrives at the same text.
+///
+/// Edits a participant has sent but not yet seen sequenced are kept pending and applied on top of
+/// the sequenced text using the same rules, so that what the participant sees is what the
+/// document will be once their edits are sequenced (barring further concurrent edits).
+pub struct CollabDoc {
+    author: SessionId,
+
+    /// text as of `seq`, made only of sequenced messages
+    text: String,
+    segs: UnicodeSegs,
+    seq: u64,
+    joined: bool,
+
+    /// applied replacements in the form they were applied, used to transform late arrivals
+    log: Vec<Applied>,
+    pending: VecDeque<Pending>,
+    presence: HashMap<SessionId, Presence>,
+}
+
+struct Applied {
+    seq: u64,
+    author: SessionId,
+    replace: Replace,
+}
+
+struct Pending {
+    parent: u64,
+    ops: Vec<Replace>,
+}
+
+impl CollabDoc {
+    pub fn new(author: SessionId) -> Self {
+        Self {
+            author,
+            text: Default::default(),
+            segs: unicode_segs::calc(""),
+            seq: 0,
+            joined: false,
+            log: Default::default(),
+            pending: Default::default(),
+            presence: Default::default(),
+        }
+    }
+
+    pub fn author(&self) -> SessionId {
+        self.author
+    }
+
+    /// Sequence number of the last message applied
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// Whether the session's snapshot has been applied. Edits can't be made before then.
+    pub fn is_joined(&self) -> bool {
+        self.joined
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// The oldest version of the document a pending edit is based on
+    pub fn pending_parent(&self) -> Option<u64> {
+        self.pending.front().map(|pending| pending.parent)
+    }
+
+    /// The session's text without this participant's pending edits
+    pub fn sequenced_text(&self) -> &str {
+        &self.text
+    }
+
+    /// Applies the message the server sequenced as `seq`. Messages must be applied in order.
+    pub fn apply(&mut self, seq: u64, msg: CollabMessage) -> LbResult<()> {
+        if self.joined && seq != self.seq + 1 {
+            return Err(LbErrKind::Unexpected(format!(
+                "collab message {seq} out of order, expected {}",
+                self.seq + 1
+            ))
+            .into());
+        }
+
+        match msg {
+            CollabMessage::Snapshot { text } => {
+                if !self.joined {
+                    self.segs = unicode_segs::calc(&text);
+                    self.text = text;
+                    self.joined = true;
+                } else {
+                    // the session was compacted once everyone had caught up, so nothing sequenced
+                    // from here on is based on an earlier message; participants who join now
+                    // start with an empty log too
+                    self.log.clear();
+                }
+            }
+            CollabMessage::Edit { author, parent, ops } => {
+                if !self.joined {
+                    return Err(LbErrKind::Unexpected("collab edit before snapshot".into()).into());
+                }
+                if author == self.author {
+                    self.pending.pop_front();
+                }
+                for mut op in ops {
+                    for applied in &self.log {
+                        // ops in one message are based on the same version, so they transform
+                        // each other like concurrent edits
+                        if applied.seq > parent && (applied.author != author || applied.seq == seq)
+                        {
+                            transform(&mut op, &applied.replace);
+                        }
+                    }
+                    clamp(&mut op, self.segs.last_cursor_position());
+                    apply_replace(&mut self.text, &mut self.segs, &op);
+                    for presence in self.presence.values_mut() {
+                        adjust(&mut presence.selection, &op);
+                    }
+                    self.log.push(Applied { seq, author, replace: op });
+                }
+            }
+            CollabMessage::Presence { author, username, parent, mut selection } => {
+                if self.joined {
+                    for applied in &self.log {
+                        if applied.seq > parent && applied.author != author {
+                            adjust(&mut selection, &applied.replace);
+                        }
+                    }
+                    let end = self.segs.last_cursor_position();
+                    selection = (selection.0.min(end), selection.1.min(end));
+                }
+                if author != self.author {
+                    self.presence
+                        .insert(author, Presence { username, selection });
+                }
+            }
+            CollabMessage::Leave { author } => {
+                self.presence.remove(&author);
+            }
+        }
+
+        self.seq = seq;
+        Ok(())
+    }
+
+    /// Records replacements this participant made while looking at the [View] as of `parent` and
+    /// returns the message to send
+    pub fn local_edit(&mut self, parent: u64, ops: Vec<Replace>) -> CollabMessage {
+        self.pending.push_back(Pending { parent, ops: ops.clone() });
+        CollabMessage::Edit { author: self.author, parent, ops }
+    }
+
+    /// The document as this participant should see it: sequenced messages plus pending edits
+    pub fn view(&self) -> View {
+        let mut text = self.text.clone();
+        let mut segs = self.segs.clone();
+        let mut presence = self.presence.clone();
+
+        let mut applied_pending: Vec<Replace> = vec![];
+        for pending in &self.pending {
+            let first_applied = applied_pending.len();
+            for mut op in pending.ops.iter().cloned() {
+                for applied in &self.log {
+                    if applied.seq > pending.parent && applied.author != self.author {
+                        transform(&mut op, &applied.replace);
+                    }
+                }
+                // pending edits are sequenced after everything in the log, and each sees its
+                // own earlier ops as concurrent
+                for earlier in &applied_pending[first_applied..] {
+                    transform(&mut op, earlier);
+                }
+                clamp(&mut op, segs.last_cursor_position());
+                apply_replace(&mut text, &mut segs, &op);
+                for presence in presence.values_mut() {
+                    adjust(&mut presence.selection, &op);
+                }
+                applied_pending.push(op);
+            }
+        }
+
+        View { text, presence }
+    }
+}
+
+/// Adjusts `op`, made without knowledge of `preceding`, to apply after it. Concurrent
+/// replacements to intersecting ranges keep the one that was sequenced first.
+fn transform(op: &mut Replace, preceding: &Replace) {
+    if preceding.range.intersects(&op.range, true)
+        && !(preceding.range.is_empty() && op.range.is_empty())
+    {
+        op.text = "".into();
+        op.range.1 = op.range.0;
+    }
+    adjust(&mut op.range, preceding);
+}
+
+fn adjust(range: &mut (DocCharOffset, DocCharOffset), replace: &Replace) {
+    adjust_subsequent_range(
+        replace.range,
+        replace.text.graphemes(true).count().into(),
+        true,
+        range,
+    );
+}
+
+/// Collaborators' edits come over the network; don't trust them to be in bounds
+fn clamp(op: &mut Replace, end: DocCharOffset) {
+    op.range.1 = op.range.1.min(end);
+    op.range.0 = op.range.0.min(op.range.1);
+}
+
+fn apply_replace(text: &mut String, segs: &mut UnicodeSegs, replace: &Replace) {
+    let byte_range = segs.range_to_byte(replace.range);
+    text.replace_range(byte_range.start().0..byte_range.end().0, &replace.text);
+    *segs = unicode_segs::calc(text);
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CollabDoc, CollabMessage};
+    use crate::model::text::diff;
+    use uuid::Uuid;
+
+    fn session(text: &str) -> (CollabDoc, CollabDoc) {
+        let mut a = CollabDoc::new(Uuid::new_v4());
+        let mut b = CollabDoc::new(Uuid::new_v4());
+        let snapshot = CollabMessage::Snapshot { text: text.into() };
+        a.apply(1, snapshot.clone()).unwrap();
+        b.apply(1, snapshot).unwrap();
+        (a, b)
+    }
+
+    fn edit(doc: &mut CollabDoc, to: &str) -> CollabMessage {
+        let view = doc.view();
+        doc.local_edit(doc.seq(), diff(&view.text, to))
+    }
+
+    fn relay<const N: usize>(docs: [&mut CollabDoc; N], first_seq: u64, msgs: Vec<CollabMessage>) {
+        for doc in docs {
+            for (i, msg) in msgs.iter().enumerate() {
+                doc.apply(first_seq + i as u64, msg.clone()).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn collab_sequential_edits() {
+        let (mut a, mut b) = session("hello");
+
+        let msg = edit(&mut a, "hello world");
+        assert_eq!(a.view().text, "hello world");
+        relay([&mut a, &mut b], 2, vec![msg]);
+
+        let msg = edit(&mut b, "hello, world");
+        relay([&mut a, &mut b], 3, vec![msg]);
+
+        assert_eq!(a.view().text, "hello, world");
+        assert_eq!(b.view().text, "hello, world");
+        assert!(!a.has_pending() && !b.has_pending());
+    }
+
+    #[test]
+    fn collab_concurrent_edits_converge() {
+        let (mut a, mut b) = session("one two three");
+
+        let msg_a = edit(&mut a, "zero one two three");
+        let msg_b = edit(&mut b, "one two three four");
+
+        // each sees their own edit right away
+        assert_eq!(a.view().text, "zero one two three");
+        assert_eq!(b.view().text, "one two three four");
+
+        relay([&mut a, &mut b], 2, vec![msg_a, msg_b]);
+
+        assert_eq!(a.view().text, "zero one two three four");
+        assert_eq!(b.view().text, a.view().text);
+    }
+
+    #[test]
+    fn collab_pending_edits_rebase() {
+        let (mut a, mut b) = session("abc");
+
+        let msg_b = edit(&mut b, "abcd");
+        relay([&mut a], 2, vec![msg_b.clone()]);
+
+        // a edits before hearing about its first edit
+        let msg_a1 = edit(&mut a, "Xabcd");
+        let msg_a2 = edit(&mut a, "XabcdY");
+        assert_eq!(a.view().text, "XabcdY");
+
+        relay([&mut b], 2, vec![msg_b]);
+        relay([&mut a, &mut b], 3, vec![msg_a1, msg_a2]);
+
+        assert_eq!(a.view().text, "XabcdY");
+        assert_eq!(b.view().text, "XabcdY");
+    }
+
+    #[test]
+    fn collab_conflicting_replacements_keep_first() {
+        let (mut a, mut b) = session("the cat sat");
+
+        let msg_a = edit(&mut a, "the dog sat");
+        let msg_b = edit(&mut b, "the bird sat");
+        relay([&mut a, &mut b], 2, vec![msg_a, msg_b]);
+
+        assert_eq!(a.view().text, "the dog sat");
+        assert_eq!(b.view().text, "the dog sat");
+    }
+
+    #[test]
+    fn collab_presence_follows_edits() {
+        let (mut a, mut b) = session("hello world");
+
+        let presence = CollabMessage::Presence {
+            author: b.author(),
+            username: "b".into(),
+            parent: b.seq(),
+            selection: (6.into(), 11.into()),
+        };
+        let msg_a = edit(&mut a, "oh hello world");
+        relay([&mut a, &mut b], 2, vec![msg_a, presence]);
+
+        let view = a.view();
+        let presence = &view.presence[&b.author()];
+        assert_eq!(presence.selection, (9.into(), 14.into()));
+        assert!(b.view().presence.is_empty());
+
+        let leave = CollabMessage::Leave { author: b.author() };
+        relay([&mut a, &mut b], 4, vec![leave]);
+        assert!(a.view().presence.is_empty());
+    }
+
+    #[test]
+    fn collab_compaction_trims_log() {
+        let (mut a, mut b) = session("one");
+        let msg = edit(&mut a, "one two");
+        relay([&mut a, &mut b], 2, vec![msg]);
+        assert!(!a.log.is_empty());
+
+        let snapshot = CollabMessage::Snapshot { text: a.sequenced_text().into() };
+        relay([&mut a, &mut b], 3, vec![snapshot]);
+        assert!(a.log.is_empty() && b.log.is_empty());
+
+        let msg_a = edit(&mut a, "zero one two");
+        let msg_b = edit(&mut b, "one two three");
+        relay([&mut a, &mut b], 4, vec![msg_a, msg_b]);
+        assert_eq!(a.view().text, "zero one two three");
+        assert_eq!(b.view().text, a.view().text);
+    }
+
+    #[test]
+    fn collab_out_of_bounds_edit() {
+        let (mut a, mut b) = session("short");
+        let msg = CollabMessage::Edit {
+            author: b.author(),
+            parent: 1,
+            ops: vec![super::Replace { range: (3.into(), 100.into()), text: "!".into() }],
+        };
+        relay([&mut a, &mut b], 2, vec![msg]);
+        assert_eq!(a.view().text, "sho!");
+    }
+
+    #[test]
+    fn collab_out_of_order() {
+        let (mut a, _) = session("");
+        assert!(a
+            .apply(3, CollabMessage::Leave { author: Uuid::new_v4() })
+            .is_err());
+    }
+}
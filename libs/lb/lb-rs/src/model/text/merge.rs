@@ -0,0 +1,238 @@
+use super::diff;
+use super::offset_types::{DocCharOffset, RangeExt as _};
+use super::operation_types::Replace;
+use unicode_segmentation::UnicodeSegmentation as _;
+
+/// Ranges of `base` that `a` and `b` both changed, and changed differently. Changes conflict when
+/// they overlap or touch, including two insertions at the same place, because then the merged
+/// result depends on an arbitrary choice of which change goes first.
+pub fn conflicts(base: &str, a: &str, b: &str) -> Vec<(DocCharOffset, DocCharOffset)> {
+    hunks(&diff(base, a), &diff(base, b))
+        .into_iter()
+        .filter(|hunk| hunk.conflicting)
+        .map(|hunk| hunk.range)
+        .collect()
+}
+
+/// 3-way merges `a` and `b`, writing both versions of each conflicting region between conflict
+/// markers. Conflicting regions are widened to whole lines of `base`. Changes outside of them are
+/// merged normally.
+pub fn merge_with_markers(base: &str, a: &str, b: &str, a_label: &str, b_label: &str) -> String {
+    let ops_a = diff(base, a);
+    let ops_b = diff(base, b);
+    let graphemes: Vec<&str> = base.graphemes(true).collect();
+
+    // widen conflicts to whole lines, then absorb any change that now straddles a region boundary
+    let mut regions: Vec<(DocCharOffset, DocCharOffset)> = vec![];
+    for hunk in hunks(&ops_a, &ops_b) {
+        if !hunk.conflicting {
+            continue;
+        }
+        let mut region = to_lines(&graphemes, hunk.range);
+        loop {
+            let widened = ops_a
+                .iter()
+                .chain(&ops_b)
+                .filter(|op| op.range.intersects(&region, true))
+                .fold(region, |acc, op| (acc.0.min(op.range.0), acc.1.max(op.range.1)));
+            let widened = to_lines(&graphemes, widened);
+            if widened == region {
+                break;
+            }
+            region = widened;
+        }
+        match regions.last_mut() {
+            Some(last) if last.intersects(&region, true) => {
+                *last = (last.0.min(region.0), last.1.max(region.1))
+            }
+            _ => regions.push(region),
+        }
+    }
+
+    let slice = |range: (DocCharOffset, DocCharOffset)| graphemes[range.0 .0..range.1 .0].concat();
+    let apply = |range: (DocCharOffset, DocCharOffset), ops: &[&Replace]| {
+        let mut result = String::new();
+        let mut cursor = range.0;
+        for op in ops {
+            result.push_str(&slice((cursor, op.range.0)));
+            result.push_str(&op.text);
+            cursor = op.range.1;
+        }
+        result.push_str(&slice((cursor, range.1)));
+        result
+    };
+    // changes outside of conflicting regions don't overlap, except identical ones made on both
+    // sides which are applied once
+    let mut outside: Vec<&Replace> = ops_a
+        .iter()
+        .chain(ops_b.iter().filter(|op| !ops_a.contains(op)))
+        .filter(|op| {
+            !regions
+                .iter()
+                .any(|r| r.contains_range(&op.range, true, true))
+        })
+        .collect();
+    outside.sort_by_key(|op| op.range);
+
+    let mut result = String::new();
+    let mut cursor = DocCharOffset(0);
+    let mut outside = outside.into_iter().peekable();
+    for &region in &regions {
+        let before: Vec<&Replace> =
+            std::iter::from_fn(|| outside.next_if(|op| op.range.1 <= region.0)).collect();
+        result.push_str(&apply((cursor, region.0), &before));
+
+        let side = |ops: &[Replace]| {
+            let ops: Vec<&Replace> = ops
+                .iter()
+                .filter(|op| region.contains_range(&op.range, true, true))
+                .collect();
+            let mut text = apply(region, &ops);
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+            text
+        };
+        result.push_str(&format!("<<<<<<< {a_label}\n"));
+        result.push_str(&side(&ops_a));
+        result.push_str("=======\n");
+        result.push_str(&side(&ops_b));
+        result.push_str(&format!(">>>>>>> {b_label}\n"));
+        cursor = region.1;
+    }
+    let rest: Vec<&Replace> = outside.collect();
+    result.push_str(&apply((cursor, DocCharOffset(graphemes.len())), &rest));
+    result
+}
+
+struct Hunk {
+    range: (DocCharOffset, DocCharOffset),
+    conflicting: bool,
+}
+
+/// Groups overlapping or touching changes from both sides. `ops_a` and `ops_b` are each sorted and
+/// don't touch one another, as produced by [diff].
+fn hunks(ops_a: &[Replace], ops_b: &[Replace]) -> Vec<Hunk> {
+    let mut ops: Vec<(bool, &Replace)> = ops_a
+        .iter()
+        .map(|op| (true, op))
+        .chain(ops_b.iter().map(|op| (false, op)))
+        .collect();
+    ops.sort_by_key(|(_, op)| op.range);
+
+    let mut hunks: Vec<Hunk> = vec![];
+    let (mut hunk_a, mut hunk_b): (Vec<&Replace>, Vec<&Replace>) = (vec![], vec![]);
+    for (is_a, op) in ops {
+        match hunks.last_mut() {
+            Some(hunk) if hunk.range.intersects(&op.range, true) => {
+                hunk.range.1 = hunk.range.1.max(op.range.1);
+            }
+            _ => {
+                if let Some(hunk) = hunks.last_mut() {
+                    hunk.conflicting = is_conflict(&hunk_a, &hunk_b);
+                }
+                hunk_a.clear();
+                hunk_b.clear();
+                hunks.push(Hunk { range: op.range, conflicting: false });
+            }
+        }
+        if is_a {
+            hunk_a.push(op);
+        } else {
+            hunk_b.push(op);
+        }
+    }
+    if let Some(hunk) = hunks.last_mut() {
+        hunk.conflicting = is_conflict(&hunk_a, &hunk_b);
+    }
+    hunks
+}
+
+/// Both sides changed the region, and not in the same way
+fn is_conflict(ops_a: &[&Replace], ops_b: &[&Replace]) -> bool {
+    !ops_a.is_empty() && !ops_b.is_empty() && ops_a != ops_b
+}
+
+/// Widens `range` to start at the beginning of a line and end after a line break (or at the end of
+/// the text)
+fn to_lines(
+    graphemes: &[&str], range: (DocCharOffset, DocCharOffset),
+) -> (DocCharOffset, DocCharOffset) {
+    let is_break = |i: usize| graphemes[i].contains('\n');
+    let start = (0..range.0 .0)
+        .rev()
+        .find(|&i| is_break(i))
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let end = if range.1 > range.0 && is_break(range.1 .0 - 1) {
+        range.1 .0
+    } else {
+        (range.1 .0..graphemes.len())
+            .find(|&i| is_break(i))
+            .map(|i| i + 1)
+            .unwrap_or(graphemes.len())
+    };
+    (DocCharOffset(start), DocCharOffset(end))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{conflicts, merge_with_markers};
+
+    #[test]
+    fn merge_no_conflicts() {
+        let base = "one\ntwo\nthree\n";
+        assert!(conflicts(base, "one!\ntwo\nthree\n", "one\ntwo\nthree!\n").is_empty());
+        assert_eq!(
+            merge_with_markers(base, "one!\ntwo\nthree\n", "one\ntwo\nthree!\n", "a", "b"),
+            "one!\ntwo\nthree!\n"
+        );
+    }
+
+    #[test]
+    fn merge_identical_changes() {
+        let base = "one\ntwo\n";
+        assert!(conflicts(base, "one\n2\n", "one\n2\n").is_empty());
+        assert_eq!(merge_with_markers(base, "one\n2\n", "one\n2\n", "a", "b"), "one\n2\n");
+    }
+
+    #[test]
+    fn merge_overlapping_replacements() {
+        let base = "one\ntwo\nthree\n";
+        let a = "one\n2\nthree\n";
+        let b = "one\nII\nthree!\n";
+        assert_eq!(conflicts(base, a, b).len(), 1);
+        assert_eq!(
+            merge_with_markers(base, a, b, "local", "remote"),
+            "one\n<<<<<<< local\n2\n=======\nII\n>>>>>>> remote\nthree!\n"
+        );
+    }
+
+    #[test]
+    fn merge_inserts_at_same_place() {
+        let base = "one\n";
+        let a = "one\nfrom a\n";
+        let b = "one\nfrom b\n";
+        assert_eq!(conflicts(base, a, b).len(), 1);
+        assert_eq!(
+            merge_with_markers(base, a, b, "a", "b"),
+            "one\n<<<<<<< a\nfrom a\n=======\nfrom b\n>>>>>>> b\n"
+        );
+    }
+
+    #[test]
+    fn merge_conflict_without_trailing_newline() {
+        let base = "hello world";
+        let a = "hello there";
+        let b = "goodbye world";
+        assert_eq!(conflicts(base, a, b).len(), 0);
+
+        let a = "hello there";
+        let b = "hello friend";
+        assert_eq!(conflicts(base, a, b).len(), 1);
+        assert_eq!(
+            merge_with_markers(base, a, b, "a", "b"),
+            "<<<<<<< a\nhello there\n=======\nhello friend\n>>>>>>> b\n"
+        );
+    }
+}
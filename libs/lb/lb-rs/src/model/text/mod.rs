@@ -1,4 +1,6 @@
 pub mod buffer;
+pub mod collab;
+pub mod merge;
 pub mod offset_types;
 pub mod operation_types;
 pub mod unicode_segs;
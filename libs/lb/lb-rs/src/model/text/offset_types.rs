@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use std::cmp::{max, min, Ordering};
 use std::fmt::{Debug, Formatter};
 use std::ops::{Add, AddAssign, Sub, SubAssign};
@@ -14,7 +15,7 @@ pub struct RelByteOffset(pub usize);
 
 /// A character position in a buffer
 #[repr(transparent)]
-#[derive(Default, Copy, Clone, PartialEq, PartialOrd, Eq, Ord, Hash)]
+#[derive(Default, Copy, Clone, PartialEq, PartialOrd, Eq, Ord, Hash, Serialize, Deserialize)]
 pub struct DocCharOffset(pub usize);
 
 /// A character offset from a position in a buffer or a distance between two positions
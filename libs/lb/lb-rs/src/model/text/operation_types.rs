@@ -1,4 +1,5 @@
 use super::offset_types::DocCharOffset;
+use serde::{Deserialize, Serialize};
 
 /// Buffer operation optimized for simplicity. Used in buffer's interface and internals to represent a building block
 /// of text manipulation with support for undo/redo and collaborative editing.
@@ -16,7 +17,7 @@ pub struct InverseOperation {
     pub replace: Option<Replace>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Replace {
     pub range: (DocCharOffset, DocCharOffset),
     pub text: String,
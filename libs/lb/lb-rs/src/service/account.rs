@@ -78,25 +78,13 @@ impl Lb {
             return Err(LbErrKind::AccountExists.into());
         }
 
-        if let Ok(key) = base64::decode(key) {
-            if let Ok(account) = bincode::deserialize(&key[..]) {
-                return self.import_account_private_key_v1(account).await;
-            } else if let Ok(key) = SecretKey::parse_slice(&key) {
-                return self
-                    .import_account_private_key_v2(key, api_url.unwrap_or(DEFAULT_API_LOCATION))
-                    .await;
+        match AccountKey::parse(key)? {
+            AccountKey::V1(account) => self.import_account_private_key_v1(account).await,
+            AccountKey::V2(key) => {
+                self.import_account_private_key_v2(key, api_url.unwrap_or(DEFAULT_API_LOCATION))
+                    .await
             }
         }
-
-        let phrase: [&str; 24] = key
-            .split([' ', ','])
-            .filter(|maybe_word| !maybe_word.is_empty())
-            .collect::<Vec<_>>()
-            .try_into()
-            .map_err(|_| LbErrKind::AccountStringCorrupted)?;
-
-        self.import_account_phrase(phrase, api_url.unwrap_or(DEFAULT_API_LOCATION))
-            .await
     }
 
     pub async fn import_account_private_key_v1(&self, account: Account) -> LbResult<Account> {
@@ -186,6 +174,9 @@ impl Lb {
             .await
             .map_err(|err| match err {
                 ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+                ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+                ApiError::AccountSuspended => LbErrKind::AccountSuspended,
                 ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
                 _ => core_err_unexpected(err),
             })?;
@@ -255,3 +246,31 @@ Numbered list items
 Happy note taking! You can report any issues to our [Github project](https://github.com/lockbook/lockbook/issues/new) or join our [Discord server](https://discord.gg/qv9fmAZCm6)."#).into()
     }
 }
+
+/// The forms of account string [Lb::import_account] accepts: an exported [Account], or just its
+/// private key, as bytes or a phrase
+pub(crate) enum AccountKey {
+    V1(Account),
+    V2(SecretKey),
+}
+
+impl AccountKey {
+    pub(crate) fn parse(key: &str) -> LbResult<Self> {
+        if let Ok(key) = base64::decode(key) {
+            if let Ok(account) = bincode::deserialize(&key[..]) {
+                return Ok(Self::V1(account));
+            } else if let Ok(key) = SecretKey::parse_slice(&key) {
+                return Ok(Self::V2(key));
+            }
+        }
+
+        let phrase: [&str; 24] = key
+            .split([' ', ','])
+            .filter(|maybe_word| !maybe_word.is_empty())
+            .collect::<Vec<_>>()
+            .try_into()
+            .map_err(|_| LbErrKind::AccountStringCorrupted)?;
+
+        Ok(Self::V2(Account::phrase_to_private_key(phrase)?))
+    }
+}
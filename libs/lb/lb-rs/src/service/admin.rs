@@ -22,6 +22,9 @@ impl Lb {
                         LbErrKind::InsufficientPermission
                     }
                     ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                    ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+                    ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+                    ApiError::AccountSuspended => LbErrKind::AccountSuspended,
                     ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
                     _ => core_err_unexpected(err),
                 }
@@ -44,6 +47,9 @@ impl Lb {
                         LbErrKind::InsufficientPermission
                     }
                     ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                    ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+                    ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+                    ApiError::AccountSuspended => LbErrKind::AccountSuspended,
                     ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
                     _ => core_err_unexpected(err),
                 }
@@ -64,6 +70,9 @@ impl Lb {
                     LbErrKind::InsufficientPermission
                 }
                 ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+                ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+                ApiError::AccountSuspended => LbErrKind::AccountSuspended,
                 ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
                 _ => core_err_unexpected(err),
             })?
@@ -86,6 +95,9 @@ impl Lb {
                     LbErrKind::UsernameNotFound
                 }
                 ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+                ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+                ApiError::AccountSuspended => LbErrKind::AccountSuspended,
                 ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
                 _ => core_err_unexpected(err),
             })?
@@ -107,6 +119,9 @@ impl Lb {
                         LbErrKind::UsernameNotFound
                     }
                     ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                    ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+                    ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+                    ApiError::AccountSuspended => LbErrKind::AccountSuspended,
                     ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
                     _ => core_err_unexpected(err),
                 }
@@ -126,6 +141,9 @@ impl Lb {
                         LbErrKind::InsufficientPermission
                     }
                     ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                    ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+                    ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+                    ApiError::AccountSuspended => LbErrKind::AccountSuspended,
                     ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
                     _ => core_err_unexpected(err),
                 }
@@ -148,6 +166,9 @@ impl Lb {
                         LbErrKind::InsufficientPermission
                     }
                     ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                    ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+                    ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+                    ApiError::AccountSuspended => LbErrKind::AccountSuspended,
                     ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
                     _ => core_err_unexpected(err),
                 }
@@ -167,6 +188,33 @@ impl Lb {
                         LbErrKind::InsufficientPermission
                     }
                     ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                    ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+                    ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+                    ApiError::AccountSuspended => LbErrKind::AccountSuspended,
+                    ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+                    _ => core_err_unexpected(err),
+                }
+                .into()
+            })
+    }
+
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn repair_server(
+        &self, repair: ServerRepair, dry_run: bool,
+    ) -> LbResult<AdminRepairServer> {
+        let account = self.get_account()?;
+        self.client
+            .request(account, AdminRepairServerRequest { repair, dry_run })
+            .await
+            .map_err(|err| {
+                match err {
+                    ApiError::Endpoint(AdminRepairServerError::NotPermissioned) => {
+                        LbErrKind::InsufficientPermission
+                    }
+                    ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                    ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+                    ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+                    ApiError::AccountSuspended => LbErrKind::AccountSuspended,
                     ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
                     _ => core_err_unexpected(err),
                 }
@@ -191,10 +239,90 @@ impl Lb {
                     LbErrKind::ExistingRequestPending
                 }
                 ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+                ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+                ApiError::AccountSuspended => LbErrKind::AccountSuspended,
+                ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+                _ => core_err_unexpected(err),
+            })?;
+
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn set_data_cap(&self, username: Option<&str>, cap: Option<u64>) -> LbResult<()> {
+        let account = self.get_account()?;
+        self.client
+            .request(
+                account,
+                AdminSetDataCapRequest { username: username.map(|u| u.to_string()), cap },
+            )
+            .await
+            .map_err(|err| match err {
+                ApiError::Endpoint(AdminSetDataCapError::NotPermissioned) => {
+                    LbErrKind::InsufficientPermission
+                }
+                ApiError::Endpoint(AdminSetDataCapError::UserNotFound) => {
+                    LbErrKind::UsernameNotFound
+                }
+                ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+                ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+                ApiError::AccountSuspended => LbErrKind::AccountSuspended,
                 ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
                 _ => core_err_unexpected(err),
             })?;
 
         Ok(())
     }
+
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn set_account_state(&self, username: &str, state: AccountState) -> LbResult<()> {
+        let account = self.get_account()?;
+        self.client
+            .request(account, AdminSetAccountStateRequest { username: username.to_string(), state })
+            .await
+            .map_err(|err| match err {
+                ApiError::Endpoint(AdminSetAccountStateError::NotPermissioned) => {
+                    LbErrKind::InsufficientPermission
+                }
+                ApiError::Endpoint(AdminSetAccountStateError::UserNotFound) => {
+                    LbErrKind::UsernameNotFound
+                }
+                ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+                ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+                ApiError::AccountSuspended => LbErrKind::AccountSuspended,
+                ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+                _ => core_err_unexpected(err),
+            })?;
+
+        Ok(())
+    }
+
+    /// Entries of the server's audit log matching `filter`, oldest first
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn get_audit_log(
+        &self, filter: AdminGetAuditLogRequest,
+    ) -> LbResult<Vec<AuditEntry>> {
+        let account = self.get_account()?;
+        self.client
+            .request(account, filter)
+            .await
+            .map(|response| response.entries)
+            .map_err(|err| {
+                match err {
+                    ApiError::Endpoint(AdminGetAuditLogError::NotPermissioned) => {
+                        LbErrKind::InsufficientPermission
+                    }
+                    ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                    ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+                    ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+                    ApiError::AccountSuspended => LbErrKind::AccountSuspended,
+                    ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+                    _ => core_err_unexpected(err),
+                }
+                .into()
+            })
+    }
 }
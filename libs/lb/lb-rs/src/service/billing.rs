@@ -39,8 +39,12 @@ impl Lb {
                         LbErrKind::ExistingRequestPending
                     }
                     UpgradeAccountStripeError::UserNotFound => LbErrKind::AccountNonexistent,
+                    UpgradeAccountStripeError::Disabled => LbErrKind::ServerDisabled,
                 },
                 ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+                ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+                ApiError::AccountSuspended => LbErrKind::AccountSuspended,
                 ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
                 _ => core_err_unexpected(err),
             })?;
@@ -73,8 +77,12 @@ impl Lb {
                         LbErrKind::ExistingRequestPending
                     }
                     UpgradeAccountGooglePlayError::UserNotFound => core_err_unexpected(err),
+                    UpgradeAccountGooglePlayError::Disabled => LbErrKind::ServerDisabled,
                 },
                 ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+                ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+                ApiError::AccountSuspended => LbErrKind::AccountSuspended,
                 ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
                 _ => core_err_unexpected(err),
             })?;
@@ -107,8 +115,12 @@ impl Lb {
                         LbErrKind::AppStoreAccountAlreadyLinked
                     }
                     UpgradeAccountAppStoreError::UserNotFound => core_err_unexpected(err),
+                    UpgradeAccountAppStoreError::Disabled => LbErrKind::ServerDisabled,
                 },
                 ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+                ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+                ApiError::AccountSuspended => LbErrKind::AccountSuspended,
                 ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
                 _ => core_err_unexpected(err),
             })?;
@@ -137,7 +149,11 @@ impl Lb {
                 ApiError::Endpoint(CancelSubscriptionError::CannotCancelForAppStore) => {
                     LbErrKind::CannotCancelSubscriptionForAppStore
                 }
+                ApiError::Endpoint(CancelSubscriptionError::Disabled) => LbErrKind::ServerDisabled,
                 ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+                ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+                ApiError::AccountSuspended => LbErrKind::AccountSuspended,
                 ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
                 _ => core_err_unexpected(err),
             })?;
@@ -155,6 +171,9 @@ impl Lb {
             .await
             .map_err(|err| match err {
                 ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+                ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+                ApiError::AccountSuspended => LbErrKind::AccountSuspended,
                 ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
                 _ => core_err_unexpected(err),
             })?
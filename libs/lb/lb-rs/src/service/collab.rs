@@ -0,0 +1,523 @@
+use crate::io::network::ApiError;
+use crate::model::account::Account;
+use crate::model::api::{
+    CollabAck, PullCollabError, PullCollabRequest, PushCollabError, PushCollabRequest,
+};
+use crate::model::crypto::AESKey;
+use crate::model::errors::{LbErr, LbErrKind, LbResult};
+use crate::model::file_like::FileLike;
+use crate::model::symkey;
+use crate::model::text::collab::{CollabDoc, CollabMessage, Presence, SessionId};
+use crate::model::text::diff;
+use crate::model::text::offset_types::DocCharOffset;
+use crate::model::tree_like::TreeLike;
+use crate::Lb;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// How often participants re-announce their cursor so others can tell they're still around
+const PRESENCE_HEARTBEAT: Duration = Duration::from_secs(30);
+
+/// Participants we haven't heard from in this long are assumed gone
+const PRESENCE_EXPIRY: Duration = Duration::from_secs(90);
+
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// How many messages pile up on the server before the participant that persists the document
+/// asks it to replace them with a snapshot
+const COMPACT_AFTER: usize = 1000;
+
+/// A collaborative editing session for a text document, see [Lb::join_collab].
+///
+/// Editors hand the session their text after every change with [CollabSession::edit] and pick up
+/// collaborators' changes with [CollabSession::take_view]. Only one participant, the one for which
+/// [CollabSession::persists] returns true, should save the document while the session is active;
+/// everyone's edits reach them through the session.
+///
+/// Dropping the last clone of a session leaves it.
+#[derive(Clone)]
+pub struct CollabSession {
+    inner: Arc<SessionInner>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CollabStatus {
+    /// Waiting for the session's history. Edits made now are picked up once joined.
+    Joining,
+    Joined,
+    /// The session is over, either because the server forgot it, it grew too large, or because
+    /// of the error. Editors should go back to saving the document themselves.
+    Ended(Option<LbErrKind>),
+}
+
+struct SessionInner {
+    id: Uuid,
+    username: String,
+    state: Mutex<SessionState>,
+    outbox: mpsc::UnboundedSender<CollabMessage>,
+    waker: Mutex<Option<Box<dyn Fn() + Send + Sync>>>,
+}
+
+struct SessionState {
+    doc: CollabDoc,
+    status: CollabStatus,
+
+    /// sequence number and text of the view last returned by [CollabSession::take_view]; local
+    /// edits are diffed against it
+    handed_out: Option<(u64, String)>,
+    selection: (DocCharOffset, DocCharOffset),
+    last_seen: HashMap<SessionId, Instant>,
+}
+
+impl Lb {
+    /// Joins (or starts) a collaborative editing session for a text document. Returns right away;
+    /// the session's history is fetched in the background.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn join_collab(&self, id: Uuid) -> LbResult<CollabSession> {
+        let account = self.get_account()?.clone();
+        let username = account.username.clone();
+        let key = {
+            let tx = self.ro_tx().await;
+            let db = tx.db();
+            let mut tree = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+            if !tree.find(&id)?.is_document() {
+                return Err(LbErrKind::FileNotDocument.into());
+            }
+            tree.decrypt_key(&id, &self.keychain)?
+        };
+
+        let author = Uuid::new_v4();
+        let (outbox, rx) = mpsc::unbounded_channel();
+        let session = CollabSession {
+            inner: Arc::new(SessionInner {
+                id,
+                username,
+                state: Mutex::new(SessionState {
+                    doc: CollabDoc::new(author),
+                    status: CollabStatus::Joining,
+                    handed_out: None,
+                    selection: Default::default(),
+                    last_seen: Default::default(),
+                }),
+                outbox,
+                waker: Default::default(),
+            }),
+        };
+
+        tokio::spawn(self.clone().collab_pusher(
+            id,
+            key,
+            account.clone(),
+            author,
+            rx,
+            session.weak(),
+        ));
+        tokio::spawn(self.clone().collab_puller(id, key, account, session.weak()));
+
+        Ok(session)
+    }
+
+    async fn collab_pusher(
+        self, id: Uuid, key: AESKey, account: Account, author: SessionId,
+        mut rx: mpsc::UnboundedReceiver<CollabMessage>, session: Weak<SessionInner>,
+    ) {
+        let mut heartbeat = tokio::time::interval(PRESENCE_HEARTBEAT);
+        loop {
+            let message = tokio::select! {
+                message = rx.recv() => message,
+                _ = heartbeat.tick() => {
+                    let Some(session) = session.upgrade() else { continue };
+                    match CollabSession::from(session).presence_message() {
+                        Some(presence) => Some(presence),
+                        None => continue,
+                    }
+                }
+            };
+            let Some(message) = message else {
+                // everyone's done with the session
+                let leave = CollabMessage::Leave { author };
+                if let Err(err) = self
+                    .push_collab(id, &key, &account, &leave, false, None)
+                    .await
+                {
+                    debug!(?err, "could not leave collab session");
+                }
+                return;
+            };
+
+            let is_edit = matches!(message, CollabMessage::Edit { .. });
+            let ended =
+                match self
+                    .push_collab(id, &key, &account, &message, false, None)
+                    .await
+                {
+                    Ok(()) => continue,
+                    // readers follow along without being seen
+                    Err(CollabPushError::Api(ApiError::Endpoint(
+                        PushCollabError::NotPermissioned,
+                    ))) if !is_edit => continue,
+                    Err(err) if err.is_capacity() => None,
+                    Err(CollabPushError::Api(err)) => Some(LbErr::from(err).kind),
+                    Err(CollabPushError::Lb(err)) => Some(err.kind),
+                };
+            if let Some(session) = session.upgrade() {
+                CollabSession::from(session).end(ended);
+            }
+            return;
+        }
+    }
+
+    async fn collab_puller(
+        self, id: Uuid, key: AESKey, account: Account, session: Weak<SessionInner>,
+    ) {
+        let started = self.start_collab(id, &key, &account).await;
+        if let Err(err) = started {
+            if let Some(session) = session.upgrade() {
+                CollabSession::from(session).end(match err {
+                    err if err.is_capacity() => None,
+                    CollabPushError::Api(err) => Some(LbErr::from(err).kind),
+                    CollabPushError::Lb(err) => Some(err.kind),
+                });
+            }
+            return;
+        }
+
+        let mut since_snapshot = 0;
+        loop {
+            let Some((since, ack)) = session.upgrade().map(|s| {
+                let session = CollabSession::from(s);
+                (session.seq(), session.ack())
+            }) else {
+                return;
+            };
+
+            let result = self
+                .client
+                .request(&account, PullCollabRequest { id, since, ack })
+                .await;
+
+            if matches!(&result, Err(ApiError::SendFailed(_))) {
+                tokio::time::sleep(RETRY_DELAY).await;
+                continue;
+            }
+
+            let Some(session) = session.upgrade().map(CollabSession::from) else { return };
+            if matches!(&result, Err(ApiError::Endpoint(PullCollabError::TooManySessions))) {
+                session.end(None);
+                return;
+            }
+            let applied = result.map_err(LbErr::from).and_then(|response| {
+                if response.head < since {
+                    return Ok(false);
+                }
+                for (seq, message) in response.messages {
+                    let message = symkey::decrypt(&key, &message)?;
+                    if matches!(message, CollabMessage::Snapshot { .. }) {
+                        since_snapshot = 0;
+                    } else {
+                        since_snapshot += 1;
+                    }
+                    session.apply(seq, message)?;
+                }
+                Ok(true)
+            });
+            match applied {
+                Ok(true) => session.wake(),
+                Ok(false) => {
+                    // the server forgot the session, probably because we were away for a while
+                    session.end(None);
+                    return;
+                }
+                Err(err) => {
+                    session.end(Some(err.kind));
+                    return;
+                }
+            }
+            if matches!(session.status(), CollabStatus::Ended(_)) {
+                return;
+            }
+
+            if since_snapshot >= COMPACT_AFTER {
+                if let Some((seq, snapshot)) = session.compaction() {
+                    let compacts = Some(CollabAck { participant: session.author(), seq });
+                    // fails if others haven't caught up; we'll try again after the next pull
+                    if let Err(err) = self
+                        .push_collab(id, &key, &account, &snapshot, true, compacts)
+                        .await
+                    {
+                        debug!(?err, "could not compact collab session");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Starts the session with the document as we have it, unless someone else already has. Readers
+    /// can't start sessions and wait for a writer to.
+    async fn start_collab(
+        &self, id: Uuid, key: &AESKey, account: &Account,
+    ) -> Result<(), CollabPushError> {
+        let text = self
+            .read_document(id, false)
+            .await
+            .map_err(CollabPushError::Lb)?;
+        let text = String::from_utf8_lossy(&text).into_owned();
+        let snapshot = CollabMessage::Snapshot { text };
+        match self
+            .push_collab(id, key, account, &snapshot, true, None)
+            .await
+        {
+            Err(CollabPushError::Api(ApiError::Endpoint(
+                PushCollabError::SessionStarted | PushCollabError::NotPermissioned,
+            ))) => Ok(()),
+            result => result,
+        }
+    }
+
+    async fn push_collab(
+        &self, id: Uuid, key: &AESKey, account: &Account, message: &CollabMessage,
+        is_snapshot: bool, compacts: Option<CollabAck>,
+    ) -> Result<(), CollabPushError> {
+        let message = symkey::encrypt(key, message).map_err(CollabPushError::Lb)?;
+        self.client
+            .request(account, PushCollabRequest { id, message, is_snapshot, compacts })
+            .await
+            .map_err(CollabPushError::Api)?;
+        Ok(())
+    }
+}
+
+/// Some push failures are part of the protocol, so callers need the endpoint's error
+#[derive(Debug)]
+enum CollabPushError {
+    Api(ApiError<PushCollabError>),
+    Lb(LbErr),
+}
+
+impl CollabPushError {
+    /// Whether the server turned the message away for lack of room, which ends the session without
+    /// anything having gone wrong
+    fn is_capacity(&self) -> bool {
+        matches!(
+            self,
+            CollabPushError::Api(ApiError::Endpoint(
+                PushCollabError::SessionFull
+                    | PushCollabError::MessageTooLarge
+                    | PushCollabError::TooManySessions
+            ))
+        )
+    }
+}
+
+impl From<Arc<SessionInner>> for CollabSession {
+    fn from(inner: Arc<SessionInner>) -> Self {
+        Self { inner }
+    }
+}
+
+impl CollabSession {
+    pub fn id(&self) -> Uuid {
+        self.inner.id
+    }
+
+    pub fn status(&self) -> CollabStatus {
+        self.inner.state.lock().unwrap().status.clone()
+    }
+
+    /// Called whenever collaborators' changes arrive or the session's status changes, for
+    /// instance to request a repaint
+    pub fn set_waker(&self, waker: impl Fn() + Send + Sync + 'static) {
+        *self.inner.waker.lock().unwrap() = Some(Box::new(waker));
+    }
+
+    /// The document's text if it changed since the last call. Edits passed to
+    /// [CollabSession::edit] are interpreted relative to the text returned here.
+    pub fn take_view(&self) -> Option<String> {
+        let mut state = self.inner.state.lock().unwrap();
+        if state.status != CollabStatus::Joined {
+            return None;
+        }
+        let seq = state.doc.seq();
+        if matches!(&state.handed_out, Some((handed_out, _)) if *handed_out == seq) {
+            return None;
+        }
+        let text = state.doc.view().text;
+        state.handed_out = Some((seq, text.clone()));
+        Some(text)
+    }
+
+    /// Shares the changes that turned the last view into `text` with collaborators. Returns false
+    /// if there hasn't been a view to edit yet, in which case the changes should be merged into the
+    /// first one.
+    pub fn edit(&self, text: &str) -> bool {
+        let mut state = self.inner.state.lock().unwrap();
+        if state.status != CollabStatus::Joined {
+            return false;
+        }
+        let Some((parent, from)) = &mut state.handed_out else { return false };
+        if from == text {
+            return true;
+        }
+        let ops = diff(from, text);
+        let parent = *parent;
+        *from = text.to_string();
+
+        let message = state.doc.local_edit(parent, ops);
+        drop(state);
+        self.send(message);
+        true
+    }
+
+    /// Shares this participant's cursor with collaborators, in the coordinates of the last view
+    pub fn set_selection(&self, selection: (DocCharOffset, DocCharOffset)) {
+        let mut state = self.inner.state.lock().unwrap();
+        if state.selection == selection {
+            return;
+        }
+        state.selection = selection;
+        drop(state);
+
+        if let Some(presence) = self.presence_message() {
+            self.send(presence);
+        }
+    }
+
+    /// Collaborators' cursors, in the coordinates of the current view
+    pub fn presence(&self) -> Vec<Presence> {
+        let state = self.inner.state.lock().unwrap();
+        let now = Instant::now();
+        let mut view = state.doc.view();
+        view.presence.retain(|author, _| {
+            state
+                .last_seen
+                .get(author)
+                .map(|seen| now.duration_since(*seen) < PRESENCE_EXPIRY)
+                .unwrap_or_default()
+        });
+        view.presence.into_values().collect()
+    }
+
+    /// Whether this participant should be the one saving the document. Participants agree on
+    /// this without talking to each other: it's whoever has the lowest session id.
+    pub fn persists(&self) -> bool {
+        let state = self.inner.state.lock().unwrap();
+        if state.status != CollabStatus::Joined {
+            return true;
+        }
+        let now = Instant::now();
+        let author = state.doc.author();
+        !state
+            .last_seen
+            .iter()
+            .any(|(other, seen)| *other < author && now.duration_since(*seen) < PRESENCE_EXPIRY)
+    }
+
+    fn weak(&self) -> Weak<SessionInner> {
+        Arc::downgrade(&self.inner)
+    }
+
+    fn seq(&self) -> u64 {
+        self.inner.state.lock().unwrap().doc.seq()
+    }
+
+    fn author(&self) -> SessionId {
+        self.inner.state.lock().unwrap().doc.author()
+    }
+
+    /// Tells the server which messages our edits may still be based on
+    fn ack(&self) -> CollabAck {
+        let state = self.inner.state.lock().unwrap();
+        let viewed = state
+            .handed_out
+            .as_ref()
+            .map(|(seq, _)| *seq)
+            .unwrap_or(state.doc.seq());
+        let seq = state
+            .doc
+            .pending_parent()
+            .map(|parent| parent.min(viewed))
+            .unwrap_or(viewed);
+        CollabAck { participant: state.doc.author(), seq }
+    }
+
+    /// A snapshot of the session's text to compact it with, if this participant is the one that
+    /// persists the document and has nothing in flight
+    fn compaction(&self) -> Option<(u64, CollabMessage)> {
+        if !self.persists() {
+            return None;
+        }
+        let state = self.inner.state.lock().unwrap();
+        let seq = state.doc.seq();
+        if state.status != CollabStatus::Joined
+            || state.doc.has_pending()
+            || matches!(&state.handed_out, Some((handed_out, _)) if *handed_out != seq)
+        {
+            return None;
+        }
+        let text = state.doc.sequenced_text().to_string();
+        Some((seq, CollabMessage::Snapshot { text }))
+    }
+
+    fn presence_message(&self) -> Option<CollabMessage> {
+        let state = self.inner.state.lock().unwrap();
+        if state.status != CollabStatus::Joined {
+            return None;
+        }
+        let parent = state
+            .handed_out
+            .as_ref()
+            .map(|(seq, _)| *seq)
+            .unwrap_or(state.doc.seq());
+        Some(CollabMessage::Presence {
+            author: state.doc.author(),
+            username: self.inner.username.clone(),
+            parent,
+            selection: state.selection,
+        })
+    }
+
+    fn apply(&self, seq: u64, message: CollabMessage) -> LbResult<()> {
+        let mut state = self.inner.state.lock().unwrap();
+        match &message {
+            CollabMessage::Presence { author, .. } => {
+                state.last_seen.insert(*author, Instant::now());
+            }
+            CollabMessage::Leave { author } => {
+                state.last_seen.remove(author);
+            }
+            _ => {}
+        }
+        state.doc.apply(seq, message)?;
+
+        if state.status == CollabStatus::Joining && state.doc.is_joined() {
+            state.status = CollabStatus::Joined;
+            drop(state);
+            // let everyone know we're here
+            if let Some(presence) = self.presence_message() {
+                self.send(presence);
+            }
+        }
+        Ok(())
+    }
+
+    fn send(&self, message: CollabMessage) {
+        if self.inner.outbox.send(message).is_err() {
+            warn!("collab session outbox closed");
+        }
+    }
+
+    fn end(&self, err: Option<LbErrKind>) {
+        warn!(?err, id = ?self.inner.id, "collab session ended");
+        self.inner.state.lock().unwrap().status = CollabStatus::Ended(err);
+        self.wake();
+    }
+
+    fn wake(&self) {
+        if let Some(waker) = self.inner.waker.lock().unwrap().as_ref() {
+            waker();
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use crate::model::crypto::EncryptedDocument;
+use crate::model::errors::{LbErrKind, LbResult};
+use crate::model::symkey;
+use crate::model::tree_like::TreeLike;
+use crate::Lb;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// What sync does with a text document when this device's changes overlap someone else's
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConflictStrategy {
+    /// Merge both versions as usual
+    #[default]
+    Merge,
+
+    /// Merge both versions as usual and save this device's version next to the document as a
+    /// conflict copy
+    Copy,
+
+    /// Write both versions of each conflicting region into the document between conflict markers
+    Markers,
+}
+
+/// A text document whose last merge combined overlapping changes. Recorded until resolved with
+/// [Lb::resolve_conflict].
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Conflict {
+    pub id: Uuid,
+    pub detected_at: i64,
+
+    /// number of overlapping regions
+    pub regions: usize,
+    pub strategy: ConflictStrategy,
+
+    /// the conflict copy, if one was made
+    pub copy: Option<Uuid>,
+
+    /// both versions as they were before the merge, encrypted with the document's key
+    pub(crate) local: EncryptedDocument,
+    pub(crate) remote: EncryptedDocument,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictResolution {
+    /// Keep the document as it is now, including any edits made since the merge
+    KeepMerged,
+
+    /// Replace the document with this device's version from before the merge
+    KeepLocal,
+
+    /// Replace the document with the version it was merged with
+    KeepRemote,
+}
+
+impl Lb {
+    pub async fn get_conflict_strategy(&self) -> ConflictStrategy {
+        let tx = self.ro_tx().await;
+        tx.db().conflict_strategy.get().copied().unwrap_or_default()
+    }
+
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn set_conflict_strategy(&self, strategy: ConflictStrategy) -> LbResult<()> {
+        let mut tx = self.begin_tx().await;
+        tx.db().conflict_strategy.insert(strategy)?;
+        tx.end();
+        Ok(())
+    }
+
+    /// Unresolved conflicts in documents that still exist, oldest first
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn list_conflicts(&self) -> LbResult<Vec<Conflict>> {
+        let tx = self.ro_tx().await;
+        let db = tx.db();
+        let mut tree = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+
+        let mut conflicts = vec![];
+        for conflict in db.conflicts.get().values() {
+            if tree.maybe_find(&conflict.id).is_none() || tree.calculate_deleted(&conflict.id)? {
+                continue;
+            }
+            conflicts.push(conflict.clone());
+        }
+        conflicts.sort_by_key(|c| c.detected_at);
+
+        Ok(conflicts)
+    }
+
+    /// Settles a conflict by choosing which version of the document to keep. The conflict copy, if
+    /// there is one, is deleted.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn resolve_conflict(&self, id: Uuid, resolution: ConflictResolution) -> LbResult<()> {
+        let tx = self.ro_tx().await;
+        let db = tx.db();
+        let conflict = db
+            .conflicts
+            .get()
+            .get(&id)
+            .cloned()
+            .ok_or(LbErrKind::ConflictNonexistent)?;
+        let mut tree = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+        let key = tree.decrypt_key(&id, &self.keychain)?;
+        let copy_exists = match conflict.copy {
+            Some(copy) => tree.maybe_find(&copy).is_some() && !tree.calculate_deleted(&copy)?,
+            None => false,
+        };
+        drop(tx);
+
+        let content = match resolution {
+            ConflictResolution::KeepMerged => None,
+            ConflictResolution::KeepLocal => Some(symkey::decrypt(&key, &conflict.local)?),
+            ConflictResolution::KeepRemote => Some(symkey::decrypt(&key, &conflict.remote)?),
+        };
+        if let Some(content) = content {
+            self.write_document(id, &content).await?;
+        }
+        if let (Some(copy), true) = (conflict.copy, copy_exists) {
+            self.delete(&copy).await?;
+        }
+
+        let mut tx = self.begin_tx().await;
+        tx.db().conflicts.remove(&id)?;
+        tx.end();
+
+        Ok(())
+    }
+}
@@ -13,7 +13,7 @@ use crate::model::validate;
 use crate::Lb;
 use uuid::Uuid;
 
-use super::activity;
+use super::{activity, history};
 
 impl Lb {
     #[instrument(level = "debug", skip(self), err(Debug))]
@@ -154,14 +154,16 @@ impl Lb {
         let base_files = tree.base.all_files()?.into_iter();
         let local_files = tree.staged.all_files()?.into_iter();
 
-        let file_hmacs = base_files
+        let mut file_hmacs = base_files
             .chain(local_files)
             .filter_map(|f| f.document_hmac().map(|hmac| (*f.id(), *hmac)))
             .collect::<HashSet<_>>();
+        file_hmacs.extend(history::recorded_hmacs(db));
 
-        drop(tx);
-
+        // documents are written under a tx, so holding this one keeps writes from landing between
+        // reading what to keep and deleting the rest
         self.docs.retain(file_hmacs).await?;
+        drop(tx);
 
         Ok(())
     }
@@ -1,24 +1,125 @@
-use tokio::sync::broadcast::{self, Receiver, Sender};
+use tokio::sync::broadcast::{self, Sender};
 use tracing::*;
 use uuid::Uuid;
 
+use crate::model::file_like::FileLike;
+use crate::model::file_metadata::{Diff, FileDiff};
 use crate::Lb;
 
+pub use tokio::sync::broadcast::error::TryRecvError;
+pub use tokio::sync::broadcast::Receiver;
+
 #[derive(Clone)]
 pub struct EventSubs {
     tx: Sender<Event>,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Event {
     /// A metadata for a given id or it's descendants changed. The id returned
     /// may be deleted. Updates to document contents will not cause this
     /// message to be sent (unless a document was deleted).
+    ///
+    /// Prefer the more specific events below, this is only sent when a change
+    /// can't be described more precisely (such as a new account being created)
     MetadataChanged(Uuid),
 
     /// The contents of this document have changed either by this lb
     /// library or as a result of sync
     DocumentWritten(Uuid),
+
+    /// A file was created locally or appeared as a result of sync (including
+    /// files that were shared with us). Content, if any, will arrive as a
+    /// subsequent [Event::DocumentWritten]
+    FileCreated(Uuid),
+
+    /// A file now has a different parent. Descendants of this file moved with
+    /// it and will not receive their own event
+    FileMoved { id: Uuid, from: Uuid, to: Uuid },
+
+    /// A file's name changed
+    FileRenamed { id: Uuid, old: String, new: String },
+
+    /// A file was deleted. Descendants of this file are implicitly deleted
+    /// and will not receive their own event
+    FileDeleted(Uuid),
+
+    /// This file was shared with a new user
+    ShareAdded(Uuid),
+
+    /// A user's access to this file was revoked (or they rejected the share)
+    ShareRemoved(Uuid),
+
+    /// Sync merged overlapping changes into this document. See [crate::Lb::list_conflicts]
+    ConflictDetected(Uuid),
+}
+
+impl Event {
+    /// Describes the user-visible changes between two versions of a file. Names are passed in
+    /// decrypted because they can only be resolved in the context of a tree.
+    pub(crate) fn from_diff<F: FileLike>(
+        diff: &FileDiff<F>, old_name: Option<String>, new_name: Option<String>,
+    ) -> Vec<Self> {
+        let id = *diff.id();
+        let mut events = vec![];
+        for change in diff.diff() {
+            match change {
+                Diff::New => {
+                    if diff.new.explicitly_deleted() {
+                        continue;
+                    }
+                    events.push(Self::FileCreated(id));
+                    if diff.new.is_shared() {
+                        events.push(Self::ShareAdded(id));
+                    }
+                }
+                Diff::Parent => {
+                    if let Some(old) = &diff.old {
+                        events.push(Self::FileMoved {
+                            id,
+                            from: *old.parent(),
+                            to: *diff.new.parent(),
+                        });
+                    }
+                }
+                Diff::Name => {
+                    if let (Some(old), Some(new)) = (&old_name, &new_name) {
+                        if old != new {
+                            events.push(Self::FileRenamed {
+                                id,
+                                old: old.clone(),
+                                new: new.clone(),
+                            });
+                        }
+                    }
+                }
+                Diff::Deleted => {
+                    if diff.new.explicitly_deleted() {
+                        events.push(Self::FileDeleted(id));
+                    }
+                }
+                Diff::UserKeys => {
+                    let Some(old) = &diff.old else { continue };
+                    let sharees = |file: &F| {
+                        file.user_access_keys()
+                            .iter()
+                            .filter(|k| !k.deleted && k.encrypted_for != k.encrypted_by)
+                            .map(|k| k.encrypted_for)
+                            .collect::<Vec<_>>()
+                    };
+                    let (before, after) = (sharees(old), sharees(&diff.new));
+                    if after.iter().any(|k| !before.contains(k)) {
+                        events.push(Self::ShareAdded(id));
+                    }
+                    if before.iter().any(|k| !after.contains(k)) {
+                        events.push(Self::ShareRemoved(id));
+                    }
+                }
+                Diff::Owner | Diff::Hmac => {}
+            }
+        }
+        events
+    }
 }
 
 impl Default for EventSubs {
@@ -37,9 +138,33 @@ impl EventSubs {
         self.queue(Event::DocumentWritten(id));
     }
 
-    fn queue(&self, evt: Event) {
+    pub fn file_created(&self, id: Uuid) {
+        self.queue(Event::FileCreated(id));
+    }
+
+    pub fn file_moved(&self, id: Uuid, from: Uuid, to: Uuid) {
+        self.queue(Event::FileMoved { id, from, to });
+    }
+
+    pub fn file_renamed(&self, id: Uuid, old: String, new: String) {
+        self.queue(Event::FileRenamed { id, old, new });
+    }
+
+    pub fn file_deleted(&self, id: Uuid) {
+        self.queue(Event::FileDeleted(id));
+    }
+
+    pub fn share_added(&self, id: Uuid) {
+        self.queue(Event::ShareAdded(id));
+    }
+
+    pub fn share_removed(&self, id: Uuid) {
+        self.queue(Event::ShareRemoved(id));
+    }
+
+    pub(crate) fn queue(&self, evt: Event) {
         if let Err(e) = self.tx.send(evt) {
-            error!(?evt, ?e, "could not queue");
+            error!(evt = ?e.0, "could not queue");
         }
     }
 }
@@ -1,6 +1,7 @@
 use crate::model::access_info::UserAccessMode;
 use crate::model::errors::{LbErrKind, LbResult};
 use crate::model::file::File;
+use crate::model::file_like::FileLike;
 use crate::model::file_metadata::{FileType, Owner};
 use crate::model::filename::MAX_FILENAME_LENGTH;
 use crate::model::symkey;
@@ -38,7 +39,7 @@ impl Lb {
 
         tx.end();
 
-        self.events.meta_changed(id);
+        self.events.file_created(id);
         Ok(ui_file)
     }
 
@@ -55,12 +56,14 @@ impl Lb {
             .to_lazy();
 
         let id = &tree.linked_by(id)?.unwrap_or(*id);
+        let old_name = tree.name(id, &self.keychain)?;
 
         tree.rename(id, new_name, &self.keychain)?;
 
         tx.end();
 
-        self.events.meta_changed(*id);
+        self.events
+            .file_renamed(*id, old_name, new_name.to_string());
 
         Ok(())
     }
@@ -75,11 +78,12 @@ impl Lb {
             .to_lazy();
 
         let id = &tree.linked_by(id)?.unwrap_or(*id);
+        let old_parent = *tree.find(id)?.parent();
 
         tree.move_file(id, new_parent, &self.keychain)?;
         tx.end();
 
-        self.events.meta_changed(*id);
+        self.events.file_moved(*id, old_parent, *new_parent);
 
         Ok(())
     }
@@ -99,7 +103,7 @@ impl Lb {
 
         tx.end();
 
-        self.events.meta_changed(*id);
+        self.events.file_deleted(*id);
 
         Ok(())
     }
@@ -0,0 +1,293 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::io::CoreDb;
+use crate::model::crypto::DecryptedDocument;
+use crate::model::errors::{LbErrKind, LbResult};
+use crate::model::file_like::FileLike;
+use crate::model::file_metadata::{DocumentHmac, Owner};
+use crate::model::signed_file::SignedFile;
+use crate::model::tree_like::TreeLike;
+use crate::model::validate;
+use crate::Lb;
+use libsecp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+use uuid::Uuid;
+
+/// How many synced versions of each document a device keeps. The oldest is forgotten when sync
+/// records a new one.
+pub const MAX_DOCUMENT_VERSIONS: usize = 32;
+
+/// How much space a device spends on versions of documents other than their current ones. The
+/// oldest versions, across all documents, are forgotten first.
+pub const MAX_HISTORY_BYTES: u64 = 64 * 1024 * 1024;
+
+/// A version of a document this device has seen
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DocumentVersion {
+    /// counts up from 1, and stays the same as newer versions arrive
+    pub version: usize,
+    pub last_modified: u64,
+    pub last_modified_by: String,
+
+    /// false for edits made on this device that haven't been pushed yet
+    pub synced: bool,
+}
+
+/// A synced version recorded by sync. Its content stays in the document store until it ages out.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct VersionRecord {
+    pub(crate) version: usize,
+    pub(crate) hmac: DocumentHmac,
+    pub(crate) timestamp: i64,
+    pub(crate) author: PublicKey,
+    /// bytes the content takes up in the document store
+    pub(crate) size: u64,
+}
+
+impl Lb {
+    /// The versions of a document this device knows about, oldest first. The last one is the
+    /// document as it is now.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn get_document_history(&self, id: Uuid) -> LbResult<Vec<DocumentVersion>> {
+        let tx = self.ro_tx().await;
+        let db = tx.db();
+
+        let versions = self.known_versions(db, id)?;
+        let pk = self.keychain.get_pk()?;
+        let my_name = &self.keychain.get_account()?.username;
+
+        Ok(versions
+            .into_iter()
+            .map(|(record, synced)| DocumentVersion {
+                version: record.version,
+                last_modified: record.timestamp as u64,
+                last_modified_by: if record.author == pk {
+                    my_name.clone()
+                } else {
+                    db.pub_key_lookup
+                        .get()
+                        .get(&Owner(record.author))
+                        .cloned()
+                        .unwrap_or_else(|| String::from("<unknown>"))
+                },
+                synced,
+            })
+            .collect())
+    }
+
+    /// The content of a document as it was at `version`, see [Lb::get_document_history]
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn read_document_version(
+        &self, id: Uuid, version: usize,
+    ) -> LbResult<DecryptedDocument> {
+        let tx = self.ro_tx().await;
+        let db = tx.db();
+
+        let record = self
+            .known_versions(db, id)?
+            .into_iter()
+            .find(|(record, _)| record.version == version)
+            .map(|(record, _)| record)
+            .ok_or(LbErrKind::DocumentVersionNonexistent)?;
+
+        let mut tree = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+        let doc = self.docs.get(id, Some(record.hmac)).await?;
+        tree.decrypt_document(&id, &doc, &self.keychain)
+    }
+
+    /// Makes an old version of a document current again. The restore is an ordinary edit: it
+    /// syncs like one and leaves the versions in between in the history.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn restore_document_version(&self, id: Uuid, version: usize) -> LbResult<()> {
+        let content = self.read_document_version(id, version).await?;
+        self.write_document(id, &content).await
+    }
+
+    /// A unified diff from `version` of a document to the document as it is now. Without a
+    /// version, the diff is from the last synced version, which shows the edits sync would push.
+    /// Empty when nothing changed.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn diff_document(&self, id: Uuid, version: Option<usize>) -> LbResult<String> {
+        let history = self.get_document_history(id).await?;
+        let from = match version {
+            Some(version) => Some(
+                history
+                    .iter()
+                    .find(|v| v.version == version)
+                    .ok_or(LbErrKind::DocumentVersionNonexistent)?,
+            ),
+            None => history.iter().rev().find(|v| v.synced),
+        };
+
+        let old = match from {
+            Some(from) => self.read_document_version(id, from.version).await?,
+            None => vec![],
+        };
+        let new = self.read_document(id, false).await?;
+        if old == new {
+            return Ok(String::new());
+        }
+
+        let path = self.get_path_by_id(id).await?;
+        let old_name = match from {
+            Some(from) => format!("{path} (version {})", from.version),
+            None => format!("{path} (new)"),
+        };
+        let new_name = format!("{path} (current)");
+
+        let (Ok(old), Ok(new)) = (String::from_utf8(old), String::from_utf8(new)) else {
+            return Ok(format!("Binary versions {old_name} and {new_name} differ\n"));
+        };
+        Ok(TextDiff::from_lines(&old, &new)
+            .unified_diff()
+            .context_radius(3)
+            .header(&old_name, &new_name)
+            .to_string())
+    }
+
+    /// Records the synced versions of `ids`, the documents sync pulled or pushed, and forgets
+    /// documents that are gone. Called by sync once base is up to date.
+    pub(crate) async fn record_document_versions(
+        &self, ids: impl IntoIterator<Item = Uuid>,
+    ) -> LbResult<()> {
+        let mut tx = self.begin_tx().await;
+        let db = tx.db();
+
+        let mut new_records = vec![];
+        for id in ids {
+            let Some(file) = db.base_metadata.maybe_find(&id) else {
+                continue;
+            };
+            let Some(hmac) = file.document_hmac().copied() else {
+                continue;
+            };
+            let last = db
+                .doc_versions
+                .get()
+                .get(&id)
+                .and_then(|versions| versions.last());
+            if last.map(|last| last.hmac) == Some(hmac) {
+                continue;
+            }
+            let size = self.docs.size(id, hmac).await?.unwrap_or_default();
+            new_records.push((id, record(file, hmac, last.map(|last| last.version), size)));
+        }
+
+        let recorded = !new_records.is_empty();
+        for (id, record) in new_records {
+            let mut versions = db.doc_versions.get().get(&id).cloned().unwrap_or_default();
+            versions.push(record);
+            if versions.len() > MAX_DOCUMENT_VERSIONS {
+                versions.drain(..versions.len() - MAX_DOCUMENT_VERSIONS);
+            }
+            db.doc_versions.insert(id, versions)?;
+        }
+
+        let gone = db
+            .doc_versions
+            .get()
+            .keys()
+            .filter(|id| db.base_metadata.maybe_find(id).is_none())
+            .copied()
+            .collect::<Vec<_>>();
+        for id in gone {
+            db.doc_versions.remove(&id)?;
+        }
+
+        if recorded {
+            // current versions are kept whether or not there's history, so they don't count
+            let mut old_versions = db
+                .doc_versions
+                .get()
+                .iter()
+                .flat_map(|(id, versions)| {
+                    let old = &versions[..versions.len().saturating_sub(1)];
+                    old.iter()
+                        .map(|record| (record.timestamp, *id, record.size))
+                })
+                .collect::<Vec<_>>();
+            old_versions.sort();
+
+            let mut total = old_versions.iter().map(|(_, _, size)| size).sum::<u64>();
+            let mut forget = HashMap::<Uuid, usize>::new();
+            for (_, id, size) in old_versions {
+                if total <= MAX_HISTORY_BYTES {
+                    break;
+                }
+                total -= size;
+                *forget.entry(id).or_default() += 1;
+            }
+            for (id, count) in forget {
+                let mut versions = db.doc_versions.get()[&id].clone();
+                versions.drain(..count);
+                db.doc_versions.insert(id, versions)?;
+            }
+        }
+
+        tx.end();
+        Ok(())
+    }
+
+    /// Recorded versions plus any newer ones sync hasn't recorded yet: a base version from before
+    /// history was kept, and local edits, which are the only unsynced version.
+    fn known_versions(&self, db: &CoreDb, id: Uuid) -> LbResult<Vec<(VersionRecord, bool)>> {
+        let mut tree = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+        let file = tree.find(&id)?;
+        validate::is_document(file)?;
+        if tree.calculate_deleted(&id)? {
+            return Err(LbErrKind::FileNonexistent.into());
+        }
+
+        let mut versions = db
+            .doc_versions
+            .get()
+            .get(&id)
+            .cloned()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|record| (record, true))
+            .collect::<Vec<_>>();
+
+        let base = tree.tree.base.maybe_find(&id);
+        let local = tree.find(&id)?;
+        for (file, synced) in [(base, true), (Some(local), false)] {
+            let Some(file) = file else {
+                continue;
+            };
+            let Some(hmac) = file.document_hmac().copied() else {
+                continue;
+            };
+            let last = versions.last().map(|(last, _)| last);
+            if last.map(|last| last.hmac) == Some(hmac) {
+                continue;
+            }
+            // not in the history yet, so not counted against its budget
+            let record = record(file, hmac, last.map(|last| last.version), 0);
+            versions.push((record, synced));
+        }
+
+        Ok(versions)
+    }
+}
+
+/// The document hmacs history refers to, which document cleanup has to keep
+pub(crate) fn recorded_hmacs(db: &CoreDb) -> HashSet<(Uuid, DocumentHmac)> {
+    db.doc_versions
+        .get()
+        .iter()
+        .flat_map(|(id, versions)| versions.iter().map(|record| (*id, record.hmac)))
+        .collect()
+}
+
+fn record(
+    file: &SignedFile, hmac: DocumentHmac, previous: Option<usize>, size: u64,
+) -> VersionRecord {
+    VersionRecord {
+        version: previous.unwrap_or_default() + 1,
+        hmac,
+        timestamp: file.timestamped_value.timestamp,
+        author: file.public_key,
+        size,
+    }
+}
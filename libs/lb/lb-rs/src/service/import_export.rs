@@ -15,6 +15,18 @@ pub enum ImportStatus {
     CalculatedTotal(usize),
     StartingItem(String),
     FinishedItem(File),
+
+    /// Something an importer for another app's format couldn't carry over, see
+    /// [crate::service::importers]
+    Unconverted(Unconverted),
+}
+
+/// Content an importer couldn't carry over into lockbook
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Unconverted {
+    /// the file in the export it's from
+    pub source: PathBuf,
+    pub detail: String,
 }
 
 impl Lb {
@@ -63,29 +75,7 @@ impl Lb {
             .to_string();
 
         let file_type = if disk_path.is_file() { FileType::Document } else { FileType::Folder };
-
-        let mut tries = 0;
-        let mut retry_name = name.clone();
-        let file: File;
-
-        loop {
-            match self.create_file(&retry_name, &dest, file_type).await {
-                Ok(new_file) => {
-                    file = new_file;
-                    break;
-                }
-                Err(err)
-                    if matches!(
-                        err.kind,
-                        LbErrKind::Validation(ValidationFailure::PathConflict(_))
-                    ) =>
-                {
-                    tries += 1;
-                    retry_name = format!("{}-{}", name, tries);
-                }
-                Err(err) => return Err(err),
-            }
-        }
+        let file = self.create_file_renaming(&name, dest, file_type).await?;
 
         match file_type {
             FileType::Document => {
@@ -127,6 +117,30 @@ impl Lb {
         Ok(())
     }
 
+    /// Creates a file, appending `-N` to its name until it doesn't conflict with a sibling
+    pub(crate) async fn create_file_renaming(
+        &self, name: &str, parent: Uuid, file_type: FileType,
+    ) -> LbResult<File> {
+        let mut tries = 0;
+        let mut retry_name = name.to_string();
+
+        loop {
+            match self.create_file(&retry_name, &parent, file_type).await {
+                Ok(file) => return Ok(file),
+                Err(err)
+                    if matches!(
+                        err.kind,
+                        LbErrKind::Validation(ValidationFailure::PathConflict(_))
+                    ) =>
+                {
+                    tries += 1;
+                    retry_name = format!("{}-{}", name, tries);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
     #[instrument(level = "debug", skip(self, update_status), err(Debug))]
     pub async fn export_file<F: Fn(ExportFileInfo)>(
         &self, id: Uuid, dest: PathBuf, edit: bool, update_status: &Option<F>,
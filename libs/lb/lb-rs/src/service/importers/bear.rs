@@ -0,0 +1,120 @@
+//! Bear exports each note as a `.bearnote` bundle: a folder with the note's markdown and an
+//! `assets` folder of its images and files. A note becomes `Title.md`, its assets go in a `Title`
+//! folder next to it, and `[[wikilinks]]` between notes are rewritten like Obsidian's.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use regex::{Captures, Regex};
+
+use super::{file_name, link, rewrite_links, rewrite_wikilinks, Import};
+use crate::model::errors::{LbErrKind, LbResult};
+use crate::model::path_ops::percent_decode;
+
+pub(super) fn convert(source: &Path) -> LbResult<Import> {
+    let mut bundles = if is_bundle(source) {
+        vec![source.to_path_buf()]
+    } else {
+        let mut bundles = vec![];
+        for entry in fs::read_dir(source)? {
+            let path = entry?.path();
+            if is_bundle(&path) {
+                bundles.push(path);
+            }
+        }
+        bundles
+    };
+    if bundles.is_empty() {
+        return Err(LbErrKind::DiskPathInvalid.into());
+    }
+    bundles.sort();
+
+    // every note's path is decided first so wikilinks to notes later on resolve
+    let mut import = Import::new(source);
+    let mut notes = vec![];
+    let mut titles: HashMap<String, String> = HashMap::new();
+    for bundle in bundles {
+        let title = file_name(&bundle.file_stem().unwrap_or_default().to_string_lossy());
+        let path = import.unique_path("", &format!("{title}.md"));
+        import.add_file(path.clone(), vec![]);
+        // bear matches titles regardless of case
+        titles.entry(title.to_lowercase()).or_insert(path.clone());
+        notes.push((bundle, path));
+    }
+
+    for (bundle, path) in notes {
+        convert_note(&bundle, &path, &titles, &mut import)?;
+    }
+    Ok(import)
+}
+
+fn convert_note(
+    bundle: &Path, path: &str, titles: &HashMap<String, String>, import: &mut Import,
+) -> LbResult<()> {
+    let Some(text) = ["text.markdown", "text.md", "text.txt"]
+        .iter()
+        .map(|name| bundle.join(name))
+        .find(|text| text.is_file())
+    else {
+        import.unconverted(bundle, "bundle has no note text");
+        return Ok(());
+    };
+    let text = fs::read_to_string(text)?;
+
+    let mut assets = HashMap::new();
+    let assets_dir = bundle.join("assets");
+    if assets_dir.is_dir() {
+        let note_name = path.trim_end_matches(".md");
+        let folder = import.unique_path("", note_name);
+        import.add_folder(&folder);
+
+        let mut entries = fs::read_dir(&assets_dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<PathBuf>, _>>()?;
+        entries.sort();
+        for asset in entries.into_iter().filter(|asset| asset.is_file()) {
+            let name = asset.file_name().unwrap_or_default().to_string_lossy();
+            let asset_path = import.unique_path(&folder, &file_name(&name));
+            import.add_file(asset_path.clone(), fs::read(&asset)?);
+            assets.insert(name.to_string(), asset_path);
+        }
+    }
+
+    let text = rewrite_links(&text, |dest| {
+        let dest = percent_decode(dest);
+        let asset = dest.trim_start_matches("./").strip_prefix("assets/")?;
+        assets.get(asset).map(|asset| link(path, asset))
+    });
+
+    // notes from older versions of bear refer to their assets as [image:name] or [file:name]
+    let mut missing = vec![];
+    let legacy = Regex::new(r"\[(image|file):([^\]]+)\]").unwrap();
+    let text = legacy.replace_all(&text, |caps: &Captures| {
+        let name = caps[2].rsplit('/').next().unwrap_or_default();
+        match assets.get(name) {
+            Some(asset) if &caps[1] == "image" => format!("![{name}]({})", link(path, asset)),
+            Some(asset) => format!("[{name}]({})", link(path, asset)),
+            None => {
+                missing.push(name.to_string());
+                caps[0].to_string()
+            }
+        }
+    });
+    for name in missing {
+        import.unconverted(bundle, format!("{name}, which isn't in the bundle's assets"));
+    }
+
+    let text = rewrite_wikilinks(&text, path, bundle, import, |title| {
+        titles.get(&title.to_lowercase()).cloned()
+    });
+    import.add_file(path.to_string(), text.into_bytes());
+    Ok(())
+}
+
+fn is_bundle(path: &Path) -> bool {
+    path.is_dir()
+        && path
+            .extension()
+            .is_some_and(|ext| ext == "bearnote" || ext == "textbundle")
+}
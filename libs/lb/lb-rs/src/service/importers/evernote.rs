@@ -0,0 +1,407 @@
+//! Evernote exports a notebook as one `.enex` file: xml with each note's title, tags, attachments
+//! and content. The content is ENML, Evernote's subset of xhtml, which is converted to markdown
+//! here. A note becomes `Title.md`, and its attachments go in a `Title` folder next to it.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use md5::{Digest, Md5};
+use regex::{Captures, Regex};
+use roxmltree::{Document, Node, ParsingOptions};
+
+use super::{file_name, link, walk, Import};
+use crate::model::errors::LbResult;
+
+pub(super) fn convert(source: &Path) -> LbResult<Import> {
+    let mut import = Import::new(source);
+    if source.is_dir() {
+        // a folder of notebooks becomes a folder per notebook
+        for (_, disk) in walk(source)? {
+            if disk.extension().is_some_and(|ext| ext == "enex") {
+                let name = file_name(&disk.file_stem().unwrap_or_default().to_string_lossy());
+                let folder = import.unique_path("", &name);
+                import.add_folder(&folder);
+                convert_notebook(&disk, &folder, &mut import)?;
+            }
+        }
+    } else {
+        convert_notebook(source, "", &mut import)?;
+    }
+    Ok(import)
+}
+
+/// An unreadable notebook or note is reported and skipped, so one bad note doesn't stop the rest
+fn convert_notebook(enex: &Path, folder: &str, import: &mut Import) -> LbResult<()> {
+    let xml = fs::read_to_string(enex)?;
+    let xml = xml_entities(&xml);
+    let doc = match Document::parse_with_options(&xml, parsing_options()) {
+        Ok(doc) => doc,
+        Err(err) => {
+            import.unconverted(enex, format!("couldn't read the notebook: {err}"));
+            return Ok(());
+        }
+    };
+
+    for note in doc
+        .root_element()
+        .children()
+        .filter(|node| node.has_tag_name("note"))
+    {
+        convert_note(note, enex, folder, import);
+    }
+    Ok(())
+}
+
+fn convert_note(note: Node, enex: &Path, folder: &str, import: &mut Import) {
+    let title = file_name(child_text(note, "title").unwrap_or_default());
+    let path = import.unique_path(folder, &format!("{title}.md"));
+
+    // attachments are found by the md5 of their content, which is how the note refers to them
+    let mut attachments: HashMap<String, (String, bool)> = HashMap::new();
+    let mut attachment_folder = None;
+    for (i, resource) in note
+        .children()
+        .filter(|node| node.has_tag_name("resource"))
+        .enumerate()
+    {
+        let data = child_text(resource, "data")
+            .unwrap_or_default()
+            .split_whitespace()
+            .collect::<String>();
+        let Ok(data) = base64::decode(data) else {
+            import
+                .unconverted(enex, format!("attachment {} of {title} couldn't be decoded", i + 1));
+            continue;
+        };
+        let mime = child_text(resource, "mime").unwrap_or_default();
+        let name = resource
+            .children()
+            .find(|node| node.has_tag_name("resource-attributes"))
+            .and_then(|attributes| child_text(attributes, "file-name"))
+            .map(file_name)
+            .unwrap_or_else(|| {
+                let ext = mime
+                    .rsplit('/')
+                    .next()
+                    .filter(|ext| !ext.is_empty())
+                    .unwrap_or("bin");
+                format!("attachment-{}.{ext}", i + 1)
+            });
+
+        let attachment_folder = attachment_folder.get_or_insert_with(|| {
+            let note_name = path.trim_end_matches(".md").rsplit('/').next().unwrap();
+            let attachment_folder = import.unique_path(folder, note_name);
+            import.add_folder(&attachment_folder);
+            attachment_folder
+        });
+        let attachment = import.unique_path(attachment_folder, &name);
+        let hash = Md5::digest(&data)
+            .iter()
+            .fold(String::new(), |mut hash, byte| {
+                let _ = write!(hash, "{byte:02x}");
+                hash
+            });
+        attachments.insert(hash, (attachment.clone(), mime.starts_with("image/")));
+        import.add_file(attachment, data);
+    }
+
+    let enml = xml_entities(child_text(note, "content").unwrap_or_default());
+    let mut markdown = match Document::parse_with_options(&enml, parsing_options()) {
+        Ok(doc) => {
+            let mut converter =
+                Enml { path: &path, attachments: &attachments, unconverted: BTreeSet::new() };
+            let markdown = converter.children(doc.root_element());
+            for detail in converter.unconverted {
+                import.unconverted(enex, format!("{detail} in {title}"));
+            }
+            tidy(&markdown)
+        }
+        Err(err) => {
+            import.unconverted(enex, format!("couldn't read {title}: {err}"));
+            return;
+        }
+    };
+
+    let tags = note
+        .children()
+        .filter(|node| node.has_tag_name("tag"))
+        .filter_map(|tag| tag.text())
+        .map(|tag| format!("#{}", tag.split_whitespace().collect::<Vec<_>>().join("-")))
+        .collect::<Vec<_>>();
+    if !tags.is_empty() {
+        markdown.push_str(&format!("\n{}\n", tags.join(" ")));
+    }
+
+    import.add_file(path, markdown.into_bytes());
+}
+
+/// ENML to markdown. Each element becomes its markdown, with blocks surrounded by blank lines that
+/// [tidy] collapses afterwards.
+struct Enml<'a> {
+    /// where the note will be, for links to its attachments
+    path: &'a str,
+    attachments: &'a HashMap<String, (String, bool)>,
+    unconverted: BTreeSet<&'static str>,
+}
+
+impl Enml<'_> {
+    fn children(&mut self, node: Node) -> String {
+        node.children().map(|child| self.node(child)).collect()
+    }
+
+    fn node(&mut self, node: Node) -> String {
+        if node.is_text() {
+            // like html, runs of whitespace are one space
+            let text = node.text().unwrap_or_default();
+            let mut collapsed = String::with_capacity(text.len());
+            for c in text.chars() {
+                if !c.is_whitespace() {
+                    collapsed.push(c);
+                } else if !collapsed.ends_with(' ') {
+                    collapsed.push(' ');
+                }
+            }
+            return collapsed;
+        }
+        if !node.is_element() {
+            return String::new();
+        }
+
+        let name = node.tag_name().name();
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = name[1..].parse().unwrap_or(1);
+                format!("\n\n{} {}\n\n", "#".repeat(level), self.children(node).trim())
+            }
+            "b" | "strong" => wrap(&self.children(node), "**"),
+            "i" | "em" => wrap(&self.children(node), "*"),
+            "s" | "strike" | "del" => wrap(&self.children(node), "~~"),
+            "code" => wrap(&self.children(node), "`"),
+            "u" => {
+                self.unconverted.insert("underlined text");
+                self.children(node)
+            }
+            "a" => {
+                let text = self.children(node);
+                match node.attribute("href") {
+                    Some(href) => format!("[{}]({href})", text.trim()),
+                    None => text,
+                }
+            }
+            "img" => match node.attribute("src") {
+                Some(src) => format!("![{}]({src})", node.attribute("alt").unwrap_or_default()),
+                None => String::new(),
+            },
+            "br" => "\n\n".to_string(),
+            "hr" => "\n\n---\n\n".to_string(),
+            "en-todo" => {
+                if node.attribute("checked") == Some("true") {
+                    "- [x] ".to_string()
+                } else {
+                    "- [ ] ".to_string()
+                }
+            }
+            "en-media" => self.media(node),
+            "en-crypt" => {
+                self.unconverted.insert("encrypted text");
+                String::new()
+            }
+            "ul" | "ol" => self.list(node, name == "ol"),
+            "table" => self.table(node),
+            "blockquote" => {
+                let quote = tidy(&self.children(node));
+                let quote = quote
+                    .lines()
+                    .map(|line| format!("> {line}").trim_end().to_string())
+                    .collect::<Vec<_>>();
+                format!("\n\n{}\n\n", quote.join("\n"))
+            }
+            "pre" => format!("\n\n```\n{}\n```\n\n", raw_text(node).trim_end()),
+            _ if node
+                .attribute("style")
+                .is_some_and(|style| style.contains("-en-codeblock")) =>
+            {
+                format!("\n\n```\n{}\n```\n\n", raw_text(node).trim_end())
+            }
+            "div" | "p" | "en-note" => format!("\n\n{}\n\n", self.children(node)),
+            // formatting markdown has no equivalent for, like fonts and colors, is dropped
+            _ => self.children(node),
+        }
+    }
+
+    fn media(&mut self, node: Node) -> String {
+        let attachment = node
+            .attribute("hash")
+            .and_then(|hash| self.attachments.get(hash));
+        let Some((attachment, image)) = attachment else {
+            self.unconverted
+                .insert("an attachment missing from the export");
+            return String::new();
+        };
+        let name = attachment.rsplit('/').next().unwrap_or_default();
+        let dest = link(self.path, attachment);
+        if *image {
+            format!("![{name}]({dest})")
+        } else {
+            format!("[{name}]({dest})")
+        }
+    }
+
+    fn list(&mut self, node: Node, ordered: bool) -> String {
+        let mut list = String::from("\n\n");
+        let items = node.children().filter(|child| child.has_tag_name("li"));
+        for (i, item) in items.enumerate() {
+            let marker = if ordered { format!("{}. ", i + 1) } else { "- ".to_string() };
+            let indent = " ".repeat(marker.len());
+            let text = tidy(&self.children(item));
+            // tight lists, with nested lists indented under their item
+            for (j, line) in text
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .enumerate()
+            {
+                if j == 0 {
+                    list.push_str(&format!("{marker}{line}\n"));
+                } else {
+                    list.push_str(&format!("{indent}{line}\n"));
+                }
+            }
+        }
+        list.push('\n');
+        list
+    }
+
+    fn table(&mut self, node: Node) -> String {
+        let rows = node
+            .descendants()
+            .filter(|row| row.has_tag_name("tr"))
+            .map(|row| {
+                row.children()
+                    .filter(|cell| cell.has_tag_name("td") || cell.has_tag_name("th"))
+                    .map(|cell| {
+                        tidy(&self.children(cell))
+                            .replace('\n', " ")
+                            .replace('|', "\\|")
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let columns = rows.iter().map(|row| row.len()).max().unwrap_or_default();
+        if columns == 0 {
+            return String::new();
+        }
+
+        let mut table = String::from("\n\n");
+        for (i, row) in rows.iter().enumerate() {
+            let mut cells = row.clone();
+            cells.resize(columns, String::new());
+            table.push_str(&format!("| {} |\n", cells.join(" | ")));
+            // markdown tables always have a header, so the first row is it
+            if i == 0 {
+                table.push_str(&format!("|{}\n", " --- |".repeat(columns)));
+            }
+        }
+        table.push('\n');
+        table
+    }
+}
+
+/// Text as written, for code blocks, where each div or br is a line
+fn raw_text(node: Node) -> String {
+    let mut text = String::new();
+    for child in node.children() {
+        if child.is_text() {
+            text.push_str(child.text().unwrap_or_default());
+        } else if child.has_tag_name("br") {
+            text.push('\n');
+        } else {
+            text.push_str(&raw_text(child));
+            if child.has_tag_name("div") || child.has_tag_name("p") {
+                text.push('\n');
+            }
+        }
+    }
+    text
+}
+
+fn wrap(text: &str, marker: &str) -> String {
+    if text.trim().is_empty() {
+        return text.to_string();
+    }
+    format!("{marker}{}{marker}", text.trim())
+}
+
+/// Trims each line and collapses the blank lines between blocks to one. Code blocks are left as
+/// they are.
+fn tidy(markdown: &str) -> String {
+    let mut tidy = String::new();
+    let mut blank = false;
+    let mut code = false;
+    for line in markdown.lines() {
+        if line.starts_with("```") {
+            code = !code;
+        } else if code {
+            tidy.push_str(line);
+            tidy.push('\n');
+            continue;
+        }
+
+        let line = line.trim_end();
+        // nested list items keep their indentation, other lines lose the spaces left between
+        // inline elements
+        let line = if line.trim_start().starts_with(['-', '|', '>']) || line.starts_with("  ") {
+            line
+        } else {
+            line.trim_start()
+        };
+        if line.is_empty() {
+            blank = !tidy.is_empty();
+            continue;
+        }
+        if blank {
+            tidy.push('\n');
+            blank = false;
+        }
+        tidy.push_str(line);
+        tidy.push('\n');
+    }
+    tidy
+}
+
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.children()
+        .find(|child| child.has_tag_name(name))
+        .and_then(|child| child.text())
+}
+
+fn parsing_options() -> ParsingOptions {
+    // exports start with a doctype
+    ParsingOptions { allow_dtd: true, ..Default::default() }
+}
+
+/// ENML uses html's named entities, which xml parsers only know from the external DTD
+fn xml_entities(xml: &str) -> String {
+    let entities = Regex::new(r"&([A-Za-z][A-Za-z0-9]*);").unwrap();
+    entities
+        .replace_all(xml, |caps: &Captures| {
+            let code = match &caps[1] {
+                "amp" | "lt" | "gt" | "quot" | "apos" => return caps[0].to_string(),
+                "nbsp" => 160,
+                "copy" => 169,
+                "reg" => 174,
+                "ndash" => 8211,
+                "mdash" => 8212,
+                "lsquo" => 8216,
+                "rsquo" => 8217,
+                "ldquo" => 8220,
+                "rdquo" => 8221,
+                "bull" => 8226,
+                "hellip" => 8230,
+                _ => return format!("&amp;{};", &caps[1]),
+            };
+            format!("&#{code};")
+        })
+        .into_owned()
+}
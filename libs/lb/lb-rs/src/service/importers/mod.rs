@@ -0,0 +1,350 @@
+//! Importers for other note apps' exports. Each format's adapter reads an export from disk and
+//! converts it into an [Import]: documents and folders laid out the way they'll be created in
+//! lockbook, with links between notes rewritten to relative lockbook links. Whatever an adapter
+//! can't carry over is reported as [Unconverted] rather than silently dropped.
+
+mod bear;
+mod evernote;
+mod notion;
+mod obsidian;
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use regex::{Captures, Regex};
+use uuid::Uuid;
+
+use crate::model::errors::{LbErrKind, LbResult};
+use crate::model::file_metadata::FileType;
+use crate::model::filename::MAX_FILENAME_LENGTH;
+use crate::model::ValidationFailure;
+use crate::service::import_export::ImportStatus;
+use crate::Lb;
+
+pub use crate::service::import_export::Unconverted;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// an Obsidian vault: a folder of markdown notes linked with `[[wikilinks]]`
+    Obsidian,
+
+    /// an unzipped Notion "Markdown & CSV" export
+    Notion,
+
+    /// one Evernote `.enex` notebook export, or a folder of them
+    Evernote,
+
+    /// one Bear `.bearnote` bundle, or a folder of them
+    Bear,
+}
+
+impl FromStr for ImportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "obsidian" => Ok(Self::Obsidian),
+            "notion" => Ok(Self::Notion),
+            "evernote" => Ok(Self::Evernote),
+            "bear" => Ok(Self::Bear),
+            other => {
+                Err(format!("unknown format {other}, expected obsidian, notion, evernote or bear"))
+            }
+        }
+    }
+}
+
+impl Display for ImportFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Obsidian => write!(f, "obsidian"),
+            Self::Notion => write!(f, "notion"),
+            Self::Evernote => write!(f, "evernote"),
+            Self::Bear => write!(f, "bear"),
+        }
+    }
+}
+
+impl Lb {
+    /// Imports another app's export into a new folder inside `dest`. Progress is reported like
+    /// [Lb::import_files], plus [ImportStatus::Unconverted] for each thing the format's adapter
+    /// couldn't convert.
+    #[instrument(level = "debug", skip(self, update_status), err(Debug))]
+    pub async fn import_from<F: Fn(ImportStatus)>(
+        &self, format: ImportFormat, source: &Path, dest: Uuid, update_status: &F,
+    ) -> LbResult<()> {
+        let parent = self.get_file_by_id(dest).await?;
+        if !parent.is_folder() {
+            return Err(LbErrKind::Validation(ValidationFailure::NonFolderWithChildren(dest)))?;
+        }
+        if !source.exists() {
+            return Err(LbErrKind::DiskPathInvalid.into());
+        }
+
+        let import = match format {
+            ImportFormat::Obsidian => obsidian::convert(source)?,
+            ImportFormat::Notion => notion::convert(source)?,
+            ImportFormat::Evernote => evernote::convert(source)?,
+            ImportFormat::Bear => bear::convert(source)?,
+        };
+
+        update_status(ImportStatus::CalculatedTotal(1 + import.folders.len() + import.files.len()));
+        for unconverted in &import.unconverted {
+            update_status(ImportStatus::Unconverted(unconverted.clone()));
+        }
+
+        // everything lands in one new folder so links between imported notes stay relative
+        update_status(ImportStatus::StartingItem(import.name.clone()));
+        let root = self
+            .create_file_renaming(&import.name, dest, FileType::Folder)
+            .await?;
+        let root_path = self.get_path_by_id(root.id).await?;
+        let root_path = root_path.trim_end_matches('/');
+        update_status(ImportStatus::FinishedItem(root));
+
+        for folder in &import.folders {
+            update_status(ImportStatus::StartingItem(folder.clone()));
+            let file = self
+                .create_at_path(&format!("{root_path}/{folder}/"))
+                .await?;
+            update_status(ImportStatus::FinishedItem(file));
+        }
+        for (path, content) in &import.files {
+            update_status(ImportStatus::StartingItem(path.clone()));
+            let file = self.create_at_path(&format!("{root_path}/{path}")).await?;
+            self.write_document(file.id, content).await?;
+            update_status(ImportStatus::FinishedItem(file));
+        }
+
+        Ok(())
+    }
+}
+
+/// What an adapter converted an export into. Paths are relative to the folder the import is
+/// created in and use `/`.
+#[derive(Default)]
+struct Import {
+    /// name of the folder the import is created in
+    name: String,
+    folders: BTreeSet<String>,
+    files: BTreeMap<String, Vec<u8>>,
+    unconverted: Vec<Unconverted>,
+}
+
+impl Import {
+    /// An import named after the folder it's from, or the file without its extension
+    fn new(source: &Path) -> Self {
+        let name = if source.is_dir() { source.file_name() } else { source.file_stem() };
+        let name = match name {
+            Some(name) => file_name(&name.to_string_lossy()),
+            None => "import".to_string(),
+        };
+        Self { name, ..Default::default() }
+    }
+
+    fn add_folder(&mut self, path: &str) {
+        let mut folder = String::new();
+        for component in path.split('/') {
+            if !folder.is_empty() {
+                folder.push('/');
+            }
+            folder.push_str(component);
+            self.folders.insert(folder.clone());
+        }
+    }
+
+    fn add_file(&mut self, path: String, content: Vec<u8>) {
+        if let Some((parent, _)) = path.rsplit_once('/') {
+            self.add_folder(parent);
+        }
+        self.files.insert(path, content);
+    }
+
+    fn unconverted(&mut self, source: &Path, detail: impl Into<String>) {
+        self.unconverted
+            .push(Unconverted { source: source.to_path_buf(), detail: detail.into() });
+    }
+
+    /// A name for a new file in `folder` that no other file or folder there has yet
+    fn unique_path(&self, folder: &str, name: &str) -> String {
+        let join = |name: &str| {
+            if folder.is_empty() {
+                name.to_string()
+            } else {
+                format!("{folder}/{name}")
+            }
+        };
+        let (stem, ext) = match name.rsplit_once('.') {
+            Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{ext}")),
+            _ => (name, String::new()),
+        };
+
+        let mut path = join(name);
+        let mut tries = 0;
+        while self.files.contains_key(&path) || self.folders.contains(&path) {
+            tries += 1;
+            path = join(&format!("{stem}-{tries}{ext}"));
+        }
+        path
+    }
+}
+
+/// A valid lockbook file name for a note title or a name from another app
+fn file_name(name: &str) -> String {
+    let mut name = name.trim().replace('/', "-");
+    if name.is_empty() {
+        name = "Untitled".to_string();
+    }
+    if name.len() > MAX_FILENAME_LENGTH {
+        let mut end = MAX_FILENAME_LENGTH;
+        while !name.is_char_boundary(end) {
+            end -= 1;
+        }
+        name.truncate(end);
+    }
+    name
+}
+
+/// The link from one imported file to another. Lockbook resolves relative links from the
+/// document itself, so even a sibling is one `..` away.
+fn link(from: &str, to: &str) -> String {
+    let from = from.split('/').collect::<Vec<_>>();
+    let to = to.split('/').collect::<Vec<_>>();
+    let common = from
+        .iter()
+        .zip(&to)
+        .take_while(|(from, to)| from == to)
+        .count();
+
+    let mut link = "../".repeat(from.len() - common);
+    link.push_str(&to[common..].join("/"));
+    // markdown link destinations can't contain spaces unless they're in angle brackets
+    if link.contains([' ', '(', ')']) {
+        link = format!("<{link}>");
+    }
+    link
+}
+
+/// Resolves a relative link in a file at `from` to the path it points to, with any `..`
+/// resolved. `None` if it points outside the import.
+fn resolve(from: &str, target: &str) -> Option<String> {
+    let mut path = from.split('/').collect::<Vec<_>>();
+    path.pop();
+    for component in target.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                path.pop()?;
+            }
+            component => path.push(component),
+        }
+    }
+    Some(path.join("/"))
+}
+
+/// Rewrites the destinations of markdown links and images. `rewrite` gets each destination
+/// that isn't a url or an anchor and returns its replacement, or `None` to leave it alone.
+fn rewrite_links(text: &str, mut rewrite: impl FnMut(&str) -> Option<String>) -> String {
+    let links = Regex::new(r"(!?\[[^\]]*\])\((?:<([^>]*)>|([^)\s]*))\)").unwrap();
+    links
+        .replace_all(text, |caps: &Captures| {
+            let label = &caps[1];
+            let dest = caps.get(2).or(caps.get(3)).unwrap().as_str();
+            let is_url = dest.contains("://") || dest.starts_with("mailto:");
+            if dest.is_empty() || is_url || dest.starts_with('#') {
+                return caps[0].to_string();
+            }
+            match rewrite(dest) {
+                Some(dest) => format!("{label}({dest})"),
+                None => caps[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// Rewrites `[[wikilinks]]` and `![[embeds]]` into markdown links. `find` resolves the target
+/// named in a link to an imported file.
+fn rewrite_wikilinks(
+    text: &str, from: &str, source: &Path, import: &mut Import,
+    find: impl Fn(&str) -> Option<String>,
+) -> String {
+    let wikilinks = Regex::new(r"(!?)\[\[([^\]|#^]*)([#^][^\]|]*)?(?:\|([^\]]*))?\]\]").unwrap();
+    let mut unconverted = vec![];
+    let text = wikilinks.replace_all(text, |caps: &Captures| {
+        let embed = !caps[1].is_empty();
+        let target = caps[2].trim();
+        let alias = caps.get(4).map(|alias| alias.as_str().trim());
+
+        // a link within the note itself
+        if target.is_empty() {
+            unconverted.push(format!("link to a heading or block in the same note: {}", &caps[0]));
+            return alias.unwrap_or(&caps[0]).to_string();
+        }
+        let Some(path) = find(target) else {
+            unconverted.push(format!("link to {target}, which isn't in the export"));
+            return caps[0].to_string();
+        };
+        if let Some(anchor) = caps.get(3) {
+            unconverted.push(format!(
+                "link to {}{} now links to the whole note",
+                target,
+                anchor.as_str()
+            ));
+        }
+
+        let is_note = path.ends_with(".md");
+        let label = match alias {
+            // image embeds use the alias for their size, e.g. ![[image.png|300]]
+            Some(alias) if is_note || !embed => alias,
+            _ => target,
+        };
+        let dest = link(from, &path);
+        if embed && is_note {
+            unconverted.push(format!("embedded note {target} is now a link"));
+            format!("[{label}]({dest})")
+        } else if embed {
+            format!("![{label}]({dest})")
+        } else {
+            format!("[{label}]({dest})")
+        }
+    });
+    for detail in unconverted {
+        import.unconverted(source, detail);
+    }
+    text.into_owned()
+}
+
+/// Every file and folder in a folder on disk, recursively, with its path relative to that folder.
+/// Hidden files and folders are skipped.
+fn walk(dir: &Path) -> LbResult<Vec<(String, PathBuf)>> {
+    let mut files = vec![];
+    let mut seen = HashSet::new();
+    walk_into(dir, "", &mut files, &mut seen)?;
+    files.sort();
+    Ok(files)
+}
+
+fn walk_into(
+    dir: &Path, prefix: &str, files: &mut Vec<(String, PathBuf)>, seen: &mut HashSet<PathBuf>,
+) -> LbResult<()> {
+    // symlinks can make a loop
+    if !seen.insert(dir.canonicalize()?) {
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if name.starts_with('.') {
+            continue;
+        }
+        let relative =
+            if prefix.is_empty() { name.to_string() } else { format!("{prefix}/{name}") };
+        files.push((relative.clone(), path.clone()));
+        if path.is_dir() {
+            walk_into(&path, &relative, files, seen)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,91 @@
+//! Notion's "Markdown & CSV" export is a folder per page with subpages, a markdown file per page
+//! and a CSV per database. Every name ends in the page's 32 character id, which is stripped, and
+//! links between pages are rewritten to match.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use regex::Regex;
+
+use super::{file_name, link, resolve, rewrite_links, walk, Import};
+use crate::model::errors::{LbErrKind, LbResult};
+use crate::model::path_ops::percent_decode;
+
+pub(super) fn convert(export: &Path) -> LbResult<Import> {
+    // exports are downloaded as a zip, which needs to be extracted first
+    if !export.is_dir() {
+        return Err(LbErrKind::DiskPathInvalid.into());
+    }
+
+    let mut import = Import::new(export);
+    let entries = walk(export)?;
+
+    // parents are walked before their children, so a parent's new path is always known
+    let mut renamed: HashMap<&str, String> = HashMap::new();
+    for (path, disk) in &entries {
+        let (parent, name) = match path.rsplit_once('/') {
+            Some((parent, name)) => (renamed[parent].as_str(), name),
+            None => ("", path.as_str()),
+        };
+        let new_path = import.unique_path(parent, &file_name(&strip_id(name)));
+        if disk.is_dir() {
+            import.add_folder(&new_path);
+        } else {
+            // reserves the name until the content is converted
+            import.add_file(new_path.clone(), vec![]);
+        }
+        renamed.insert(path, new_path);
+    }
+
+    for (path, disk) in &entries {
+        if disk.is_dir() {
+            continue;
+        }
+        let new_path = &renamed[path.as_str()];
+        let content = fs::read(disk)?;
+        if path.ends_with(".html") {
+            import.unconverted(
+                disk,
+                "html page copied as is, export as Markdown & CSV to convert it",
+            );
+        }
+        if !path.ends_with(".md") {
+            import.add_file(new_path.clone(), content);
+            continue;
+        }
+
+        let mut missing = vec![];
+        let text = rewrite_links(&String::from_utf8_lossy(&content), |dest| {
+            let target = resolve(path, &percent_decode(dest))?;
+            match renamed.get(target.as_str()) {
+                Some(target) => Some(link(new_path, target)),
+                None => {
+                    missing.push(target);
+                    None
+                }
+            }
+        });
+        for target in missing {
+            import.unconverted(disk, format!("link to {target}, which isn't in the export"));
+        }
+        import.add_file(new_path.clone(), text.into_bytes());
+    }
+
+    Ok(import)
+}
+
+/// `Meeting notes 0123456789abcdef0123456789abcdef.md` is `Meeting notes.md`. A database's
+/// `_all` CSV, which has every row rather than just the ones its view shows, keeps its suffix.
+fn strip_id(name: &str) -> String {
+    let id = Regex::new(r"^(.*?) ?[0-9a-f]{32}(_all)?(\.[^.]+)?$").unwrap();
+    match id.captures(name) {
+        Some(caps) if !caps[1].is_empty() => format!(
+            "{}{}{}",
+            &caps[1],
+            caps.get(2).map(|all| all.as_str()).unwrap_or_default(),
+            caps.get(3).map(|ext| ext.as_str()).unwrap_or_default()
+        ),
+        _ => name.to_string(),
+    }
+}
@@ -0,0 +1,82 @@
+//! An Obsidian vault is already a folder of markdown, so the notes keep their layout. What changes
+//! is links: `[[wikilinks]]` and `![[embeds]]` name a note or attachment anywhere in the vault,
+//! which lockbook can't resolve, so they become relative markdown links.
+
+use std::fs;
+use std::path::Path;
+
+use super::{link, resolve, rewrite_links, rewrite_wikilinks, walk, Import};
+use crate::model::errors::{LbErrKind, LbResult};
+use crate::model::path_ops::percent_decode;
+
+pub(super) fn convert(vault: &Path) -> LbResult<Import> {
+    if !vault.is_dir() {
+        return Err(LbErrKind::DiskPathInvalid.into());
+    }
+
+    let mut import = Import::new(vault);
+    // hidden entries, which include the vault's .obsidian settings and .trash, are skipped
+    let entries = walk(vault)?;
+    let files = entries
+        .iter()
+        .filter(|(_, disk)| disk.is_file())
+        .map(|(path, _)| path.as_str())
+        .collect::<Vec<_>>();
+
+    // a link names a file by its path in the vault, or by just enough of it to be unambiguous,
+    // and the extension is optional for notes. When several files match, Obsidian picks the
+    // one closest to the vault's root.
+    let find = |target: &str| {
+        let target = target.trim_start_matches('/');
+        let note = format!("{target}.md");
+        files
+            .iter()
+            .copied()
+            .filter(|path| {
+                [target, note.as_str()]
+                    .iter()
+                    .any(|name| *path == *name || path.ends_with(&format!("/{name}")))
+            })
+            .min_by_key(|path| (path.matches('/').count(), path.len()))
+            .map(|path| path.to_string())
+    };
+
+    for (path, disk) in &entries {
+        if disk.is_dir() {
+            import.add_folder(path);
+            continue;
+        }
+
+        let content = fs::read(disk)?;
+        if path.ends_with(".canvas") {
+            import.unconverted(disk, "canvas copied as is, lockbook can't display it");
+        }
+        if !path.ends_with(".md") {
+            import.add_file(path.clone(), content);
+            continue;
+        }
+
+        let text = String::from_utf8_lossy(&content);
+        if text.contains("```dataview") {
+            import.unconverted(disk, "dataview query copied as a code block");
+        }
+        if text.contains("%%") {
+            import.unconverted(disk, "%% comments %% are visible in lockbook");
+        }
+
+        let text = rewrite_wikilinks(&text, path, disk, &mut import, find);
+        // markdown links are relative to the note's folder, or to the vault's root depending on
+        // the vault's settings
+        let text = rewrite_links(&text, |dest| {
+            let dest = percent_decode(dest);
+            [resolve(path, &dest), resolve("", &dest)]
+                .into_iter()
+                .flatten()
+                .find(|target| files.contains(&target.as_str()))
+                .map(|target| link(path, &target))
+        });
+        import.add_file(path.clone(), text.into_bytes());
+    }
+
+    Ok(import)
+}
@@ -0,0 +1,436 @@
+//! Two-way sync between a lockbook folder and a directory on disk, so people can keep using their
+//! usual tools on their notes. Changes are detected against what both sides looked like the last
+//! time they were mirrored, which is kept in a state file at the top of the directory.
+
+use crate::model::clock::get_time;
+use crate::model::errors::{LbErr, LbErrKind, LbResult, Unexpected};
+use crate::model::file_like::FileLike;
+use crate::model::file_metadata::DocumentHmac;
+use crate::model::tree_like::TreeLike;
+use crate::model::ValidationFailure;
+use crate::Lb;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use uuid::Uuid;
+
+/// Kept in the mirrored directory. Hidden, so never mirrored itself.
+pub const MIRROR_STATE_FILE: &str = ".lockbook-mirror";
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+struct MirrorState {
+    folder: Uuid,
+    /// by path relative to the mirrored folder, with `/` separators
+    files: HashMap<String, MirroredFile>,
+}
+
+/// A file as it was on both sides after the last mirror
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+struct MirroredFile {
+    id: Uuid,
+    is_folder: bool,
+    hmac: Option<DocumentHmac>,
+    /// modification time on disk, in ms
+    mtime: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RemoteFile {
+    id: Uuid,
+    is_folder: bool,
+    hmac: Option<DocumentHmac>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LocalFile {
+    is_folder: bool,
+    mtime: u64,
+}
+
+/// What a [Lb::mirror] changed, by path relative to the mirrored folder
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct MirrorReport {
+    /// written to disk
+    pub pulled: Vec<String>,
+    /// written to lockbook
+    pub pushed: Vec<String>,
+    /// deleted from disk because they were deleted in lockbook
+    pub deleted_locally: Vec<String>,
+    /// deleted in lockbook because they were deleted from disk
+    pub deleted_remotely: Vec<String>,
+    /// changed on both sides; the lockbook version kept the path and the version from disk was
+    /// saved alongside it as a conflicted copy
+    pub conflicts: Vec<String>,
+}
+
+impl MirrorReport {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+impl Lb {
+    /// Reconciles `dir` with `folder`, creating `dir` if need be. Only local state is touched; sync
+    /// before and after to mirror what's on the server. A directory mirrors one folder for life.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn mirror(&self, folder: Uuid, dir: &Path) -> LbResult<MirrorReport> {
+        if !self.get_file_by_id(folder).await?.is_folder() {
+            return Err(LbErrKind::Validation(ValidationFailure::NonFolderWithChildren(folder)))?;
+        }
+        fs::create_dir_all(dir).map_err(LbErr::from)?;
+
+        let mut report = self.mirror_pass(folder, dir).await?;
+        if !report.conflicts.is_empty() {
+            // the conflicted copies are new files on disk, this pushes them right away
+            let next = self.mirror_pass(folder, dir).await?;
+            report.pushed.extend(next.pushed);
+        }
+        Ok(report)
+    }
+
+    async fn mirror_pass(&self, folder: Uuid, dir: &Path) -> LbResult<MirrorReport> {
+        let state_path = dir.join(MIRROR_STATE_FILE);
+        let (mut state, before) = match fs::read(&state_path) {
+            Ok(state) => {
+                let state = serde_json::from_slice::<MirrorState>(&state).map_unexpected()?;
+                (state.clone(), Some(state))
+            }
+            // written on the first pass even if there's nothing in it, to tie the directory to
+            // the folder
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                (MirrorState { folder, ..Default::default() }, None)
+            }
+            Err(err) => return Err(err.into()),
+        };
+        if state.folder != folder {
+            return Err(LbErrKind::DiskPathTaken.into());
+        }
+
+        let folder_path = self.get_path_by_id(folder).await?;
+        let remotes = self.mirror_remote_files(folder).await?;
+        let locals = local_files(dir)?;
+
+        let paths: BTreeSet<String> = state
+            .files
+            .keys()
+            .chain(remotes.keys())
+            .chain(locals.keys())
+            .cloned()
+            .collect();
+
+        // a folder deleted from disk comes back if anything in it was written in lockbook meanwhile
+        let remotely_written: BTreeSet<&String> = remotes
+            .iter()
+            .filter(|(path, remote)| {
+                remote_changed(state.files.get(*path).copied(), Some(**remote))
+            })
+            .map(|(path, _)| path)
+            .collect();
+
+        let mut report = MirrorReport::default();
+        let mut local_deletions = vec![];
+
+        // parents sort before their children, so folders exist by the time their contents arrive
+        for path in paths {
+            let base = state.files.get(&path).copied();
+            let remote = remotes.get(&path).copied();
+            let local = locals.get(&path).copied();
+            let disk_path = disk_path(dir, &path);
+
+            let remote_changed = remote_changed(base, remote);
+            let local_changed = match (base, local) {
+                // a folder's mtime changes with its contents, which are mirrored on their own
+                (Some(base), Some(local)) => {
+                    base.is_folder != local.is_folder
+                        || (!local.is_folder && base.mtime != local.mtime)
+                }
+                (None, None) => false,
+                _ => true,
+            };
+
+            let outcome = match (remote_changed, local_changed, remote, local) {
+                (false, false, _, _) => Outcome::Unchanged,
+
+                // changed in lockbook, which wins over a deletion from disk
+                (true, false, Some(remote), _) | (true, true, Some(remote), None) => {
+                    Outcome::Pull(remote)
+                }
+                (true, false, None, Some(_)) => Outcome::DeleteLocal,
+
+                // changed on disk, which wins over a deletion from lockbook
+                (false, true, _, Some(local)) | (true, true, None, Some(local)) => {
+                    Outcome::Push(remote, local)
+                }
+                (false, true, Some(remote), None) => {
+                    let prefix = format!("{path}/");
+                    if remote.is_folder
+                        && remotely_written
+                            .range::<&String, _>(&prefix..)
+                            .next()
+                            .is_some_and(|written| written.starts_with(&prefix))
+                    {
+                        Outcome::Pull(remote)
+                    } else {
+                        Outcome::DeleteRemote(remote)
+                    }
+                }
+
+                (true, true, Some(remote), Some(local)) => {
+                    if remote.is_folder && local.is_folder
+                        || !remote.is_folder
+                            && !local.is_folder
+                            && self.read_document(remote.id, false).await?
+                                == fs::read(&disk_path).map_err(LbErr::from)?
+                    {
+                        Outcome::Agreed(remote)
+                    } else {
+                        Outcome::Conflict(remote)
+                    }
+                }
+                (_, _, None, None) => Outcome::Gone,
+            };
+
+            match outcome {
+                Outcome::Unchanged => {}
+                Outcome::Gone => {
+                    state.files.remove(&path);
+                }
+                Outcome::Agreed(remote) => {
+                    state.files.insert(path, mirrored(remote, &disk_path)?);
+                }
+                Outcome::Pull(remote) => {
+                    self.pull(remote, &disk_path).await?;
+                    state
+                        .files
+                        .insert(path.clone(), mirrored(remote, &disk_path)?);
+                    report.pulled.push(path);
+                }
+                Outcome::Push(remote, local) => {
+                    let remote = self
+                        .push(&folder_path, &path, remote, local, &disk_path)
+                        .await?;
+                    state
+                        .files
+                        .insert(path.clone(), mirrored(remote, &disk_path)?);
+                    report.pushed.push(path);
+                }
+                Outcome::Conflict(remote) => {
+                    fs::rename(&disk_path, conflicted_copy(&disk_path)).map_err(LbErr::from)?;
+                    self.pull(remote, &disk_path).await?;
+                    state
+                        .files
+                        .insert(path.clone(), mirrored(remote, &disk_path)?);
+                    report.conflicts.push(path);
+                }
+                Outcome::DeleteLocal => {
+                    local_deletions.push((path.clone(), disk_path));
+                    state.files.remove(&path);
+                }
+                Outcome::DeleteRemote(remote) => {
+                    match self.delete(&remote.id).await {
+                        Ok(()) => {}
+                        // already gone with a deleted parent
+                        Err(err) if err.kind == LbErrKind::FileNonexistent => {}
+                        Err(err) => return Err(err),
+                    }
+                    state.files.remove(&path);
+                    report.deleted_remotely.push(path);
+                }
+            }
+        }
+
+        // children before their parents; folders that still have something in them stay
+        for (path, disk_path) in local_deletions.into_iter().rev() {
+            let result = if disk_path.is_dir() {
+                fs::remove_dir(&disk_path)
+            } else {
+                fs::remove_file(&disk_path)
+            };
+            if result.is_ok() {
+                report.deleted_locally.push(path);
+            }
+        }
+
+        // left alone when nothing changed, so someone watching the directory isn't woken up by
+        // every pass
+        if before.as_ref() != Some(&state) {
+            let tmp = dir.join(format!("{MIRROR_STATE_FILE}.tmp"));
+            fs::write(&tmp, serde_json::to_vec(&state).map_unexpected()?).map_err(LbErr::from)?;
+            fs::rename(&tmp, &state_path).map_err(LbErr::from)?;
+        }
+
+        Ok(report)
+    }
+
+    async fn mirror_remote_files(&self, folder: Uuid) -> LbResult<HashMap<String, RemoteFile>> {
+        let tx = self.ro_tx().await;
+        let db = tx.db();
+
+        let mut tree = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+        let folder_path = tree.id_to_path(&folder, &self.keychain)?;
+
+        let mut files = HashMap::new();
+        for id in tree.descendants_using_links(&folder)? {
+            if tree.calculate_deleted(&id)? || tree.in_pending_share(&id)? {
+                continue;
+            }
+            let file = tree.find(&id)?;
+            if file.is_link() {
+                continue;
+            }
+            let (is_folder, hmac) = (file.is_folder(), file.document_hmac().copied());
+
+            let path = tree.id_to_path(&id, &self.keychain)?;
+            let path = path
+                .strip_prefix(&folder_path)
+                .unwrap_or(&path)
+                .trim_end_matches('/')
+                .to_string();
+            if path.split('/').any(ignored) {
+                continue;
+            }
+            files.insert(path, RemoteFile { id, is_folder, hmac });
+        }
+
+        Ok(files)
+    }
+
+    async fn pull(&self, remote: RemoteFile, disk_path: &Path) -> LbResult<()> {
+        if remote.is_folder {
+            fs::create_dir_all(disk_path).map_err(LbErr::from)?;
+        } else {
+            if let Some(parent) = disk_path.parent() {
+                fs::create_dir_all(parent).map_err(LbErr::from)?;
+            }
+            let content = self.read_document(remote.id, false).await?;
+            fs::write(disk_path, content).map_err(LbErr::from)?;
+        }
+        Ok(())
+    }
+
+    async fn push(
+        &self, folder_path: &str, path: &str, remote: Option<RemoteFile>, local: LocalFile,
+        disk_path: &Path,
+    ) -> LbResult<RemoteFile> {
+        let id = match remote {
+            Some(remote) if remote.is_folder == local.is_folder => remote.id,
+            _ => {
+                if let Some(remote) = remote {
+                    self.delete(&remote.id).await?;
+                }
+                let suffix = if local.is_folder { "/" } else { "" };
+                self.create_at_path(&format!("{folder_path}{path}{suffix}"))
+                    .await?
+                    .id
+            }
+        };
+
+        let hmac = if local.is_folder {
+            None
+        } else {
+            let content = fs::read(disk_path).map_err(LbErr::from)?;
+            // fails if lockbook changed since we looked, rather than overwriting it
+            let expected = remote
+                .filter(|remote| remote.id == id)
+                .and_then(|remote| remote.hmac);
+            Some(self.safe_write(id, expected, content).await?)
+        };
+
+        Ok(RemoteFile { id, is_folder: local.is_folder, hmac })
+    }
+}
+
+enum Outcome {
+    Unchanged,
+    Gone,
+    Agreed(RemoteFile),
+    Pull(RemoteFile),
+    Push(Option<RemoteFile>, LocalFile),
+    Conflict(RemoteFile),
+    DeleteLocal,
+    DeleteRemote(RemoteFile),
+}
+
+fn remote_changed(base: Option<MirroredFile>, remote: Option<RemoteFile>) -> bool {
+    match (base, remote) {
+        (Some(base), Some(remote)) => base.id != remote.id || base.hmac != remote.hmac,
+        (None, None) => false,
+        _ => true,
+    }
+}
+
+fn local_files(dir: &Path) -> LbResult<HashMap<String, LocalFile>> {
+    let mut files = HashMap::new();
+    let mut to_visit = vec![dir.to_path_buf()];
+    while let Some(current) = to_visit.pop() {
+        for entry in fs::read_dir(&current).map_err(LbErr::from)? {
+            let entry = entry.map_err(LbErr::from)?;
+            let disk_path = entry.path();
+            if ignored(&entry.file_name().to_string_lossy()) {
+                continue;
+            }
+
+            let metadata = entry.metadata().map_err(LbErr::from)?;
+            if metadata.is_dir() {
+                to_visit.push(disk_path.clone());
+            } else if !metadata.is_file() {
+                continue;
+            }
+
+            let path = disk_path
+                .strip_prefix(dir)
+                .map_unexpected()?
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            files.insert(
+                path,
+                LocalFile { is_folder: metadata.is_dir(), mtime: mtime(&disk_path)? },
+            );
+        }
+    }
+    Ok(files)
+}
+
+/// Hidden files and editor backups stay on whichever side they're on. This covers vim's swap files
+/// and the mirror's own state.
+fn ignored(name: &str) -> bool {
+    name.starts_with('.') || name.ends_with('~')
+}
+
+fn disk_path(dir: &Path, path: &str) -> PathBuf {
+    path.split('/')
+        .fold(dir.to_path_buf(), |disk_path, name| disk_path.join(name))
+}
+
+fn mtime(disk_path: &Path) -> LbResult<u64> {
+    let modified = fs::metadata(disk_path)
+        .and_then(|metadata| metadata.modified())
+        .map_err(LbErr::from)?;
+    Ok(modified
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64)
+}
+
+fn mirrored(remote: RemoteFile, disk_path: &Path) -> LbResult<MirroredFile> {
+    Ok(MirroredFile {
+        id: remote.id,
+        is_folder: remote.is_folder,
+        hmac: remote.hmac,
+        mtime: mtime(disk_path)?,
+    })
+}
+
+/// `notes.md` becomes `notes (conflicted copy 1700000000000).md`
+fn conflicted_copy(disk_path: &Path) -> PathBuf {
+    let stem = disk_path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match disk_path.extension() {
+        Some(ext) => format!("{stem} (conflicted copy {}).{}", get_time().0, ext.to_string_lossy()),
+        None => format!("{stem} (conflicted copy {})", get_time().0),
+    };
+    disk_path.with_file_name(name)
+}
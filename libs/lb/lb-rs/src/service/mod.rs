@@ -7,16 +7,27 @@ pub mod account;
 pub mod activity;
 pub mod admin;
 pub mod billing;
+pub mod collab;
+pub mod conflicts;
 pub mod debug;
 pub mod documents;
 pub mod events;
 pub mod file;
+pub mod history;
 pub mod import_export;
+#[cfg(feature = "importers")]
+pub mod importers;
 pub mod integrity;
 pub mod keychain;
 pub mod logging;
+pub mod mirror;
 pub mod path;
+pub mod publish;
+#[cfg(feature = "render")]
+pub mod render;
 pub mod search;
 pub mod share;
 pub mod sync;
+pub mod sync_scheduler;
+pub mod takeout;
 pub mod usage;
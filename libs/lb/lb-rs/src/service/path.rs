@@ -3,6 +3,7 @@ use crate::model::file::File;
 use crate::model::path_ops::Filter;
 use crate::model::tree_like::TreeLike;
 use crate::Lb;
+use std::collections::HashSet;
 use uuid::Uuid;
 
 impl Lb {
@@ -11,6 +12,9 @@ impl Lb {
         let mut tx = self.begin_tx().await;
         let db = tx.db();
 
+        // paths may create any missing parent folders along the way
+        let existing: HashSet<Uuid> = db.local_metadata.get().keys().copied().collect();
+
         let mut tree = (&db.base_metadata)
             .to_staged(&mut db.local_metadata)
             .to_lazy();
@@ -21,7 +25,11 @@ impl Lb {
 
         let ui_file = tree.decrypt(&self.keychain, &id, &db.pub_key_lookup)?;
 
-        self.events.meta_changed(*root);
+        for created in db.local_metadata.get().keys() {
+            if !existing.contains(created) {
+                self.events.file_created(*created);
+            }
+        }
 
         Ok(ui_file)
     }
@@ -31,6 +39,9 @@ impl Lb {
         let mut tx = self.begin_tx().await;
         let db = tx.db();
 
+        // paths may create any missing parent folders along the way
+        let existing: HashSet<Uuid> = db.local_metadata.get().keys().copied().collect();
+
         let mut tree = (&db.base_metadata)
             .to_staged(&mut db.local_metadata)
             .to_lazy();
@@ -41,7 +52,11 @@ impl Lb {
 
         let ui_file = tree.decrypt(&self.keychain, &id, &db.pub_key_lookup)?;
 
-        self.events.meta_changed(*root);
+        for created in db.local_metadata.get().keys() {
+            if !existing.contains(created) {
+                self.events.file_created(*created);
+            }
+        }
 
         Ok(ui_file)
     }
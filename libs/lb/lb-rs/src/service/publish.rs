@@ -0,0 +1,96 @@
+use crate::io::network::ApiError;
+use crate::model::api::{PublishError, PublishRequest, UnpublishError, UnpublishRequest};
+use crate::model::clock::get_time;
+use crate::model::errors::{core_err_unexpected, LbErrKind, LbResult, Unexpected};
+use crate::model::publication::{publish_link, Publication, PublishedFile};
+use crate::model::symkey;
+use crate::Lb;
+use std::time::Duration;
+use uuid::Uuid;
+
+impl Lb {
+    /// Publishes a copy of a document, or of a folder and every document in it, that anyone with
+    /// the returned link can read without an account. The key is only in the link's fragment, so
+    /// the server never sees what's published. Publishing a file again replaces its old link.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn publish(&self, id: Uuid, expires_in: Option<Duration>) -> LbResult<String> {
+        let account = self.get_account()?;
+        let file = self.get_file_by_id(id).await?;
+
+        let mut files = vec![];
+        if file.is_document() {
+            let content = self.read_document(id, false).await?;
+            files.push(PublishedFile::new(file.name.clone(), &content));
+        } else {
+            let folder_path = self.get_path_by_id(id).await?;
+            for child in self.get_and_get_children_recursively(&id).await? {
+                if !child.is_document() {
+                    continue;
+                }
+                let path = self.get_path_by_id(child.id).await?;
+                let path = path.strip_prefix(&folder_path).unwrap_or(&path).to_string();
+                let content = self.read_document(child.id, false).await?;
+                files.push(PublishedFile::new(path, &content));
+            }
+            files.sort_by(|a, b| a.path.cmp(&b.path));
+        }
+
+        let publication = Publication { name: file.name, files };
+        let key = symkey::generate_key();
+        let content = symkey::encrypt(&key, &serde_json::to_vec(&publication).map_unexpected()?)?;
+        let expires_at =
+            expires_in.map(|expires_in| get_time().0 as u64 + expires_in.as_millis() as u64);
+
+        let link_id = self
+            .client
+            .request(account, PublishRequest { file_id: id, content, expires_at })
+            .await
+            .map_err(|err| match err {
+                ApiError::Endpoint(PublishError::FileNonexistent) => LbErrKind::FileNonexistent,
+                ApiError::Endpoint(PublishError::NotPermissioned) => {
+                    LbErrKind::InsufficientPermission
+                }
+                ApiError::Endpoint(PublishError::TooLarge) => LbErrKind::PublicationTooLarge,
+                ApiError::Endpoint(PublishError::TooMany) => LbErrKind::TooManyPublications,
+                ApiError::Endpoint(PublishError::UsageIsOverDataCap) => {
+                    LbErrKind::UsageIsOverDataCap
+                }
+                ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+                ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+                ApiError::AccountSuspended => LbErrKind::AccountSuspended,
+                ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+                _ => core_err_unexpected(err),
+            })?
+            .link_id;
+
+        Ok(publish_link(&account.api_url, link_id, &key))
+    }
+
+    /// Takes down the link made by the last [Lb::publish] of this file
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn unpublish(&self, id: Uuid) -> LbResult<()> {
+        let account = self.get_account()?;
+
+        self.client
+            .request(account, UnpublishRequest { file_id: id })
+            .await
+            .map_err(|err| {
+                match err {
+                    ApiError::Endpoint(UnpublishError::NotPublished) => {
+                        LbErrKind::PublicationNonexistent
+                    }
+                    ApiError::Endpoint(UnpublishError::NotPermissioned) => {
+                        LbErrKind::InsufficientPermission
+                    }
+                    ApiError::SendFailed(_) => LbErrKind::ServerUnreachable,
+                    ApiError::RateLimited { .. } => LbErrKind::RateLimited,
+                    ApiError::AccountReadOnly => LbErrKind::AccountReadOnly,
+                    ApiError::AccountSuspended => LbErrKind::AccountSuspended,
+                    ApiError::ClientUpdateRequired => LbErrKind::ClientUpdateRequired,
+                    _ => core_err_unexpected(err),
+                }
+                .into()
+            })
+    }
+}
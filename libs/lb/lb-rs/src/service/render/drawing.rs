@@ -0,0 +1,127 @@
+//! Drawings are stored with a light and a dark color for each stroke, zoom state and references to
+//! images by id, none of which anything but lockbook understands. Flattening picks the theme's
+//! colors, drops the zoom and inlines the images, leaving a plain SVG.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use usvg::fontdb::Database;
+use usvg::Options;
+use uuid::Uuid;
+
+use super::Theme;
+use crate::model::svg::buffer::Buffer;
+use crate::model::svg::element::Element;
+
+/// space left around the strokes, so the widest ones aren't cut off at the edge
+const PADDING: f32 = 20.0;
+
+/// The images a drawing refers to, which need to be read before it can be flattened
+pub(super) fn image_ids(buffer: &Buffer) -> Vec<Uuid> {
+    buffer
+        .weak_images
+        .values()
+        .map(|image| image.href)
+        .collect()
+}
+
+/// `images` are data urls of the drawing's images by id. Images missing from it are left out.
+pub(super) fn flatten(buffer: &Buffer, theme: Theme, images: &HashMap<Uuid, String>) -> String {
+    // elements are kept in paint order, and images say where in it they go
+    let mut layers: Vec<(usize, String)> = vec![];
+    for (index, element) in buffer.elements.values().enumerate() {
+        let Element::Path(path) = element else {
+            continue;
+        };
+        let Some(stroke) = path.stroke else {
+            continue;
+        };
+        if path.deleted || path.data.len() < 2 {
+            continue;
+        }
+
+        let color = theme.pick(stroke.color);
+        let attributes = format!(
+            "stroke-width='{}' stroke='rgb({},{},{})' stroke-opacity='{}' fill='none' \
+             stroke-linecap='round' stroke-linejoin='round'",
+            stroke.width * path.transform.sx,
+            color.red,
+            color.green,
+            color.blue,
+            stroke.opacity * path.opacity,
+        );
+        // the data already has the element's transform applied, which only scales the width
+        let mut svg = String::new();
+        path.data
+            .to_svg(&mut svg, attributes, "".into(), "".into(), "".into());
+        layers.push((index, svg));
+    }
+
+    let strokes = layers
+        .iter()
+        .map(|(_, svg)| svg.as_str())
+        .collect::<String>();
+    let mut bounds = stroke_bounds(&strokes);
+
+    for image in buffer.weak_images.values() {
+        let Some(href) = images.get(&image.href) else {
+            continue;
+        };
+        bounds = union(bounds, Some((image.x, image.y, image.width, image.height)));
+        layers.push((
+            image.z_index,
+            format!(
+                "<image href='{href}' x='{}' y='{}' width='{}' height='{}' opacity='{}' \
+                 preserveAspectRatio='none'/>",
+                image.x, image.y, image.width, image.height, image.opacity
+            ),
+        ));
+    }
+    layers.sort_by_key(|(index, _)| *index);
+
+    let (x, y, width, height) = match bounds {
+        Some((x, y, width, height)) => {
+            (x - PADDING, y - PADDING, width + 2.0 * PADDING, height + 2.0 * PADDING)
+        }
+        None => (0.0, 0.0, 2.0 * PADDING, 2.0 * PADDING),
+    };
+    let (red, green, blue) = theme.background();
+
+    let mut svg = format!(
+        "<svg xmlns='http://www.w3.org/2000/svg' viewBox='{x} {y} {width} {height}' \
+         width='{width}' height='{height}'>"
+    );
+    let _ = write!(
+        svg,
+        "<rect x='{x}' y='{y}' width='{width}' height='{height}' fill='rgb({red},{green},{blue})'/>"
+    );
+    for (_, layer) in layers {
+        svg.push_str(&layer);
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// The area the strokes cover, including their width, as `(x, y, width, height)`
+fn stroke_bounds(strokes: &str) -> Option<(f32, f32, f32, f32)> {
+    let svg = format!("<svg xmlns='http://www.w3.org/2000/svg'>{strokes}</svg>");
+    let tree = usvg::Tree::from_str(&svg, &Options::default(), &Database::default()).ok()?;
+    if !tree.root().has_children() {
+        return None;
+    }
+    let bounds = tree.root().abs_stroke_bounding_box();
+    Some((bounds.x(), bounds.y(), bounds.width(), bounds.height()))
+}
+
+fn union(
+    a: Option<(f32, f32, f32, f32)>, b: Option<(f32, f32, f32, f32)>,
+) -> Option<(f32, f32, f32, f32)> {
+    match (a, b) {
+        (Some((ax, ay, aw, ah)), Some((bx, by, bw, bh))) => {
+            let (left, top) = (ax.min(bx), ay.min(by));
+            let (right, bottom) = ((ax + aw).max(bx + bw), (ay + ah).max(by + bh));
+            Some((left, top, right - left, bottom - top))
+        }
+        (a, b) => a.or(b),
+    }
+}
@@ -0,0 +1,73 @@
+use pulldown_cmark::{html, Event, Options, Parser, Tag};
+
+use super::Theme;
+
+/// The markdown extensions lockbook's editor renders
+pub(super) fn options() -> Options {
+    Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS
+}
+
+/// Markdown to html. `rewrite` gets the destination of each link and image and returns its
+/// replacement, or `None` to leave it alone.
+pub(super) fn markdown(text: &str, mut rewrite: impl FnMut(&str) -> Option<String>) -> String {
+    let events = Parser::new_ext(text, options()).map(|event| match event {
+        Event::Start(Tag::Link(kind, dest, title)) => {
+            let dest = rewrite(&dest).map(Into::into).unwrap_or(dest);
+            Event::Start(Tag::Link(kind, dest, title))
+        }
+        Event::Start(Tag::Image(kind, dest, title)) => {
+            let dest = rewrite(&dest).map(Into::into).unwrap_or(dest);
+            Event::Start(Tag::Image(kind, dest, title))
+        }
+        event => event,
+    });
+
+    let mut html = String::new();
+    html::push_html(&mut html, events);
+    html
+}
+
+/// A whole page around rendered content. `home` links back to the site's index, unless this is it.
+pub(super) fn page(title: &str, body: &str, theme: Theme, home: Option<&str>) -> String {
+    let (bg, fg, link) = (hex(theme.background()), hex(theme.foreground()), hex(theme.link()));
+    let nav = match home {
+        Some(home) => format!("<nav><a href=\"{}\">Index</a></nav>\n", escape(home)),
+        None => String::new(),
+    };
+    format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">
+<title>{}</title>
+<style>
+body {{ max-width: 48em; margin: 2em auto; padding: 0 1em; font-family: sans-serif; \
+line-height: 1.5; background: {bg}; color: {fg}; }}
+a {{ color: {link}; }}
+img {{ max-width: 100%; }}
+pre, code {{ font-family: monospace; }}
+pre {{ overflow-x: auto; padding: 0.5em; border: 1px solid {fg}33; }}
+blockquote {{ margin-left: 0; padding-left: 1em; border-left: 3px solid {fg}55; }}
+table {{ border-collapse: collapse; }}
+th, td {{ border: 1px solid {fg}55; padding: 0.25em 0.5em; }}
+</style>
+</head>
+<body>
+{nav}{body}</body>
+</html>
+",
+        escape(title)
+    )
+}
+
+pub(super) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn hex((red, green, blue): (u8, u8, u8)) -> String {
+    format!("#{red:02x}{green:02x}{blue:02x}")
+}
@@ -0,0 +1,415 @@
+//! Exports that other programs can show as they are, rather than the files lockbook stores: a
+//! folder as a static html site, or a single document as a pdf.
+
+mod drawing;
+mod html;
+mod pdf;
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use uuid::Uuid;
+
+use crate::model::errors::{LbErrKind, LbResult, Warning};
+use crate::model::path_ops::percent_decode;
+use crate::model::svg::buffer::Buffer;
+use crate::model::svg::element::DynamicColor;
+use crate::service::import_export::ExportFileInfo;
+use crate::Lb;
+
+/// Which of a drawing's colors to use, and the colors of everything around them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Theme {
+    #[default]
+    Light,
+    Dark,
+}
+
+impl Theme {
+    fn pick(self, color: DynamicColor) -> usvg::Color {
+        match self {
+            Theme::Light => color.light,
+            Theme::Dark => color.dark,
+        }
+    }
+
+    fn background(self) -> (u8, u8, u8) {
+        match self {
+            Theme::Light => (255, 255, 255),
+            Theme::Dark => (30, 30, 30),
+        }
+    }
+
+    fn foreground(self) -> (u8, u8, u8) {
+        match self {
+            Theme::Light => (31, 31, 31),
+            Theme::Dark => (230, 230, 230),
+        }
+    }
+
+    fn link(self) -> (u8, u8, u8) {
+        match self {
+            Theme::Light => (26, 95, 180),
+            Theme::Dark => (120, 174, 237),
+        }
+    }
+}
+
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "light" => Ok(Theme::Light),
+            "dark" => Ok(Theme::Dark),
+            other => Err(format!("unknown theme {other}, expected light or dark")),
+        }
+    }
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Theme::Light => write!(f, "light"),
+            Theme::Dark => write!(f, "dark"),
+        }
+    }
+}
+
+impl Lb {
+    /// Writes a folder as a static site into `dest`, creating it if needed. Markdown becomes html
+    /// pages whose links and images point at the other exported files, drawings become plain svgs
+    /// in `theme`'s colors and everything else is copied as is. Unless the folder has its own
+    /// `index.md`, an `index.html` listing every page is added.
+    #[instrument(level = "debug", skip(self, update_status), err(Debug))]
+    pub async fn export_site<F: Fn(ExportFileInfo)>(
+        &self, id: Uuid, dest: PathBuf, theme: Theme, update_status: &Option<F>,
+    ) -> LbResult<()> {
+        if dest.is_file() {
+            return Err(LbErrKind::DiskPathInvalid.into());
+        }
+
+        let site = self.site(id).await?;
+        fs::create_dir_all(&dest)?;
+
+        for (path, &id) in &site.files {
+            let content = self.read_document(id, false).await?;
+            let out = match extension(path).as_str() {
+                "md" => {
+                    let page = page(path);
+                    let body = html::markdown(&String::from_utf8_lossy(&content), |dest| {
+                        site.href(path, dest)
+                    });
+                    let home = (page != "index.html").then(|| relative(&page, "index.html"));
+                    let html = html::page(&title(path), &body, theme, home.as_deref());
+                    write(&dest, &page, html.as_bytes())?
+                }
+                "svg" => {
+                    let svg = self.flatten_drawing(&content, theme).await?;
+                    write(&dest, path, svg.as_bytes())?
+                }
+                _ => write(&dest, path, &content)?,
+            };
+
+            if let Some(update_status) = update_status {
+                update_status(ExportFileInfo {
+                    disk_path: out,
+                    lockbook_path: format!("{}{path}", site.root),
+                });
+            }
+        }
+
+        if !site.files.contains_key("index.md") {
+            write(&dest, "index.html", site.index(theme).as_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a document as a pdf at `dest`. Drawings get a page of their own size; anything else
+    /// is laid out as markdown, with the images it embeds that are in lockbook drawn inline.
+    /// Characters the pdf's fonts don't have print as `?` and are returned as a warning.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn export_pdf(
+        &self, id: Uuid, dest: PathBuf, theme: Theme,
+    ) -> LbResult<Vec<Warning>> {
+        let file = self.get_file_by_id(id).await?;
+        if !file.is_document() {
+            return Err(LbErrKind::FileNotDocument.into());
+        }
+        if dest.is_dir() {
+            return Err(LbErrKind::DiskPathInvalid.into());
+        }
+
+        let content = self.read_document(id, false).await?;
+        let (pdf, unprintable) = if extension(&file.name) == "svg" {
+            pdf::drawing(&self.flatten_drawing(&content, theme).await?, theme)
+        } else {
+            let text = String::from_utf8_lossy(&content);
+            let from = self.get_path_by_id(id).await?;
+
+            let mut embeds = HashMap::new();
+            for dest in pdf::image_dests(&text) {
+                let image = match link_target(&from, &dest) {
+                    Some(Target::Id(id)) => self.get_file_by_id(id).await,
+                    Some(Target::Path(path)) => self.get_by_path(&path).await,
+                    None => continue,
+                };
+                // images that were moved or deleted leave their alt text behind
+                let Ok(image) = image else {
+                    continue;
+                };
+                if !image.is_document() {
+                    continue;
+                }
+                let data = self.read_document(image.id, false).await?;
+                let embed = match extension(&image.name).as_str() {
+                    "svg" => pdf::Embed::Drawing(self.flatten_drawing(&data, theme).await?),
+                    "png" => pdf::Embed::Png(data),
+                    "jpg" | "jpeg" => pdf::Embed::Jpeg(data),
+                    _ => continue,
+                };
+                embeds.insert(dest, embed);
+            }
+            pdf::markdown(&text, theme, &embeds)
+        };
+
+        fs::write(dest, pdf)?;
+        let mut warnings = vec![];
+        if !unprintable.is_empty() {
+            warnings.push(Warning::UnprintableCharacters(id, unprintable));
+        }
+        Ok(warnings)
+    }
+
+    async fn flatten_drawing(&self, content: &[u8], theme: Theme) -> LbResult<String> {
+        let buffer = Buffer::new(&String::from_utf8_lossy(content));
+
+        let mut images = HashMap::new();
+        for id in drawing::image_ids(&buffer) {
+            let Ok(file) = self.get_file_by_id(id).await else {
+                continue;
+            };
+            let mime = match extension(&file.name).as_str() {
+                "png" => "image/png",
+                "jpg" | "jpeg" => "image/jpeg",
+                "gif" => "image/gif",
+                "webp" => "image/webp",
+                "svg" => "image/svg+xml",
+                _ => "application/octet-stream",
+            };
+            let data = self.read_document(id, false).await?;
+            images.insert(id, format!("data:{mime};base64,{}", base64::encode(data)));
+        }
+
+        Ok(drawing::flatten(&buffer, theme, &images))
+    }
+
+    async fn site(&self, id: Uuid) -> LbResult<Site> {
+        let file = self.get_file_by_id(id).await?;
+        let root = if file.is_folder() {
+            self.get_path_by_id(id).await?
+        } else {
+            self.get_path_by_id(file.parent).await?
+        };
+
+        let mut site =
+            Site { name: file.name.clone(), root, files: BTreeMap::new(), paths: HashMap::new() };
+        let files = if file.is_folder() {
+            self.get_and_get_children_recursively(&id).await?
+        } else {
+            vec![file]
+        };
+        for file in files.into_iter().filter(|file| file.is_document()) {
+            let path = self.get_path_by_id(file.id).await?;
+            let Some(path) = path.strip_prefix(&site.root) else {
+                continue;
+            };
+            site.files.insert(path.to_string(), file.id);
+            site.paths.insert(file.id, path.to_string());
+        }
+        Ok(site)
+    }
+}
+
+/// The documents being exported, by their path relative to the exported folder
+struct Site {
+    name: String,
+    /// lockbook path of the exported folder, ending in `/`
+    root: String,
+    files: BTreeMap<String, Uuid>,
+    paths: HashMap<Uuid, String>,
+}
+
+impl Site {
+    /// Where a link from the page for `from` to `dest` should point, if `dest` is part of the
+    /// site. Links elsewhere, including to other lockbook files, are left alone.
+    fn href(&self, from: &str, dest: &str) -> Option<String> {
+        let (dest, fragment) = match dest.split_once('#') {
+            Some((dest, fragment)) if !dest.is_empty() => (dest, Some(fragment)),
+            _ => (dest, None),
+        };
+        let to = match link_target(&format!("{}{from}", self.root), dest)? {
+            Target::Id(id) => self.paths.get(&id)?,
+            Target::Path(path) => {
+                let path = path.strip_prefix(&self.root)?;
+                self.files.get_key_value(path)?.0
+            }
+        };
+
+        let mut href = relative(&page(from), &page(to));
+        if let Some(fragment) = fragment {
+            href.push('#');
+            href.push_str(fragment);
+        }
+        Some(href)
+    }
+
+    /// A page listing every page and drawing, nested by folder
+    fn index(&self, theme: Theme) -> String {
+        let mut body = format!("<h1>{}</h1>\n<ul>\n", html::escape(&self.name));
+        let mut open: Vec<&str> = vec![];
+        for path in self.files.keys() {
+            if !matches!(extension(path).as_str(), "md" | "svg") {
+                continue;
+            }
+            let mut folders = path.split('/').collect::<Vec<_>>();
+            folders.pop();
+
+            let common = open
+                .iter()
+                .zip(&folders)
+                .take_while(|(a, b)| a == b)
+                .count();
+            for _ in common..open.len() {
+                body.push_str("</ul></li>\n");
+            }
+            open.truncate(common);
+            for folder in &folders[common..] {
+                body.push_str(&format!("<li>{}\n<ul>\n", html::escape(folder)));
+                open.push(folder);
+            }
+
+            body.push_str(&format!(
+                "<li><a href=\"{}\">{}</a></li>\n",
+                html::escape(&relative("index.html", &page(path))),
+                html::escape(&title(path))
+            ));
+        }
+        for _ in 0..open.len() {
+            body.push_str("</ul></li>\n");
+        }
+        body.push_str("</ul>\n");
+
+        html::page(&self.name, &body, theme, None)
+    }
+}
+
+enum Target {
+    Id(Uuid),
+    Path(String),
+}
+
+/// What a link in the document at lockbook path `from` points to, the way lockbook's editor
+/// follows it: `lb://` links by id, paths starting with `/` from the root and anything else from
+/// the document itself, so a sibling is `../sibling.md`. Urls and anchors are `None`.
+fn link_target(from: &str, dest: &str) -> Option<Target> {
+    if let Some(id) = dest.strip_prefix("lb://") {
+        return Uuid::parse_str(id).ok().map(Target::Id);
+    }
+    if dest.is_empty()
+        || dest.starts_with('#')
+        || dest.contains("://")
+        || dest.starts_with("mailto:")
+    {
+        return None;
+    }
+
+    let dest = percent_decode(dest);
+    let mut components = if dest.starts_with('/') {
+        vec![]
+    } else {
+        from.split('/')
+            .filter(|component| !component.is_empty())
+            .collect()
+    };
+    for component in dest.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop()?;
+            }
+            component => components.push(component),
+        }
+    }
+    Some(Target::Path(format!("/{}", components.join("/"))))
+}
+
+/// The exported name of a document: markdown becomes html, everything else keeps its name
+fn page(path: &str) -> String {
+    match path.strip_suffix(".md") {
+        Some(stem) => format!("{stem}.html"),
+        None => path.to_string(),
+    }
+}
+
+/// An href from one exported file to another, both relative to the site's root
+fn relative(from: &str, to: &str) -> String {
+    let mut from = from.split('/').collect::<Vec<_>>();
+    from.pop();
+    let to = to.split('/').collect::<Vec<_>>();
+
+    let common = from
+        .iter()
+        .zip(&to)
+        .take_while(|(a, b)| a == b)
+        .count()
+        .min(to.len() - 1);
+    let mut href = "../".repeat(from.len() - common);
+    let rest = to[common..]
+        .iter()
+        .map(|component| percent_encode(component))
+        .collect::<Vec<_>>();
+    href.push_str(&rest.join("/"));
+    href
+}
+
+fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            ' ' | '"' | '#' | '%' | '<' | '>' | '?' => {
+                encoded.push_str(&format!("%{:02X}", c as u8))
+            }
+            c => encoded.push(c),
+        }
+    }
+    encoded
+}
+
+fn title(path: &str) -> String {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    match name.rsplit_once('.') {
+        Some((stem, _)) if !stem.is_empty() => stem.to_string(),
+        _ => name.to_string(),
+    }
+}
+
+fn extension(name: &str) -> String {
+    Path::new(name)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+fn write(dest: &Path, path: &str, content: &[u8]) -> LbResult<PathBuf> {
+    let out = dest.join(path);
+    if let Some(parent) = out.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&out, content)?;
+    Ok(out)
+}
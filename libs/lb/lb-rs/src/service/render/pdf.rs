@@ -0,0 +1,858 @@
+//! A small PDF writer for single documents. Text is set in the standard fonts every PDF reader
+//! has, so nothing needs embedding but images, at the cost of characters outside Windows-1252,
+//! which print as `?` and are handed back so callers can say so.
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::Write;
+use std::mem;
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use pdf_writer::types::{LineCapStyle, LineJoinStyle};
+use pdf_writer::{Content, Filter, Finish, Name, Pdf, Rect, Ref, Str};
+use pulldown_cmark::{Event, HeadingLevel, Parser, Tag};
+use usvg::fontdb::Database;
+use usvg::tiny_skia_path::PathSegment;
+use usvg::{Group, Node, Options, Paint};
+
+use super::{html, Theme};
+
+/// A4, in points
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 56.0;
+const TEXT_SIZE: f32 = 11.0;
+const CODE_SIZE: f32 = 9.5;
+/// how far list items and quotes are indented
+const INDENT: f32 = 18.0;
+/// points per pixel, so images and drawings come out at the size they'd be on screen
+const PX: f32 = 0.75;
+
+/// An image a document embeds, read ahead of time since rendering doesn't touch lockbook
+pub(super) enum Embed {
+    /// flattened, see [super::drawing::flatten]
+    Drawing(String),
+    Jpeg(Vec<u8>),
+    Png(Vec<u8>),
+}
+
+/// The destinations of the images `text` embeds, which are the keys of the embeds
+/// [markdown] expects
+pub(super) fn image_dests(text: &str) -> Vec<String> {
+    Parser::new_ext(text, html::options())
+        .filter_map(|event| match event {
+            Event::Start(Tag::Image(_, dest, _)) => Some(dest.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// A pdf and the characters in it that printed as `?`
+pub(super) type Rendered = (Vec<u8>, Vec<char>);
+
+/// Markdown laid out on A4 pages. Images missing from `embeds` are replaced by their alt text.
+pub(super) fn markdown(text: &str, theme: Theme, embeds: &HashMap<String, Embed>) -> Rendered {
+    let mut writer = Writer::new(theme, PAGE_WIDTH, PAGE_HEIGHT);
+    let mut layout = Layout::default();
+    // when inside an embedded image, its alt text is skipped
+    let mut embedding = false;
+
+    for event in Parser::new_ext(text, html::options()) {
+        match event {
+            Event::Start(Tag::Image(_, dest, _)) => {
+                if let Some(embed) = embeds.get(dest.as_ref()) {
+                    layout.flush(&mut writer);
+                    writer.embed(embed, layout.indent);
+                    embedding = true;
+                }
+            }
+            Event::End(Tag::Image(..)) => embedding = false,
+            _ if embedding => {}
+
+            Event::Start(Tag::Heading(level, ..)) => {
+                layout.flush(&mut writer);
+                layout.size = match level {
+                    HeadingLevel::H1 => 20.0,
+                    HeadingLevel::H2 => 16.0,
+                    HeadingLevel::H3 => 14.0,
+                    _ => 12.0,
+                };
+                layout.bold += 1;
+            }
+            Event::End(Tag::Heading(..)) => {
+                layout.flush(&mut writer);
+                layout.size = TEXT_SIZE;
+                layout.bold -= 1;
+            }
+            Event::End(Tag::Paragraph) => layout.flush(&mut writer),
+            Event::Start(Tag::BlockQuote) => {
+                layout.flush(&mut writer);
+                layout.indent += INDENT;
+                layout.quotes += 1;
+            }
+            Event::End(Tag::BlockQuote) => {
+                layout.flush(&mut writer);
+                layout.indent -= INDENT;
+                layout.quotes -= 1;
+            }
+            Event::Start(Tag::CodeBlock(_)) => {
+                layout.flush(&mut writer);
+                layout.code = Some(String::new());
+            }
+            Event::End(Tag::CodeBlock(_)) => {
+                let code = layout.code.take().unwrap_or_default();
+                writer.code(&code, layout.indent);
+            }
+            Event::Start(Tag::List(start)) => {
+                layout.flush(&mut writer);
+                layout.lists.push(start);
+            }
+            Event::End(Tag::List(_)) => {
+                layout.flush(&mut writer);
+                layout.lists.pop();
+            }
+            Event::Start(Tag::Item) => {
+                layout.flush(&mut writer);
+                layout.marker = Some(match layout.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}.", *n - 1)
+                    }
+                    _ => "•".to_string(),
+                });
+                layout.indent += INDENT;
+            }
+            Event::End(Tag::Item) => {
+                layout.flush(&mut writer);
+                layout.indent -= INDENT;
+            }
+            Event::Start(Tag::TableHead) => layout.bold += 1,
+            Event::End(Tag::TableHead) => {
+                layout.flush(&mut writer);
+                layout.bold -= 1;
+            }
+            Event::Start(Tag::TableCell) if !layout.spans.is_empty() => layout.push("  |  "),
+            Event::End(Tag::TableRow) | Event::End(Tag::Table(_)) => layout.flush(&mut writer),
+            Event::Start(Tag::Strong) => layout.bold += 1,
+            Event::End(Tag::Strong) => layout.bold -= 1,
+            Event::Start(Tag::Emphasis) => layout.italic += 1,
+            Event::End(Tag::Emphasis) => layout.italic -= 1,
+            Event::Start(Tag::Link(..)) => layout.links += 1,
+            Event::End(Tag::Link(..)) => layout.links -= 1,
+
+            Event::Text(text) => match &mut layout.code {
+                Some(code) => code.push_str(&text),
+                None => layout.push(&text),
+            },
+            Event::Code(code) => layout.spans.push(Span {
+                text: code.to_string(),
+                font: Font::Mono,
+                link: layout.links > 0,
+            }),
+            Event::SoftBreak => layout.push(" "),
+            Event::HardBreak => layout.push("\n"),
+            Event::TaskListMarker(checked) => layout.push(if checked { "[x] " } else { "[ ] " }),
+            Event::Rule => {
+                layout.flush(&mut writer);
+                writer.rule(layout.indent);
+            }
+            // raw html and footnotes have nothing to draw
+            _ => {}
+        }
+    }
+    layout.flush(&mut writer);
+
+    writer.finish()
+}
+
+/// A drawing on a page of its own size
+pub(super) fn drawing(svg: &str, theme: Theme) -> Rendered {
+    let Ok(tree) = usvg::Tree::from_str(svg, &Options::default(), &Database::default()) else {
+        return Writer::new(theme, PAGE_WIDTH, PAGE_HEIGHT).finish();
+    };
+    let view_box = tree.view_box().rect;
+    // pdf readers don't open pages over 200 inches on a side
+    let scale = PX.min(14400.0 / view_box.width().max(view_box.height()));
+
+    let mut writer = Writer::new(theme, view_box.width() * scale, view_box.height() * scale);
+    writer.svg(&tree, 0.0, scale);
+    writer.finish()
+}
+
+/// The blocks of markdown, one at a time, and the styles they're in
+#[derive(Default)]
+struct Layout {
+    spans: Vec<Span>,
+    size: f32,
+    indent: f32,
+    bold: usize,
+    italic: usize,
+    links: usize,
+    quotes: usize,
+    /// the next number of each list being laid out, or `None` for bulleted ones
+    lists: Vec<Option<u64>>,
+    /// the bullet or number of a list item, drawn with its first line
+    marker: Option<String>,
+    code: Option<String>,
+}
+
+impl Layout {
+    fn push(&mut self, text: &str) {
+        let font = match (self.bold > 0, self.italic > 0) {
+            (false, false) => Font::Regular,
+            (true, false) => Font::Bold,
+            (false, true) => Font::Italic,
+            (true, true) => Font::BoldItalic,
+        };
+        self.spans
+            .push(Span { text: text.to_string(), font, link: self.links > 0 });
+    }
+
+    /// Draws the text collected so far as a block
+    fn flush(&mut self, writer: &mut Writer) {
+        let spans = mem::take(&mut self.spans);
+        if spans.iter().all(|span| span.text.trim().is_empty()) {
+            return;
+        }
+        let size = if self.size == 0.0 { TEXT_SIZE } else { self.size };
+        writer.text(&spans, size, self.indent, self.marker.take(), self.quotes);
+    }
+}
+
+struct Span {
+    text: String,
+    font: Font,
+    link: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Font {
+    Regular,
+    Bold,
+    Italic,
+    BoldItalic,
+    Mono,
+}
+
+impl Font {
+    const ALL: [Font; 5] = [Font::Regular, Font::Bold, Font::Italic, Font::BoldItalic, Font::Mono];
+
+    fn name(self) -> Name<'static> {
+        match self {
+            Font::Regular => Name(b"F1"),
+            Font::Bold => Name(b"F2"),
+            Font::Italic => Name(b"F3"),
+            Font::BoldItalic => Name(b"F4"),
+            Font::Mono => Name(b"F5"),
+        }
+    }
+
+    fn base_font(self) -> Name<'static> {
+        match self {
+            Font::Regular => Name(b"Helvetica"),
+            Font::Bold => Name(b"Helvetica-Bold"),
+            Font::Italic => Name(b"Helvetica-Oblique"),
+            Font::BoldItalic => Name(b"Helvetica-BoldOblique"),
+            Font::Mono => Name(b"Courier"),
+        }
+    }
+
+    fn width(self, text: &str, size: f32) -> f32 {
+        let units: f32 = match self {
+            Font::Mono => 600.0 * text.chars().count() as f32,
+            // bold is a little wider, which is close enough to wrap lines by
+            Font::Bold | Font::BoldItalic => text.chars().map(helvetica).sum::<f32>() * 1.06,
+            Font::Regular | Font::Italic => text.chars().map(helvetica).sum(),
+        };
+        units * size / 1000.0
+    }
+}
+
+/// Glyph widths of printable ascii in Helvetica, in thousandths of the font size
+const HELVETICA: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+fn helvetica(c: char) -> f32 {
+    match c {
+        ' '..='~' => HELVETICA[c as usize - ' ' as usize] as f32,
+        _ => 556.0,
+    }
+}
+
+/// The encoding the standard fonts are set to
+fn win_ansi(c: char) -> Option<u8> {
+    Some(match c {
+        ' '..='~' => c as u8,
+        '\u{a0}'..='\u{ff}' => c as u32 as u8,
+        '€' => 0x80,
+        '…' => 0x85,
+        '‘' => 0x91,
+        '’' => 0x92,
+        '“' => 0x93,
+        '”' => 0x94,
+        '•' => 0x95,
+        '–' => 0x96,
+        '—' => 0x97,
+        '\t' => b' ',
+        _ => return None,
+    })
+}
+
+struct Writer {
+    theme: Theme,
+    width: f32,
+    height: f32,
+    pdf: Pdf,
+    last_ref: i32,
+    /// content of each finished page
+    pages: Vec<Vec<u8>>,
+    content: Content,
+    /// the top of the space left on the page, from the bottom
+    y: f32,
+    images: Vec<(String, Ref)>,
+    /// stroke and fill opacities, each used through a graphics state of its own
+    alphas: Vec<(f32, f32)>,
+    /// characters shown that the standard fonts don't have
+    unprintable: BTreeSet<char>,
+}
+
+impl Writer {
+    fn new(theme: Theme, width: f32, height: f32) -> Self {
+        let mut writer = Self {
+            theme,
+            width,
+            height,
+            pdf: Pdf::new(),
+            last_ref: 0,
+            pages: vec![],
+            content: Content::new(),
+            y: 0.0,
+            images: vec![],
+            alphas: vec![],
+            unprintable: Default::default(),
+        };
+        writer.start_page();
+        writer
+    }
+
+    fn next_ref(&mut self) -> Ref {
+        self.last_ref += 1;
+        Ref::new(self.last_ref)
+    }
+
+    fn start_page(&mut self) {
+        let (red, green, blue) = self.theme.background();
+        self.content
+            .set_fill_rgb(red as f32 / 255.0, green as f32 / 255.0, blue as f32 / 255.0)
+            .rect(0.0, 0.0, self.width, self.height)
+            .fill_nonzero();
+        self.y = self.height - MARGIN;
+    }
+
+    /// Starts a new page unless there's `height` left on this one, or it's empty anyway
+    fn ensure(&mut self, height: f32) {
+        if self.y - height < MARGIN && self.y < self.height - MARGIN {
+            let content = mem::replace(&mut self.content, Content::new());
+            self.pages.push(content.finish());
+            self.start_page();
+        }
+    }
+
+    fn set_fill(&mut self, (red, green, blue): (u8, u8, u8)) {
+        self.content
+            .set_fill_rgb(red as f32 / 255.0, green as f32 / 255.0, blue as f32 / 255.0);
+    }
+
+    fn show(&mut self, text: &str, font: Font, size: f32, x: f32, y: f32) {
+        let encoded = text
+            .chars()
+            .map(|c| {
+                win_ansi(c).unwrap_or_else(|| {
+                    self.unprintable.insert(c);
+                    b'?'
+                })
+            })
+            .collect::<Vec<_>>();
+        self.content
+            .begin_text()
+            .set_font(font.name(), size)
+            .next_line(x, y)
+            .show(Str(&encoded))
+            .end_text();
+    }
+
+    /// A block of text, wrapped to the width of the page
+    fn text(
+        &mut self, spans: &[Span], size: f32, indent: f32, marker: Option<String>, quotes: usize,
+    ) {
+        let leading = size * 1.4;
+        let left = MARGIN + indent;
+        let lines = wrap(spans, size, self.width - MARGIN - left);
+
+        for (i, line) in lines.iter().enumerate() {
+            self.ensure(leading);
+            let baseline = self.y - size;
+            if i == 0 {
+                if let Some(marker) = &marker {
+                    self.set_fill(self.theme.foreground());
+                    self.show(marker, Font::Regular, size, left - INDENT, baseline);
+                }
+            }
+            for quote in 0..quotes {
+                let x = MARGIN + INDENT * quote as f32 + 4.0;
+                self.set_fill(self.theme.foreground());
+                self.content
+                    .rect(x, self.y - leading, 1.5, leading)
+                    .fill_nonzero();
+            }
+
+            let mut x = left;
+            for span in line {
+                let color = if span.link { self.theme.link() } else { self.theme.foreground() };
+                self.set_fill(color);
+                self.show(&span.text, span.font, size, x, baseline);
+                x += span.font.width(&span.text, size);
+            }
+            self.y -= leading;
+        }
+        self.y -= size * 0.5;
+    }
+
+    fn code(&mut self, code: &str, indent: f32) {
+        let leading = CODE_SIZE * 1.3;
+        let width = self.width - 2.0 * MARGIN - indent - INDENT;
+        let columns = ((width / Font::Mono.width(" ", CODE_SIZE)) as usize).max(1);
+
+        self.set_fill(self.theme.foreground());
+        for line in code.trim_end_matches('\n').lines() {
+            let line = line.replace('\t', "    ").chars().collect::<Vec<_>>();
+            // long lines are broken wherever they reach the margin
+            let chunks =
+                if line.is_empty() { vec![&line[..]] } else { line.chunks(columns).collect() };
+            for chunk in chunks {
+                self.ensure(leading);
+                let text = chunk.iter().collect::<String>();
+                let y = self.y - CODE_SIZE;
+                self.show(&text, Font::Mono, CODE_SIZE, MARGIN + indent + INDENT / 2.0, y);
+                self.y -= leading;
+            }
+        }
+        self.y -= TEXT_SIZE * 0.5;
+    }
+
+    fn rule(&mut self, indent: f32) {
+        self.ensure(TEXT_SIZE);
+        self.set_fill(self.theme.foreground());
+        let y = self.y - TEXT_SIZE / 2.0;
+        self.content
+            .rect(MARGIN + indent, y, self.width - 2.0 * MARGIN - indent, 0.75)
+            .fill_nonzero();
+        self.y -= TEXT_SIZE;
+    }
+
+    /// An image as a block of its own, shrunk to fit the page
+    fn embed(&mut self, embed: &Embed, indent: f32) {
+        let max_width = self.width - 2.0 * MARGIN - indent;
+        let max_height = self.height - 2.0 * MARGIN;
+        let x = MARGIN + indent;
+
+        match embed {
+            Embed::Drawing(svg) => {
+                let Ok(tree) = usvg::Tree::from_str(svg, &Options::default(), &Database::default())
+                else {
+                    return;
+                };
+                let view_box = tree.view_box().rect;
+                let scale = PX
+                    .min(max_width / view_box.width())
+                    .min(max_height / view_box.height());
+                self.ensure(view_box.height() * scale);
+                self.svg(&tree, x, scale);
+                self.y -= TEXT_SIZE * 0.5;
+            }
+            Embed::Jpeg(data) | Embed::Png(data) => {
+                let image = match embed {
+                    Embed::Jpeg(_) => self.jpeg(data),
+                    _ => self.png(data),
+                };
+                let Some((name, width, height)) = image else {
+                    return;
+                };
+                let scale = PX
+                    .min(max_width / width as f32)
+                    .min(max_height / height as f32);
+                let (width, height) = (width as f32 * scale, height as f32 * scale);
+                self.ensure(height);
+                self.content
+                    .save_state()
+                    .transform([width, 0.0, 0.0, height, x, self.y - height])
+                    .x_object(Name(name.as_bytes()))
+                    .restore_state();
+                self.y -= height + TEXT_SIZE * 0.5;
+            }
+        }
+    }
+
+    /// Adds a jpeg as is, since pdf readers decode them themselves
+    fn jpeg(&mut self, data: &[u8]) -> Option<(String, u32, u32)> {
+        let (width, height, components) = jpeg_info(data)?;
+        // cmyk jpegs come inverted or not depending on what wrote them
+        if components != 1 && components != 3 {
+            return None;
+        }
+        let id = self.next_ref();
+        let mut image = self.pdf.image_xobject(id, data);
+        image.filter(Filter::DctDecode);
+        image.width(width as i32);
+        image.height(height as i32);
+        if components == 1 {
+            image.color_space().device_gray();
+        } else {
+            image.color_space().device_rgb();
+        }
+        image.bits_per_component(8);
+        image.finish();
+        Some(self.add_image(id, width, height))
+    }
+
+    /// Adds a png decoded to raw pixels, with any transparency as a mask
+    fn png(&mut self, data: &[u8]) -> Option<(String, u32, u32)> {
+        let mut decoder = png::Decoder::new(data);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().ok()?;
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).ok()?;
+        pixels.truncate(info.buffer_size());
+
+        let (channels, alpha) = match info.color_type {
+            png::ColorType::Grayscale => (1, false),
+            png::ColorType::GrayscaleAlpha => (1, true),
+            png::ColorType::Rgb => (3, false),
+            png::ColorType::Rgba => (3, true),
+            png::ColorType::Indexed => return None,
+        };
+        let mut color = Vec::with_capacity(pixels.len());
+        let mut mask = vec![];
+        for pixel in pixels.chunks(channels + alpha as usize) {
+            color.extend_from_slice(&pixel[..channels]);
+            if alpha {
+                mask.push(pixel[channels]);
+            }
+        }
+
+        let mask = if alpha {
+            let id = self.next_ref();
+            let mask = deflate(&mask);
+            let mut image = self.pdf.image_xobject(id, &mask);
+            image.filter(Filter::FlateDecode);
+            image.width(info.width as i32);
+            image.height(info.height as i32);
+            image.color_space().device_gray();
+            image.bits_per_component(8);
+            image.finish();
+            Some(id)
+        } else {
+            None
+        };
+
+        let id = self.next_ref();
+        let color = deflate(&color);
+        let mut image = self.pdf.image_xobject(id, &color);
+        image.filter(Filter::FlateDecode);
+        image.width(info.width as i32);
+        image.height(info.height as i32);
+        if channels == 1 {
+            image.color_space().device_gray();
+        } else {
+            image.color_space().device_rgb();
+        }
+        image.bits_per_component(8);
+        if let Some(mask) = mask {
+            image.s_mask(mask);
+        }
+        image.finish();
+        Some(self.add_image(id, info.width, info.height))
+    }
+
+    fn add_image(&mut self, id: Ref, width: u32, height: u32) -> (String, u32, u32) {
+        let name = format!("Im{}", self.images.len() + 1);
+        self.images.push((name.clone(), id));
+        (name, width, height)
+    }
+
+    /// The graphics state for a pair of opacities
+    fn alpha(&mut self, stroke: f32, fill: f32) -> String {
+        let index = match self
+            .alphas
+            .iter()
+            .position(|alpha| *alpha == (stroke, fill))
+        {
+            Some(index) => index,
+            None => {
+                self.alphas.push((stroke, fill));
+                self.alphas.len() - 1
+            }
+        };
+        format!("Gs{}", index + 1)
+    }
+
+    /// Draws a flattened drawing with its top left corner at `x` and the top of the space left
+    fn svg(&mut self, tree: &usvg::Tree, x: f32, scale: f32) {
+        let view_box = tree.view_box().rect;
+        // svg's y axis points down, pdf's up
+        self.content.save_state().transform([
+            scale,
+            0.0,
+            0.0,
+            -scale,
+            x - view_box.x() * scale,
+            self.y + view_box.y() * scale,
+        ]);
+        self.group(tree.root());
+        self.content.restore_state();
+        self.y -= view_box.height() * scale;
+    }
+
+    fn group(&mut self, group: &Group) {
+        for node in group.children() {
+            match node {
+                Node::Group(group) => self.group(group),
+                Node::Path(path) => self.path(path),
+                // images inside drawings aren't carried over into pdfs
+                _ => {}
+            }
+        }
+    }
+
+    fn path(&mut self, path: &usvg::Path) {
+        let fill = path.fill().and_then(|fill| match fill.paint() {
+            Paint::Color(color) => Some((*color, fill.opacity().get())),
+            _ => None,
+        });
+        let stroke = path.stroke().and_then(|stroke| match stroke.paint() {
+            Paint::Color(color) => Some((*color, stroke.opacity().get(), stroke.width().get())),
+            _ => None,
+        });
+        if fill.is_none() && stroke.is_none() {
+            return;
+        }
+
+        let gs = self.alpha(
+            stroke.map(|(_, opacity, _)| opacity).unwrap_or(1.0),
+            fill.map(|(_, opacity)| opacity).unwrap_or(1.0),
+        );
+        let t = path.abs_transform();
+        self.content
+            .save_state()
+            .set_parameters(Name(gs.as_bytes()))
+            .transform([t.sx, t.ky, t.kx, t.sy, t.tx, t.ty]);
+        if let Some((color, ..)) = fill {
+            self.set_fill((color.red, color.green, color.blue));
+        }
+        if let Some((color, _, width)) = stroke {
+            self.content
+                .set_stroke_rgb(
+                    color.red as f32 / 255.0,
+                    color.green as f32 / 255.0,
+                    color.blue as f32 / 255.0,
+                )
+                .set_line_width(width)
+                .set_line_cap(LineCapStyle::RoundCap)
+                .set_line_join(LineJoinStyle::RoundJoin);
+        }
+
+        let mut last = (0.0, 0.0);
+        for segment in path.data().segments() {
+            match segment {
+                PathSegment::MoveTo(p) => {
+                    self.content.move_to(p.x, p.y);
+                    last = (p.x, p.y);
+                }
+                PathSegment::LineTo(p) => {
+                    self.content.line_to(p.x, p.y);
+                    last = (p.x, p.y);
+                }
+                // pdf only has cubic curves, which can draw any quadratic one
+                PathSegment::QuadTo(q, p) => {
+                    let c1 =
+                        (last.0 + 2.0 / 3.0 * (q.x - last.0), last.1 + 2.0 / 3.0 * (q.y - last.1));
+                    let c2 = (p.x + 2.0 / 3.0 * (q.x - p.x), p.y + 2.0 / 3.0 * (q.y - p.y));
+                    self.content.cubic_to(c1.0, c1.1, c2.0, c2.1, p.x, p.y);
+                    last = (p.x, p.y);
+                }
+                PathSegment::CubicTo(c1, c2, p) => {
+                    self.content.cubic_to(c1.x, c1.y, c2.x, c2.y, p.x, p.y);
+                    last = (p.x, p.y);
+                }
+                PathSegment::Close => {
+                    self.content.close_path();
+                }
+            }
+        }
+
+        match (fill, stroke) {
+            (Some(_), Some(_)) => self.content.fill_nonzero_and_stroke(),
+            (Some(_), None) => self.content.fill_nonzero(),
+            _ => self.content.stroke(),
+        };
+        self.content.restore_state();
+    }
+
+    fn finish(mut self) -> Rendered {
+        let content = mem::replace(&mut self.content, Content::new());
+        self.pages.push(content.finish());
+
+        let catalog_id = self.next_ref();
+        let tree_id = self.next_ref();
+
+        let mut fonts = vec![];
+        for font in Font::ALL {
+            let id = self.next_ref();
+            self.pdf
+                .type1_font(id)
+                .base_font(font.base_font())
+                .encoding_predefined(Name(b"WinAnsiEncoding"));
+            fonts.push((font.name(), id));
+        }
+
+        let mut states = vec![];
+        for (i, (stroke, fill)) in self.alphas.clone().into_iter().enumerate() {
+            let id = self.next_ref();
+            self.pdf
+                .ext_graphics(id)
+                .stroking_alpha(stroke)
+                .non_stroking_alpha(fill);
+            states.push((format!("Gs{}", i + 1), id));
+        }
+
+        let mut page_ids = vec![];
+        for content in mem::take(&mut self.pages) {
+            let page_id = self.next_ref();
+            let content_id = self.next_ref();
+            self.pdf
+                .stream(content_id, &deflate(&content))
+                .filter(Filter::FlateDecode);
+
+            let mut page = self.pdf.page(page_id);
+            page.media_box(Rect::new(0.0, 0.0, self.width, self.height))
+                .parent(tree_id)
+                .contents(content_id);
+            let mut resources = page.resources();
+            resources.fonts().pairs(fonts.iter().copied());
+            resources.x_objects().pairs(
+                self.images
+                    .iter()
+                    .map(|(name, id)| (Name(name.as_bytes()), *id)),
+            );
+            resources
+                .ext_g_states()
+                .pairs(states.iter().map(|(name, id)| (Name(name.as_bytes()), *id)));
+            resources.finish();
+            page.finish();
+            page_ids.push(page_id);
+        }
+
+        self.pdf
+            .pages(tree_id)
+            .count(page_ids.len() as i32)
+            .kids(page_ids);
+        self.pdf.catalog(catalog_id).pages(tree_id);
+        (self.pdf.finish(), self.unprintable.into_iter().collect())
+    }
+}
+
+/// Breaks styled text into lines that fit `width`, at spaces where possible
+fn wrap(spans: &[Span], size: f32, width: f32) -> Vec<Vec<Span>> {
+    let mut lines: Vec<Vec<Span>> = vec![vec![]];
+    let mut x = 0.0;
+    for span in spans {
+        for (i, part) in span.text.split('\n').enumerate() {
+            if i > 0 {
+                lines.push(vec![]);
+                x = 0.0;
+            }
+            for word in part.split_inclusive(' ') {
+                let mut word = word.to_string();
+                if x > 0.0 && x + span.font.width(word.trim_end(), size) > width {
+                    lines.push(vec![]);
+                    x = 0.0;
+                }
+                if x == 0.0 {
+                    word = word.trim_start().to_string();
+                }
+                // a word wider than a whole line is broken wherever it reaches the margin
+                while span.font.width(&word, size) > width - x && word.chars().count() > 1 {
+                    let mut fits = 1;
+                    while fits < word.chars().count() {
+                        let head = word.chars().take(fits + 1).collect::<String>();
+                        if span.font.width(&head, size) > width - x {
+                            break;
+                        }
+                        fits += 1;
+                    }
+                    let head = word.chars().take(fits).collect::<String>();
+                    push_span(lines.last_mut().unwrap(), &head, span);
+                    lines.push(vec![]);
+                    x = 0.0;
+                    word = word.chars().skip(fits).collect();
+                }
+                x += span.font.width(&word, size);
+                push_span(lines.last_mut().unwrap(), &word, span);
+            }
+        }
+    }
+    lines
+}
+
+/// Adds text to a line, joining it to the span before when they're styled the same
+fn push_span(line: &mut Vec<Span>, text: &str, style: &Span) {
+    if text.is_empty() {
+        return;
+    }
+    match line.last_mut() {
+        Some(last) if last.font == style.font && last.link == style.link => {
+            last.text.push_str(text)
+        }
+        _ => line.push(Span { text: text.to_string(), font: style.font, link: style.link }),
+    }
+}
+
+/// The size and number of color components of a jpeg, from its frame header
+fn jpeg_info(data: &[u8]) -> Option<(u32, u32, u8)> {
+    if data.get(..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+    let mut i = 2;
+    while i + 9 < data.len() {
+        if data[i] != 0xFF {
+            return None;
+        }
+        let marker = data[i + 1];
+        // markers can be padded with any number of 0xFF
+        if marker == 0xFF {
+            i += 1;
+            continue;
+        }
+        let is_frame = (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker);
+        if is_frame {
+            let height = u16::from_be_bytes([data[i + 5], data[i + 6]]);
+            let width = u16::from_be_bytes([data[i + 7], data[i + 8]]);
+            return Some((width as u32, height as u32, data[i + 9]));
+        }
+        let len = u16::from_be_bytes([data[i + 2], data[i + 3]]) as usize;
+        i += 2 + len;
+    }
+    None
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    // writing to a vec can't fail
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
@@ -45,7 +45,8 @@ pub enum SearchConfig {
     PathsAndDocuments,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind")]
 pub enum SearchResult {
     DocumentMatch { id: Uuid, path: String, content_matches: Vec<ContentMatch> },
     PathMatch { id: Uuid, path: String, matched_indices: Vec<usize>, score: i64 },
@@ -260,6 +261,67 @@ impl Lb {
                             }
                         }
 
+                        Event::FileCreated(id) => {
+                            let Ok(path) = lb.get_path_by_id(id).await else {
+                                // not reachable from our root, such as a pending share
+                                continue;
+                            };
+
+                            let mut index = lb.search.index.write().await;
+                            match index.iter_mut().find(|entry| entry.id == id) {
+                                Some(entry) => entry.path = path,
+                                // any content should come in as a result of DocumentWritten
+                                None => index.push(SearchIndexEntry { id, path, content: None }),
+                            }
+                        }
+
+                        Event::FileMoved { id, .. } | Event::FileRenamed { id, .. } => {
+                            let Ok(new_path) = lb.get_path_by_id(id).await else {
+                                continue;
+                            };
+
+                            let mut index = lb.search.index.write().await;
+                            let Some(old_path) = index
+                                .iter()
+                                .find(|entry| entry.id == id)
+                                .map(|e| e.path.clone())
+                            else {
+                                continue;
+                            };
+
+                            // folder paths end in a slash, descendants move along with them
+                            for entry in index.iter_mut() {
+                                if entry.id == id {
+                                    entry.path = new_path.clone();
+                                } else if old_path.ends_with('/')
+                                    && entry.path.starts_with(&old_path)
+                                {
+                                    entry.path =
+                                        format!("{new_path}{}", &entry.path[old_path.len()..]);
+                                }
+                            }
+                        }
+
+                        Event::FileDeleted(id) => {
+                            let mut index = lb.search.index.write().await;
+                            let Some(path) = index
+                                .iter()
+                                .find(|entry| entry.id == id)
+                                .map(|e| e.path.clone())
+                            else {
+                                continue;
+                            };
+
+                            index.retain(|entry| {
+                                entry.id != id
+                                    && !(path.ends_with('/') && entry.path.starts_with(&path))
+                            });
+                        }
+
+                        Event::ShareAdded(_)
+                        | Event::ShareRemoved(_)
+                        | Event::ConflictDetected(_) => {}
+
                         Event::DocumentWritten(id) => {
                             let file = lb.get_file_by_id(id).await.unwrap();
                             let is_searchable =
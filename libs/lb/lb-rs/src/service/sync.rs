@@ -1,3 +1,5 @@
+use super::conflicts::{Conflict, ConflictStrategy};
+use super::events::Event;
 use crate::io::network::ApiError;
 use crate::model::access_info::UserAccessMode;
 use crate::model::api::{
@@ -14,6 +16,7 @@ use crate::model::staged::StagedTreeLikeMut;
 use crate::model::svg::buffer::u_transform_to_bezier;
 use crate::model::svg::element::Element;
 use crate::model::text::buffer::Buffer;
+use crate::model::text::merge;
 use crate::model::tree_like::TreeLike;
 use crate::model::work_unit::WorkUnit;
 use crate::model::{clock, svg};
@@ -35,6 +38,9 @@ use uuid::Uuid;
 
 pub type SyncFlag = Arc<AtomicBool>;
 
+/// how many file updates to ask the server for at a time
+const GET_UPDATES_PAGE_SIZE: usize = 1000;
+
 pub struct SyncContext {
     progress: Option<Box<dyn Fn(SyncProgress) + Send>>,
     current: usize,
@@ -48,6 +54,7 @@ pub struct SyncContext {
     pushed_metas: Vec<FileDiff<SignedFile>>,
     pushed_docs: Vec<FileDiff<SignedFile>>,
     pulled_docs: Vec<Uuid>,
+    changes: Vec<Event>,
 }
 
 impl Lb {
@@ -58,10 +65,7 @@ impl Lb {
         let last_synced = db.last_synced.get().copied().unwrap_or_default() as u64;
         drop(tx);
 
-        let remote_changes = self
-            .client
-            .request(self.get_account()?, GetUpdatesRequest { since_metadata_version: last_synced })
-            .await?;
+        let remote_changes = self.get_updates(last_synced).await?;
         let (deduped, latest_server_ts, _) = self.dedup(remote_changes).await?;
         let remote_dirty = deduped
             .into_iter()
@@ -114,6 +118,15 @@ impl Lb {
         if pipeline.is_ok() {
             pipeline = self.commit_last_synced(&mut ctx).await;
         }
+        if pipeline.is_ok() {
+            let synced_docs: Vec<Uuid> = ctx
+                .pulled_docs
+                .iter()
+                .copied()
+                .chain(ctx.pushed_docs.iter().map(|diff| *diff.id()))
+                .collect();
+            pipeline = self.record_document_versions(synced_docs).await;
+        }
 
         let cleanup = self.cleanup().await;
 
@@ -123,7 +136,9 @@ impl Lb {
         ctx.done_msg();
 
         if got_updates {
-            self.events.meta_changed(self.root().await?.id);
+            for evt in ctx.changes.drain(..) {
+                self.events.queue(evt);
+            }
             for id in &ctx.pulled_docs {
                 self.events.doc_written(*id);
             }
@@ -158,6 +173,7 @@ impl Lb {
             pushed_docs: Default::default(),
             pushed_metas: Default::default(),
             pulled_docs: Default::default(),
+            changes: Default::default(),
         })
     }
 
@@ -206,13 +222,7 @@ impl Lb {
     /// Returns true if there were any updates
     async fn fetch_meta(&self, ctx: &mut SyncContext) -> LbResult<bool> {
         ctx.msg("Fetching tree updates...");
-        let updates = self
-            .client
-            .request(
-                self.get_account()?,
-                GetUpdatesRequest { since_metadata_version: ctx.last_synced },
-            )
-            .await?;
+        let updates = self.get_updates(ctx.last_synced).await?;
 
         let empty = updates.file_metadata.is_empty();
         let (remote, as_of, root) = self.dedup(updates).await?;
@@ -223,6 +233,46 @@ impl Lb {
         Ok(!empty)
     }
 
+    /// Pages through the server's updates, keeping the latest version of files that changed while
+    /// paging
+    async fn get_updates(&self, since_metadata_version: u64) -> LbResult<GetUpdatesResponse> {
+        let mut file_metadata: Vec<SignedFile> = vec![];
+        let mut positions = HashMap::new();
+        let mut cursor = None;
+        loop {
+            let page = self
+                .client
+                .request(
+                    self.get_account()?,
+                    GetUpdatesRequest {
+                        since_metadata_version,
+                        cursor,
+                        page_size: Some(GET_UPDATES_PAGE_SIZE),
+                    },
+                )
+                .await?;
+
+            for file in page.file_metadata {
+                match positions.entry(*file.id()) {
+                    hash_map::Entry::Occupied(e) => file_metadata[*e.get()] = file,
+                    hash_map::Entry::Vacant(e) => {
+                        e.insert(file_metadata.len());
+                        file_metadata.push(file);
+                    }
+                }
+            }
+
+            if page.next_cursor.is_none() {
+                return Ok(GetUpdatesResponse {
+                    as_of_metadata_version: page.as_of_metadata_version,
+                    file_metadata,
+                    next_cursor: None,
+                });
+            }
+            cursor = page.next_cursor;
+        }
+    }
+
     async fn populate_pk_cache(&self, ctx: &mut SyncContext) -> LbResult<()> {
         ctx.msg("Updating public key cache...");
         let mut all_owners = HashSet::new();
@@ -341,6 +391,8 @@ impl Lb {
 
         // fetch document updates and local documents for merge
         let me = Owner(self.keychain.get_pk()?);
+        let conflict_strategy = db.conflict_strategy.get().copied().unwrap_or_default();
+        let mut conflicts: HashMap<Uuid, Conflict> = HashMap::new();
 
         // compute merge changes
         let merge_changes = {
@@ -597,18 +649,56 @@ impl Lb {
                                 let local_document =
                                     self.read_document_helper(id, &mut local).await?;
 
+                                let mut conflict_copy = false;
                                 match document_type {
                                     DocumentType::Text => {
                                         // 3-way merge
                                         // todo: a couple more clones than necessary
-                                        let base_document =
+                                        let base_text =
                                             String::from_utf8_lossy(&base_document).to_string();
-                                        let remote_document =
+                                        let remote_text =
                                             String::from_utf8_lossy(&remote_document).to_string();
-                                        let local_document =
+                                        let local_text =
                                             String::from_utf8_lossy(&local_document).to_string();
-                                        let merged_document = Buffer::from(base_document.as_str())
-                                            .merge(local_document, remote_document);
+
+                                        let regions =
+                                            merge::conflicts(&base_text, &local_text, &remote_text)
+                                                .len();
+                                        if regions > 0 {
+                                            let key = merge.decrypt_key(&id, &self.keychain)?;
+                                            conflicts.insert(
+                                                id,
+                                                Conflict {
+                                                    id,
+                                                    detected_at: clock::get_time().0,
+                                                    regions,
+                                                    strategy: conflict_strategy,
+                                                    copy: None,
+                                                    local: symkey::encrypt(&key, &local_document)?,
+                                                    remote: symkey::encrypt(
+                                                        &key,
+                                                        &remote_document,
+                                                    )?,
+                                                },
+                                            );
+                                            conflict_copy =
+                                                conflict_strategy == ConflictStrategy::Copy;
+                                        }
+
+                                        let merged_document = if regions > 0
+                                            && conflict_strategy == ConflictStrategy::Markers
+                                        {
+                                            merge::merge_with_markers(
+                                                &base_text,
+                                                &local_text,
+                                                &remote_text,
+                                                "local",
+                                                "remote",
+                                            )
+                                        } else {
+                                            Buffer::from(base_text.as_str())
+                                                .merge(local_text, remote_text)
+                                        };
                                         let encrypted_document = merge
                                             .update_document_unvalidated(
                                                 &id,
@@ -659,11 +749,15 @@ impl Lb {
                                         self.docs.insert(id, hmac, &encrypted_document).await?;
                                     }
                                     DocumentType::Other => {
-                                        // duplicate file
-                                        let merge_parent = *merge.find(&id)?.parent();
-                                        let duplicate_id = if let Some(&duplicate_id) =
-                                            duplicate_file_ids.get(&id)
-                                        {
+                                        conflict_copy = true;
+                                    }
+                                }
+
+                                if conflict_copy {
+                                    // duplicate file
+                                    let merge_parent = *merge.find(&id)?.parent();
+                                    let duplicate_id =
+                                        if let Some(&duplicate_id) = duplicate_file_ids.get(&id) {
                                             duplicate_id
                                         } else {
                                             let duplicate_id = Uuid::new_v4();
@@ -672,39 +766,36 @@ impl Lb {
                                             duplicate_id
                                         };
 
-                                        let mut merge_name = merge_name;
-                                        merge_name = NameComponents::from(&merge_name)
-                                            .generate_incremented(
-                                                rename_increments
-                                                    .get(&duplicate_id)
-                                                    .copied()
-                                                    .unwrap_or_default(),
-                                            )
-                                            .to_name();
-
-                                        merge.create_unvalidated(
-                                            duplicate_id,
-                                            symkey::generate_key(),
-                                            &merge_parent,
-                                            &merge_name,
-                                            FileType::Document,
-                                            &self.keychain,
-                                        )?;
-                                        let encrypted_document = merge
-                                            .update_document_unvalidated(
-                                                &duplicate_id,
-                                                &local_document,
-                                                &self.keychain,
-                                            )?;
-                                        let duplicate_hmac =
-                                            merge.find(&duplicate_id)?.document_hmac().copied();
-                                        self.docs
-                                            .insert(
-                                                duplicate_id,
-                                                duplicate_hmac,
-                                                &encrypted_document,
-                                            )
-                                            .await?;
+                                    let mut merge_name = merge_name;
+                                    merge_name = NameComponents::from(&merge_name)
+                                        .generate_incremented(
+                                            rename_increments
+                                                .get(&duplicate_id)
+                                                .copied()
+                                                .unwrap_or_default(),
+                                        )
+                                        .to_name();
+
+                                    merge.create_unvalidated(
+                                        duplicate_id,
+                                        symkey::generate_key(),
+                                        &merge_parent,
+                                        &merge_name,
+                                        FileType::Document,
+                                        &self.keychain,
+                                    )?;
+                                    let encrypted_document = merge.update_document_unvalidated(
+                                        &duplicate_id,
+                                        &local_document,
+                                        &self.keychain,
+                                    )?;
+                                    let duplicate_hmac =
+                                        merge.find(&duplicate_id)?.document_hmac().copied();
+                                    self.docs
+                                        .insert(duplicate_id, duplicate_hmac, &encrypted_document)
+                                        .await?;
+                                    if let Some(conflict) = conflicts.get_mut(&id) {
+                                        conflict.copy = Some(duplicate_id);
                                     }
                                 }
                             } else {
@@ -907,6 +998,19 @@ impl Lb {
             }
         };
 
+        // snapshot what the user saw before this sync for everything it touches
+        let mut touched: HashSet<Uuid> = remote_changes.iter().map(|f| *f.id()).collect();
+        touched.extend(merge_changes.ids());
+        let mut before = HashMap::new();
+        {
+            let mut local = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+            for id in &touched {
+                if let Some(file) = local.maybe_find(id).cloned() {
+                    before.insert(*id, (file, local.name(id, &self.keychain).ok()));
+                }
+            }
+        }
+
         // base = remote; local = merge
         (&mut db.base_metadata)
             .to_staged(remote_changes.clone())
@@ -922,6 +1026,34 @@ impl Lb {
         // self.cleanup_local_metadata()?;
         db.base_metadata.stage(&mut db.local_metadata).prune()?;
 
+        // describe the difference between what the user saw before and after this sync
+        let mut changes = vec![];
+        let mut local = (&db.base_metadata).to_staged(&db.local_metadata).to_lazy();
+        for id in touched {
+            let old = before.remove(&id);
+            let Some(new) = local.maybe_find(&id).cloned() else {
+                // pruned as a result of a deletion
+                if matches!(&old, Some((file, _)) if !file.explicitly_deleted()) {
+                    changes.push(Event::FileDeleted(id));
+                }
+                continue;
+            };
+            let new_name = local.name(&id, &self.keychain).ok();
+            let (old, old_name) = match old {
+                Some((file, name)) => (Some(file), name),
+                None => (None, None),
+            };
+            changes.extend(Event::from_diff(&FileDiff { old, new }, old_name, new_name));
+        }
+        for (id, mut conflict) in conflicts {
+            if let Some(previous) = db.conflicts.get().get(&id) {
+                conflict.copy = conflict.copy.or(previous.copy);
+            }
+            db.conflicts.insert(id, conflict)?;
+            changes.push(Event::ConflictDetected(id));
+        }
+        ctx.changes = changes;
+
         if start.elapsed() > std::time::Duration::from_millis(100) {
             warn!("sync merge held lock for {:?}", start.elapsed());
         }
@@ -0,0 +1,407 @@
+use crate::model::api::{AwaitUpdatesRequest, AwaitUpdatesResponse};
+use crate::model::errors::{LbErr, LbErrKind, LbResult};
+use crate::service::sync::{SyncProgress, SyncStatus};
+use crate::Lb;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{watch, Notify};
+
+/// How often we sync when nothing else prompts us to
+pub const DEFAULT_SYNC_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// How long local writes have to settle before we sync them
+const WRITE_DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// Someone typing continuously will still see their work synced this often
+const MAX_WRITE_DEBOUNCE: Duration = Duration::from_secs(30);
+
+const MIN_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+/// While over the data cap we only check whether space has been freed up this often
+const DATA_CAP_RECHECK: Duration = Duration::from_secs(10 * 60);
+
+pub type SyncStatusWatch = watch::Receiver<BackgroundSyncStatus>;
+
+/// Syncs on behalf of clients that have opted into [Config::background_work]. Consumers can
+/// influence it through [Lb::sync_now], [Lb::pause_sync], [Lb::resume_sync] and
+/// [Lb::set_sync_interval] and observe it through [Lb::background_sync_status].
+///
+/// [Config::background_work]: crate::model::core_config::Config::background_work
+#[derive(Clone)]
+pub struct SyncScheduler {
+    state: Arc<Mutex<SchedulerState>>,
+    status: Arc<watch::Sender<BackgroundSyncStatus>>,
+    wake: Arc<Notify>,
+
+    /// background syncs and [Lb::sync_now] wait their turn rather than failing with
+    /// [LbErrKind::AlreadySyncing]
+    turn: Arc<tokio::sync::Mutex<()>>,
+}
+
+struct SchedulerState {
+    interval: Duration,
+    last_attempt: Option<Instant>,
+    first_write: Option<Instant>,
+    last_write: Option<Instant>,
+    /// the server told us something changed that we haven't pulled yet
+    remote_changes: bool,
+    failures: u32,
+    /// the server rate limited our last sync and asked us not to come back before this
+    retry_at: Option<Instant>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BackgroundSyncStatus {
+    /// [Lb::pause_sync] was called and [Lb::resume_sync] has not been
+    pub paused: bool,
+    /// The last sync could not reach the server, we're backing off
+    pub offline: bool,
+    /// The account is over its data cap, syncs are paused until space is freed up
+    pub over_data_cap: bool,
+    /// The server no longer accepts this client, syncs are paused
+    pub update_required: bool,
+    /// The account is read-only or suspended, syncs are paused until [Lb::sync_now] succeeds
+    pub account_restricted: bool,
+    /// The outcome of the most recent sync, whether it was performed in the background or
+    /// through [Lb::sync_now]
+    pub last_sync: Option<Result<SyncStatus, LbErrKind>>,
+    /// When the most recent sync completed
+    pub last_sync_at: Option<Instant>,
+}
+
+impl Default for SyncScheduler {
+    fn default() -> Self {
+        let state = SchedulerState {
+            interval: DEFAULT_SYNC_INTERVAL,
+            last_attempt: None,
+            first_write: None,
+            last_write: None,
+            remote_changes: false,
+            failures: 0,
+            retry_at: None,
+        };
+        let (status, _) = watch::channel(BackgroundSyncStatus::default());
+
+        Self {
+            state: Arc::new(Mutex::new(state)),
+            status: Arc::new(status),
+            wake: Default::default(),
+            turn: Default::default(),
+        }
+    }
+}
+
+impl SyncScheduler {
+    /// When the next background sync should happen, or None if we shouldn't sync until
+    /// something changes
+    fn next_sync_at(&self) -> Option<Instant> {
+        let status = self.status.borrow();
+        if status.paused || status.update_required || status.account_restricted {
+            return None;
+        }
+
+        let state = self.state.lock().unwrap();
+        let Some(last_attempt) = state.last_attempt else {
+            return Some(Instant::now());
+        };
+
+        if status.over_data_cap {
+            return Some(last_attempt + DATA_CAP_RECHECK);
+        }
+
+        if state.failures > 0 {
+            let backoff = MIN_BACKOFF
+                .saturating_mul(2u32.saturating_pow(state.failures - 1))
+                .min(MAX_BACKOFF);
+            let next = last_attempt + backoff;
+            return Some(state.retry_at.map_or(next, |retry_at| retry_at.max(next)));
+        }
+
+        if state.remote_changes {
+            return Some(Instant::now());
+        }
+
+        let mut next = last_attempt + state.interval;
+        if let (Some(first), Some(last)) = (state.first_write, state.last_write) {
+            next = next.min((last + WRITE_DEBOUNCE).min(first + MAX_WRITE_DEBOUNCE));
+        }
+        Some(next)
+    }
+
+    fn record_write(&self) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        state.first_write.get_or_insert(now);
+        state.last_write = Some(now);
+    }
+
+    fn record_remote_change(&self) {
+        self.state.lock().unwrap().remote_changes = true;
+        self.wake.notify_one();
+    }
+
+    /// `rate_limited_until` is [Network::rate_limited_until] as of when the sync finished
+    ///
+    /// [Network::rate_limited_until]: crate::io::network::Network::rate_limited_until
+    fn record_sync(&self, result: &LbResult<SyncStatus>, rate_limited_until: Option<Instant>) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        state.last_attempt = Some(now);
+
+        let kind = result.as_ref().err().map(|err| err.kind.clone());
+        match &kind {
+            // someone else is syncing, they'll record their own outcome
+            Some(LbErrKind::AlreadySyncing) => return,
+            // whatever went wrong, trying again right away is unlikely to go better
+            Some(_) => state.failures += 1,
+            None => state.failures = 0,
+        }
+        state.retry_at = match &kind {
+            Some(LbErrKind::RateLimited) => rate_limited_until,
+            _ => None,
+        };
+        if result.is_ok() {
+            state.first_write = None;
+            state.last_write = None;
+            state.remote_changes = false;
+        }
+        drop(state);
+
+        self.status.send_modify(|status| {
+            status.offline = kind == Some(LbErrKind::ServerUnreachable);
+            status.over_data_cap = kind == Some(LbErrKind::UsageIsOverDataCap);
+            status.update_required = kind == Some(LbErrKind::ClientUpdateRequired);
+            status.account_restricted =
+                matches!(kind, Some(LbErrKind::AccountReadOnly | LbErrKind::AccountSuspended));
+            status.last_sync = Some(match result {
+                Ok(sync_status) => Ok(sync_status.clone()),
+                Err(err) => Err(err.kind.clone()),
+            });
+            status.last_sync_at = Some(now);
+        });
+    }
+}
+
+impl Lb {
+    /// Syncs immediately regardless of whether background sync is paused or backing off. The
+    /// outcome is reflected in [Lb::background_sync_status].
+    #[instrument(level = "debug", skip_all, err(Debug))]
+    pub async fn sync_now(
+        &self, f: Option<Box<dyn Fn(SyncProgress) + Send>>,
+    ) -> LbResult<SyncStatus> {
+        let result = {
+            let _turn = self.sync_scheduler.turn.lock().await;
+            self.sync(f).await
+        };
+        self.sync_scheduler
+            .record_sync(&result, self.client.rate_limited_until());
+        self.sync_scheduler.wake.notify_one();
+        result
+    }
+
+    /// Stops background syncs until [Lb::resume_sync] is called. [Lb::sync_now] still works.
+    pub fn pause_sync(&self) {
+        self.set_sync_paused(true);
+    }
+
+    pub fn resume_sync(&self) {
+        self.set_sync_paused(false);
+    }
+
+    fn set_sync_paused(&self, paused: bool) {
+        let modified = self.sync_scheduler.status.send_if_modified(|status| {
+            let modified = status.paused != paused;
+            status.paused = paused;
+            modified
+        });
+        if modified {
+            self.sync_scheduler.wake.notify_one();
+        }
+    }
+
+    /// Adjusts how often background syncs happen when nothing else prompts them, for instance
+    /// UIs may want to sync more often while the user is active.
+    pub fn set_sync_interval(&self, interval: Duration) {
+        let mut state = self.sync_scheduler.state.lock().unwrap();
+        if state.interval != interval {
+            state.interval = interval;
+            drop(state);
+            self.sync_scheduler.wake.notify_one();
+        }
+    }
+
+    pub fn background_sync_status(&self) -> SyncStatusWatch {
+        self.sync_scheduler.status.subscribe()
+    }
+
+    #[instrument(level = "debug", skip(self))]
+    pub fn setup_sync_scheduler(&self) {
+        if !self.config.background_work {
+            return;
+        }
+
+        self.setup_update_listener();
+
+        let lb = self.clone();
+        let mut rx = self.subscribe();
+        tokio::spawn(async move {
+            loop {
+                let next_sync = lb.sync_scheduler.next_sync_at();
+                let sleep = async {
+                    match next_sync {
+                        Some(at) => tokio::time::sleep_until(at.into()).await,
+                        None => std::future::pending::<()>().await,
+                    }
+                };
+
+                tokio::select! {
+                    _ = sleep => lb.background_sync().await,
+                    _ = lb.sync_scheduler.wake.notified() => {}
+                    evt = rx.recv() => match evt {
+                        // events are also emitted by sync itself, only local changes matter
+                        Ok(_) | Err(RecvError::Lagged(_)) => {
+                            if lb.has_local_changes().await {
+                                lb.sync_scheduler.record_write();
+                            }
+                        }
+                        Err(RecvError::Closed) => {
+                            error!("event channel closed, stopping background sync");
+                            return;
+                        }
+                    },
+                }
+            }
+        });
+    }
+
+    /// Holds a request open with the server which returns once another device (or someone we
+    /// share with) changes a file we can see, and prompts a background sync when it does
+    fn setup_update_listener(&self) {
+        let lb = self.clone();
+        tokio::spawn(async move {
+            let mut status = lb.background_sync_status();
+            let mut failures = 0;
+            loop {
+                let Ok(account) = lb.get_account().cloned() else {
+                    tokio::time::sleep(MIN_BACKOFF).await;
+                    continue;
+                };
+                let since_metadata_version = {
+                    let tx = lb.ro_tx().await;
+                    tx.db().last_synced.get().copied().unwrap_or_default() as u64
+                };
+
+                let result: LbResult<_> = lb
+                    .client
+                    .request(&account, AwaitUpdatesRequest { since_metadata_version })
+                    .await
+                    .map_err(LbErr::from);
+
+                match result {
+                    Ok(AwaitUpdatesResponse { updated: true }) => {
+                        failures = 0;
+                        debug!("server reported updates");
+                        lb.sync_scheduler.record_remote_change();
+                        // don't ask again until we've pulled, we'd just be told the same thing
+                        status.borrow_and_update();
+                        if status.changed().await.is_err() {
+                            return;
+                        }
+                    }
+                    Ok(AwaitUpdatesResponse { updated: false }) => failures = 0,
+                    Err(err) => {
+                        if err.kind == LbErrKind::ClientUpdateRequired {
+                            warn!("client update required, no longer listening for updates");
+                            return;
+                        }
+                        debug!(?err, "could not await updates");
+                        failures += 1;
+                        let backoff = MIN_BACKOFF
+                            .saturating_mul(2u32.saturating_pow(failures - 1))
+                            .min(MAX_BACKOFF);
+                        tokio::time::sleep(backoff).await;
+                    }
+                }
+            }
+        });
+    }
+
+    async fn background_sync(&self) {
+        if self.sync_scheduler.status.borrow().over_data_cap {
+            match self.get_usage().await {
+                Ok(usage) if usage.server_usage.exact < usage.data_cap.exact => {}
+                _ => {
+                    debug!("still over data cap, not syncing");
+                    self.sync_scheduler.state.lock().unwrap().last_attempt = Some(Instant::now());
+                    return;
+                }
+            }
+        }
+
+        let result = {
+            let _turn = self.sync_scheduler.turn.lock().await;
+            self.sync(None).await
+        };
+        match &result {
+            Ok(_) => debug!("background sync complete"),
+            Err(err) => warn!(?err, "background sync failed"),
+        }
+        self.sync_scheduler
+            .record_sync(&result, self.client.rate_limited_until());
+    }
+
+    async fn has_local_changes(&self) -> bool {
+        let tx = self.ro_tx().await;
+        let db = tx.db();
+        !db.local_metadata.get().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn failed(scheduler: &SyncScheduler, kind: LbErrKind, rate_limited_until: Option<Instant>) {
+        scheduler.record_sync(&Err(kind.into()), rate_limited_until);
+    }
+
+    #[test]
+    fn every_error_backs_off() {
+        let scheduler = SyncScheduler::default();
+        scheduler.record_write();
+
+        failed(&scheduler, LbErrKind::ServerDisabled, None);
+        let first = scheduler.next_sync_at().unwrap();
+        assert!(first >= Instant::now() + MIN_BACKOFF - Duration::from_secs(1));
+
+        failed(&scheduler, LbErrKind::InsufficientPermission, None);
+        let second = scheduler.next_sync_at().unwrap();
+        assert!(second >= first + MIN_BACKOFF);
+    }
+
+    #[test]
+    fn rate_limits_are_honoured() {
+        let scheduler = SyncScheduler::default();
+        let until = Instant::now() + Duration::from_secs(60 * 60);
+
+        failed(&scheduler, LbErrKind::RateLimited, Some(until));
+        assert_eq!(scheduler.next_sync_at(), Some(until));
+    }
+
+    #[test]
+    fn restricted_accounts_stop_syncing() {
+        let scheduler = SyncScheduler::default();
+
+        failed(&scheduler, LbErrKind::AccountSuspended, None);
+        assert_eq!(scheduler.next_sync_at(), None);
+
+        let status = SyncStatus { work_units: vec![], latest_server_ts: 0 };
+        scheduler.record_sync(&Ok(status), None);
+        assert!(scheduler.next_sync_at().is_some());
+
+        failed(&scheduler, LbErrKind::AccountReadOnly, None);
+        assert_eq!(scheduler.next_sync_at(), None);
+    }
+}
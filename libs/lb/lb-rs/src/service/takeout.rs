@@ -0,0 +1,311 @@
+//! Offline backups of everything an account owns, as the server stores it. Archives are a series
+//! of length-prefixed, bincode-encoded [TakeoutEntry]s: a header, the files and documents, and a
+//! footer signed by the account over a digest of everything before it. Contents stay encrypted;
+//! importing an archive with the account's key gives a client the same view of its files it would
+//! have after syncing, without contacting the server.
+
+use crate::model::account::Account;
+use crate::model::api::{TakeoutDocument, TakeoutRequest};
+use crate::model::clock::get_time;
+use crate::model::crypto::ECSigned;
+use crate::model::errors::{LbErr, LbErrKind, LbResult, Unexpected};
+use crate::model::file_like::FileLike;
+use crate::model::file_metadata::Owner;
+use crate::model::pubkey;
+use crate::model::server_file::ServerFile;
+use crate::service::account::AccountKey;
+use crate::Lb;
+use libsecp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use uuid::Uuid;
+
+const TAKEOUT_FORMAT: u8 = 1;
+
+/// Entries larger than this are taken as a sign of corruption rather than allocated
+const MAX_ENTRY_SIZE: u64 = 1 << 30;
+
+#[derive(Serialize, Deserialize)]
+enum TakeoutEntry {
+    Header(TakeoutHeader),
+    File(Box<ServerFile>),
+    Document(TakeoutDocument),
+    Footer(ECSigned<TakeoutSummary>),
+}
+
+/// Enough to set up an account from its key without asking the server who it belongs to
+#[derive(Serialize, Deserialize)]
+struct TakeoutHeader {
+    format: u8,
+    username: String,
+    api_url: String,
+    public_key: PublicKey,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TakeoutSummary {
+    /// sha256 over every entry before the footer, including their length prefixes
+    #[serde(with = "serde_bytes")]
+    pub digest: Vec<u8>,
+    pub files: u64,
+    pub documents: u64,
+}
+
+impl Lb {
+    /// Writes an archive of every file this account owns, and the contents of those documents
+    /// that have any, to `dest`, which must not exist yet. Files shared with this account are
+    /// left to their owners' archives.
+    #[instrument(level = "debug", skip(self), err(Debug))]
+    pub async fn takeout(&self, dest: &Path) -> LbResult<TakeoutSummary> {
+        let account = self.get_account()?;
+
+        let file = OpenOptions::new().write(true).create_new(true).open(dest)?;
+
+        let result = self.write_takeout(account, file).await;
+        if result.is_err() {
+            // don't leave something behind that looks like it might be a backup
+            let _ = fs::remove_file(dest);
+        }
+        result
+    }
+
+    async fn write_takeout(&self, account: &Account, file: File) -> LbResult<TakeoutSummary> {
+        let mut writer = TakeoutWriter { out: BufWriter::new(file), hasher: Sha256::new() };
+        writer.write(&TakeoutEntry::Header(TakeoutHeader {
+            format: TAKEOUT_FORMAT,
+            username: account.username.clone(),
+            api_url: account.api_url.clone(),
+            public_key: account.public_key(),
+        }))?;
+
+        let (mut files, mut documents) = (0, 0);
+        let mut after = None;
+        loop {
+            let page = self
+                .client
+                .request(account, TakeoutRequest { username: None, after })
+                .await?;
+            for file in page.files {
+                writer.write(&TakeoutEntry::File(Box::new(file)))?;
+                files += 1;
+            }
+            for document in page.documents {
+                writer.write(&TakeoutEntry::Document(document))?;
+                documents += 1;
+            }
+            match page.next {
+                Some(next) => after = Some(next),
+                None => break,
+            }
+        }
+
+        let summary =
+            TakeoutSummary { digest: writer.hasher.finalize().to_vec(), files, documents };
+        let footer =
+            pubkey::sign(&account.private_key, &account.public_key(), summary.clone(), get_time)?;
+        let mut out = writer.out;
+        write_entry(&mut out, &TakeoutEntry::Footer(footer))?;
+        out.into_inner()
+            .map_err(|err| err.into_error())?
+            .sync_all()?;
+
+        Ok(summary)
+    }
+
+    /// Sets up this client with the account and files in a [Lb::takeout] archive, checking that
+    /// the archive is complete and was made by the account `key` belongs to. `key` is anything
+    /// [Lb::import_account] accepts. The server isn't contacted until the next sync.
+    #[instrument(level = "debug", skip(self, key), err(Debug))]
+    pub async fn import_takeout(&self, key: &str, archive: &Path) -> LbResult<Account> {
+        if self.get_account().is_ok() {
+            return Err(LbErrKind::AccountExists.into());
+        }
+
+        // a first pass checks the footer so nothing is imported from a bad archive
+        let mut reader = TakeoutReader::open(archive)?;
+        let header = match reader.next()? {
+            Some(TakeoutEntry::Header(header)) if header.format == TAKEOUT_FORMAT => header,
+            _ => return Err(LbErrKind::TakeoutCorrupted.into()),
+        };
+
+        let account = match AccountKey::parse(key)? {
+            AccountKey::V1(account) => account,
+            AccountKey::V2(private_key) => Account {
+                username: header.username.clone(),
+                api_url: header.api_url.clone(),
+                private_key,
+            },
+        };
+        if account.public_key() != header.public_key || account.username != header.username {
+            return Err(LbErrKind::UsernamePublicKeyMismatch.into());
+        }
+
+        let mut files = vec![];
+        let summary = loop {
+            match reader.next()? {
+                Some(TakeoutEntry::File(file)) => files.push(*file),
+                Some(TakeoutEntry::Document(_)) => {}
+                Some(TakeoutEntry::Footer(footer)) => break reader.check(&account, footer)?,
+                Some(TakeoutEntry::Header(_)) | None => {
+                    return Err(LbErrKind::TakeoutCorrupted.into())
+                }
+            }
+        };
+
+        let owner = Owner(account.public_key());
+        let Some(root) = files
+            .iter()
+            .find(|file| file.is_root() && file.owner() == owner)
+            .map(|file| *file.id())
+        else {
+            return Err(LbErrKind::TakeoutCorrupted.into());
+        };
+
+        // archives are written a page at a time, so a file moved into a folder that an earlier
+        // page had already passed over shows up without its parent
+        let ids: HashSet<Uuid> = files.iter().map(|file| *file.id()).collect();
+        if files.iter().any(|file| !ids.contains(file.parent())) {
+            return Err(LbErrKind::TakeoutCorrupted.into());
+        }
+
+        let mut reader = TakeoutReader::open(archive)?;
+        let mut documents = 0;
+        while let Some(entry) = reader.next()? {
+            if let TakeoutEntry::Document(document) = entry {
+                self.docs
+                    .insert(document.id, Some(document.hmac), &document.content)
+                    .await?;
+                documents += 1;
+            }
+        }
+        if documents != summary.documents {
+            return Err(LbErrKind::TakeoutCorrupted.into());
+        }
+
+        let mut tx = self.begin_tx().await;
+        let db = tx.db();
+
+        db.account.insert(account.clone())?;
+        for file in files {
+            db.base_metadata.insert(*file.id(), file.file)?;
+        }
+        db.root.insert(root)?;
+        db.pub_key_lookup.insert(owner, account.username.clone())?;
+
+        self.keychain.cache_account(account.clone()).await?;
+
+        tx.end();
+
+        self.events.meta_changed(root);
+
+        Ok(account)
+    }
+}
+
+fn write_entry<W: Write>(out: &mut W, entry: &TakeoutEntry) -> LbResult<Vec<u8>> {
+    let bytes = bincode::serialize(entry).map_unexpected()?;
+    let mut framed = (bytes.len() as u64).to_le_bytes().to_vec();
+    framed.extend(bytes);
+    out.write_all(&framed)?;
+    Ok(framed)
+}
+
+struct TakeoutWriter {
+    out: BufWriter<File>,
+    hasher: Sha256,
+}
+
+impl TakeoutWriter {
+    fn write(&mut self, entry: &TakeoutEntry) -> LbResult<()> {
+        let framed = write_entry(&mut self.out, entry)?;
+        self.hasher.update(&framed);
+        Ok(())
+    }
+}
+
+struct TakeoutReader {
+    input: BufReader<File>,
+    hasher: Sha256,
+    files: u64,
+    documents: u64,
+}
+
+impl TakeoutReader {
+    fn open(path: &Path) -> LbResult<Self> {
+        Ok(Self {
+            input: BufReader::new(File::open(path)?),
+            hasher: Sha256::new(),
+            files: 0,
+            documents: 0,
+        })
+    }
+
+    /// The next entry, or `None` at the end of the archive. The footer isn't part of the digest.
+    fn next(&mut self) -> LbResult<Option<TakeoutEntry>> {
+        let mut len = [0; 8];
+        match self.input.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+        let size = u64::from_le_bytes(len);
+        if size > MAX_ENTRY_SIZE {
+            return Err(LbErrKind::TakeoutCorrupted.into());
+        }
+
+        let mut bytes = vec![0; size as usize];
+        self.input
+            .read_exact(&mut bytes)
+            .map_err(|err| match err.kind() {
+                ErrorKind::UnexpectedEof => LbErr::from(LbErrKind::TakeoutCorrupted),
+                _ => LbErr::from(err),
+            })?;
+        let entry: TakeoutEntry =
+            bincode::deserialize(&bytes).map_err(|_| LbErrKind::TakeoutCorrupted)?;
+
+        match entry {
+            TakeoutEntry::Footer(_) => return Ok(Some(entry)),
+            TakeoutEntry::File(_) => self.files += 1,
+            TakeoutEntry::Document(_) => self.documents += 1,
+            TakeoutEntry::Header(_) => {}
+        }
+        self.hasher.update(len);
+        self.hasher.update(&bytes);
+        Ok(Some(entry))
+    }
+
+    /// Checks that `footer` was signed by `account` and describes exactly what was read before
+    /// it, and that nothing follows it
+    fn check(
+        &mut self, account: &Account, footer: ECSigned<TakeoutSummary>,
+    ) -> LbResult<TakeoutSummary> {
+        // archives are meant to outlive the usual freshness window of a signature, so allow for
+        // however long ago (or, with a skewed clock, ahead) it was signed
+        let age = get_time().0 - footer.timestamped_value.timestamp;
+        let slack = 60_000;
+        pubkey::verify(
+            &account.public_key(),
+            &footer,
+            age.max(0) as u64 + slack,
+            (-age).max(0) as u64 + slack,
+            get_time,
+        )
+        .map_err(|_| LbErrKind::TakeoutCorrupted)?;
+
+        let summary = footer.timestamped_value.value;
+        let digest = self.hasher.clone().finalize().to_vec();
+        if summary.digest != digest
+            || summary.files != self.files
+            || summary.documents != self.documents
+            || self.next()?.is_some()
+        {
+            return Err(LbErrKind::TakeoutCorrupted.into());
+        }
+
+        Ok(summary)
+    }
+}
@@ -1,4 +1,8 @@
-use lb_rs::model::api::ServerIndex;
+use lb_rs::model::api::{
+    AccountState, AdminGetAuditLogRequest, AdminRepairServer, AuditOutcome, ServerIndex,
+    ServerRepair,
+};
+use lb_rs::model::errors::LbErrKind;
 use lb_rs::model::file::ShareMode;
 use test_utils::*;
 
@@ -271,3 +275,132 @@ async fn admin_rebuild_shared_files_index_test() {
     let cust2_new_device = test_core_from(&customer2).await;
     cust2_new_device.test_repo_integrity().await.unwrap();
 }
+
+#[tokio::test]
+#[ignore]
+async fn admin_repair_dry_run_test() {
+    let admin_core = test_core().await;
+    admin_core
+        .create_account("admin1", &url(), false)
+        .await
+        .unwrap();
+
+    let customer = test_core_with_account().await;
+    let doc = customer.create_at_path("test.md").await.unwrap();
+    customer.write_document(doc.id, b"contents").await.unwrap();
+    customer.sync(None).await.unwrap();
+
+    let planned = admin_core
+        .repair_server(ServerRepair::Indexes, true)
+        .await
+        .unwrap();
+    assert_eq!(
+        admin_core
+            .repair_server(ServerRepair::Indexes, true)
+            .await
+            .unwrap(),
+        planned
+    );
+
+    admin_core
+        .repair_server(ServerRepair::Indexes, false)
+        .await
+        .unwrap();
+    assert_eq!(
+        admin_core
+            .repair_server(ServerRepair::Indexes, true)
+            .await
+            .unwrap(),
+        AdminRepairServer::default()
+    );
+
+    let cust_new_device = test_core_from(&customer).await;
+    cust_new_device.test_repo_integrity().await.unwrap();
+}
+
+#[tokio::test]
+#[ignore]
+async fn admin_account_state_test() {
+    let admin_core = test_core().await;
+    admin_core
+        .create_account("admin1", &url(), false)
+        .await
+        .unwrap();
+
+    let customer = test_core_with_account().await;
+    let username = customer.get_account().unwrap().username.clone();
+    customer.create_at_path("before.md").await.unwrap();
+    customer.sync(None).await.unwrap();
+
+    admin_core
+        .set_account_state(&username, AccountState::ReadOnly)
+        .await
+        .unwrap();
+    customer.create_at_path("during.md").await.unwrap();
+    assert_matches!(
+        customer.sync(None).await.map_err(|err| err.kind),
+        Err(LbErrKind::AccountReadOnly)
+    );
+
+    admin_core
+        .set_account_state(&username, AccountState::Suspended)
+        .await
+        .unwrap();
+    assert_matches!(
+        customer.get_usage().await.map_err(|err| err.kind),
+        Err(LbErrKind::AccountSuspended)
+    );
+
+    admin_core
+        .set_account_state(&username, AccountState::Active)
+        .await
+        .unwrap();
+    customer.sync(None).await.unwrap();
+}
+
+#[tokio::test]
+#[ignore]
+async fn admin_audit_log_test() {
+    let admin_core = test_core().await;
+    admin_core
+        .create_account("admin1", &url(), false)
+        .await
+        .unwrap();
+
+    let customer = test_core_with_account().await;
+    let doc = customer.create_at_path("test.md").await.unwrap();
+    customer.sync(None).await.unwrap();
+
+    admin_core.disappear_file(doc.id).await.unwrap();
+    // only admins' attempts are journaled
+    assert_matches!(
+        customer
+            .disappear_file(doc.id)
+            .await
+            .map_err(|err| err.kind),
+        Err(LbErrKind::InsufficientPermission)
+    );
+    assert!(admin_core.disappear_file(doc.id).await.is_err());
+
+    let entries = admin_core
+        .get_audit_log(AdminGetAuditLogRequest {
+            target: Some(doc.id.to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].actor_username.as_deref(), Some("admin1"));
+    assert_eq!(entries[0].action, "/admin-disappear-file");
+    assert_eq!(entries[0].outcome, AuditOutcome::Succeeded);
+    assert_eq!(entries[1].actor_username.as_deref(), Some("admin1"));
+    assert_matches!(&entries[1].outcome, AuditOutcome::Rejected(_));
+
+    assert_matches!(
+        customer
+            .get_audit_log(Default::default())
+            .await
+            .map_err(|err| err.kind),
+        Err(LbErrKind::InsufficientPermission)
+    );
+}
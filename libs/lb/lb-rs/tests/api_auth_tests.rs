@@ -19,7 +19,7 @@ async fn upsert_id_takeover() {
 
         core1
             .client
-            .request(acc1, GetUpdatesRequest { since_metadata_version: 0 })
+            .request(acc1, GetUpdatesRequest::default())
             .await
             .unwrap()
             .file_metadata
@@ -54,7 +54,7 @@ async fn upsert_id_takeover_change_parent() {
         core1.sync(None).await.unwrap();
         core1
             .client
-            .request(account1, GetUpdatesRequest { since_metadata_version: 0 })
+            .request(account1, GetUpdatesRequest::default())
             .await
             .unwrap()
             .file_metadata
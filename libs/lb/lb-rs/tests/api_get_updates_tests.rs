@@ -0,0 +1,140 @@
+use lb_rs::io::network::ApiError;
+use lb_rs::model::api::{GetUpdatesError, GetUpdatesRequest};
+use lb_rs::model::file::ShareMode;
+use lb_rs::model::file_like::FileLike;
+use std::collections::HashSet;
+use test_utils::*;
+
+#[tokio::test]
+async fn pages_cover_every_update() {
+    let core = test_core_with_account().await;
+    for i in 0..25 {
+        core.create_at_path(&format!("file{i}.md")).await.unwrap();
+    }
+    core.sync(None).await.unwrap();
+    let account = core.get_account().unwrap();
+
+    let all = core
+        .client
+        .request(account, GetUpdatesRequest::default())
+        .await
+        .unwrap();
+    assert_eq!(all.file_metadata.len(), 26);
+    assert_eq!(all.next_cursor, None);
+
+    let mut ids = HashSet::new();
+    let mut pages = 0;
+    let mut cursor = None;
+    loop {
+        let page = core
+            .client
+            .request(
+                account,
+                GetUpdatesRequest { since_metadata_version: 0, cursor, page_size: Some(10) },
+            )
+            .await
+            .unwrap();
+        pages += 1;
+        assert!(page.file_metadata.len() <= 10);
+        ids.extend(page.file_metadata.iter().map(|f| *f.id()));
+        if page.next_cursor.is_none() {
+            break;
+        }
+        cursor = page.next_cursor;
+    }
+    assert_eq!(pages, 3);
+    assert_eq!(ids.len(), 26);
+}
+
+#[tokio::test]
+async fn only_newer_updates() {
+    let core = test_core_with_account().await;
+    core.create_at_path("a.md").await.unwrap();
+    core.sync(None).await.unwrap();
+    let account = core.get_account().unwrap();
+
+    let as_of = core
+        .client
+        .request(account, GetUpdatesRequest::default())
+        .await
+        .unwrap()
+        .as_of_metadata_version;
+    let b = core.create_at_path("b.md").await.unwrap();
+    core.sync(None).await.unwrap();
+
+    let updates = core
+        .client
+        .request(account, GetUpdatesRequest { since_metadata_version: as_of, ..Default::default() })
+        .await
+        .unwrap();
+    assert_eq!(updates.file_metadata.len(), 1);
+    assert_eq!(updates.file_metadata[0].id(), &b.id);
+}
+
+#[tokio::test]
+async fn cursor_from_another_listing() {
+    let core = test_core_with_account().await;
+    core.create_at_path("a.md").await.unwrap();
+    core.sync(None).await.unwrap();
+    let account = core.get_account().unwrap();
+
+    let cursor = core
+        .client
+        .request(account, GetUpdatesRequest { page_size: Some(1), ..Default::default() })
+        .await
+        .unwrap()
+        .next_cursor;
+    assert!(cursor.is_some());
+
+    let result = core
+        .client
+        .request(
+            account,
+            GetUpdatesRequest { since_metadata_version: 1, cursor, page_size: Some(1) },
+        )
+        .await;
+    assert_matches!(
+        result,
+        Err(ApiError::<GetUpdatesError>::Endpoint(GetUpdatesError::InvalidCursor))
+    );
+}
+
+#[tokio::test]
+async fn newly_shared_folder_includes_contents() {
+    let c1 = test_core_with_account().await;
+    let c2 = test_core_with_account().await;
+    let folder = c1.create_at_path("shared/").await.unwrap();
+    c1.create_at_path("shared/a.md").await.unwrap();
+    c1.create_at_path("shared/b.md").await.unwrap();
+    c1.sync(None).await.unwrap();
+
+    let account = c2.get_account().unwrap();
+    let as_of = c2
+        .client
+        .request(account, GetUpdatesRequest::default())
+        .await
+        .unwrap()
+        .as_of_metadata_version;
+
+    c1.share_file(folder.id, &account.username, ShareMode::Read)
+        .await
+        .unwrap();
+    c1.sync(None).await.unwrap();
+
+    let updates = c2
+        .client
+        .request(
+            account,
+            GetUpdatesRequest { since_metadata_version: as_of, page_size: Some(2), cursor: None },
+        )
+        .await
+        .unwrap();
+    // the folder and everything in it, though the contents haven't changed in a while
+    assert_eq!(updates.file_metadata.len(), 2);
+    assert!(updates.next_cursor.is_some());
+
+    c2.sync(None).await.unwrap();
+    // pending shares hide their contents until they're linked
+    c2.create_link_at_path("link", folder.id).await.unwrap();
+    assert_eq!(c2.get_children(&folder.id).await.unwrap().len(), 2);
+}
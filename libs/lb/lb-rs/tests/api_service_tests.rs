@@ -1,6 +1,5 @@
 use libsecp256k1::PublicKey;
 
-use lb_rs::get_code_version;
 use lb_rs::io::network::{ApiError, Network};
 use lb_rs::model::api::{GetPublicKeyError, GetPublicKeyRequest, GetPublicKeyResponse};
 use lb_rs::model::clock::{get_time, Timestamp};
@@ -14,7 +13,7 @@ async fn forced_upgrade() {
     let core = test_core_with_account().await;
     let account = core.get_account().unwrap();
 
-    let client = Network { client: Default::default(), get_code_version: CODE_VERSION, get_time };
+    let client = Network { get_code_version: CODE_VERSION, ..Default::default() };
 
     let result: Result<PublicKey, ApiError<GetPublicKeyError>> = client
         .request(account, GetPublicKeyRequest { username: account.username.clone() })
@@ -31,7 +30,7 @@ async fn expired_request() {
     let core = test_core_with_account().await;
     let account = core.get_account().unwrap();
 
-    let client = Network { client: Default::default(), get_code_version, get_time: EARLY_CLOCK };
+    let client = Network { get_time: EARLY_CLOCK, ..Default::default() };
 
     let result = client
         .request(account, GetPublicKeyRequest { username: account.username.clone() })
@@ -0,0 +1,119 @@
+use lb_rs::model::errors::LbErrKind;
+use lb_rs::model::file::ShareMode;
+use lb_rs::service::collab::{CollabSession, CollabStatus};
+use std::time::Duration;
+use test_utils::*;
+
+async fn eventually(mut f: impl FnMut() -> bool) {
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while !f() {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .unwrap();
+}
+
+async fn joined(session: &CollabSession) -> String {
+    eventually(|| session.status() == CollabStatus::Joined).await;
+    let mut view = None;
+    eventually(|| {
+        view = session.take_view().or(view.take());
+        view.is_some()
+    })
+    .await;
+    view.unwrap()
+}
+
+#[tokio::test]
+async fn edits_reach_collaborators() {
+    let c1 = test_core_with_account().await;
+    let doc = c1.create_at_path("/doc.md").await.unwrap();
+    c1.write_document(doc.id, b"hello").await.unwrap();
+    c1.sync(None).await.unwrap();
+    let c2 = test_core_from(&c1).await;
+
+    let s1 = c1.join_collab(doc.id).await.unwrap();
+    assert_eq!(joined(&s1).await, "hello");
+    let s2 = c2.join_collab(doc.id).await.unwrap();
+    assert_eq!(joined(&s2).await, "hello");
+
+    assert!(s1.edit("hello world"));
+    let mut view = None;
+    eventually(|| {
+        view = s2.take_view();
+        view.as_deref() == Some("hello world")
+    })
+    .await;
+
+    // concurrent edits converge
+    assert!(s1.edit("oh hello world"));
+    assert!(s2.edit("hello world!"));
+    let (mut v1, mut v2) = (String::new(), String::new());
+    eventually(|| {
+        if let Some(view) = s1.take_view() {
+            v1 = view;
+        }
+        if let Some(view) = s2.take_view() {
+            v2 = view;
+        }
+        v1 == "oh hello world!" && v2 == v1
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn presence_and_persistence() {
+    let c1 = test_core_with_account().await;
+    let doc = c1.create_at_path("/doc.md").await.unwrap();
+    c1.sync(None).await.unwrap();
+    let c2 = test_core_from(&c1).await;
+
+    let s1 = c1.join_collab(doc.id).await.unwrap();
+    joined(&s1).await;
+    let s2 = c2.join_collab(doc.id).await.unwrap();
+    joined(&s2).await;
+
+    let username = c1.get_account().unwrap().username.clone();
+    eventually(|| s1.presence().len() == 1 && s2.presence().len() == 1).await;
+    assert_eq!(s1.presence()[0].username, username);
+
+    // exactly one participant saves the document
+    assert!(s1.persists() ^ s2.persists());
+
+    drop(s2);
+    eventually(|| s1.presence().is_empty()).await;
+    assert!(s1.persists());
+}
+
+#[tokio::test]
+async fn sharees_collaborate() {
+    let c1 = test_core_with_account().await;
+    let c2 = test_core_with_account().await;
+    let c3 = test_core_with_account().await;
+    let doc = c1.create_at_path("/doc.md").await.unwrap();
+    c1.share_file(doc.id, &c2.get_account().unwrap().username, ShareMode::Write)
+        .await
+        .unwrap();
+    c1.share_file(doc.id, &c3.get_account().unwrap().username, ShareMode::Read)
+        .await
+        .unwrap();
+    c1.sync(None).await.unwrap();
+    c2.sync(None).await.unwrap();
+    c3.sync(None).await.unwrap();
+
+    let s1 = c1.join_collab(doc.id).await.unwrap();
+    joined(&s1).await;
+    let s2 = c2.join_collab(doc.id).await.unwrap();
+    joined(&s2).await;
+
+    assert!(s2.edit("from a sharee"));
+    eventually(|| s1.take_view().as_deref() == Some("from a sharee")).await;
+
+    // readers can follow along but their edits are rejected
+    let s3 = c3.join_collab(doc.id).await.unwrap();
+    assert_eq!(joined(&s3).await, "from a sharee");
+    assert!(s3.edit("vandalism"));
+    eventually(|| s3.status() == CollabStatus::Ended(Some(LbErrKind::InsufficientPermission)))
+        .await;
+}
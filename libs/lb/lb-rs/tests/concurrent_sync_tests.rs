@@ -1,3 +1,5 @@
+use lb_rs::model::file::ShareMode;
+use lb_rs::model::file_metadata::FileType;
 use lb_rs::Lb;
 use std::future::IntoFuture;
 use std::thread;
@@ -104,3 +106,40 @@ async fn new_account_test() {
         .unwrap();
     assert_eq!(core.calculate_work().await.unwrap().work_units, vec![]);
 }
+
+#[tokio::test]
+async fn sharer_sharee_and_stranger_sync_concurrently() {
+    let c1 = test_core_with_account().await;
+    let c2 = test_core_with_account().await;
+    let c3 = test_core_with_account().await;
+    let folder = c1.create_at_path("shared/").await.unwrap();
+    c1.share_file(folder.id, &c2.get_account().unwrap().username, ShareMode::Write)
+        .await
+        .unwrap();
+    c1.sync(None).await.unwrap();
+    c2.sync(None).await.unwrap();
+    c2.create_link_at_path("link", folder.id).await.unwrap();
+
+    for i in 0..10 {
+        c1.create_file(&format!("from_c1_{i}.md"), &folder.id, FileType::Document)
+            .await
+            .unwrap();
+        c2.create_file(&format!("from_c2_{i}.md"), &folder.id, FileType::Document)
+            .await
+            .unwrap();
+        c3.create_at_path(&format!("c3_{i}.md")).await.unwrap();
+
+        let (r1, r2, r3) = tokio::join!(c1.sync(None), c2.sync(None), c3.sync(None));
+        r1.unwrap();
+        r2.unwrap();
+        r3.unwrap();
+    }
+    c1.sync(None).await.unwrap();
+    c2.sync(None).await.unwrap();
+
+    assert_eq!(c1.get_children(&folder.id).await.unwrap().len(), 20);
+    assert_eq!(c2.get_children(&folder.id).await.unwrap().len(), 20);
+    for c in [&c1, &c2, &c3] {
+        c.test_repo_integrity().await.unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+use lb_rs::model::errors::LbErrKind;
+use lb_rs::service::conflicts::{ConflictResolution, ConflictStrategy};
+use lb_rs::Lb;
+use test_utils::*;
+use uuid::Uuid;
+
+const BASE: &[u8] = b"one\ntwo\nthree\n";
+const LOCAL: &[u8] = b"one\n2\nthree\n";
+const REMOTE: &[u8] = b"one\nII\nthree\n";
+
+/// Two clients edit the same line of a document; the second one to sync merges
+async fn conflicting_edits(strategy: ConflictStrategy) -> (Lb, Lb, Uuid) {
+    let c1 = test_core_with_account().await;
+    let doc = c1.create_at_path("/doc.md").await.unwrap();
+    c1.write_document(doc.id, BASE).await.unwrap();
+    c1.sync(None).await.unwrap();
+    let c2 = another_client(&c1).await;
+    c2.sync(None).await.unwrap();
+    c2.set_conflict_strategy(strategy).await.unwrap();
+
+    c1.write_document(doc.id, REMOTE).await.unwrap();
+    c1.sync(None).await.unwrap();
+    c2.write_document(doc.id, LOCAL).await.unwrap();
+    c2.sync(None).await.unwrap();
+
+    (c1, c2, doc.id)
+}
+
+#[tokio::test]
+async fn clean_merge_records_nothing() {
+    let c1 = test_core_with_account().await;
+    let doc = c1.create_at_path("/doc.md").await.unwrap();
+    c1.write_document(doc.id, BASE).await.unwrap();
+    c1.sync(None).await.unwrap();
+    let c2 = another_client(&c1).await;
+    c2.sync(None).await.unwrap();
+
+    c1.write_document(doc.id, b"one!\ntwo\nthree\n")
+        .await
+        .unwrap();
+    c1.sync(None).await.unwrap();
+    c2.write_document(doc.id, b"one\ntwo\nthree!\n")
+        .await
+        .unwrap();
+    c2.sync(None).await.unwrap();
+
+    assert!(c2.list_conflicts().await.unwrap().is_empty());
+    assert_eq!(c2.read_document(doc.id, false).await.unwrap(), b"one!\ntwo\nthree!\n");
+}
+
+#[tokio::test]
+async fn overlapping_merge_is_recorded() {
+    let (_c1, c2, id) = conflicting_edits(ConflictStrategy::Merge).await;
+
+    let conflicts = c2.list_conflicts().await.unwrap();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].id, id);
+    assert_eq!(conflicts[0].regions, 1);
+    assert_eq!(conflicts[0].copy, None);
+    assert_eq!(c2.list_metadatas().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn markers_strategy() {
+    let (c1, c2, id) = conflicting_edits(ConflictStrategy::Markers).await;
+
+    let expected = b"one\n<<<<<<< local\n2\n=======\nII\n>>>>>>> remote\nthree\n";
+    assert_eq!(c2.read_document(id, false).await.unwrap(), expected);
+    c2.sync(None).await.unwrap();
+    c1.sync(None).await.unwrap();
+    assert_eq!(c1.read_document(id, false).await.unwrap(), expected);
+}
+
+#[tokio::test]
+async fn copy_strategy() {
+    let (_c1, c2, id) = conflicting_edits(ConflictStrategy::Copy).await;
+
+    let conflict = c2.list_conflicts().await.unwrap().remove(0);
+    let copy = conflict.copy.unwrap();
+    assert_eq!(c2.read_document(copy, false).await.unwrap(), LOCAL);
+    assert_eq!(
+        c2.get_file_by_id(copy).await.unwrap().parent,
+        c2.get_file_by_id(id).await.unwrap().parent
+    );
+
+    c2.resolve_conflict(id, ConflictResolution::KeepMerged)
+        .await
+        .unwrap();
+    assert!(c2.list_conflicts().await.unwrap().is_empty());
+    assert_eq!(c2.get_file_by_id(copy).await.unwrap_err().kind, LbErrKind::FileNonexistent);
+}
+
+#[tokio::test]
+async fn resolve_keep_local_and_remote() {
+    let (_c1, c2, id) = conflicting_edits(ConflictStrategy::Merge).await;
+    c2.resolve_conflict(id, ConflictResolution::KeepLocal)
+        .await
+        .unwrap();
+    assert_eq!(c2.read_document(id, false).await.unwrap(), LOCAL);
+
+    let (_c1, c2, id) = conflicting_edits(ConflictStrategy::Merge).await;
+    c2.resolve_conflict(id, ConflictResolution::KeepRemote)
+        .await
+        .unwrap();
+    assert_eq!(c2.read_document(id, false).await.unwrap(), REMOTE);
+
+    assert_eq!(
+        c2.resolve_conflict(id, ConflictResolution::KeepRemote)
+            .await
+            .unwrap_err()
+            .kind,
+        LbErrKind::ConflictNonexistent
+    );
+}
+
+#[tokio::test]
+async fn deleted_documents_have_no_conflicts() {
+    let (_c1, c2, id) = conflicting_edits(ConflictStrategy::Merge).await;
+    c2.delete(&id).await.unwrap();
+    assert!(c2.list_conflicts().await.unwrap().is_empty());
+}
@@ -0,0 +1,123 @@
+use lb_rs::model::file::ShareMode;
+use lb_rs::service::events::{Event, Receiver};
+use test_utils::*;
+
+fn drain(rx: &mut Receiver<Event>) -> Vec<Event> {
+    let mut events = vec![];
+    while let Ok(evt) = rx.try_recv() {
+        events.push(evt);
+    }
+    events
+}
+
+#[tokio::test]
+async fn local_ops() {
+    let core = test_core_with_account().await;
+    let root = core.root().await.unwrap();
+    let mut rx = core.subscribe();
+
+    let folder = core.create_at_path("/folder/").await.unwrap();
+    let doc = core.create_at_path("/doc.md").await.unwrap();
+    assert_eq!(drain(&mut rx), vec![Event::FileCreated(folder.id), Event::FileCreated(doc.id)]);
+
+    core.rename_file(&doc.id, "renamed.md").await.unwrap();
+    assert_eq!(
+        drain(&mut rx),
+        vec![Event::FileRenamed { id: doc.id, old: "doc.md".into(), new: "renamed.md".into() }]
+    );
+
+    core.move_file(&doc.id, &folder.id).await.unwrap();
+    assert_eq!(drain(&mut rx), vec![Event::FileMoved { id: doc.id, from: root.id, to: folder.id }]);
+
+    core.delete(&folder.id).await.unwrap();
+    assert_eq!(drain(&mut rx), vec![Event::FileDeleted(folder.id)]);
+}
+
+#[tokio::test]
+async fn create_at_path_creates_parents() {
+    let core = test_core_with_account().await;
+    let mut rx = core.subscribe();
+
+    let doc = core.create_at_path("/a/b/doc.md").await.unwrap();
+    let a = core.get_by_path("/a/").await.unwrap();
+    let b = core.get_by_path("/a/b/").await.unwrap();
+
+    let events = drain(&mut rx);
+    assert_eq!(events.len(), 3);
+    for id in [a.id, b.id, doc.id] {
+        assert!(events.contains(&Event::FileCreated(id)));
+    }
+}
+
+#[tokio::test]
+async fn sync_new_files() {
+    let c1 = test_core_with_account().await;
+    let doc = c1.create_at_path("/folder/doc.md").await.unwrap();
+    let folder = c1.get_by_path("/folder/").await.unwrap();
+    c1.write_document(doc.id, b"hello").await.unwrap();
+    c1.sync(None).await.unwrap();
+
+    let c2 = test_core_from(&c1).await;
+    let mut rx = c2.subscribe();
+
+    let doc2 = c1.create_at_path("/folder/doc2.md").await.unwrap();
+    c1.sync(None).await.unwrap();
+    c2.sync(None).await.unwrap();
+
+    let events = drain(&mut rx);
+    assert_eq!(events, vec![Event::FileCreated(doc2.id)]);
+    assert!(!events.contains(&Event::FileCreated(folder.id)));
+}
+
+#[tokio::test]
+async fn sync_rename_move_delete() {
+    let c1 = test_core_with_account().await;
+    let root = c1.root().await.unwrap();
+    let folder = c1.create_at_path("/folder/").await.unwrap();
+    let doc = c1.create_at_path("/doc.md").await.unwrap();
+    c1.sync(None).await.unwrap();
+
+    let c2 = test_core_from(&c1).await;
+    let mut rx = c2.subscribe();
+
+    c1.rename_file(&doc.id, "renamed.md").await.unwrap();
+    c1.sync(None).await.unwrap();
+    c2.sync(None).await.unwrap();
+    assert_eq!(
+        drain(&mut rx),
+        vec![Event::FileRenamed { id: doc.id, old: "doc.md".into(), new: "renamed.md".into() }]
+    );
+
+    c1.move_file(&doc.id, &folder.id).await.unwrap();
+    c1.sync(None).await.unwrap();
+    c2.sync(None).await.unwrap();
+    assert_eq!(drain(&mut rx), vec![Event::FileMoved { id: doc.id, from: root.id, to: folder.id }]);
+
+    c1.delete(&folder.id).await.unwrap();
+    c1.sync(None).await.unwrap();
+    c2.sync(None).await.unwrap();
+    let events = drain(&mut rx);
+    assert!(events.contains(&Event::FileDeleted(folder.id)));
+    assert!(!events
+        .iter()
+        .any(|evt| matches!(evt, Event::MetadataChanged(_))));
+}
+
+#[tokio::test]
+async fn sync_share() {
+    let c1 = test_core_with_account().await;
+    let c2 = test_core_with_account().await;
+    let doc = c1.create_at_path("/doc.md").await.unwrap();
+    c1.sync(None).await.unwrap();
+
+    let c1_other = test_core_from(&c1).await;
+    let mut rx = c1_other.subscribe();
+
+    c1.share_file(doc.id, &c2.get_account().unwrap().username, ShareMode::Read)
+        .await
+        .unwrap();
+    c1.sync(None).await.unwrap();
+    c1_other.sync(None).await.unwrap();
+
+    assert_eq!(drain(&mut rx), vec![Event::ShareAdded(doc.id)]);
+}
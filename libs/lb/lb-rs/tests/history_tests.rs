@@ -0,0 +1,90 @@
+use lb_rs::model::errors::LbErrKind;
+use test_utils::*;
+
+#[tokio::test]
+async fn history_lists_synced_versions_and_local_edits() {
+    let c1 = test_core_with_account().await;
+    let doc = c1.create_at_path("/doc.md").await.unwrap();
+    c1.write_document(doc.id, b"one\n").await.unwrap();
+    c1.sync(None).await.unwrap();
+    c1.write_document(doc.id, b"two\n").await.unwrap();
+    c1.sync(None).await.unwrap();
+    c1.write_document(doc.id, b"three\n").await.unwrap();
+
+    let history = c1.get_document_history(doc.id).await.unwrap();
+    assert_eq!(history.iter().map(|v| v.version).collect::<Vec<_>>(), vec![1, 2, 3]);
+    assert_eq!(history.iter().map(|v| v.synced).collect::<Vec<_>>(), vec![true, true, false]);
+    let username = &c1.get_account().unwrap().username;
+    assert!(history.iter().all(|v| &v.last_modified_by == username));
+
+    assert_eq!(c1.read_document_version(doc.id, 1).await.unwrap(), b"one\n");
+    assert_eq!(c1.read_document_version(doc.id, 3).await.unwrap(), b"three\n");
+}
+
+#[tokio::test]
+async fn history_records_pulled_versions_with_their_author() {
+    let c1 = test_core_with_account().await;
+    let doc = c1.create_at_path("/doc.md").await.unwrap();
+    c1.write_document(doc.id, b"one\n").await.unwrap();
+    c1.sync(None).await.unwrap();
+    let c2 = another_client(&c1).await;
+    c2.sync(None).await.unwrap();
+
+    c1.write_document(doc.id, b"two\n").await.unwrap();
+    c1.sync(None).await.unwrap();
+    c2.sync(None).await.unwrap();
+
+    let history = c2.get_document_history(doc.id).await.unwrap();
+    assert_eq!(history.len(), 2);
+    assert!(history.iter().all(|v| v.synced));
+    assert_eq!(c2.read_document_version(doc.id, 1).await.unwrap(), b"one\n");
+    assert_eq!(c2.read_document_version(doc.id, 2).await.unwrap(), b"two\n");
+}
+
+#[tokio::test]
+async fn diff_shows_unsynced_edits() {
+    let c1 = test_core_with_account().await;
+    let doc = c1.create_at_path("/doc.md").await.unwrap();
+    c1.write_document(doc.id, b"one\ntwo\n").await.unwrap();
+    c1.sync(None).await.unwrap();
+    assert_eq!(c1.diff_document(doc.id, None).await.unwrap(), "");
+
+    c1.write_document(doc.id, b"one\n2\n").await.unwrap();
+    let diff = c1.diff_document(doc.id, None).await.unwrap();
+    assert!(diff.contains("-two\n"));
+    assert!(diff.contains("+2\n"));
+    assert!(diff.contains(" one\n"));
+}
+
+#[tokio::test]
+async fn restore_writes_old_version() {
+    let c1 = test_core_with_account().await;
+    let doc = c1.create_at_path("/doc.md").await.unwrap();
+    c1.write_document(doc.id, b"one\n").await.unwrap();
+    c1.sync(None).await.unwrap();
+    c1.write_document(doc.id, b"two\n").await.unwrap();
+    c1.sync(None).await.unwrap();
+
+    c1.restore_document_version(doc.id, 1).await.unwrap();
+    assert_eq!(c1.read_document(doc.id, false).await.unwrap(), b"one\n");
+    c1.sync(None).await.unwrap();
+
+    // cleanup after syncing keeps the versions history refers to
+    assert_eq!(c1.read_document_version(doc.id, 2).await.unwrap(), b"two\n");
+    assert_eq!(c1.get_document_history(doc.id).await.unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn unknown_version() {
+    let c1 = test_core_with_account().await;
+    let doc = c1.create_at_path("/doc.md").await.unwrap();
+    c1.write_document(doc.id, b"one\n").await.unwrap();
+
+    assert_eq!(
+        c1.restore_document_version(doc.id, 7)
+            .await
+            .unwrap_err()
+            .kind,
+        LbErrKind::DocumentVersionNonexistent
+    );
+}
@@ -31,6 +31,7 @@ async fn import_file_successfully() {
                 assert!(disk_path.exists());
             }
             ImportStatus::FinishedItem(_metadata) => {}
+            ImportStatus::Unconverted(_) => {}
         }
     };
 
@@ -0,0 +1,188 @@
+use std::cell::RefCell;
+use std::fs;
+use std::path::Path;
+
+use lb_rs::model::errors::LbErrKind;
+use lb_rs::service::import_export::ImportStatus;
+use lb_rs::service::importers::{ImportFormat, Unconverted};
+use lb_rs::Lb;
+use test_utils::test_core_with_account;
+
+async fn import(core: &Lb, format: ImportFormat, source: &Path) -> Vec<Unconverted> {
+    let root = core.root().await.unwrap();
+    let unconverted = RefCell::new(vec![]);
+    core.import_from(format, source, root.id, &|status| {
+        if let ImportStatus::Unconverted(item) = status {
+            unconverted.borrow_mut().push(item);
+        }
+    })
+    .await
+    .unwrap();
+    unconverted.into_inner()
+}
+
+async fn read(core: &Lb, path: &str) -> String {
+    let file = core.get_by_path(path).await.unwrap();
+    String::from_utf8(core.read_document(file.id, false).await.unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn obsidian_wikilinks_become_relative_links() {
+    let core = test_core_with_account().await;
+    let tmp = tempfile::tempdir().unwrap();
+    let vault = tmp.path().join("vault");
+    fs::create_dir_all(vault.join("projects")).unwrap();
+    fs::create_dir_all(vault.join("attachments")).unwrap();
+    fs::create_dir_all(vault.join(".obsidian")).unwrap();
+    fs::write(
+        vault.join("Home.md"),
+        "See [[Ideas]] and [[projects/Plan|the plan]].\n![[pic.png]]\n[[Nowhere]]\n",
+    )
+    .unwrap();
+    fs::write(vault.join("Ideas.md"), "ideas\n").unwrap();
+    fs::write(vault.join("projects/Plan.md"), "[home](../Home.md)\n").unwrap();
+    fs::write(vault.join("attachments/pic.png"), b"png").unwrap();
+    fs::write(vault.join(".obsidian/app.json"), "{}").unwrap();
+
+    let unconverted = import(&core, ImportFormat::Obsidian, &vault).await;
+
+    assert_eq!(
+        read(&core, "/vault/Home.md").await,
+        "See [Ideas](../Ideas.md) and [the plan](../projects/Plan.md).\n\
+         ![pic.png](../attachments/pic.png)\n[[Nowhere]]\n"
+    );
+    assert_eq!(read(&core, "/vault/projects/Plan.md").await, "[home](../../Home.md)\n");
+    assert!(core.get_by_path("/vault/.obsidian").await.is_err());
+    assert_eq!(unconverted.len(), 1);
+    assert_eq!(unconverted[0].source, vault.join("Home.md"));
+}
+
+#[tokio::test]
+async fn notion_ids_are_stripped() {
+    let core = test_core_with_account().await;
+    let tmp = tempfile::tempdir().unwrap();
+    let export = tmp.path().join("notion");
+    let page = "Roadmap 0123456789abcdef0123456789abcdef";
+    fs::create_dir_all(export.join(page)).unwrap();
+    fs::write(
+        export.join(format!("{page}.md")),
+        "[Q1](Roadmap%200123456789abcdef0123456789abcdef/Q1%20fedcba9876543210fedcba9876543210.md)\n",
+    )
+    .unwrap();
+    fs::write(
+        export
+            .join(page)
+            .join("Q1 fedcba9876543210fedcba9876543210.md"),
+        "goals\n",
+    )
+    .unwrap();
+
+    let unconverted = import(&core, ImportFormat::Notion, &export).await;
+
+    assert_eq!(read(&core, "/notion/Roadmap.md").await, "[Q1](../Roadmap/Q1.md)\n");
+    assert_eq!(read(&core, "/notion/Roadmap/Q1.md").await, "goals\n");
+    assert!(unconverted.is_empty());
+}
+
+#[tokio::test]
+async fn evernote_notes_become_markdown_with_attachments() {
+    let core = test_core_with_account().await;
+    let tmp = tempfile::tempdir().unwrap();
+    let enex = tmp.path().join("Travel.enex");
+    fs::write(
+        &enex,
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE en-export SYSTEM "http://xml.evernote.com/pub/evernote-export4.dtd">
+<en-export>
+  <note>
+    <title>Trip</title>
+    <content><![CDATA[<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE en-note SYSTEM "http://xml.evernote.com/pub/enml2.dtd">
+<en-note><div>Packing&nbsp;<b>list</b></div><ul><li>tent</li><li>stove</li></ul><en-media type="image/png" hash="a4f84feadf4cad85108478e074357b33"/><en-crypt>secret</en-crypt></en-note>]]></content>
+    <tag>travel</tag>
+    <resource>
+      <data encoding="base64">bm90IHJlYWxseSBhIHBuZw==</data>
+      <mime>image/png</mime>
+      <resource-attributes><file-name>map.png</file-name></resource-attributes>
+    </resource>
+  </note>
+</en-export>
+"#,
+    )
+    .unwrap();
+
+    let unconverted = import(&core, ImportFormat::Evernote, &enex).await;
+
+    assert_eq!(
+        read(&core, "/Travel/Trip.md").await,
+        "Packing **list**\n\n- tent\n- stove\n\n![map.png](../Trip/map.png)\n\n#travel\n"
+    );
+    assert_eq!(read(&core, "/Travel/Trip/map.png").await, "not really a png");
+    assert_eq!(unconverted.len(), 1);
+    assert_eq!(unconverted[0].detail, "encrypted text in Trip");
+}
+
+#[tokio::test]
+async fn bear_bundles_bring_their_assets() {
+    let core = test_core_with_account().await;
+    let tmp = tempfile::tempdir().unwrap();
+    let bear = tmp.path().join("bear");
+    fs::create_dir_all(bear.join("Groceries.bearnote/assets")).unwrap();
+    fs::create_dir_all(bear.join("Recipes.bearnote")).unwrap();
+    fs::write(
+        bear.join("Groceries.bearnote/text.markdown"),
+        "Buy [[recipes]]\n![](assets/list%20photo.jpg)\n",
+    )
+    .unwrap();
+    fs::write(bear.join("Groceries.bearnote/assets/list photo.jpg"), "jpg").unwrap();
+    fs::write(bear.join("Recipes.bearnote/text.markdown"), "Soup\n").unwrap();
+
+    let unconverted = import(&core, ImportFormat::Bear, &bear).await;
+
+    assert_eq!(
+        read(&core, "/bear/Groceries.md").await,
+        "Buy [recipes](../Recipes.md)\n![](<../Groceries/list photo.jpg>)\n"
+    );
+    assert_eq!(read(&core, "/bear/Groceries/list photo.jpg").await, "jpg");
+    assert_eq!(read(&core, "/bear/Recipes.md").await, "Soup\n");
+    assert!(unconverted.is_empty());
+}
+
+#[tokio::test]
+async fn import_renames_on_conflict() {
+    let core = test_core_with_account().await;
+    core.create_at_path("/vault/").await.unwrap();
+    let tmp = tempfile::tempdir().unwrap();
+    let vault = tmp.path().join("vault");
+    fs::create_dir_all(&vault).unwrap();
+    fs::write(vault.join("note.md"), "note\n").unwrap();
+
+    import(&core, ImportFormat::Obsidian, &vault).await;
+
+    assert_eq!(read(&core, "/vault-1/note.md").await, "note\n");
+}
+
+#[tokio::test]
+async fn import_needs_a_source_and_a_folder() {
+    let core = test_core_with_account().await;
+    let root = core.root().await.unwrap();
+    let tmp = tempfile::tempdir().unwrap();
+
+    let result = core
+        .import_from(ImportFormat::Obsidian, &tmp.path().join("missing"), root.id, &|_| {})
+        .await;
+    assert_eq!(result.unwrap_err().kind, LbErrKind::DiskPathInvalid);
+
+    let doc = core.create_at_path("/doc.md").await.unwrap();
+    let result = core
+        .import_from(ImportFormat::Obsidian, tmp.path(), doc.id, &|_| {})
+        .await;
+    assert!(result.is_err());
+}
+
+#[test]
+fn import_format_parses() {
+    assert_eq!("Obsidian".parse::<ImportFormat>(), Ok(ImportFormat::Obsidian));
+    assert_eq!("bear".parse::<ImportFormat>(), Ok(ImportFormat::Bear));
+    assert!("onenote".parse::<ImportFormat>().is_err());
+}
@@ -0,0 +1,148 @@
+use lb_rs::service::mirror::MIRROR_STATE_FILE;
+use std::fs;
+use std::thread;
+use std::time::Duration;
+use test_utils::{delete_path, test_core_with_account, write_path};
+
+/// so a write right after a mirror changes the file's mtime on filesystems with coarse timestamps
+fn tick() {
+    thread::sleep(Duration::from_millis(20));
+}
+
+#[tokio::test]
+async fn mirror_pulls_and_pushes() {
+    let core = test_core_with_account().await;
+    let folder = core.create_at_path("/notes/").await.unwrap();
+    core.create_at_path("/notes/a.md").await.unwrap();
+    write_path(&core, "/notes/a.md", b"from lockbook")
+        .await
+        .unwrap();
+    core.create_at_path("/notes/sub/b.md").await.unwrap();
+
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+
+    let report = core.mirror(folder.id, dir).await.unwrap();
+    assert_eq!(report.pulled.len(), 3);
+    assert_eq!(fs::read(dir.join("a.md")).unwrap(), b"from lockbook");
+    assert!(dir.join("sub").join("b.md").exists());
+
+    tick();
+    fs::write(dir.join("a.md"), b"from disk").unwrap();
+    fs::write(dir.join("sub").join("c.md"), b"new").unwrap();
+
+    let report = core.mirror(folder.id, dir).await.unwrap();
+    assert_eq!(report.pushed, vec!["a.md".to_string(), "sub/c.md".to_string()]);
+    assert_eq!(
+        core.read_document(core.get_by_path("/notes/a.md").await.unwrap().id, false)
+            .await
+            .unwrap(),
+        b"from disk"
+    );
+    core.get_by_path("/notes/sub/c.md").await.unwrap();
+
+    assert!(core.mirror(folder.id, dir).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn mirror_propagates_deletes() {
+    let core = test_core_with_account().await;
+    let folder = core.create_at_path("/notes/").await.unwrap();
+    core.create_at_path("/notes/a.md").await.unwrap();
+    core.create_at_path("/notes/b.md").await.unwrap();
+
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    core.mirror(folder.id, dir).await.unwrap();
+
+    delete_path(&core, "/notes/a.md").await.unwrap();
+    fs::remove_file(dir.join("b.md")).unwrap();
+
+    let report = core.mirror(folder.id, dir).await.unwrap();
+    assert_eq!(report.deleted_locally, vec!["a.md".to_string()]);
+    assert_eq!(report.deleted_remotely, vec!["b.md".to_string()]);
+    assert!(!dir.join("a.md").exists());
+    assert!(core.get_by_path("/notes/b.md").await.is_err());
+}
+
+#[tokio::test]
+async fn mirror_keeps_deleted_folders_with_remote_changes() {
+    let core = test_core_with_account().await;
+    let folder = core.create_at_path("/notes/").await.unwrap();
+    core.create_at_path("/notes/sub/a.md").await.unwrap();
+    core.create_at_path("/notes/sub/b.md").await.unwrap();
+
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    core.mirror(folder.id, dir).await.unwrap();
+
+    write_path(&core, "/notes/sub/a.md", b"from lockbook")
+        .await
+        .unwrap();
+    fs::remove_dir_all(dir.join("sub")).unwrap();
+
+    let report = core.mirror(folder.id, dir).await.unwrap();
+    assert_eq!(report.pulled, vec!["sub".to_string(), "sub/a.md".to_string()]);
+    assert_eq!(report.deleted_remotely, vec!["sub/b.md".to_string()]);
+    assert_eq!(fs::read(dir.join("sub").join("a.md")).unwrap(), b"from lockbook");
+    assert!(core.get_by_path("/notes/sub/b.md").await.is_err());
+}
+
+#[tokio::test]
+async fn mirror_keeps_both_sides_of_a_conflict() {
+    let core = test_core_with_account().await;
+    let folder = core.create_at_path("/notes/").await.unwrap();
+    core.create_at_path("/notes/a.md").await.unwrap();
+
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    core.mirror(folder.id, dir).await.unwrap();
+
+    tick();
+    write_path(&core, "/notes/a.md", b"from lockbook")
+        .await
+        .unwrap();
+    fs::write(dir.join("a.md"), b"from disk").unwrap();
+
+    let report = core.mirror(folder.id, dir).await.unwrap();
+    assert_eq!(report.conflicts, vec!["a.md".to_string()]);
+    assert_eq!(report.pushed.len(), 1);
+    assert_eq!(fs::read(dir.join("a.md")).unwrap(), b"from lockbook");
+
+    let copy = core
+        .get_children(&folder.id)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|file| file.name.starts_with("a (conflicted copy "))
+        .unwrap();
+    assert_eq!(core.read_document(copy.id, false).await.unwrap(), b"from disk");
+}
+
+#[tokio::test]
+async fn mirror_leaves_state_alone_when_nothing_changed() {
+    let core = test_core_with_account().await;
+    let folder = core.create_at_path("/notes/").await.unwrap();
+    core.create_at_path("/notes/a.md").await.unwrap();
+
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path();
+    core.mirror(folder.id, dir).await.unwrap();
+
+    let state = dir.join(MIRROR_STATE_FILE);
+    let written = fs::metadata(&state).unwrap().modified().unwrap();
+    tick();
+    assert!(core.mirror(folder.id, dir).await.unwrap().is_empty());
+    assert_eq!(fs::metadata(&state).unwrap().modified().unwrap(), written);
+}
+
+#[tokio::test]
+async fn mirror_is_tied_to_one_folder() {
+    let core = test_core_with_account().await;
+    let notes = core.create_at_path("/notes/").await.unwrap();
+    let drafts = core.create_at_path("/drafts/").await.unwrap();
+
+    let tmp = tempfile::tempdir().unwrap();
+    core.mirror(notes.id, tmp.path()).await.unwrap();
+    assert!(core.mirror(drafts.id, tmp.path()).await.is_err());
+}
@@ -0,0 +1,149 @@
+use lb_rs::model::api::AccountState;
+use lb_rs::model::crypto::{AESEncrypted, AESKey};
+use lb_rs::model::errors::LbErrKind;
+use lb_rs::model::file::ShareMode;
+use lb_rs::model::publication::Publication;
+use lb_rs::model::symkey;
+use serde::Deserialize;
+use std::time::Duration;
+use test_utils::*;
+
+#[derive(Deserialize)]
+struct PublishedContent {
+    value: String,
+    nonce: String,
+}
+
+/// Does what the publish page does in the browser
+async fn open(link: &str) -> Option<Publication> {
+    let (url, key) = link.split_once('#').unwrap();
+    let response = reqwest::get(format!("{url}/content")).await.unwrap();
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return None;
+    }
+    let content: PublishedContent = response.json().await.unwrap();
+
+    let key: AESKey = base64::decode_config(key, base64::URL_SAFE_NO_PAD)
+        .unwrap()
+        .try_into()
+        .unwrap();
+    let encrypted: AESEncrypted<Vec<u8>> = AESEncrypted::new(
+        base64::decode(content.value).unwrap(),
+        base64::decode(content.nonce).unwrap(),
+    );
+    let json = symkey::decrypt(&key, &encrypted).unwrap();
+    Some(serde_json::from_slice(&json).unwrap())
+}
+
+#[tokio::test]
+async fn publish_folder() {
+    let core = test_core_with_account().await;
+    let folder = core.create_at_path("runbook/").await.unwrap();
+    core.create_at_path("runbook/steps.md").await.unwrap();
+    core.create_at_path("runbook/more/contacts.md")
+        .await
+        .unwrap();
+    write_path(&core, "runbook/steps.md", b"1. turn it off")
+        .await
+        .unwrap();
+    write_path(&core, "runbook/more/contacts.md", b"call us")
+        .await
+        .unwrap();
+    core.sync(None).await.unwrap();
+
+    let link = core.publish(folder.id, None).await.unwrap();
+    assert!(link.starts_with(&url()));
+
+    let publication = open(&link).await.unwrap();
+    assert_eq!(publication.name, "runbook");
+    let paths: Vec<&str> = publication.files.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(paths, vec!["more/contacts.md", "steps.md"]);
+    assert_eq!(base64::decode(&publication.files[1].content).unwrap(), b"1. turn it off");
+}
+
+#[tokio::test]
+async fn republish_replaces_link() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("note.md").await.unwrap();
+    core.sync(None).await.unwrap();
+
+    let old = core.publish(doc.id, None).await.unwrap();
+    let new = core.publish(doc.id, None).await.unwrap();
+    assert!(open(&old).await.is_none());
+    assert!(open(&new).await.is_some());
+
+    core.unpublish(doc.id).await.unwrap();
+    assert!(open(&new).await.is_none());
+    assert_eq!(core.unpublish(doc.id).await.unwrap_err().kind, LbErrKind::PublicationNonexistent);
+}
+
+#[tokio::test]
+async fn publish_expires() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("note.md").await.unwrap();
+    core.sync(None).await.unwrap();
+
+    let link = core
+        .publish(doc.id, Some(Duration::from_millis(1)))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert!(open(&link).await.is_none());
+}
+
+#[tokio::test]
+async fn publish_deleted() {
+    let core = test_core_with_account().await;
+    let doc = core.create_at_path("note.md").await.unwrap();
+    core.sync(None).await.unwrap();
+
+    let link = core.publish(doc.id, None).await.unwrap();
+    core.delete(&doc.id).await.unwrap();
+    core.sync(None).await.unwrap();
+    assert!(open(&link).await.is_none());
+    assert_eq!(core.publish(doc.id, None).await.unwrap_err().kind, LbErrKind::FileNonexistent);
+}
+
+#[tokio::test]
+async fn publish_not_owned() {
+    let owner = test_core_with_account().await;
+    let sharee = test_core_with_account().await;
+    let doc = owner.create_at_path("note.md").await.unwrap();
+    owner
+        .share_file(doc.id, &sharee.get_account().unwrap().username, ShareMode::Write)
+        .await
+        .unwrap();
+    owner.sync(None).await.unwrap();
+    sharee.sync(None).await.unwrap();
+
+    let result = sharee.publish(doc.id, None).await;
+    assert_eq!(result.unwrap_err().kind, LbErrKind::InsufficientPermission);
+}
+
+#[tokio::test]
+#[ignore]
+async fn publish_restricted_owner() {
+    let admin_core = test_core().await;
+    admin_core
+        .create_account("admin1", &url(), false)
+        .await
+        .unwrap();
+
+    let core = test_core_with_account().await;
+    let username = core.get_account().unwrap().username.clone();
+    let doc = core.create_at_path("note.md").await.unwrap();
+    core.sync(None).await.unwrap();
+    let link = core.publish(doc.id, None).await.unwrap();
+
+    admin_core
+        .set_account_state(&username, AccountState::Suspended)
+        .await
+        .unwrap();
+    assert!(open(&link).await.is_none());
+
+    admin_core
+        .set_account_state(&username, AccountState::Active)
+        .await
+        .unwrap();
+    assert!(open(&link).await.is_some());
+}
@@ -0,0 +1,169 @@
+use std::fs;
+
+use lb_rs::model::errors::{LbErrKind, Warning};
+use lb_rs::service::render::Theme;
+use lb_rs::Lb;
+use test_utils::test_core_with_account;
+
+/// A red stroke, which lockbook draws in a darker red in dark mode
+const DRAWING: &str = "<svg xmlns='http://www.w3.org/2000/svg'>\
+    <path d='M 10 10 C 20 20 30 20 40 10' stroke-width='3' stroke='rgba(218,21,21,1)' \
+    fill='none' id='stroke'/></svg>";
+
+async fn write(core: &Lb, path: &str, content: &str) {
+    let file = core.create_at_path(path).await.unwrap();
+    core.write_document(file.id, content.as_bytes())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn site_links_point_at_rendered_pages() {
+    let core = test_core_with_account().await;
+    write(&core, "/handbook/welcome.md", "# Hi\n[setup](../guides/setup.md#laptop)\n").await;
+    write(
+        &core,
+        "/handbook/guides/setup.md",
+        "![plan](../../plan.svg)\n[web](https://lockbook.net)\n",
+    )
+    .await;
+    write(&core, "/handbook/plan.svg", DRAWING).await;
+    write(&core, "/elsewhere.md", "not exported\n").await;
+    let handbook = core.get_by_path("/handbook/").await.unwrap();
+    let tmp = tempfile::tempdir().unwrap();
+
+    core.export_site(handbook.id, tmp.path().to_path_buf(), Theme::Light, &None::<fn(_)>)
+        .await
+        .unwrap();
+
+    let welcome = fs::read_to_string(tmp.path().join("welcome.html")).unwrap();
+    assert!(welcome.contains("<h1>Hi</h1>"));
+    assert!(welcome.contains("href=\"guides/setup.html#laptop\""));
+    assert!(welcome.contains("href=\"index.html\""));
+
+    let setup = fs::read_to_string(tmp.path().join("guides/setup.html")).unwrap();
+    assert!(setup.contains("src=\"../plan.svg\""));
+    assert!(setup.contains("href=\"https://lockbook.net\""));
+    assert!(setup.contains("href=\"../index.html\""));
+
+    let index = fs::read_to_string(tmp.path().join("index.html")).unwrap();
+    assert!(index.contains("<a href=\"welcome.html\">welcome</a>"));
+    assert!(index.contains("<a href=\"guides/setup.html\">setup</a>"));
+    assert!(index.contains("<a href=\"plan.svg\">plan</a>"));
+    assert!(!tmp.path().join("elsewhere.html").exists());
+}
+
+#[tokio::test]
+async fn site_keeps_its_own_index() {
+    let core = test_core_with_account().await;
+    write(&core, "/site/index.md", "home\n").await;
+    let site = core.get_by_path("/site/").await.unwrap();
+    let tmp = tempfile::tempdir().unwrap();
+
+    core.export_site(site.id, tmp.path().to_path_buf(), Theme::Light, &None::<fn(_)>)
+        .await
+        .unwrap();
+
+    let index = fs::read_to_string(tmp.path().join("index.html")).unwrap();
+    assert!(index.contains("<p>home</p>"));
+    assert!(!index.contains("<nav>"));
+}
+
+#[tokio::test]
+async fn drawings_are_flattened_to_the_theme() {
+    let core = test_core_with_account().await;
+    write(&core, "/drawings/plan.svg", DRAWING).await;
+    let drawings = core.get_by_path("/drawings/").await.unwrap();
+    let light = tempfile::tempdir().unwrap();
+    let dark = tempfile::tempdir().unwrap();
+
+    core.export_site(drawings.id, light.path().to_path_buf(), Theme::Light, &None::<fn(_)>)
+        .await
+        .unwrap();
+    core.export_site(drawings.id, dark.path().to_path_buf(), Theme::Dark, &None::<fn(_)>)
+        .await
+        .unwrap();
+
+    let light = fs::read_to_string(light.path().join("plan.svg")).unwrap();
+    let dark = fs::read_to_string(dark.path().join("plan.svg")).unwrap();
+    assert!(light.contains("stroke='rgb(218,21,21)'"));
+    assert!(light.contains("fill='rgb(255,255,255)'"));
+    assert!(dark.contains("stroke='rgb(174,33,33)'"));
+    assert!(dark.contains("fill='rgb(30,30,30)'"));
+}
+
+#[tokio::test]
+async fn documents_render_to_pdf() {
+    let core = test_core_with_account().await;
+    write(&core, "/notes/trip.md", "# Trip\n\n- tent\n- stove\n\n![plan](../plan.svg)\n").await;
+    write(&core, "/notes/plan.svg", DRAWING).await;
+    let trip = core.get_by_path("/notes/trip.md").await.unwrap();
+    let plan = core.get_by_path("/notes/plan.svg").await.unwrap();
+    let tmp = tempfile::tempdir().unwrap();
+
+    core.export_pdf(trip.id, tmp.path().join("trip.pdf"), Theme::Light)
+        .await
+        .unwrap();
+    core.export_pdf(plan.id, tmp.path().join("plan.pdf"), Theme::Dark)
+        .await
+        .unwrap();
+
+    assert!(fs::read(tmp.path().join("trip.pdf"))
+        .unwrap()
+        .starts_with(b"%PDF"));
+    assert!(fs::read(tmp.path().join("plan.pdf"))
+        .unwrap()
+        .starts_with(b"%PDF"));
+}
+
+#[tokio::test]
+async fn pdf_reports_characters_it_cannot_show() {
+    let core = test_core_with_account().await;
+    write(&core, "/notes/done.md", "café ✓ 日本\n").await;
+    let done = core.get_by_path("/notes/done.md").await.unwrap();
+    let tmp = tempfile::tempdir().unwrap();
+
+    let warnings = core
+        .export_pdf(done.id, tmp.path().join("done.pdf"), Theme::Light)
+        .await
+        .unwrap();
+
+    match warnings.as_slice() {
+        [Warning::UnprintableCharacters(id, chars)] => {
+            assert_eq!(*id, done.id);
+            assert_eq!(chars, &['✓', '日', '本']);
+        }
+        warnings => panic!("unexpected warnings: {warnings:?}"),
+    }
+}
+
+#[tokio::test]
+async fn render_needs_the_right_kind_of_file() {
+    let core = test_core_with_account().await;
+    let folder = core.create_at_path("/folder/").await.unwrap();
+    let doc = core.create_at_path("/folder/doc.md").await.unwrap();
+    let tmp = tempfile::tempdir().unwrap();
+
+    let result = core
+        .export_pdf(folder.id, tmp.path().join("folder.pdf"), Theme::Light)
+        .await;
+    assert_eq!(result.unwrap_err().kind, LbErrKind::FileNotDocument);
+
+    let result = core
+        .export_pdf(doc.id, tmp.path().to_path_buf(), Theme::Light)
+        .await;
+    assert_eq!(result.unwrap_err().kind, LbErrKind::DiskPathInvalid);
+
+    fs::write(tmp.path().join("file"), "").unwrap();
+    let result = core
+        .export_site(folder.id, tmp.path().join("file"), Theme::Light, &None::<fn(_)>)
+        .await;
+    assert_eq!(result.unwrap_err().kind, LbErrKind::DiskPathInvalid);
+}
+
+#[test]
+fn theme_parses() {
+    assert_eq!("Dark".parse::<Theme>(), Ok(Theme::Dark));
+    assert_eq!("light".parse::<Theme>(), Ok(Theme::Light));
+    assert!("sepia".parse::<Theme>().is_err());
+}
@@ -0,0 +1,147 @@
+use lb_rs::model::api::AwaitUpdatesRequest;
+use lb_rs::model::clock::get_time;
+use lb_rs::model::core_config::Config;
+use lb_rs::model::work_unit::WorkUnit;
+use lb_rs::Lb;
+use std::time::Duration;
+use test_utils::*;
+
+async fn background_core() -> Lb {
+    let core = Lb::init(Config { background_work: true, ..test_config() })
+        .await
+        .unwrap();
+    core.create_account(&random_name(), &url(), false)
+        .await
+        .unwrap();
+    core
+}
+
+#[tokio::test]
+async fn sync_now_reports_status() {
+    let core = test_core_with_account().await;
+    let status = core.background_sync_status();
+
+    core.create_at_path("/doc.md").await.unwrap();
+    core.sync_now(None).await.unwrap();
+
+    let status = status.borrow();
+    assert!(matches!(status.last_sync, Some(Ok(_))));
+    assert!(status.last_sync_at.is_some());
+    assert!(!status.offline);
+}
+
+#[tokio::test]
+async fn pause_and_resume() {
+    let core = test_core_with_account().await;
+    let status = core.background_sync_status();
+
+    core.pause_sync();
+    assert!(status.borrow().paused);
+
+    // explicit syncs still go through while paused
+    core.sync_now(None).await.unwrap();
+
+    core.resume_sync();
+    assert!(!status.borrow().paused);
+}
+
+#[tokio::test]
+async fn local_writes_are_synced() {
+    let core = background_core().await;
+    let mut status = core.background_sync_status();
+
+    let doc = core.create_at_path("/doc.md").await.unwrap();
+    core.write_document(doc.id, b"hello").await.unwrap();
+
+    tokio::time::timeout(Duration::from_secs(30), async {
+        loop {
+            status.changed().await.unwrap();
+            let local_changes = core
+                .calculate_work()
+                .await
+                .unwrap()
+                .work_units
+                .into_iter()
+                .filter(|wu| matches!(wu, WorkUnit::LocalChange(_)))
+                .count();
+            if local_changes == 0 {
+                break;
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    let other = test_core_from(&core).await;
+    assert_eq!(other.read_document(doc.id, false).await.unwrap(), b"hello");
+}
+
+#[tokio::test]
+async fn paused_scheduler_leaves_local_writes() {
+    let core = background_core().await;
+    // pausing doesn't stop a sync that's already running, like the first one
+    core.background_sync_status()
+        .wait_for(|status| status.last_sync.is_some())
+        .await
+        .unwrap();
+    core.pause_sync();
+
+    core.create_at_path("/doc.md").await.unwrap();
+    tokio::time::sleep(Duration::from_secs(5)).await;
+
+    let local_changes = core
+        .calculate_work()
+        .await
+        .unwrap()
+        .work_units
+        .into_iter()
+        .filter(|wu| matches!(wu, WorkUnit::LocalChange(_)))
+        .count();
+    assert_eq!(local_changes, 1);
+}
+
+#[tokio::test]
+async fn await_updates_returns_on_remote_change() {
+    let c1 = test_core_with_account().await;
+    c1.sync(None).await.unwrap();
+    let c2 = test_core_from(&c1).await;
+
+    let since_metadata_version = get_time().0 as u64;
+    let account = c1.get_account().unwrap().clone();
+    let client = c1.client.clone();
+    let waiting = tokio::spawn(async move {
+        client
+            .request(&account, AwaitUpdatesRequest { since_metadata_version })
+            .await
+            .unwrap()
+    });
+
+    c2.create_at_path("/doc.md").await.unwrap();
+    c2.sync(None).await.unwrap();
+
+    let response = tokio::time::timeout(Duration::from_secs(10), waiting)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(response.updated);
+}
+
+#[tokio::test]
+async fn remote_changes_are_pulled() {
+    let c1 = background_core().await;
+    let mut status = c1.background_sync_status();
+    c1.sync_now(None).await.unwrap();
+    let c2 = test_core_from(&c1).await;
+
+    let doc = c2.create_at_path("/doc.md").await.unwrap();
+    c2.sync(None).await.unwrap();
+
+    // well under the default sync interval, only a push from the server gets us there in time
+    tokio::time::timeout(Duration::from_secs(30), async {
+        while c1.get_file_by_id(doc.id).await.is_err() {
+            status.changed().await.unwrap();
+        }
+    })
+    .await
+    .unwrap();
+}
@@ -0,0 +1,80 @@
+use lb_rs::model::errors::LbErrKind;
+use std::fs;
+use test_utils::*;
+
+#[tokio::test]
+async fn takeout_round_trip() {
+    let core = test_core_with_account().await;
+    for path in ["a.md", "folder/b.md", "deleted.md"] {
+        core.create_at_path(path).await.unwrap();
+    }
+    write_path(&core, "a.md", b"a").await.unwrap();
+    write_path(&core, "folder/b.md", b"b").await.unwrap();
+    write_path(&core, "deleted.md", b"gone").await.unwrap();
+    core.sync(None).await.unwrap();
+    delete_path(&core, "deleted.md").await.unwrap();
+    core.sync(None).await.unwrap();
+
+    let tmp = tempfile::tempdir().unwrap();
+    let archive = tmp.path().join("takeout");
+    let summary = core.takeout(&archive).await.unwrap();
+    assert_eq!(summary.files, 5);
+    assert_eq!(summary.documents, 2);
+
+    let restored = test_core().await;
+    let key = core.export_account_private_key().unwrap();
+    restored.import_takeout(&key, &archive).await.unwrap();
+
+    assert_eq!(restored.get_account().unwrap().username, core.get_account().unwrap().username);
+    assert_eq!(restored.list_paths(None).await.unwrap().len(), 4);
+    let b = restored.get_by_path("folder/b.md").await.unwrap();
+    assert_eq!(restored.read_document(b.id, false).await.unwrap(), b"b");
+
+    // picks up where the archive left off
+    restored.sync(None).await.unwrap();
+    assert!(get_dirty_ids(&restored, true).await.is_empty());
+    assert_eq!(restored.list_paths(None).await.unwrap().len(), 4);
+}
+
+#[tokio::test]
+async fn takeout_dest_taken() {
+    let core = test_core_with_account().await;
+    let tmp = tempfile::tempdir().unwrap();
+
+    let result = core.takeout(tmp.path()).await;
+    assert_eq!(result.unwrap_err().kind, LbErrKind::DiskPathTaken);
+}
+
+#[tokio::test]
+async fn takeout_truncated() {
+    let core = test_core_with_account().await;
+    core.create_at_path("a.md").await.unwrap();
+    core.sync(None).await.unwrap();
+
+    let tmp = tempfile::tempdir().unwrap();
+    let archive = tmp.path().join("takeout");
+    core.takeout(&archive).await.unwrap();
+    let bytes = fs::read(&archive).unwrap();
+    fs::write(&archive, &bytes[..bytes.len() - 10]).unwrap();
+
+    let restored = test_core().await;
+    let key = core.export_account_private_key().unwrap();
+    let result = restored.import_takeout(&key, &archive).await;
+    assert_eq!(result.unwrap_err().kind, LbErrKind::TakeoutCorrupted);
+    assert!(restored.get_account().is_err());
+}
+
+#[tokio::test]
+async fn takeout_wrong_key() {
+    let core = test_core_with_account().await;
+    let other = test_core_with_account().await;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let archive = tmp.path().join("takeout");
+    core.takeout(&archive).await.unwrap();
+
+    let restored = test_core().await;
+    let key = other.export_account_private_key().unwrap();
+    let result = restored.import_takeout(&key, &archive).await;
+    assert_eq!(result.unwrap_err().kind, LbErrKind::UsernamePublicKeyMismatch);
+}
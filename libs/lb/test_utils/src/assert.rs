@@ -224,6 +224,7 @@ pub async fn server_work_paths(core: &Lb, expected_paths: &[&'static str]) {
             account,
             GetUpdatesRequest {
                 since_metadata_version: db.last_synced.get().copied().unwrap_or_default() as u64,
+                ..Default::default()
             },
         )
         .await
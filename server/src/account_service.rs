@@ -3,6 +3,7 @@ use crate::billing::billing_model::BillingPlatform;
 use crate::billing::google_play_client::GooglePlayClient;
 use crate::billing::stripe_client::StripeClient;
 use crate::document_service::DocumentService;
+use crate::publication_service::{unindex_publication, PUBLICATION_HMAC};
 use crate::schema::{Account, ServerDb};
 use crate::utils::username_is_valid;
 use crate::ServerError::ClientError;
@@ -11,20 +12,22 @@ use db_rs::Db;
 use lb_rs::model::account::Username;
 use lb_rs::model::api::NewAccountError::{FileIdTaken, PublicKeyTaken, UsernameTaken};
 use lb_rs::model::api::{
-    AccountFilter, AccountIdentifier, AccountInfo, AdminDisappearAccountError,
+    AccountFilter, AccountIdentifier, AccountInfo, AccountState, AdminDisappearAccountError,
     AdminDisappearAccountRequest, AdminGetAccountInfoError, AdminGetAccountInfoRequest,
     AdminGetAccountInfoResponse, AdminListUsersError, AdminListUsersRequest,
-    AdminListUsersResponse, DeleteAccountError, DeleteAccountRequest, FileUsage, GetPublicKeyError,
-    GetPublicKeyRequest, GetPublicKeyResponse, GetUsageError, GetUsageRequest, GetUsageResponse,
-    GetUsernameError, GetUsernameRequest, GetUsernameResponse, NewAccountError, NewAccountRequest,
-    NewAccountResponse, PaymentPlatform, METADATA_FEE,
+    AdminListUsersResponse, AdminSetAccountStateError, AdminSetAccountStateRequest,
+    AdminSetAccountStateResponse, AdminSetDataCapError, AdminSetDataCapRequest,
+    AdminSetDataCapResponse, DeleteAccountError, DeleteAccountRequest, FileUsage,
+    GetPublicKeyError, GetPublicKeyRequest, GetPublicKeyResponse, GetUsageError, GetUsageRequest,
+    GetUsageResponse, GetUsernameError, GetUsernameRequest, GetUsernameResponse, NewAccountError,
+    NewAccountRequest, NewAccountResponse, PaymentPlatform, METADATA_FEE,
 };
 use lb_rs::model::clock::get_time;
 use lb_rs::model::file_like::FileLike;
 use lb_rs::model::file_metadata::Owner;
 use lb_rs::model::lazy::LazyTree;
 use lb_rs::model::server_file::IntoServerFile;
-use lb_rs::model::server_tree::ServerTree;
+use lb_rs::model::server_tree::{ServerTree, ServerTreeView};
 use lb_rs::model::tree_like::TreeLike;
 use lb_rs::model::usage::bytes_to_human;
 use libsecp256k1::PublicKey;
@@ -63,7 +66,8 @@ where
         let now = get_time().0 as u64;
         let root = root.add_time(now);
 
-        let mut db = self.index_db.lock().await;
+        let _owner_guards = self.owner_locks.lock([Owner(request.public_key)]).await;
+        let mut db = self.index_db.write().await;
         let handle = db.begin_transaction()?;
 
         if db.accounts.get().contains_key(&Owner(request.public_key)) {
@@ -92,6 +96,9 @@ where
         db.shared_files.create_key(owner)?;
         db.file_children.create_key(*root.id())?;
         db.metas.insert(*root.id(), root.clone())?;
+        self.version_index
+            .lock()?
+            .record(db.metas.get(), db.file_children.get(), root.id());
 
         handle.drop_safely()?;
 
@@ -109,7 +116,7 @@ where
         &self, username: &str,
     ) -> Result<GetPublicKeyResponse, ServerError<GetPublicKeyError>> {
         self.index_db
-            .lock()
+            .read()
             .await
             .usernames
             .get()
@@ -128,7 +135,7 @@ where
         &self, key: PublicKey,
     ) -> Result<GetUsernameResponse, ServerError<GetUsernameError>> {
         self.index_db
-            .lock()
+            .read()
             .await
             .accounts
             .get()
@@ -140,17 +147,16 @@ where
     pub async fn get_usage(
         &self, context: RequestContext<GetUsageRequest>,
     ) -> Result<GetUsageResponse, ServerError<GetUsageError>> {
-        let mut lock = self.index_db.lock().await;
-        let db = lock.deref_mut();
+        let db = self.index_db.read().await;
 
-        let cap = Self::get_cap(db, &context.public_key)?;
+        let cap = self.get_cap(&db, &context.public_key)?;
 
-        let mut tree = ServerTree::new(
+        let mut tree = ServerTreeView::new(
             Owner(context.public_key),
-            &mut db.owned_files,
-            &mut db.shared_files,
-            &mut db.file_children,
-            &mut db.metas,
+            &db.owned_files,
+            &db.shared_files,
+            &db.file_children,
+            &db.metas,
         )?
         .to_lazy();
         let usages = Self::get_usage_helper(&mut tree, db.sizes.get())?;
@@ -199,16 +205,30 @@ where
         Ok(result)
     }
 
+    /// An admin's override for the account wins. Otherwise the cap comes from the account's
+    /// subscription or, with billing disabled, from an admin's server-wide override, then config.
     pub fn get_cap(
-        db: &ServerDb, public_key: &PublicKey,
+        &self, db: &ServerDb, public_key: &PublicKey,
     ) -> Result<u64, ServerError<GetUsageHelperError>> {
-        Ok(db
+        let owner = Owner(*public_key);
+        let account = db
             .accounts
             .get()
-            .get(&Owner(*public_key))
-            .ok_or(ServerError::ClientError(GetUsageHelperError::UserNotFound))?
-            .billing_info
-            .data_cap())
+            .get(&owner)
+            .ok_or(ServerError::ClientError(GetUsageHelperError::UserNotFound))?;
+
+        if let Some(cap) = db.data_caps.get().get(&owner) {
+            return Ok(*cap);
+        }
+        if self.config.billing.enabled {
+            return Ok(account.billing_info.data_cap());
+        }
+        Ok(db
+            .global_data_cap
+            .get()
+            .copied()
+            .or(self.config.data_caps.default)
+            .unwrap_or(u64::MAX))
     }
 
     pub async fn delete_account(
@@ -224,7 +244,7 @@ where
         &self, context: RequestContext<AdminDisappearAccountRequest>,
     ) -> Result<(), ServerError<AdminDisappearAccountError>> {
         let owner = {
-            let db = &self.index_db.lock().await;
+            let db = &self.index_db.write().await;
 
             if !Self::is_admin::<AdminDisappearAccountError>(
                 db,
@@ -258,7 +278,7 @@ where
     pub async fn admin_list_users(
         &self, context: RequestContext<AdminListUsersRequest>,
     ) -> Result<AdminListUsersResponse, ServerError<AdminListUsersError>> {
-        let (db, request) = (&self.index_db.lock().await, &context.request);
+        let (db, request) = (&self.index_db.read().await, &context.request);
 
         if !Self::is_admin::<AdminListUsersError>(
             db,
@@ -310,7 +330,7 @@ where
     pub async fn admin_get_account_info(
         &self, context: RequestContext<AdminGetAccountInfoRequest>,
     ) -> Result<AdminGetAccountInfoResponse, ServerError<AdminGetAccountInfoError>> {
-        let (mut lock, request) = (self.index_db.lock().await, &context.request);
+        let (mut lock, request) = (self.index_db.write().await, &context.request);
         let db = lock.deref_mut();
 
         if !Self::is_admin::<AdminGetAccountInfoError>(
@@ -401,6 +421,12 @@ where
                 root,
                 payment_platform,
                 usage: usage_str,
+                state: db
+                    .account_states
+                    .get()
+                    .get(&owner)
+                    .copied()
+                    .unwrap_or_default(),
             },
         })
     }
@@ -409,9 +435,11 @@ where
         &self, public_key: &PublicKey, free_username: bool,
     ) -> Result<(), ServerError<DeleteAccountHelperError>> {
         let mut docs_to_delete = Vec::new();
+        // sharees' trees change too
+        let _owner_guards = self.owner_locks.lock_all().await;
 
         {
-            let mut lock = self.index_db.lock().await;
+            let mut lock = self.index_db.write().await;
             let db = lock.deref_mut();
             let tx = db.begin_transaction()?;
 
@@ -449,11 +477,18 @@ where
                         }
                         db.metas.remove(&id)?;
                         db.file_children.clear_key(&id)?;
+                        if let Some(link_id) = db.published_files.get().get(&id).copied() {
+                            unindex_publication(db, &link_id)?;
+                            docs_to_delete.push((link_id, PUBLICATION_HMAC));
+                        }
+                        self.version_index.lock()?.forget(&id);
                     }
                 }
             }
 
             if free_username {
+                db.account_states.remove(&Owner(*public_key))?;
+                db.data_caps.remove(&Owner(*public_key))?;
                 let username = db
                     .accounts
                     .remove(&Owner(*public_key))?
@@ -472,6 +507,81 @@ where
         Ok(())
     }
 
+    pub async fn admin_set_data_cap(
+        &self, context: RequestContext<AdminSetDataCapRequest>,
+    ) -> Result<AdminSetDataCapResponse, ServerError<AdminSetDataCapError>> {
+        let (mut lock, request) = (self.index_db.write().await, &context.request);
+        let db = lock.deref_mut();
+
+        if !Self::is_admin::<AdminSetDataCapError>(
+            db,
+            &context.public_key,
+            &self.config.admin.admins,
+        )? {
+            return Err(ClientError(AdminSetDataCapError::NotPermissioned));
+        }
+
+        match &request.username {
+            Some(username) => {
+                let owner = *db
+                    .usernames
+                    .get()
+                    .get(username)
+                    .ok_or(ClientError(AdminSetDataCapError::UserNotFound))?;
+                match request.cap {
+                    Some(cap) => {
+                        db.data_caps.insert(owner, cap)?;
+                    }
+                    None => {
+                        db.data_caps.remove(&owner)?;
+                    }
+                }
+            }
+            None => match request.cap {
+                Some(cap) => {
+                    db.global_data_cap.insert(cap)?;
+                }
+                None => {
+                    db.global_data_cap.clear()?;
+                }
+            },
+        }
+
+        Ok(AdminSetDataCapResponse {})
+    }
+
+    pub async fn admin_set_account_state(
+        &self, context: RequestContext<AdminSetAccountStateRequest>,
+    ) -> Result<AdminSetAccountStateResponse, ServerError<AdminSetAccountStateError>> {
+        let (mut lock, request) = (self.index_db.write().await, &context.request);
+        let db = lock.deref_mut();
+
+        if !Self::is_admin::<AdminSetAccountStateError>(
+            db,
+            &context.public_key,
+            &self.config.admin.admins,
+        )? {
+            return Err(ClientError(AdminSetAccountStateError::NotPermissioned));
+        }
+
+        let owner = *db
+            .usernames
+            .get()
+            .get(&request.username)
+            .ok_or(ClientError(AdminSetAccountStateError::UserNotFound))?;
+        match request.state {
+            AccountState::Active => {
+                db.account_states.remove(&owner)?;
+            }
+            state => {
+                db.account_states.insert(owner, state)?;
+            }
+        }
+        warn!(?request.username, ?request.state, "Set account state");
+
+        Ok(AdminSetAccountStateResponse {})
+    }
+
     pub fn is_admin<E: Debug>(
         db: &ServerDb, public_key: &PublicKey, admins: &HashSet<Username>,
     ) -> Result<bool, ServerError<E>> {
@@ -0,0 +1,232 @@
+use crate::billing::app_store_client::AppStoreClient;
+use crate::billing::google_play_client::GooglePlayClient;
+use crate::billing::stripe_client::StripeClient;
+use crate::document_service::DocumentService;
+use crate::ServerError::ClientError;
+use crate::{RequestContext, ServerError, ServerState};
+use lb_rs::model::api::{
+    AdminDisappearAccountRequest, AdminDisappearFileRequest, AdminGetAuditLogError,
+    AdminGetAuditLogRequest, AdminGetAuditLogResponse, AdminRebuildIndexRequest,
+    AdminRepairServerRequest, AdminSetAccountStateRequest, AdminSetDataCapRequest,
+    AdminSetUserTierRequest, AuditEntry, AuditOutcome, DeleteAccountRequest, Request,
+};
+use lb_rs::model::clock::get_time;
+use libsecp256k1::PublicKey;
+use std::collections::HashMap;
+use std::ops::{DerefMut, Range};
+use tracing::*;
+
+/// Routes whose requests are journaled, whatever the outcome. Only admins can take the admin
+/// actions, so other callers' attempts aren't journaled; they'd only be noise anyone could fill
+/// the log with. Deleting an account is journaled for anyone who has one.
+pub const AUDITED_ROUTES: [&str; 8] = [
+    AdminDisappearAccountRequest::ROUTE,
+    AdminDisappearFileRequest::ROUTE,
+    AdminSetUserTierRequest::ROUTE,
+    AdminSetDataCapRequest::ROUTE,
+    AdminSetAccountStateRequest::ROUTE,
+    AdminRebuildIndexRequest::ROUTE,
+    AdminRepairServerRequest::ROUTE,
+    DeleteAccountRequest::ROUTE,
+];
+
+const DEFAULT_AUDIT_LOG_LIMIT: usize = 100;
+
+/// The oldest entries are dropped once the log holds this many
+const MAX_AUDIT_LOG_ENTRIES: u64 = 100_000;
+
+/// Billing credentials that can appear in an audited request, e.g. when an admin sets someone's
+/// tier. The audit log is for seeing who did what, and anyone who can read it shouldn't be able to
+/// act on someone's subscription.
+const REDACTED_FIELDS: [&str; 4] =
+    ["purchase_token", "account_token", "original_transaction_id", "payment_method_id"];
+
+impl<S, A, G, D> ServerState<S, A, G, D>
+where
+    S: StripeClient,
+    A: AppStoreClient,
+    G: GooglePlayClient,
+    D: DocumentService,
+{
+    /// Appends to the audit log. Called once the request has been handled so the outcome is
+    /// known; a failure here is logged rather than returned because the action already happened.
+    pub async fn journal(
+        &self, actor: PublicKey, actor_username: String, action: &str,
+        mut request: serde_json::Value, outcome: AuditOutcome,
+    ) {
+        if actor_username == "~unknown~" {
+            return;
+        }
+        let is_delete_account = action == DeleteAccountRequest::ROUTE;
+        if !is_delete_account && !self.config.admin.admins.contains(&actor_username) {
+            return;
+        }
+
+        redact(&mut request);
+
+        let target = if is_delete_account {
+            Some(actor_username.clone())
+        } else {
+            ["username", "id"]
+                .iter()
+                .find_map(|field| request.get(field)?.as_str().map(String::from))
+        };
+
+        let mut lock = self.index_db.write().await;
+        let db = lock.deref_mut();
+        let seq = audit_log_range(db.audit_log.get()).end;
+        let entry = AuditEntry {
+            seq,
+            timestamp: get_time().0 as u64,
+            actor,
+            actor_username: Some(actor_username),
+            action: action.to_string(),
+            target,
+            details: request.to_string(),
+            outcome,
+        };
+        if let Err(err) = db.audit_log.insert(entry.seq, entry.clone()) {
+            error!(?entry, "failed to journal request: {:?}", err);
+        }
+        if let Some(expired) = seq.checked_sub(MAX_AUDIT_LOG_ENTRIES) {
+            if let Err(err) = db.audit_log.remove(&expired) {
+                error!(?expired, "failed to drop old audit log entry: {:?}", err);
+            }
+        }
+    }
+
+    pub async fn admin_get_audit_log(
+        &self, context: RequestContext<AdminGetAuditLogRequest>,
+    ) -> Result<AdminGetAuditLogResponse, ServerError<AdminGetAuditLogError>> {
+        let (db, request) = (&self.index_db.read().await, &context.request);
+
+        if !Self::is_admin::<AdminGetAuditLogError>(
+            db,
+            &context.public_key,
+            &self.config.admin.admins,
+        )? {
+            return Err(ClientError(AdminGetAuditLogError::NotPermissioned));
+        }
+
+        let log = db.audit_log.get();
+        let range = audit_log_range(log);
+        let first = request
+            .after
+            .map(|after| after + 1)
+            .unwrap_or_default()
+            .max(range.start);
+        let entries = (first..range.end)
+            .filter_map(|seq| log.get(&seq))
+            .filter(|entry| match &request.actor {
+                Some(actor) => entry.actor_username.as_ref() == Some(actor),
+                None => true,
+            })
+            .filter(|entry| match &request.action {
+                Some(action) => {
+                    entry.action.trim_start_matches('/') == action.trim_start_matches('/')
+                }
+                None => true,
+            })
+            .filter(|entry| match &request.target {
+                Some(target) => entry.target.as_ref() == Some(target),
+                None => true,
+            })
+            .take(request.limit.unwrap_or(DEFAULT_AUDIT_LOG_LIMIT))
+            .cloned()
+            .collect();
+
+        Ok(AdminGetAuditLogResponse { entries })
+    }
+}
+
+/// Entries are numbered contiguously, from the oldest one kept
+fn audit_log_range(log: &HashMap<u64, AuditEntry>) -> Range<u64> {
+    let end = log.keys().max().map(|seq| seq + 1).unwrap_or_default();
+    end - log.len() as u64..end
+}
+
+fn redact(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(fields) => {
+            for (name, value) in fields {
+                if REDACTED_FIELDS.contains(&name.as_str()) {
+                    *value = serde_json::Value::String("<redacted>".to_string());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::{test_account, test_server};
+    use lb_rs::model::api::{AdminSetUserTierInfo, AdminSetUserTierRequest, AppStoreAccountState};
+
+    #[test]
+    fn billing_credentials_are_redacted() {
+        let request = AdminSetUserTierRequest {
+            username: "alice".to_string(),
+            info: AdminSetUserTierInfo::AppStore {
+                account_token: "account-token".to_string(),
+                original_transaction_id: "transaction-id".to_string(),
+                expiration_time: 0,
+                account_state: AppStoreAccountState::Ok,
+            },
+        };
+        let mut request = serde_json::to_value(request).unwrap();
+        redact(&mut request);
+
+        let details = request.to_string();
+        assert!(!details.contains("account-token"));
+        assert!(!details.contains("transaction-id"));
+        assert_eq!(request["username"], "alice");
+    }
+
+    #[tokio::test]
+    async fn only_admins_actions_are_journaled() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = test_server(dir.path());
+        let account = test_account(&server).await;
+        let (actor, username) = (account.account.public_key(), account.account.username.clone());
+
+        let journal = |action| {
+            server.journal(
+                actor,
+                username.clone(),
+                action,
+                Default::default(),
+                AuditOutcome::Succeeded,
+            )
+        };
+        journal(AdminDisappearFileRequest::ROUTE).await;
+        journal(DeleteAccountRequest::ROUTE).await;
+
+        let db = server.index_db.read().await;
+        let entries: Vec<_> = db.audit_log.get().values().collect();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].action, DeleteAccountRequest::ROUTE);
+        assert_eq!(entries[0].target, Some(username));
+    }
+
+    #[test]
+    fn audit_log_range_skips_dropped_entries() {
+        let entry = |seq| AuditEntry {
+            seq,
+            timestamp: 0,
+            actor: PublicKey::parse_compressed(&[2; 33]).unwrap(),
+            actor_username: None,
+            action: String::new(),
+            target: None,
+            details: String::new(),
+            outcome: AuditOutcome::Succeeded,
+        };
+        let log: HashMap<u64, AuditEntry> = (5..8).map(|seq| (seq, entry(seq))).collect();
+        assert_eq!(audit_log_range(&log), 5..8);
+        assert_eq!(audit_log_range(&HashMap::new()), 0..0);
+    }
+}
@@ -0,0 +1,528 @@
+use crate::billing::app_store_client::AppStoreClient;
+use crate::billing::google_play_client::GooglePlayClient;
+use crate::billing::stripe_client::StripeClient;
+use crate::billing::Nop;
+use crate::config::Config;
+use crate::document_service::{DocumentService, OnDiskDocuments};
+use crate::file_service::deleted_without_index;
+use crate::publication_service::PUBLICATION_HMAC;
+use crate::schema::ServerV4;
+use crate::ServerState;
+use db_rs::{Db, DbError};
+use lb_rs::model::api::AdminValidateServer;
+use lb_rs::model::clock::get_time;
+use lb_rs::model::errors::LbErr;
+use lb_rs::model::file_like::FileLike;
+use lb_rs::model::file_metadata::DocumentHmac;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::{fmt, fs, io};
+use tracing::*;
+use uuid::Uuid;
+
+const MANIFEST: &str = "manifest.json";
+const INDEX: &str = "index";
+const FILES: &str = "files";
+const PARTIAL: &str = ".partial";
+
+/// Describes a snapshot directory. Written last, so a snapshot without one is incomplete.
+///
+/// A snapshot holds a copy of the whole index db in `index/` and document contents in `files/`,
+/// named like the server names them. An incremental snapshot only holds contents its base chain
+/// doesn't; contents never change under a given name, so the chain together has everything.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub created_at: u64,
+    /// Name of the snapshot, in the same directory, that this one builds on
+    pub base: Option<String>,
+    /// How many snapshots this one builds on; 0 for a full snapshot
+    pub depth: u32,
+    /// Documents in the index that aren't deleted and have contents
+    pub documents: usize,
+    /// Documents whose contents are stored in this snapshot rather than in its base chain
+    pub stored: usize,
+    /// Documents whose contents the server was missing when the snapshot was taken
+    pub missing: Vec<Uuid>,
+}
+
+#[derive(Debug)]
+pub enum BackupError {
+    Io(io::Error),
+    Db(DbError),
+    Lb(LbErr),
+    Manifest(serde_json::Error),
+    Invalid(String),
+}
+
+impl From<io::Error> for BackupError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<DbError> for BackupError {
+    fn from(err: DbError) -> Self {
+        Self::Db(err)
+    }
+}
+
+impl From<LbErr> for BackupError {
+    fn from(err: LbErr) -> Self {
+        Self::Lb(err)
+    }
+}
+
+impl From<serde_json::Error> for BackupError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Manifest(err)
+    }
+}
+
+impl fmt::Display for BackupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BackupError::Io(err) => write!(f, "io error: {err}"),
+            BackupError::Db(err) => write!(f, "index db error: {err:?}"),
+            BackupError::Lb(err) => write!(f, "{err}"),
+            BackupError::Manifest(err) => write!(f, "unreadable manifest: {err}"),
+            BackupError::Invalid(msg) => write!(f, "{msg}"),
+        }
+    }
+}
+
+impl std::error::Error for BackupError {}
+
+impl<S, A, G, D> ServerState<S, A, G, D>
+where
+    S: StripeClient,
+    A: AppStoreClient,
+    G: GooglePlayClient,
+    D: DocumentService,
+{
+    /// Takes a snapshot into a new directory under `dir`, building on the snapshot named `base` if
+    /// there is one, and returns the new snapshot's name.
+    ///
+    /// Writers wait only while the index is copied and document contents are hard linked into a
+    /// directory beside them, so that a change landing afterwards can't delete contents the copy
+    /// refers to. The contents are moved into the snapshot once writers are let back in.
+    pub async fn snapshot(&self, dir: &Path, base: Option<&str>) -> Result<String, BackupError> {
+        let created_at = get_time().0 as u64;
+        let name = created_at.to_string();
+        let partial = dir.join(format!("{name}{PARTIAL}"));
+        let pins = self.config.files.path.join(format!(".snapshot-{name}"));
+
+        let result = self
+            .snapshot_into(&partial, &pins, dir, base, created_at)
+            .await;
+        if let Err(err) = fs::remove_dir_all(&pins) {
+            warn!(?pins, ?err, "failed to clean up after snapshot");
+        }
+        match result {
+            Ok(()) => {
+                fs::rename(&partial, dir.join(&name))?;
+                Ok(name)
+            }
+            Err(err) => {
+                let _ = fs::remove_dir_all(&partial);
+                Err(err)
+            }
+        }
+    }
+
+    async fn snapshot_into(
+        &self, partial: &Path, pins: &Path, dir: &Path, base: Option<&str>, created_at: u64,
+    ) -> Result<(), BackupError> {
+        let chain = match base {
+            Some(base) => chain(dir, base)?,
+            None => vec![],
+        };
+        fs::create_dir_all(partial.join(FILES))?;
+        fs::create_dir_all(pins)?;
+
+        let mut documents = 0;
+        let mut pinned = vec![];
+        let mut missing = vec![];
+        {
+            let db = self.index_db.read().await;
+
+            let mut index = ServerV4::init(db_rs::Config::in_folder(partial.join(INDEX)))?;
+            copy_index(&db, &mut index)?;
+
+            for (id, hmac) in stored_contents(&db) {
+                documents += 1;
+
+                let live = self.document_service.get_path(&id, &hmac);
+                let file_name = file_name(&live)?;
+                if find_contents(&chain, &file_name).is_some() {
+                    continue;
+                }
+                match fs::hard_link(&live, pins.join(&file_name)) {
+                    Ok(()) => pinned.push(file_name),
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => missing.push(id),
+                    Err(err) => return Err(err.into()),
+                }
+            }
+        }
+
+        for file_name in &pinned {
+            move_or_copy(&pins.join(file_name), &partial.join(FILES).join(file_name))?;
+        }
+
+        let manifest = Manifest {
+            created_at,
+            base: base.map(String::from),
+            depth: chain.len() as u32,
+            documents,
+            stored: pinned.len(),
+            missing,
+        };
+        if !manifest.missing.is_empty() {
+            warn!(missing = ?manifest.missing, "snapshot taken of documents without contents");
+        }
+        fs::write(partial.join(MANIFEST), serde_json::to_vec_pretty(&manifest)?)?;
+        Ok(())
+    }
+
+    pub fn start_backup_worker(&self) {
+        let Some(dir) = self.config.backup.path.clone() else { return };
+        let state = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(state.config.backup.time_between_backups).await;
+
+                let base = match latest(&dir) {
+                    Ok(latest) => latest.filter(|(_, manifest)| {
+                        manifest.depth + 1 < state.config.backup.backups_between_full
+                    }),
+                    Err(err) => {
+                        error!(?err, "failed to find the latest snapshot, taking a full one");
+                        None
+                    }
+                };
+                let base = base.map(|(name, _)| name);
+
+                match state.snapshot(&dir, base.as_deref()).await {
+                    Ok(name) => info!(?name, ?base, "took snapshot"),
+                    Err(err) => error!(?err, "failed to take snapshot"),
+                }
+            }
+        });
+    }
+}
+
+/// Rebuilds the server directories named in `config` from `snapshot` and validates the result.
+/// Refuses to overwrite an existing index db or document contents.
+pub async fn restore(config: &Config, snapshot: &Path) -> Result<AdminValidateServer, BackupError> {
+    let dir = snapshot
+        .parent()
+        .ok_or_else(|| BackupError::Invalid(format!("{snapshot:?} is not a snapshot")))?;
+    let name = snapshot
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| BackupError::Invalid(format!("{snapshot:?} is not a snapshot")))?;
+    let chain = chain(dir, name)?;
+
+    let index_location = Path::new(&config.index_db.db_location);
+    if is_non_empty_dir(index_location)? {
+        return Err(BackupError::Invalid(format!("{index_location:?} is not empty")));
+    }
+    if is_non_empty_dir(&config.files.path)? {
+        return Err(BackupError::Invalid(format!("{:?} is not empty", config.files.path)));
+    }
+    fs::create_dir_all(&config.files.path)?;
+
+    let snapshot_index = ServerV4::init(db_rs::Config::in_folder(snapshot.join(INDEX)))?;
+    let mut index = ServerV4::init(db_rs::Config::in_folder(index_location))?;
+    copy_index(&snapshot_index, &mut index)?;
+
+    let document_service = OnDiskDocuments::from(config);
+    let mut restored = HashSet::new();
+    for (id, hmac) in stored_contents(&index) {
+        let destination = document_service.get_path(&id, &hmac);
+        let file_name = file_name(&destination)?;
+        if !restored.insert(file_name.clone()) {
+            continue;
+        }
+        match find_contents(&chain, &file_name) {
+            Some(source) => {
+                fs::copy(source, destination)?;
+            }
+            None => warn!(?id, "snapshot has no contents for document"),
+        }
+    }
+    info!(documents = restored.len(), "restored snapshot");
+
+    let state = ServerState::new(config.clone(), index, Nop {}, Nop {}, Nop {}, document_service);
+    let mut db = state.index_db.write().await;
+    Ok(state.validate_server_helper(&mut db)?)
+}
+
+/// The snapshot with the latest name in `dir`, if any
+pub fn latest(dir: &Path) -> Result<Option<(String, Manifest)>, BackupError> {
+    if !dir.exists() {
+        return Ok(None);
+    }
+    let mut latest: Option<(u64, String)> = None;
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        if let Ok(created_at) = name.parse::<u64>() {
+            if latest
+                .as_ref()
+                .map(|(at, _)| created_at > *at)
+                .unwrap_or(true)
+            {
+                latest = Some((created_at, name));
+            }
+        }
+    }
+    match latest {
+        Some((_, name)) => {
+            let manifest = manifest(&dir.join(&name))?;
+            Ok(Some((name, manifest)))
+        }
+        None => Ok(None),
+    }
+}
+
+fn manifest(snapshot: &Path) -> Result<Manifest, BackupError> {
+    let bytes = fs::read(snapshot.join(MANIFEST)).map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => {
+            BackupError::Invalid(format!("{snapshot:?} is not a complete snapshot"))
+        }
+        _ => err.into(),
+    })?;
+    Ok(serde_json::from_slice(&bytes)?)
+}
+
+/// The snapshot named `name` followed by the snapshots it builds on
+fn chain(dir: &Path, name: &str) -> Result<Vec<PathBuf>, BackupError> {
+    let mut chain = vec![];
+    let mut next = Some(name.to_string());
+    while let Some(name) = next {
+        let snapshot = dir.join(&name);
+        next = manifest(&snapshot)?.base;
+        chain.push(snapshot);
+    }
+    Ok(chain)
+}
+
+fn find_contents(chain: &[PathBuf], file_name: &str) -> Option<PathBuf> {
+    chain
+        .iter()
+        .map(|snapshot| snapshot.join(FILES).join(file_name))
+        .find(|path| path.exists())
+}
+
+/// The contents a snapshot keeps: those of every live document and publication
+fn stored_contents(db: &ServerV4) -> Vec<(Uuid, DocumentHmac)> {
+    let metas = db.metas.get();
+    let documents = metas.iter().filter_map(|(id, meta)| {
+        let hmac = meta.document_hmac()?;
+        (!deleted_without_index(metas, meta)).then_some((*id, *hmac))
+    });
+    let publications = db
+        .publications
+        .get()
+        .keys()
+        .map(|link_id| (*link_id, PUBLICATION_HMAC));
+    documents.chain(publications).collect()
+}
+
+/// Destructures `from` without `..`, so a table added to [ServerV4] doesn't build until it's
+/// copied here too
+fn copy_index(from: &ServerV4, to: &mut ServerV4) -> Result<(), DbError> {
+    let ServerV4 {
+        usernames,
+        metas,
+        sizes,
+        google_play_ids,
+        stripe_ids,
+        app_store_ids,
+        last_seen,
+        accounts,
+        owned_files,
+        shared_files,
+        file_children,
+        data_caps,
+        global_data_cap,
+        account_states,
+        publications,
+        published_files,
+        owner_publications,
+        audit_log,
+    } = from;
+
+    let tx = to.begin_transaction()?;
+    for (k, v) in usernames.get() {
+        to.usernames.insert(k.clone(), *v)?;
+    }
+    for (k, v) in metas.get() {
+        to.metas.insert(*k, v.clone())?;
+    }
+    for (k, v) in sizes.get() {
+        to.sizes.insert(*k, *v)?;
+    }
+    for (k, v) in google_play_ids.get() {
+        to.google_play_ids.insert(k.clone(), *v)?;
+    }
+    for (k, v) in stripe_ids.get() {
+        to.stripe_ids.insert(k.clone(), *v)?;
+    }
+    for (k, v) in app_store_ids.get() {
+        to.app_store_ids.insert(k.clone(), *v)?;
+    }
+    for (k, v) in last_seen.get() {
+        to.last_seen.insert(*k, *v)?;
+    }
+    for (k, v) in accounts.get() {
+        to.accounts.insert(*k, v.clone())?;
+    }
+    for (k, ids) in owned_files.get() {
+        to.owned_files.create_key(*k)?;
+        for id in ids {
+            to.owned_files.insert(*k, *id)?;
+        }
+    }
+    for (k, ids) in shared_files.get() {
+        to.shared_files.create_key(*k)?;
+        for id in ids {
+            to.shared_files.insert(*k, *id)?;
+        }
+    }
+    for (k, ids) in file_children.get() {
+        to.file_children.create_key(*k)?;
+        for id in ids {
+            to.file_children.insert(*k, *id)?;
+        }
+    }
+    for (k, v) in data_caps.get() {
+        to.data_caps.insert(*k, *v)?;
+    }
+    if let Some(cap) = global_data_cap.get() {
+        to.global_data_cap.insert(*cap)?;
+    }
+    for (k, v) in account_states.get() {
+        to.account_states.insert(*k, *v)?;
+    }
+    for (k, v) in publications.get() {
+        to.publications.insert(*k, v.clone())?;
+    }
+    for (k, v) in published_files.get() {
+        to.published_files.insert(*k, *v)?;
+    }
+    for (k, ids) in owner_publications.get() {
+        to.owner_publications.create_key(*k)?;
+        for id in ids {
+            to.owner_publications.insert(*k, *id)?;
+        }
+    }
+    for (k, v) in audit_log.get() {
+        to.audit_log.insert(*k, v.clone())?;
+    }
+    tx.drop_safely()
+}
+
+fn file_name(path: &Path) -> Result<String, BackupError> {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| BackupError::Invalid(format!("{path:?} has no file name")))
+}
+
+/// Renames when `to` is on the same filesystem and copies when it isn't
+fn move_or_copy(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+fn is_non_empty_dir(path: &Path) -> io::Result<bool> {
+    match fs::read_dir(path) {
+        Ok(mut entries) => Ok(entries.next().is_some()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+    use lb_rs::model::api::AccountState;
+    use lb_rs::model::file_metadata::Owner;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn snapshots_restore_and_validate() {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshots = dir.path().join("snapshots");
+        let server = test_server(&dir.path().join("live"));
+        let account = test_account(&server).await;
+
+        let a = account.create_doc(&server, "a.md", b"a").await;
+        let full = server.snapshot(&snapshots, None).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(2)).await;
+        let b = account.create_doc(&server, "b.md", b"b").await;
+        let incremental = server.snapshot(&snapshots, Some(&full)).await.unwrap();
+        assert_eq!(manifest(&snapshots.join(&incremental)).unwrap().stored, 1);
+
+        let config = test_config(&dir.path().join("restored"));
+        let validation = restore(&config, &snapshots.join(&incremental))
+            .await
+            .unwrap();
+        assert_eq!(validation, AdminValidateServer::default());
+
+        let restored = test_server_with(config);
+        for (doc, content) in [(a, b"a"), (b, b"b")] {
+            let hmac = doc.document_hmac().unwrap();
+            let restored_content = restored
+                .document_service
+                .get::<()>(doc.id(), hmac)
+                .await
+                .unwrap();
+            assert_eq!(restored_content.value, content);
+        }
+        let live = server.index_db.read().await;
+        let restored = restored.index_db.read().await;
+        assert_eq!(live.metas.get(), restored.metas.get());
+        assert_eq!(live.accounts.get().len(), restored.accounts.get().len());
+    }
+
+    #[tokio::test]
+    async fn snapshots_keep_account_states() {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshots = dir.path().join("snapshots");
+        let server = test_server(&dir.path().join("live"));
+        let account = test_account(&server).await;
+        let owner = Owner(account.account.public_key());
+        server
+            .index_db
+            .write()
+            .await
+            .account_states
+            .insert(owner, AccountState::Suspended)
+            .unwrap();
+        let name = server.snapshot(&snapshots, None).await.unwrap();
+
+        let config = test_config(&dir.path().join("restored"));
+        restore(&config, &snapshots.join(name)).await.unwrap();
+
+        let restored = test_server_with(config);
+        let restored = restored.index_db.read().await;
+        assert_eq!(restored.account_states.get().get(&owner), Some(&AccountState::Suspended));
+    }
+
+    #[tokio::test]
+    async fn restore_refuses_to_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshots = dir.path().join("snapshots");
+        let server = test_server(&dir.path().join("live"));
+        test_account(&server).await;
+        let name = server.snapshot(&snapshots, None).await.unwrap();
+
+        let result = restore(&server.config, &snapshots.join(name)).await;
+        assert!(matches!(result, Err(BackupError::Invalid(_))));
+    }
+}
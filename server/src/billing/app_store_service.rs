@@ -37,7 +37,7 @@ where
     ) -> Result<PublicKey, ServerError<AppStoreNotificationError>> {
         let public_key: PublicKey = self
             .index_db
-            .lock()
+            .write()
             .await
             .app_store_ids
             .get()
@@ -49,7 +49,7 @@ where
         &self, public_key: &PublicKey,
     ) -> Result<Account, ServerError<LockBillingWorkflowError>> {
         let owner = Owner(*public_key);
-        let mut db = self.index_db.lock().await;
+        let mut db = self.index_db.write().await;
         let tx = db.begin_transaction()?;
         let mut account = db
             .accounts
@@ -82,7 +82,7 @@ where
     ) -> Result<(), ServerError<T>> {
         account.billing_info.last_in_payment_flow = 0;
         self.index_db
-            .lock()
+            .write()
             .await
             .accounts
             .insert(Owner(public_key), account)?;
@@ -92,6 +92,10 @@ where
     pub async fn upgrade_account_app_store(
         &self, context: RequestContext<UpgradeAccountAppStoreRequest>,
     ) -> Result<UpgradeAccountAppStoreResponse, ServerError<UpgradeAccountAppStoreError>> {
+        if !self.config.billing.enabled {
+            return Err(ClientError(UpgradeAccountAppStoreError::Disabled));
+        }
+
         let request = &context.request;
 
         let mut account = self.lock_subscription_profile(&context.public_key).await?;
@@ -99,7 +103,7 @@ where
         debug!("Upgrading the account of a user through app store billing");
 
         {
-            let db = self.index_db.lock().await;
+            let db = self.index_db.write().await;
             if let Some(owner) = db.app_store_ids.get().get(&request.app_account_token) {
                 if let Some(other_account) = db.accounts.get().get(owner) {
                     if let Some(BillingPlatform::AppStore(ref info)) =
@@ -140,7 +144,7 @@ where
         }));
 
         self.index_db
-            .lock()
+            .write()
             .await
             .app_store_ids
             .insert(request.app_account_token.clone(), Owner(context.public_key))?;
@@ -157,6 +161,10 @@ where
     pub async fn upgrade_account_google_play(
         &self, context: RequestContext<UpgradeAccountGooglePlayRequest>,
     ) -> Result<UpgradeAccountGooglePlayResponse, ServerError<UpgradeAccountGooglePlayError>> {
+        if !self.config.billing.enabled {
+            return Err(ClientError(UpgradeAccountGooglePlayError::Disabled));
+        }
+
         let request = &context.request;
 
         let mut account = self.lock_subscription_profile(&context.public_key).await?;
@@ -185,7 +193,7 @@ where
         )?);
 
         self.index_db
-            .lock()
+            .write()
             .await
             .google_play_ids
             .insert(request.account_id.clone(), Owner(context.public_key))?;
@@ -204,6 +212,10 @@ where
     pub async fn upgrade_account_stripe(
         &self, context: RequestContext<UpgradeAccountStripeRequest>,
     ) -> Result<UpgradeAccountStripeResponse, ServerError<UpgradeAccountStripeError>> {
+        if !self.config.billing.enabled {
+            return Err(ClientError(UpgradeAccountStripeError::Disabled));
+        }
+
         let request = &context.request;
 
         debug!("Attempting to upgrade the account tier of to premium");
@@ -240,7 +252,7 @@ where
     ) -> Result<GetSubscriptionInfoResponse, ServerError<GetSubscriptionInfoError>> {
         let platform = self
             .index_db
-            .lock()
+            .write()
             .await
             .accounts
             .get()
@@ -271,6 +283,10 @@ where
     pub async fn cancel_subscription(
         &self, context: RequestContext<CancelSubscriptionRequest>,
     ) -> Result<CancelSubscriptionResponse, ServerError<CancelSubscriptionError>> {
+        if !self.config.billing.enabled {
+            return Err(ClientError(CancelSubscriptionError::Disabled));
+        }
+
         let mut account = self.lock_subscription_profile(&context.public_key).await?;
 
         if account.billing_info.data_cap() == FREE_TIER_USAGE_SIZE {
@@ -278,7 +294,7 @@ where
         }
 
         {
-            let mut lock = self.index_db.lock().await;
+            let mut lock = self.index_db.write().await;
             let db = lock.deref_mut();
 
             let mut tree = ServerTree::new(
@@ -348,7 +364,7 @@ where
         let request = &context.request;
 
         {
-            let db = self.index_db.lock().await;
+            let db = self.index_db.write().await;
 
             if !Self::is_admin::<AdminSetUserTierError>(
                 &db,
@@ -361,7 +377,7 @@ where
 
         let public_key = self
             .index_db
-            .lock()
+            .write()
             .await
             .usernames
             .get()
@@ -699,7 +715,7 @@ where
         let owner = Owner(public_key);
         let maybe_username = &self
             .index_db
-            .lock()
+            .write()
             .await
             .accounts
             .get()
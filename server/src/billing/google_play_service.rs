@@ -41,7 +41,7 @@ where
 
         let public_key: PublicKey = self
             .index_db
-            .lock()
+            .write()
             .await
             .google_play_ids
             .get()
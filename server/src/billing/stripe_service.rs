@@ -75,7 +75,7 @@ where
                         info!(?owner, ?customer_id, "Created customer_id");
 
                         self.index_db
-                            .lock()
+                            .write()
                             .await
                             .stripe_ids
                             .insert(customer_id, Owner(*public_key))?;
@@ -167,7 +167,7 @@ where
 
         let public_key = self
             .index_db
-            .lock()
+            .write()
             .await
             .stripe_ids
             .get()
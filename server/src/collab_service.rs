@@ -0,0 +1,394 @@
+use crate::billing::app_store_client::AppStoreClient;
+use crate::billing::google_play_client::GooglePlayClient;
+use crate::billing::stripe_client::StripeClient;
+use crate::document_service::DocumentService;
+use crate::ServerError::ClientError;
+use crate::{RequestContext, ServerError, ServerState};
+use lb_rs::model::access_info::UserAccessMode;
+use lb_rs::model::api::{
+    CollabAck, EncryptedCollabMessage, PullCollabError, PullCollabRequest, PullCollabResponse,
+    PushCollabError, PushCollabRequest, PushCollabResponse,
+};
+use lb_rs::model::errors::LbResult;
+use lb_rs::model::file_like::FileLike;
+use lb_rs::model::file_metadata::Owner;
+use lb_rs::model::server_tree::ServerTreeView;
+use lb_rs::model::tree_like::TreeLike;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use uuid::Uuid;
+
+/// How long a pull is held open waiting for new messages
+const PULL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Sessions nobody has pushed to or pulled from in this long are forgotten
+const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Participants that haven't pulled in this long no longer hold back compaction. Longer than a
+/// pull is held open.
+const PARTICIPANT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Room for a snapshot of a sizeable document; edits are much smaller
+const MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Messages a session keeps since it was last compacted
+const MAX_SESSION_SIZE: usize = 16 * 1024 * 1024;
+
+const MAX_SESSIONS_PER_USER: usize = 8;
+
+/// Orders and relays the messages of collaborative editing sessions. Messages are encrypted with
+/// the document's key and are only kept in memory for as long as the session is active; the
+/// document itself is still saved through the usual endpoints.
+#[derive(Clone, Default)]
+pub struct CollabRelay {
+    sessions: Arc<Mutex<HashMap<Uuid, RelaySession>>>,
+}
+
+struct RelaySession {
+    opened_by: Owner,
+    /// sequence number of the message before the first one kept; the message sequenced as `n` is
+    /// at index `n - base - 1`
+    base: u64,
+    messages: Vec<EncryptedCollabMessage>,
+    size: usize,
+    participants: HashMap<Uuid, Participant>,
+    last_active: Instant,
+    head: watch::Sender<u64>,
+}
+
+struct Participant {
+    acked: u64,
+    last_pull: Instant,
+}
+
+impl RelaySession {
+    fn new(opened_by: Owner) -> Self {
+        let (head, _) = watch::channel(0);
+        Self {
+            opened_by,
+            base: 0,
+            messages: vec![],
+            size: 0,
+            participants: Default::default(),
+            last_active: Instant::now(),
+            head,
+        }
+    }
+
+    fn head(&self) -> u64 {
+        self.base + self.messages.len() as u64
+    }
+
+    fn push(&mut self, message: EncryptedCollabMessage) -> u64 {
+        self.size += message.value.len();
+        self.messages.push(message);
+        let seq = self.head();
+        self.head.send_replace(seq);
+        seq
+    }
+
+    fn ack(&mut self, ack: CollabAck) {
+        let participant = self
+            .participants
+            .entry(ack.participant)
+            .or_insert(Participant { acked: ack.seq, last_pull: Instant::now() });
+        participant.acked = participant.acked.max(ack.seq);
+        participant.last_pull = Instant::now();
+    }
+
+    /// Replaces the session's messages with `snapshot` if every participant has caught up to
+    /// the head, which is what it's a snapshot of
+    fn compact(&mut self, snapshot: EncryptedCollabMessage) -> bool {
+        let now = Instant::now();
+        self.participants.retain(|_, participant| {
+            now.duration_since(participant.last_pull) < PARTICIPANT_TIMEOUT
+        });
+        let head = self.head();
+        if self
+            .participants
+            .values()
+            .any(|participant| participant.acked < head)
+        {
+            return false;
+        }
+
+        self.base = head;
+        self.messages.clear();
+        self.size = 0;
+        self.push(snapshot);
+        true
+    }
+}
+
+enum CollabAccess {
+    NotFound,
+    NotPermissioned,
+    Granted(UserAccessMode),
+}
+
+impl CollabRelay {
+    /// Runs `f` on the session for document `id`, opening it on behalf of `owner` if there isn't
+    /// one. Returns `None` if `owner` has too many sessions open to open another.
+    fn session<T>(
+        &self, owner: Owner, id: Uuid, f: impl FnOnce(&mut RelaySession) -> T,
+    ) -> Option<T> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let now = Instant::now();
+        sessions.retain(|_, session| {
+            now.duration_since(session.last_active) < SESSION_IDLE_TIMEOUT
+                || session.head.receiver_count() > 0
+        });
+        if !sessions.contains_key(&id)
+            && sessions
+                .values()
+                .filter(|session| session.opened_by == owner)
+                .count()
+                >= MAX_SESSIONS_PER_USER
+        {
+            return None;
+        }
+        let session = sessions
+            .entry(id)
+            .or_insert_with(|| RelaySession::new(owner));
+        session.last_active = now;
+        Some(f(session))
+    }
+}
+
+impl<S, A, G, D> ServerState<S, A, G, D>
+where
+    S: StripeClient,
+    A: AppStoreClient,
+    G: GooglePlayClient,
+    D: DocumentService,
+{
+    pub async fn push_collab(
+        &self, context: RequestContext<PushCollabRequest>,
+    ) -> Result<PushCollabResponse, ServerError<PushCollabError>> {
+        let request = context.request;
+        let owner = Owner(context.public_key);
+        match self.collab_access(owner, request.id).await? {
+            CollabAccess::NotFound => return Err(ClientError(PushCollabError::DocumentNotFound)),
+            CollabAccess::Granted(UserAccessMode::Write | UserAccessMode::Owner) => {}
+            CollabAccess::NotPermissioned | CollabAccess::Granted(_) => {
+                return Err(ClientError(PushCollabError::NotPermissioned))
+            }
+        }
+        if request.message.value.len() > MAX_MESSAGE_SIZE {
+            return Err(ClientError(PushCollabError::MessageTooLarge));
+        }
+
+        self.collab_relay
+            .session(owner, request.id, |session| {
+                if let Some(ack) = request.compacts {
+                    if !request.is_snapshot || ack.seq != session.head() {
+                        return Err(ClientError(PushCollabError::ParticipantsBehind));
+                    }
+                    session.ack(ack);
+                    if !session.compact(request.message) {
+                        return Err(ClientError(PushCollabError::ParticipantsBehind));
+                    }
+                    return Ok(PushCollabResponse { seq: session.head() });
+                }
+
+                if request.is_snapshot && !session.messages.is_empty() {
+                    return Err(ClientError(PushCollabError::SessionStarted));
+                }
+                if !request.is_snapshot && session.messages.is_empty() {
+                    return Err(ClientError(PushCollabError::SessionNotStarted));
+                }
+                if session.size + request.message.value.len() > MAX_SESSION_SIZE {
+                    return Err(ClientError(PushCollabError::SessionFull));
+                }
+
+                Ok(PushCollabResponse { seq: session.push(request.message) })
+            })
+            .unwrap_or(Err(ClientError(PushCollabError::TooManySessions)))
+    }
+
+    pub async fn pull_collab(
+        &self, context: RequestContext<PullCollabRequest>,
+    ) -> Result<PullCollabResponse, ServerError<PullCollabError>> {
+        let request = context.request;
+        let owner = Owner(context.public_key);
+        match self.collab_access(owner, request.id).await? {
+            CollabAccess::NotFound => return Err(ClientError(PullCollabError::DocumentNotFound)),
+            CollabAccess::NotPermissioned => {
+                return Err(ClientError(PullCollabError::NotPermissioned))
+            }
+            CollabAccess::Granted(_) => {}
+        }
+
+        let mut head = self
+            .collab_relay
+            .session(owner, request.id, |session| {
+                if request.since == 0 || request.ack.seq >= session.base {
+                    session.ack(request.ack);
+                }
+                session.head.subscribe()
+            })
+            .ok_or(ClientError(PullCollabError::TooManySessions))?;
+        if *head.borrow_and_update() == request.since {
+            // a timeout just means there's nothing new
+            let _ = tokio::time::timeout(PULL_TIMEOUT, head.changed()).await;
+        }
+
+        self.collab_relay
+            .session(owner, request.id, |session| {
+                // messages the client's edits may be based on were compacted away
+                if request.since > 0 && request.ack.seq < session.base {
+                    return PullCollabResponse { head: 0, messages: vec![] };
+                }
+                let since = request.since.clamp(session.base, session.head());
+                PullCollabResponse {
+                    head: session.head(),
+                    messages: session.messages[(since - session.base) as usize..]
+                        .iter()
+                        .enumerate()
+                        .map(|(i, message)| (since + i as u64 + 1, message.clone()))
+                        .collect(),
+                }
+            })
+            .ok_or(ClientError(PullCollabError::TooManySessions))
+    }
+
+    async fn collab_access(&self, owner: Owner, id: Uuid) -> LbResult<CollabAccess> {
+        let db = self.index_db.read().await;
+
+        let meta_exists = db.metas.get().contains_key(&id);
+        let mut tree = ServerTreeView::new(
+            owner,
+            &db.owned_files,
+            &db.shared_files,
+            &db.file_children,
+            &db.metas,
+        )?
+        .to_lazy();
+
+        let Some(file) = tree.maybe_find(&id) else {
+            return Ok(if meta_exists {
+                CollabAccess::NotPermissioned
+            } else {
+                CollabAccess::NotFound
+            });
+        };
+        if !file.is_document() || tree.calculate_deleted(&id)? {
+            return Ok(CollabAccess::NotFound);
+        }
+
+        Ok(match tree.access_mode(owner, &id)? {
+            Some(mode) => CollabAccess::Granted(mode),
+            None => CollabAccess::NotPermissioned,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+    use lb_rs::model::crypto::AESEncrypted;
+
+    async fn push(
+        server: &TestServer, account: &TestAccount, id: Uuid, message: &[u8], is_snapshot: bool,
+        compacts: Option<CollabAck>,
+    ) -> Result<PushCollabResponse, ServerError<PushCollabError>> {
+        server
+            .push_collab(RequestContext {
+                request: PushCollabRequest {
+                    id,
+                    message: AESEncrypted::new(message, vec![0; 12]),
+                    is_snapshot,
+                    compacts,
+                },
+                public_key: account.account.public_key(),
+            })
+            .await
+    }
+
+    /// Pulls without waiting, as long as `since` is behind the head
+    async fn pull(
+        server: &TestServer, account: &TestAccount, id: Uuid, since: u64, ack: CollabAck,
+    ) -> PullCollabResponse {
+        server
+            .pull_collab(RequestContext {
+                request: PullCollabRequest { id, since, ack },
+                public_key: account.account.public_key(),
+            })
+            .await
+            .unwrap()
+    }
+
+    fn ack(participant: Uuid, seq: u64) -> CollabAck {
+        CollabAck { participant, seq }
+    }
+
+    #[tokio::test]
+    async fn compaction_waits_for_participants() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = test_server(dir.path());
+        let account = test_account(&server).await;
+        let id = *account.create_doc(&server, "doc.md", b"").await.id();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+
+        push(&server, &account, id, b"snapshot", true, None)
+            .await
+            .unwrap();
+        push(&server, &account, id, b"edit", false, None)
+            .await
+            .unwrap();
+        pull(&server, &account, id, 0, ack(a, 0)).await;
+
+        let result = push(&server, &account, id, b"compacted", true, Some(ack(b, 1))).await;
+        assert!(matches!(result, Err(ClientError(PushCollabError::ParticipantsBehind))));
+        let result = push(&server, &account, id, b"compacted", true, Some(ack(b, 2))).await;
+        assert!(matches!(result, Err(ClientError(PushCollabError::ParticipantsBehind))));
+
+        pull(&server, &account, id, 1, ack(a, 2)).await;
+        let response = push(&server, &account, id, b"compacted", true, Some(ack(b, 2)))
+            .await
+            .unwrap();
+        assert_eq!(response.seq, 3);
+
+        // joiners start from the snapshot
+        let joined = pull(&server, &account, id, 0, ack(Uuid::new_v4(), 0)).await;
+        assert_eq!(joined.head, 3);
+        assert_eq!(joined.messages.len(), 1);
+        assert_eq!(joined.messages[0].0, 3);
+        assert_eq!(joined.messages[0].1.value, b"compacted");
+
+        // participants that missed the compaction are told the session is over
+        let missed = pull(&server, &account, id, 1, ack(Uuid::new_v4(), 1)).await;
+        assert!(missed.head < 1);
+
+        let caught_up = pull(&server, &account, id, 2, ack(a, 2)).await;
+        assert_eq!(caught_up.head, 3);
+        assert_eq!(caught_up.messages.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn sessions_are_bounded() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = test_server(dir.path());
+        let account = test_account(&server).await;
+
+        for i in 0..MAX_SESSIONS_PER_USER {
+            let id = *account
+                .create_doc(&server, &format!("{i}.md"), b"")
+                .await
+                .id();
+            push(&server, &account, id, b"snapshot", true, None)
+                .await
+                .unwrap();
+        }
+        let id = *account.create_doc(&server, "extra.md", b"").await.id();
+        let result = push(&server, &account, id, b"snapshot", true, None).await;
+        assert!(matches!(result, Err(ClientError(PushCollabError::TooManySessions))));
+
+        let too_large = vec![0; MAX_MESSAGE_SIZE + 1];
+        let result = push(&server, &account, id, &too_large, true, None).await;
+        assert!(matches!(result, Err(ClientError(PushCollabError::MessageTooLarge))));
+    }
+}
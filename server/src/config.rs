@@ -1,9 +1,12 @@
 use crate::config::Environment::{Local, Prod, Unknown};
 use lb_rs::model::account::Username;
 use semver::VersionReq;
+use serde::Deserialize;
 use std::collections::HashSet;
+use std::error::Error;
 use std::fmt::Display;
-use std::path::PathBuf;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use std::{env, fmt, fs};
 
@@ -16,6 +19,9 @@ pub struct Config {
     pub billing: BillingConfig,
     pub admin: AdminConfig,
     pub features: FeatureFlags,
+    pub data_caps: DataCapConfig,
+    pub rate_limits: RateLimitConfig,
+    pub backup: BackupConfig,
 }
 
 impl Config {
@@ -28,9 +34,68 @@ impl Config {
             billing: BillingConfig::from_env_vars(),
             admin: AdminConfig::from_env_vars(),
             features: FeatureFlags::from_env_vars(),
+            data_caps: DataCapConfig::from_env_vars(),
+            rate_limits: RateLimitConfig::from_env_vars(),
+            backup: BackupConfig::from_env_vars(),
         }
     }
 
+    /// Loads the self-hosted profile from a TOML file. Billing is disabled entirely: no payment
+    /// provider is contacted and data caps come from the file or from an admin. See
+    /// `server/etc/self-hosted.toml` for an example.
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let file: SelfHostedFile = toml::from_str(&fs::read_to_string(path)?)?;
+
+        let ssl = match (file.server.ssl_cert_location, file.server.ssl_private_key_location) {
+            (Some(cert), Some(key)) => Some((cert, key)),
+            (None, None) => None,
+            _ => return Err("ssl_cert_location and ssl_private_key_location go together".into()),
+        };
+        let (ssl_cert_location, ssl_private_key_location) = ssl.unzip();
+
+        fs::create_dir_all(&file.files.path)?;
+
+        Ok(Self {
+            server: ServerConfig {
+                env: Environment::Unknown,
+                port: file.server.port,
+                max_auth_delay: file.server.max_auth_delay,
+                log_path: file.server.log_path,
+                pd_api_key: None,
+                ssl_cert_location,
+                ssl_private_key_location,
+                min_core_version: VersionReq::parse(&file.server.min_core_version)?,
+            },
+            index_db: IndexDbConf {
+                db_location: file.index_db.db_location,
+                time_between_compacts: Duration::from_secs(
+                    file.index_db.minutes_between_compacts * 60,
+                ),
+            },
+            files: FilesConfig { path: file.files.path },
+            metrics: MetricsConfig {
+                time_between_metrics_refresh: Duration::from_secs(
+                    file.metrics.minutes_between_refresh * 60,
+                ),
+                time_between_metrics: Duration::from_millis(file.metrics.millis_between_metrics),
+            },
+            billing: BillingConfig::disabled(),
+            admin: AdminConfig { admins: file.admin.admins },
+            features: FeatureFlags { new_accounts: file.features.new_accounts },
+            data_caps: DataCapConfig { default: file.data_caps.default },
+            rate_limits: RateLimitConfig {
+                account: file.rate_limits.account.map(RateLimit::from),
+                new_account: file.rate_limits.new_account.map(RateLimit::from),
+                trusted_proxies: file.rate_limits.trusted_proxies,
+            },
+            backup: BackupConfig {
+                path: file.backup.path,
+                time_between_backups: Duration::from_secs(file.backup.minutes_between_backups * 60),
+                backups_between_full: file.backup.backups_between_full,
+            },
+        })
+    }
+
     pub fn is_prod(&self) -> bool {
         self.server.env == Prod
     }
@@ -198,6 +263,9 @@ impl MetricsConfig {
 
 #[derive(Clone, Debug)]
 pub struct BillingConfig {
+    /// When false, upgrade and cancel requests are refused, billing webhooks aren't served, and
+    /// every account gets the data cap in [DataCapConfig]
+    pub enabled: bool,
     pub millis_between_user_payment_flows: u64,
     pub time_between_lock_attempts: Duration,
     pub google: GoogleConfig,
@@ -208,6 +276,7 @@ pub struct BillingConfig {
 impl BillingConfig {
     pub fn from_env_vars() -> Self {
         Self {
+            enabled: true,
             millis_between_user_payment_flows: env_or_panic("MILLIS_BETWEEN_PAYMENT_FLOWS")
                 .parse()
                 .unwrap(),
@@ -221,9 +290,20 @@ impl BillingConfig {
             apple: AppleConfig::from_env_vars(),
         }
     }
+
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            millis_between_user_payment_flows: 0,
+            time_between_lock_attempts: Duration::ZERO,
+            google: Default::default(),
+            stripe: Default::default(),
+            apple: Default::default(),
+        }
+    }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct AppleConfig {
     pub iap_key: String,
     pub iap_key_id: String,
@@ -273,7 +353,7 @@ impl AppleConfig {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct GoogleConfig {
     pub service_account_key: Option<String>,
     pub premium_subscription_product_id: String,
@@ -296,7 +376,7 @@ impl GoogleConfig {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct StripeConfig {
     pub stripe_secret: String,
     pub signing_secret: String,
@@ -313,6 +393,233 @@ impl StripeConfig {
     }
 }
 
+#[derive(Clone, Debug, Default)]
+pub struct DataCapConfig {
+    /// Cap for accounts without an override set by an admin. If unset, billing decides the cap
+    /// or, with billing disabled, accounts are uncapped.
+    pub default: Option<u64>,
+}
+
+impl DataCapConfig {
+    pub fn from_env_vars() -> Self {
+        Self { default: env_or_empty("DEFAULT_DATA_CAP").map(|cap| cap.parse().unwrap()) }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimit {
+    /// Requests allowed at once
+    pub burst: u32,
+    /// Time for one more request to be allowed
+    pub refill: Duration,
+}
+
+/// Limits are off unless configured
+#[derive(Clone, Debug, Default)]
+pub struct RateLimitConfig {
+    /// Per account, for every request other than account creation
+    pub account: Option<RateLimit>,
+    /// Per ip, for account creation
+    pub new_account: Option<RateLimit>,
+    /// Reverse proxies whose `X-Forwarded-For` is believed when working out a request's ip
+    pub trusted_proxies: HashSet<IpAddr>,
+}
+
+impl RateLimitConfig {
+    pub fn from_env_vars() -> Self {
+        Self {
+            account: RateLimit::from_env_vars(
+                "RATE_LIMIT_ACCOUNT_BURST",
+                "RATE_LIMIT_ACCOUNT_REFILL_MILLIS",
+            ),
+            new_account: RateLimit::from_env_vars(
+                "RATE_LIMIT_NEW_ACCOUNT_BURST",
+                "RATE_LIMIT_NEW_ACCOUNT_REFILL_MILLIS",
+            ),
+            trusted_proxies: env_or_empty("RATE_LIMIT_TRUSTED_PROXIES")
+                .map(|proxies| {
+                    proxies
+                        .split(',')
+                        .map(|proxy| proxy.trim().parse().unwrap())
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl RateLimit {
+    fn from_env_vars(burst: &str, refill_millis: &str) -> Option<Self> {
+        match (env_or_empty(burst), env_or_empty(refill_millis)) {
+            (Some(burst), Some(refill_millis)) => Some(Self {
+                burst: burst.parse().unwrap(),
+                refill: Duration::from_millis(refill_millis.parse().unwrap()),
+            }),
+            (None, None) => None,
+            _ => panic!("Invalid config, {burst} and {refill_millis} go together"),
+        }
+    }
+}
+
+impl From<SelfHostedRateLimit> for RateLimit {
+    fn from(limit: SelfHostedRateLimit) -> Self {
+        Self { burst: limit.burst, refill: Duration::from_millis(limit.refill_millis) }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BackupConfig {
+    /// Where snapshots are taken; no snapshots are taken if unset
+    pub path: Option<PathBuf>,
+    pub time_between_backups: Duration,
+    /// Every this many snapshots, one is taken without building on the previous one
+    pub backups_between_full: u32,
+}
+
+impl BackupConfig {
+    pub fn from_env_vars() -> Self {
+        Self {
+            path: env_or_empty("BACKUP_PATH").map(PathBuf::from),
+            time_between_backups: Duration::from_secs(
+                env_or_empty("MINUTES_BETWEEN_BACKUPS")
+                    .map(|minutes| minutes.parse::<u64>().unwrap())
+                    .unwrap_or(60)
+                    * 60,
+            ),
+            backups_between_full: env_or_empty("BACKUPS_BETWEEN_FULL")
+                .map(|count| count.parse().unwrap())
+                .unwrap_or(24),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SelfHostedFile {
+    server: SelfHostedServer,
+    index_db: SelfHostedIndexDb,
+    files: SelfHostedFiles,
+    #[serde(default)]
+    metrics: SelfHostedMetrics,
+    #[serde(default)]
+    admin: SelfHostedAdmin,
+    #[serde(default)]
+    features: SelfHostedFeatures,
+    #[serde(default)]
+    data_caps: SelfHostedDataCaps,
+    #[serde(default)]
+    rate_limits: SelfHostedRateLimits,
+    #[serde(default)]
+    backup: SelfHostedBackup,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SelfHostedServer {
+    port: u16,
+    log_path: String,
+    #[serde(default = "default_min_core_version")]
+    min_core_version: String,
+    #[serde(default = "default_max_auth_delay")]
+    max_auth_delay: u128,
+    ssl_cert_location: Option<String>,
+    ssl_private_key_location: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SelfHostedIndexDb {
+    db_location: String,
+    #[serde(default = "default_minutes_between_compacts")]
+    minutes_between_compacts: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SelfHostedFiles {
+    path: PathBuf,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, default)]
+struct SelfHostedMetrics {
+    minutes_between_refresh: u64,
+    millis_between_metrics: u64,
+}
+
+impl Default for SelfHostedMetrics {
+    fn default() -> Self {
+        Self { minutes_between_refresh: 5, millis_between_metrics: 100 }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct SelfHostedAdmin {
+    #[serde(default)]
+    admins: HashSet<Username>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, default)]
+struct SelfHostedFeatures {
+    new_accounts: bool,
+}
+
+impl Default for SelfHostedFeatures {
+    fn default() -> Self {
+        Self { new_accounts: true }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct SelfHostedDataCaps {
+    default: Option<u64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct SelfHostedRateLimits {
+    account: Option<SelfHostedRateLimit>,
+    new_account: Option<SelfHostedRateLimit>,
+    #[serde(default)]
+    trusted_proxies: HashSet<IpAddr>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SelfHostedRateLimit {
+    burst: u32,
+    refill_millis: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, default)]
+struct SelfHostedBackup {
+    path: Option<PathBuf>,
+    minutes_between_backups: u64,
+    backups_between_full: u32,
+}
+
+impl Default for SelfHostedBackup {
+    fn default() -> Self {
+        Self { path: None, minutes_between_backups: 60, backups_between_full: 24 }
+    }
+}
+
+fn default_min_core_version() -> String {
+    ">=0.0.0".to_string()
+}
+
+fn default_max_auth_delay() -> u128 {
+    200_000
+}
+
+fn default_minutes_between_compacts() -> u64 {
+    60
+}
+
 fn env_or_panic(var_name: &str) -> String {
     env::var(var_name).unwrap_or_else(|_| panic!("Missing environment variable {}", var_name))
 }
@@ -7,7 +7,7 @@ use std::collections::HashMap;
 use std::fmt::Debug;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use tokio::fs::{remove_file, File};
+use tokio::fs::{read_dir, remove_file, File};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use uuid::Uuid;
 
@@ -20,6 +20,8 @@ pub trait DocumentService: Send + Sync + Clone + 'static {
         &self, id: &Uuid, hmac: &DocumentHmac,
     ) -> Result<EncryptedDocument, ServerError<T>>;
     async fn delete<T: Debug>(&self, id: &Uuid, hmac: &DocumentHmac) -> Result<(), ServerError<T>>;
+    /// Every stored document, including any no file refers to anymore
+    async fn list<T: Debug>(&self) -> Result<Vec<(Uuid, DocumentHmac)>, ServerError<T>>;
 
     fn exists(&self, id: &Uuid, hmac: &DocumentHmac) -> bool;
     fn get_path(&self, id: &Uuid, hmac: &DocumentHmac) -> PathBuf;
@@ -75,6 +77,20 @@ impl DocumentService for OnDiskDocuments {
         Ok(())
     }
 
+    async fn list<T: Debug>(&self) -> Result<Vec<(Uuid, DocumentHmac)>, ServerError<T>> {
+        let mut result = vec![];
+        let mut entries = read_dir(&self.config.files.path).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if !entry.file_type().await?.is_file() {
+                continue;
+            }
+            if let Some(key) = parse_key(&entry.file_name().to_string_lossy()) {
+                result.push(key);
+            }
+        }
+        Ok(result)
+    }
+
     fn exists(&self, id: &Uuid, hmac: &DocumentHmac) -> bool {
         self.get_path(id, hmac).exists()
     }
@@ -130,4 +146,21 @@ impl DocumentService for InMemDocuments {
 
         Ok(())
     }
+
+    async fn list<T: Debug>(&self) -> Result<Vec<(Uuid, DocumentHmac)>, ServerError<T>> {
+        Ok(self
+            .docs
+            .lock()
+            .unwrap()
+            .keys()
+            .filter_map(|key| parse_key(key))
+            .collect())
+    }
+}
+
+/// Inverse of the `{id}-{hmac}` naming above; `None` for anything else found among the documents
+fn parse_key(key: &str) -> Option<(Uuid, DocumentHmac)> {
+    let id = Uuid::parse_str(key.get(..36)?).ok()?;
+    let hmac = base64::decode_config(key.get(36..)?.strip_prefix('-')?, base64::URL_SAFE).ok()?;
+    Some((id, hmac.try_into().ok()?))
 }
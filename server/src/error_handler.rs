@@ -54,6 +54,11 @@ impl From<LbErr> for ServerError<GetUsageError> {
         internal!("{:?}", err)
     }
 }
+impl From<LbErr> for ServerError<PublishError> {
+    fn from(err: LbErr) -> Self {
+        internal!("{:?}", err)
+    }
+}
 
 impl From<ServerError<GetUsageHelperError>> for ServerError<GetUsageError> {
     fn from(e: ServerError<GetUsageHelperError>) -> Self {
@@ -369,6 +374,24 @@ impl From<LbErr> for ServerError<GetUpdatesError> {
     }
 }
 
+impl From<LbErr> for ServerError<AwaitUpdatesError> {
+    fn from(err: LbErr) -> Self {
+        internal!("{:?}", err)
+    }
+}
+
+impl From<LbErr> for ServerError<PushCollabError> {
+    fn from(err: LbErr) -> Self {
+        internal!("{:?}", err)
+    }
+}
+
+impl From<LbErr> for ServerError<PullCollabError> {
+    fn from(err: LbErr) -> Self {
+        internal!("{:?}", err)
+    }
+}
+
 impl<T: Debug> From<DbError> for ServerError<T> {
     fn from(value: DbError) -> Self {
         internal!("db-rs error {:?}", value)
@@ -2,6 +2,8 @@ use crate::billing::app_store_client::AppStoreClient;
 use crate::billing::google_play_client::GooglePlayClient;
 use crate::billing::stripe_client::StripeClient;
 use crate::document_service::DocumentService;
+use crate::notification_service::interested_owners;
+use crate::publication_service::PUBLICATION_HMAC;
 use crate::schema::ServerDb;
 use crate::ServerError;
 use crate::ServerError::ClientError;
@@ -13,14 +15,19 @@ use lb_rs::model::api::*;
 use lb_rs::model::clock::get_time;
 use lb_rs::model::errors::{LbErrKind, LbResult};
 use lb_rs::model::file_like::FileLike;
-use lb_rs::model::file_metadata::{Diff, Owner};
+use lb_rs::model::file_metadata::{Diff, DocumentHmac, FileType, Owner};
 use lb_rs::model::server_file::{IntoServerFile, ServerFile};
-use lb_rs::model::server_tree::ServerTree;
+use lb_rs::model::server_tree::{ServerTree, ServerTreeView};
 use lb_rs::model::tree_like::TreeLike;
 use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
 use std::ops::DerefMut;
 use tracing::{debug, error, warn};
+use uuid::Uuid;
+
+/// Takeout pages end after this many files or once their documents add up to this many bytes
+const TAKEOUT_PAGE_FILES: usize = 1000;
+const TAKEOUT_PAGE_BYTES: usize = 32 * 1024 * 1024;
 
 impl<S, A, G, D> ServerState<S, A, G, D>
 where
@@ -35,24 +42,39 @@ where
         let request = context.request;
         let req_owner = Owner(context.public_key);
 
-        let mut new_deleted = vec![];
-        {
-            let mut prior_deleted = HashSet::new();
-            let mut current_deleted = HashSet::new();
+        // everyone whose tree this change touches: the owners of the changed files and anyone
+        // gaining or losing access to them. Descendants have the same owner as their parent, so
+        // this also covers files deleted along with a changed folder.
+        let mut owners = HashSet::from([req_owner]);
+        for update in &request.updates {
+            for file in update.old.iter().chain(Some(&update.new)) {
+                owners.insert(file.owner());
+                owners.extend(
+                    file.user_access_keys()
+                        .iter()
+                        .map(|k| Owner(k.encrypted_for)),
+                );
+            }
+        }
+        let _owner_guards = self.owner_locks.lock(owners).await;
 
-            let mut lock = self.index_db.lock().await;
-            let db = lock.deref_mut();
-            let tx = db.begin_transaction()?;
+        // validate while other requests read the db; with those owners locked, nothing can change
+        // the outcome before the change is applied
+        let mut prior_deleted = HashSet::new();
+        let mut current_deleted = HashSet::new();
+        {
+            let db = self.index_db.read().await;
 
-            let usage_cap =
-                Self::get_cap(db, &context.public_key).map_err(|err| internal!("{:?}", err))?;
+            let usage_cap = self
+                .get_cap(&db, &context.public_key)
+                .map_err(|err| internal!("{:?}", err))?;
 
-            let mut tree = ServerTree::new(
+            let mut tree = ServerTreeView::new(
                 req_owner,
-                &mut db.owned_files,
-                &mut db.shared_files,
-                &mut db.file_children,
-                &mut db.metas,
+                &db.owned_files,
+                &db.shared_files,
+                &db.file_children,
+                &db.metas,
             )?
             .to_lazy();
 
@@ -88,8 +110,27 @@ where
             if new_usage > usage_cap && new_usage >= old_usage {
                 return Err(ClientError(UpsertError::UsageIsOverDataCap));
             }
+        }
+
+        let mut new_deleted = vec![];
+        let mut notify = HashSet::new();
+        {
+            let mut lock = self.index_db.write().await;
+            let db = lock.deref_mut();
+            let tx = db.begin_transaction()?;
 
-            let tree = tree.promote()?;
+            let tree = ServerTree::new(
+                req_owner,
+                &mut db.owned_files,
+                &mut db.shared_files,
+                &mut db.file_children,
+                &mut db.metas,
+            )?
+            .to_lazy();
+
+            // staging again only repeats the cheap version checks, which document changes (which
+            // don't take owner locks) could have invalidated
+            let tree = tree.stage_diff(request.updates.clone())?.promote()?;
 
             for id in tree.ids() {
                 if tree.find(&id)?.is_document()
@@ -115,10 +156,30 @@ where
                 }
             }
 
+            let mut version_index = self.version_index.lock()?;
+            for update in &request.updates {
+                version_index.record(db.metas.get(), db.file_children.get(), update.new.id());
+            }
+            drop(version_index);
+
+            // from the metas rather than the tree, which doesn't list the files this created
+            for update in &request.updates {
+                notify.extend(interested_owners(&db.metas, update.new.id()));
+                if let Some(old) = &update.old {
+                    // people who just lost access should hear about it too
+                    notify.extend(
+                        old.user_access_keys()
+                            .iter()
+                            .map(|k| Owner(k.encrypted_for)),
+                    );
+                }
+            }
+
             db.last_seen.insert(req_owner, get_time().0 as u64)?;
 
             tx.drop_safely()?;
         }
+        self.update_notifier.notify(notify);
 
         for update in request.updates {
             let new = update.new;
@@ -211,11 +272,11 @@ where
 
         let req_pk = context.public_key;
 
-        {
-            let mut lock = self.index_db.lock().await;
-            let db = lock.deref_mut();
-            let usage_cap =
-                Self::get_cap(db, &context.public_key).map_err(|err| internal!("{:?}", err))?;
+        let meta_owner = {
+            let db = self.index_db.read().await;
+            let usage_cap = self
+                .get_cap(&db, &context.public_key)
+                .map_err(|err| internal!("{:?}", err))?;
 
             let meta = db
                 .metas
@@ -224,12 +285,12 @@ where
                 .ok_or(ClientError(DocumentNotFound))?
                 .clone();
 
-            let mut tree = ServerTree::new(
+            let mut tree = ServerTreeView::new(
                 owner,
-                &mut db.owned_files,
-                &mut db.shared_files,
-                &mut db.file_children,
-                &mut db.metas,
+                &db.owned_files,
+                &db.shared_files,
+                &db.file_children,
+                &db.metas,
             )?
             .to_lazy();
 
@@ -299,10 +360,13 @@ where
             // Here is where you would check if the person is out of space as a result of the new file.
             // You could make this a transaction and check whether or not this is an increase in size or
             // a reduction
-        }
 
-        let new_version = get_time().0 as u64;
-        let new = request.diff.new.clone().add_time(new_version);
+            meta_owner
+        };
+
+        // held until the index refers to the new contents, so they can't be collected as orphaned
+        let _owner_guards = self.owner_locks.lock([meta_owner]).await;
+
         self.document_service
             .insert(
                 request.diff.new.id(),
@@ -313,7 +377,7 @@ where
         debug!(?id, ?hmac, "Inserted document contents");
 
         let result = async {
-            let mut lock = self.index_db.lock().await;
+            let mut lock = self.index_db.write().await;
             let db = lock.deref_mut();
             let tx = db.begin_transaction()?;
 
@@ -343,12 +407,18 @@ where
             }
 
             db.sizes.insert(*meta.id(), new_size)?;
+            let notify = interested_owners(&tree, &id);
+            // versioned under the write lock so an update listing can't read past it
+            let new = request.diff.new.clone().add_time(get_time().0 as u64);
             tree.stage(vec![new]).promote()?;
+            self.version_index
+                .lock()?
+                .record(db.metas.get(), db.file_children.get(), &id);
             db.last_seen.insert(owner, get_time().0 as u64)?;
 
             tx.drop_safely()?;
             drop(lock);
-            Ok(())
+            Ok(notify)
         };
 
         let result = result.await;
@@ -361,7 +431,7 @@ where
             debug!(?id, ?hmac, "Cleaned up new document contents after failed metadata update");
         }
 
-        result?;
+        self.update_notifier.notify(result?);
 
         // New
         if let Some(hmac) = request.diff.old.unwrap().document_hmac() {
@@ -384,18 +454,16 @@ where
     ) -> Result<GetDocumentResponse, ServerError<GetDocumentError>> {
         let request = &context.request;
         {
-            let mut lock = self.index_db.lock().await;
-            let db = lock.deref_mut();
-            let tx = db.begin_transaction()?;
+            let db = self.index_db.read().await;
 
             let meta_exists = db.metas.get().get(&request.id).is_some();
 
-            let mut tree = ServerTree::new(
+            let mut tree = ServerTreeView::new(
                 Owner(context.public_key),
-                &mut db.owned_files,
-                &mut db.shared_files,
-                &mut db.file_children,
-                &mut db.metas,
+                &db.owned_files,
+                &db.shared_files,
+                &db.file_children,
+                &db.metas,
             )?
             .to_lazy();
 
@@ -419,8 +487,6 @@ where
             if tree.calculate_deleted(&request.id)? {
                 return Err(ClientError(GetDocumentError::DocumentNotFound));
             }
-
-            tx.drop_safely()?;
         }
 
         let content = self
@@ -434,16 +500,15 @@ where
         &self, context: RequestContext<GetFileIdsRequest>,
     ) -> Result<GetFileIdsResponse, ServerError<GetFileIdsError>> {
         let owner = Owner(context.public_key);
-        let mut db = self.index_db.lock().await;
-        let db = db.deref_mut();
+        let db = self.index_db.read().await;
 
         Ok(GetFileIdsResponse {
-            ids: ServerTree::new(
+            ids: ServerTreeView::new(
                 owner,
-                &mut db.owned_files,
-                &mut db.shared_files,
-                &mut db.file_children,
-                &mut db.metas,
+                &db.owned_files,
+                &db.shared_files,
+                &db.file_children,
+                &db.metas,
             )?
             .ids()
             .into_iter()
@@ -456,53 +521,135 @@ where
     ) -> Result<GetUpdatesResponse, ServerError<GetUpdatesError>> {
         let request = &context.request;
         let owner = Owner(context.public_key);
+        let since = request.since_metadata_version;
+        let after = match &request.cursor {
+            Some(cursor) => Some(
+                decode_updates_cursor(cursor, since)
+                    .ok_or(ClientError(GetUpdatesError::InvalidCursor))?,
+            ),
+            None => None,
+        };
 
-        let mut db = self.index_db.lock().await;
-        let db = db.deref_mut();
-        let mut tree = ServerTree::new(
-            owner,
-            &mut db.owned_files,
-            &mut db.shared_files,
-            &mut db.file_children,
-            &mut db.metas,
-        )?
-        .to_lazy();
+        let db = self.index_db.read().await;
+        let version_index = self.version_index.lock()?;
+        let metas = db.metas.get();
+        // one more than the page, to tell whether there's another
+        let limit = request
+            .page_size
+            .map(|page_size| page_size.max(1) + 1)
+            .unwrap_or(usize::MAX);
+
+        let updates: Vec<(u64, Uuid)> = version_index
+            .since(&owner, since, after)
+            .take(limit)
+            .collect();
 
-        let mut result_ids = HashSet::new();
-        for id in tree.ids() {
-            let file = tree.find(&id)?;
-            if file.version >= request.since_metadata_version {
-                result_ids.insert(id);
-                if file.owner() != owner
-                    && file
-                        .user_access_keys()
-                        .iter()
-                        .any(|k| !k.deleted && k.encrypted_for == context.public_key)
-                {
-                    result_ids.insert(id);
-                    result_ids.extend(tree.descendants(&id)?);
-                }
-            }
-        }
+        let end = match request.page_size {
+            Some(page_size) => updates.len().min(page_size.max(1)),
+            None => updates.len(),
+        };
+        let next_cursor =
+            (end < updates.len()).then(|| encode_updates_cursor(since, updates[end - 1]));
 
         Ok(GetUpdatesResponse {
-            as_of_metadata_version: get_time().0 as u64,
-            file_metadata: tree
-                .all_files()?
+            // listings include their `since` version, so start the next one past everything this
+            // one could see; later changes are versioned from the clock, above it
+            as_of_metadata_version: version_index.latest() + 1,
+            file_metadata: updates[..end]
                 .iter()
-                .filter(|meta| result_ids.contains(meta.id()))
+                .filter_map(|(_, id)| metas.get(id))
                 .map(|meta| meta.file.clone())
                 .collect(),
+            next_cursor,
         })
     }
 
+    pub async fn takeout(
+        &self, context: RequestContext<TakeoutRequest>,
+    ) -> Result<TakeoutResponse, ServerError<TakeoutError>> {
+        let request = &context.request;
+
+        let owner = {
+            let db = self.index_db.read().await;
+            let owner = match &request.username {
+                Some(username) => {
+                    if !Self::is_admin::<TakeoutError>(
+                        &db,
+                        &context.public_key,
+                        &self.config.admin.admins,
+                    )? {
+                        return Err(ClientError(TakeoutError::NotPermissioned));
+                    }
+                    *db.usernames
+                        .get()
+                        .get(username)
+                        .ok_or(ClientError(TakeoutError::UserNotFound))?
+                }
+                None => Owner(context.public_key),
+            };
+            if !db.accounts.get().contains_key(&owner) {
+                return Err(ClientError(TakeoutError::UserNotFound));
+            }
+            owner
+        };
+
+        // nothing changes the owner's files, so none of the documents in this page are replaced
+        // or deleted out from under us
+        let _owner_guards = self.owner_locks.lock([owner]).await;
+
+        let mut response = TakeoutResponse { files: vec![], documents: vec![], next: None };
+        let mut documents = vec![];
+        {
+            let db = self.index_db.read().await;
+            let metas = db.metas.get();
+            let sizes = db.sizes.get();
+            let mut ids: Vec<Uuid> = db
+                .owned_files
+                .get()
+                .get(&owner)
+                .into_iter()
+                .flatten()
+                .copied()
+                .filter(|id| request.after.map(|after| *id > after).unwrap_or(true))
+                .collect();
+            ids.sort();
+
+            let mut page_bytes = 0;
+            for (i, id) in ids.iter().enumerate() {
+                if i >= TAKEOUT_PAGE_FILES || page_bytes >= TAKEOUT_PAGE_BYTES {
+                    response.next = Some(ids[i - 1]);
+                    break;
+                }
+                let Some(meta) = metas.get(id) else { continue };
+                if let Some(hmac) = meta.document_hmac() {
+                    if !deleted_without_index(metas, meta) {
+                        page_bytes += sizes.get(id).copied().unwrap_or_default() as usize;
+                        documents.push((*id, *hmac));
+                    }
+                }
+                response.files.push(meta.clone());
+            }
+        }
+
+        // read without the index lock so other accounts aren't held up behind a large page
+        for (id, hmac) in documents {
+            let content = self.document_service.get(&id, &hmac).await?;
+            response
+                .documents
+                .push(TakeoutDocument { id, hmac, content });
+        }
+
+        Ok(response)
+    }
+
     pub async fn admin_disappear_file(
         &self, context: RequestContext<AdminDisappearFileRequest>,
     ) -> Result<(), ServerError<AdminDisappearFileError>> {
         let mut docs_to_delete = Vec::new();
+        let _owner_guards = self.owner_locks.lock_all().await;
 
         {
-            let mut db = self.index_db.lock().await;
+            let mut db = self.index_db.write().await;
             let db = db.deref_mut();
             let tx = db.begin_transaction()?;
 
@@ -556,6 +703,7 @@ where
                     .metas
                     .remove(&id)?
                     .ok_or(ClientError(AdminDisappearFileError::FileNonexistent))?;
+                self.version_index.lock()?.forget(&id);
 
                 // maintain index: owned_files
                 let owner = meta.owner();
@@ -613,7 +761,7 @@ where
         &self, context: RequestContext<AdminValidateAccountRequest>,
     ) -> Result<AdminValidateAccount, ServerError<AdminValidateAccountError>> {
         let request = &context.request;
-        let mut db = self.index_db.lock().await;
+        let mut db = self.index_db.write().await;
         if !Self::is_admin::<AdminValidateAccountError>(
             &db,
             &context.public_key,
@@ -682,7 +830,7 @@ where
     pub async fn admin_validate_server(
         &self, context: RequestContext<AdminValidateServerRequest>,
     ) -> Result<AdminValidateServer, ServerError<AdminValidateServerError>> {
-        let mut db = self.index_db.lock().await;
+        let mut db = self.index_db.write().await;
         let db = db.deref_mut();
 
         if !Self::is_admin::<AdminValidateServerError>(
@@ -693,6 +841,10 @@ where
             return Err(ClientError(AdminValidateServerError::NotPermissioned));
         }
 
+        Ok(self.validate_server_helper(db)?)
+    }
+
+    pub fn validate_server_helper(&self, db: &mut ServerDb) -> LbResult<AdminValidateServer> {
         let mut result: AdminValidateServer = Default::default();
 
         let mut deleted_ids = HashSet::new();
@@ -783,27 +935,7 @@ where
             }
         }
         for (id, meta) in db.metas.get().clone() {
-            // check for implicit deletion (can't use server tree which depends on index)
-            let mut deleted = false;
-            let mut ancestor = meta.clone();
-            loop {
-                if ancestor.explicitly_deleted() {
-                    deleted = true;
-                    break;
-                }
-                if ancestor.is_root() {
-                    break;
-                }
-                match db.metas.get().get(ancestor.parent()) {
-                    Some(parent) => ancestor = parent.clone(),
-                    None => {
-                        error!("missing parent for file {:?}", ancestor.parent());
-                        deleted = true;
-                        break;
-                    }
-                }
-            }
-            if deleted {
+            if deleted_without_index(db.metas.get(), &meta) {
                 continue;
             }
 
@@ -893,7 +1025,7 @@ where
         &self, context: RequestContext<AdminFileInfoRequest>,
     ) -> Result<AdminFileInfoResponse, ServerError<AdminFileInfoError>> {
         let request = &context.request;
-        let mut db = self.index_db.lock().await;
+        let mut db = self.index_db.write().await;
         let db = db.deref_mut();
         if !Self::is_admin::<AdminFileInfoError>(
             db,
@@ -938,7 +1070,8 @@ where
     pub async fn admin_rebuild_index(
         &self, context: RequestContext<AdminRebuildIndexRequest>,
     ) -> Result<(), ServerError<AdminRebuildIndexError>> {
-        let mut db = self.index_db.lock().await;
+        let _owner_guards = self.owner_locks.lock_all().await;
+        let mut db = self.index_db.write().await;
 
         match context.request.index {
             ServerIndex::OwnedFiles => {
@@ -956,28 +1089,7 @@ where
                     db.shared_files.create_key(*owner)?;
                 }
                 for (id, file) in db.metas.get().clone() {
-                    // check for implicit deletion (can't use server tree which depends on index)
-                    let mut deleted = false;
-                    let mut ancestor = file.clone();
-                    loop {
-                        if ancestor.explicitly_deleted() {
-                            deleted = true;
-                            break;
-                        }
-                        if ancestor.is_root() {
-                            break;
-                        }
-                        match db.metas.get().get(ancestor.parent()) {
-                            Some(parent) => ancestor = parent.clone(),
-                            None => {
-                                error!("missing parent for file {:?}", ancestor.parent());
-                                deleted = true;
-                                break;
-                            }
-                        }
-                    }
-
-                    if !deleted {
+                    if !deleted_without_index(db.metas.get(), &file) {
                         for user_access_key in file.user_access_keys() {
                             if !user_access_key.deleted
                                 && user_access_key.encrypted_for != user_access_key.encrypted_by
@@ -1003,6 +1115,447 @@ where
     }
 }
 
+impl<S, A, G, D> ServerState<S, A, G, D>
+where
+    S: StripeClient,
+    A: AppStoreClient,
+    G: GooglePlayClient,
+    D: DocumentService,
+{
+    /// Works out what a repair would change and, unless it's a dry run, changes it. Planning reads
+    /// every document for some repairs, so it happens while other requests carry on; writers only
+    /// wait while the plan is applied, and whatever changed in the meantime is left out.
+    pub async fn admin_repair_server(
+        &self, context: RequestContext<AdminRepairServerRequest>,
+    ) -> Result<AdminRepairServer, ServerError<AdminRepairServerError>> {
+        let request = &context.request;
+        let mut result = AdminRepairServer::default();
+        let mut sized = HashMap::new();
+        let mut docs_to_delete = Vec::new();
+
+        {
+            let db = self.index_db.read().await;
+
+            if !Self::is_admin::<AdminRepairServerError>(
+                &db,
+                &context.public_key,
+                &self.config.admin.admins,
+            )? {
+                return Err(ClientError(AdminRepairServerError::NotPermissioned));
+            }
+
+            match request.repair {
+                ServerRepair::Sizes => {
+                    sized = self.plan_size_repair(&db, &mut result).await;
+                }
+                ServerRepair::OrphanedDocuments => {
+                    for (id, hmac) in self.document_service.list().await? {
+                        if !document_referenced(&db, &id, &hmac) {
+                            docs_to_delete.push((id, hmac));
+                        }
+                    }
+                }
+                ServerRepair::Indexes => Self::plan_index_repair(&db, &mut result),
+                ServerRepair::BrokenShares => Self::plan_share_repair(&db, &mut result),
+            }
+        }
+
+        if request.dry_run {
+            for (id, _) in &docs_to_delete {
+                *result.orphaned_documents_deleted.entry(*id).or_default() += 1;
+            }
+            return Ok(result);
+        }
+
+        let _owner_guards = self.owner_locks.lock_all().await;
+        {
+            let mut db = self.index_db.write().await;
+            let db = db.deref_mut();
+
+            match request.repair {
+                ServerRepair::Sizes => {
+                    result.sizes_set.retain(|id, _| {
+                        db.metas.get().get(id).is_some_and(|meta| {
+                            meta.document_hmac() == sized.get(id)
+                                && !deleted_without_index(db.metas.get(), meta)
+                        })
+                    });
+                    result.sizes_removed.retain(|id| {
+                        !db.metas
+                            .get()
+                            .get(id)
+                            .is_some_and(|meta| meta.document_hmac().is_some())
+                    });
+                }
+                ServerRepair::OrphanedDocuments => {
+                    docs_to_delete.retain(|(id, hmac)| !document_referenced(db, id, hmac));
+                }
+                // these only read the index, so they're planned again now that it can't change
+                ServerRepair::Indexes => {
+                    result = Default::default();
+                    Self::plan_index_repair(db, &mut result);
+                }
+                ServerRepair::BrokenShares => {
+                    result = Default::default();
+                    Self::plan_share_repair(db, &mut result);
+                }
+            }
+            for (id, _) in &docs_to_delete {
+                *result.orphaned_documents_deleted.entry(*id).or_default() += 1;
+            }
+
+            let tx = db.begin_transaction()?;
+            for (id, size) in &result.sizes_set {
+                db.sizes.insert(*id, *size)?;
+            }
+            for id in &result.sizes_removed {
+                db.sizes.remove(id)?;
+            }
+            for (owner, ids) in &result.owned_files_mapped {
+                for id in ids {
+                    db.owned_files.insert(*owner, *id)?;
+                }
+            }
+            for (owner, ids) in &result.owned_files_unmapped {
+                for id in ids {
+                    db.owned_files.remove(owner, id)?;
+                }
+            }
+            for (sharee, ids) in &result.shared_files_mapped {
+                for id in ids {
+                    db.shared_files.insert(*sharee, *id)?;
+                }
+            }
+            for (sharee, ids) in &result.shared_files_unmapped {
+                for id in ids {
+                    db.shared_files.remove(sharee, id)?;
+                }
+            }
+            for (parent, ids) in &result.file_children_mapped {
+                for id in ids {
+                    db.file_children.insert(*parent, *id)?;
+                }
+            }
+            for (parent, ids) in &result.file_children_unmapped {
+                for id in ids {
+                    db.file_children.remove(parent, id)?;
+                }
+            }
+            for (owner, ids) in &result.links_disappeared {
+                for id in ids {
+                    if let Some(link) = db.metas.remove(id)? {
+                        db.owned_files.remove(owner, id)?;
+                        db.file_children.remove(link.parent(), id)?;
+                        self.version_index.lock()?.forget(id);
+                    }
+                }
+            }
+            tx.drop_safely()?;
+
+            let username = db
+                .accounts
+                .get()
+                .get(&Owner(context.public_key))
+                .map(|account| account.username.clone())
+                .unwrap_or_else(|| "~unknown~".to_string());
+            warn!(?username, ?request.repair, "Repaired server");
+        }
+
+        for (id, hmac) in docs_to_delete {
+            self.document_service.delete(&id, &hmac).await?;
+        }
+
+        Ok(result)
+    }
+
+    /// Returns the hmac of each document whose size it plans to set, so the plan can be checked
+    /// against the documents' current versions before it's applied
+    async fn plan_size_repair(
+        &self, db: &ServerDb, result: &mut AdminRepairServer,
+    ) -> HashMap<Uuid, DocumentHmac> {
+        let mut sized = HashMap::new();
+        for (id, meta) in db.metas.get() {
+            let Some(hmac) = meta.document_hmac() else { continue };
+            if deleted_without_index(db.metas.get(), meta)
+                || !self.document_service.exists(id, hmac)
+            {
+                continue;
+            }
+            match self
+                .document_service
+                .get::<AdminRepairServerError>(id, hmac)
+                .await
+            {
+                Ok(doc) => {
+                    let size = doc.value.len() as u64;
+                    if db.sizes.get().get(id) != Some(&size) {
+                        result.sizes_set.insert(*id, size);
+                        sized.insert(*id, *hmac);
+                    }
+                }
+                Err(err) => error!(?id, ?err, "could not read document to size it"),
+            }
+        }
+        for id in db.sizes.get().keys() {
+            let has_contents = db
+                .metas
+                .get()
+                .get(id)
+                .is_some_and(|meta| meta.document_hmac().is_some());
+            if !has_contents {
+                result.sizes_removed.insert(*id);
+            }
+        }
+        sized
+    }
+
+    fn plan_share_repair(db: &ServerDb, result: &mut AdminRepairServer) {
+        for (id, meta) in db.metas.get() {
+            let FileType::Link { target } = meta.file_type() else { continue };
+            if deleted_without_index(db.metas.get(), meta) {
+                continue;
+            }
+            let owner = meta.owner();
+            let broken = match db.metas.get().get(&target) {
+                Some(target) => {
+                    target.owner() == owner
+                        || deleted_without_index(db.metas.get(), target)
+                        || !shared_with(db.metas.get(), target, owner)
+                }
+                None => true,
+            };
+            if broken {
+                insert(&mut result.links_disappeared, owner, *id);
+            }
+        }
+    }
+
+    /// Compares the indexes to what they'd be if rebuilt from metas. Unlike
+    /// [Self::admin_rebuild_index], leaves entries that are already right alone.
+    fn plan_index_repair(db: &ServerDb, result: &mut AdminRepairServer) {
+        let mut owned_files = HashMap::new();
+        let mut shared_files = HashMap::new();
+        let mut file_children = HashMap::new();
+        for (id, meta) in db.metas.get() {
+            insert(&mut owned_files, meta.owner(), *id);
+            if !meta.is_root() {
+                insert(&mut file_children, *meta.parent(), *id);
+            }
+            if !deleted_without_index(db.metas.get(), meta) {
+                for k in meta.user_access_keys() {
+                    if !k.deleted && k.encrypted_for != k.encrypted_by {
+                        insert(&mut shared_files, Owner(k.encrypted_for), *id);
+                    }
+                }
+            }
+        }
+
+        (result.owned_files_mapped, result.owned_files_unmapped) =
+            index_diff(&owned_files, db.owned_files.get());
+        (result.shared_files_mapped, result.shared_files_unmapped) =
+            index_diff(&shared_files, db.shared_files.get());
+        (result.file_children_mapped, result.file_children_unmapped) =
+            index_diff(&file_children, db.file_children.get());
+    }
+}
+
+/// Whether these are the current, undeleted contents of a document
+fn document_referenced(db: &ServerDb, id: &Uuid, hmac: &DocumentHmac) -> bool {
+    if *hmac == PUBLICATION_HMAC && db.publications.get().contains_key(id) {
+        return true;
+    }
+    db.metas.get().get(id).is_some_and(|meta| {
+        meta.document_hmac() == Some(hmac) && !deleted_without_index(db.metas.get(), meta)
+    })
+}
+
+/// Entries in `expected` missing from `actual`, and entries in `actual` missing from `expected`
+#[allow(clippy::type_complexity)]
+fn index_diff<K: Hash + Eq + Copy>(
+    expected: &HashMap<K, HashSet<Uuid>>, actual: &HashMap<K, HashSet<Uuid>>,
+) -> (HashMap<K, HashSet<Uuid>>, HashMap<K, HashSet<Uuid>>) {
+    let mut missing = HashMap::new();
+    let mut extra = HashMap::new();
+    for (k, ids) in expected {
+        for id in ids {
+            if !actual.get(k).is_some_and(|actual| actual.contains(id)) {
+                insert(&mut missing, *k, *id);
+            }
+        }
+    }
+    for (k, ids) in actual {
+        for id in ids {
+            if !expected
+                .get(k)
+                .is_some_and(|expected| expected.contains(id))
+            {
+                insert(&mut extra, *k, *id);
+            }
+        }
+    }
+    (missing, extra)
+}
+
+/// Whether `file` or one of its ancestors has a live share with `sharee`
+fn shared_with(metas: &HashMap<Uuid, ServerFile>, file: &ServerFile, sharee: Owner) -> bool {
+    let mut ancestor = file;
+    loop {
+        if ancestor
+            .user_access_keys()
+            .iter()
+            .any(|k| !k.deleted && k.encrypted_for == sharee.0)
+        {
+            return true;
+        }
+        if ancestor.is_root() {
+            return false;
+        }
+        match metas.get(ancestor.parent()) {
+            Some(parent) => ancestor = parent,
+            None => return false,
+        }
+    }
+}
+
+/// Opaque to clients: the version the listing started from and the last update returned
+fn encode_updates_cursor(since: u64, last: (u64, Uuid)) -> String {
+    base64::encode_config(bincode::serialize(&(since, last)).unwrap(), base64::URL_SAFE)
+}
+
+fn decode_updates_cursor(cursor: &str, since: u64) -> Option<(u64, Uuid)> {
+    let bytes = base64::decode_config(cursor, base64::URL_SAFE).ok()?;
+    let (cursor_since, last): (u64, (u64, Uuid)) = bincode::deserialize(&bytes).ok()?;
+    (cursor_since == since).then_some(last)
+}
+
+/// Whether `file` or one of its ancestors is deleted, walking `metas` directly for when the
+/// server tree's indexes can't be trusted
+pub fn deleted_without_index(metas: &HashMap<Uuid, ServerFile>, file: &ServerFile) -> bool {
+    let mut ancestor = file;
+    loop {
+        if ancestor.explicitly_deleted() {
+            return true;
+        }
+        if ancestor.is_root() {
+            return false;
+        }
+        match metas.get(ancestor.parent()) {
+            Some(parent) => ancestor = parent,
+            None => {
+                error!("missing parent for file {:?}", ancestor.parent());
+                return true;
+            }
+        }
+    }
+}
+
 fn insert<K: Hash + Eq, V: Hash + Eq>(map: &mut HashMap<K, HashSet<V>>, k: K, v: V) {
     map.entry(k).or_default().insert(v);
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::*;
+    use lb_rs::model::crypto::AESEncrypted;
+    use std::path::Path;
+
+    async fn admin_server(dir: &Path) -> (TestServer, TestAccount) {
+        let mut server = test_server(dir);
+        let admin = test_account(&server).await;
+        server
+            .config
+            .admin
+            .admins
+            .insert(admin.account.username.clone());
+        (server, admin)
+    }
+
+    async fn repair(
+        server: &TestServer, admin: &TestAccount, repair: ServerRepair, dry_run: bool,
+    ) -> AdminRepairServer {
+        server
+            .admin_repair_server(RequestContext {
+                request: AdminRepairServerRequest { repair, dry_run },
+                public_key: admin.account.public_key(),
+            })
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn repair_sizes() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server, admin) = admin_server(dir.path()).await;
+        let doc = admin.create_doc(&server, "doc.md", b"content").await;
+        let gone = Uuid::new_v4();
+        {
+            let mut db = server.index_db.write().await;
+            db.sizes.insert(*doc.id(), 1).unwrap();
+            db.sizes.insert(gone, 1).unwrap();
+        }
+
+        let planned = repair(&server, &admin, ServerRepair::Sizes, true).await;
+        assert_eq!(planned.sizes_set, HashMap::from([(*doc.id(), 7)]));
+        assert_eq!(planned.sizes_removed, HashSet::from([gone]));
+        assert_eq!(server.index_db.read().await.sizes.get().get(doc.id()), Some(&1));
+
+        let repaired = repair(&server, &admin, ServerRepair::Sizes, false).await;
+        assert_eq!(repaired, planned);
+        let db = server.index_db.read().await;
+        assert_eq!(db.sizes.get().get(doc.id()), Some(&7));
+        assert_eq!(db.sizes.get().get(&gone), None);
+    }
+
+    #[tokio::test]
+    async fn repair_orphaned_documents() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server, admin) = admin_server(dir.path()).await;
+        let doc = admin.create_doc(&server, "doc.md", b"content").await;
+        let (orphan, hmac) = (Uuid::new_v4(), rand_hmac());
+        server
+            .document_service
+            .insert::<()>(&orphan, &hmac, &AESEncrypted::new(b"orphan".as_slice(), vec![0; 12]))
+            .await
+            .unwrap();
+
+        let repaired = repair(&server, &admin, ServerRepair::OrphanedDocuments, false).await;
+        assert_eq!(repaired.orphaned_documents_deleted, HashMap::from([(orphan, 1)]));
+        assert!(!server.document_service.exists(&orphan, &hmac));
+        assert!(server
+            .document_service
+            .exists(doc.id(), doc.document_hmac().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn repair_broken_shares() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server, admin) = admin_server(dir.path()).await;
+        let other = test_account(&server).await;
+        let unshared = other.create_doc(&server, "doc.md", b"content").await;
+
+        // a link to a file that was never shared with its owner, which sync would have refused
+        let link = admin.new_file("link", FileType::Link { target: *unshared.id() });
+        let owner = Owner(admin.account.public_key());
+        {
+            let mut db = server.index_db.write().await;
+            db.metas
+                .insert(*link.id(), link.clone().add_time(0))
+                .unwrap();
+            db.owned_files.insert(owner, *link.id()).unwrap();
+            db.file_children
+                .insert(*admin.root.id(), *link.id())
+                .unwrap();
+        }
+
+        let repaired = repair(&server, &admin, ServerRepair::BrokenShares, false).await;
+        assert_eq!(
+            repaired.links_disappeared,
+            HashMap::from([(owner, HashSet::from([*link.id()]))])
+        );
+        let db = server.index_db.read().await;
+        assert!(!db.metas.get().contains_key(link.id()));
+        assert!(!db.file_children.get()[admin.root.id()].contains(link.id()));
+        assert!(db.metas.get().contains_key(unshared.id()));
+    }
+}
@@ -1,13 +1,18 @@
 use billing::app_store_client::AppStoreClient;
 use billing::google_play_client::GooglePlayClient;
 use billing::stripe_client::StripeClient;
+use collab_service::CollabRelay;
 use document_service::DocumentService;
 use lb_rs::model::clock;
 use lb_rs::model::errors::LbResult;
+use notification_service::UpdateNotifier;
+use owner_locks::OwnerLocks;
+use rate_limiter::RateLimits;
 use std::env;
 use std::fmt::Debug;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::sync::{Arc, Mutex};
+use tokio::sync::RwLock;
+use version_index::VersionIndex;
 
 use lb_rs::model::api::{ErrorWrapper, Request, RequestWrapper};
 use lb_rs::model::pubkey;
@@ -34,11 +39,43 @@ where
     D: DocumentService,
 {
     pub config: config::Config,
-    pub index_db: Arc<Mutex<ServerV4>>,
+    pub index_db: Arc<RwLock<ServerV4>>,
+    pub owner_locks: OwnerLocks,
+    pub rate_limits: Arc<RateLimits>,
+    pub version_index: Arc<Mutex<VersionIndex>>,
     pub stripe_client: S,
     pub google_play_client: G,
     pub app_store_client: A,
     pub document_service: D,
+    pub update_notifier: UpdateNotifier,
+    pub collab_relay: CollabRelay,
+}
+
+impl<S, A, G, D> ServerState<S, A, G, D>
+where
+    S: StripeClient,
+    A: AppStoreClient,
+    G: GooglePlayClient,
+    D: DocumentService,
+{
+    pub fn new(
+        config: config::Config, index_db: ServerV4, stripe_client: S, google_play_client: G,
+        app_store_client: A, document_service: D,
+    ) -> Self {
+        Self {
+            rate_limits: Arc::new(RateLimits::new(&config.rate_limits)),
+            version_index: Arc::new(Mutex::new(VersionIndex::new(index_db.metas.get()))),
+            index_db: Arc::new(RwLock::new(index_db)),
+            owner_locks: Default::default(),
+            stripe_client,
+            google_play_client,
+            app_store_client,
+            document_service,
+            update_notifier: Default::default(),
+            collab_relay: Default::default(),
+            config,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -95,13 +132,23 @@ where
 }
 
 pub mod account_service;
+pub mod audit_service;
+pub mod backup;
 pub mod billing;
+pub mod collab_service;
 pub mod config;
 pub mod document_service;
 pub mod error_handler;
 pub mod file_service;
 pub mod loggers;
 pub mod metrics;
+pub mod notification_service;
+pub mod owner_locks;
+pub mod publication_service;
+pub mod rate_limiter;
 pub mod router_service;
 pub mod schema;
+#[cfg(test)]
+mod test_utils;
 pub mod utils;
+pub mod version_index;
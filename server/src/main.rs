@@ -2,72 +2,133 @@
 
 use db_rs::Db;
 use lockbook_server_lib::billing::google_play_client::get_google_play_client;
+use lockbook_server_lib::billing::Nop;
 use lockbook_server_lib::config::Config;
 use lockbook_server_lib::document_service::OnDiskDocuments;
 use lockbook_server_lib::router_service::{
     app_store_notification_webhooks, build_info, core_routes, get_metrics,
-    google_play_notification_webhooks, stripe_webhooks,
+    google_play_notification_webhooks, publications, stripe_webhooks,
 };
 use lockbook_server_lib::schema::ServerV4;
 use lockbook_server_lib::*;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::RwLock;
 use tracing::*;
-use warp::Filter;
+use warp::{Filter, Rejection, Reply};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let cfg = Config::from_env_vars();
+    let mut args = std::env::args().skip(1).peekable();
+
+    // `restore <snapshot>` rebuilds the configured index db and files from a snapshot, then exits
+    let restore_from = if args.peek().map(String::as_str) == Some("restore") {
+        args.next();
+        Some(
+            args.next()
+                .map(PathBuf::from)
+                .ok_or("usage: restore <snapshot> [config file]")?,
+        )
+    } else {
+        None
+    };
+
+    // a config file (next argument or LOCKBOOK_CONFIG) selects the self-hosted profile
+    let config_file = args
+        .next()
+        .or_else(|| std::env::var("LOCKBOOK_CONFIG").ok())
+        .map(PathBuf::from);
+    let cfg = match config_file {
+        Some(path) => Config::from_file(&path)?,
+        None => Config::from_env_vars(),
+    };
     loggers::init(&cfg);
 
+    if let Some(snapshot) = restore_from {
+        let validation = backup::restore(&cfg, &snapshot).await?;
+        if validation == Default::default() {
+            println!("restored {} and it validated successfully", snapshot.display());
+        } else {
+            println!("restored {} but it failed validation: {validation:#?}", snapshot.display());
+        }
+        return Ok(());
+    }
+
     let config = cfg.clone();
-    let stripe_client = stripe::Client::new(&cfg.billing.stripe.stripe_secret);
-    let google_play_client = get_google_play_client(&cfg.billing.google.service_account_key).await;
     let index_db = ServerV4::init(db_rs::Config::in_folder(&cfg.index_db.db_location))
         .expect("Failed to load index_db");
-    let app_store_client = reqwest::Client::new();
 
     if index_db.incomplete_write().unwrap() {
         error!("dbrs indicated that the last write to the log was unsuccessful")
     }
 
-    let index_db = Arc::new(Mutex::new(index_db));
-    spawn_compacter(&cfg, &index_db);
-
     let document_service = OnDiskDocuments::from(&config);
 
-    let server_state = Arc::new(ServerState {
-        config,
-        index_db,
-        stripe_client,
-        google_play_client,
-        app_store_client,
-        document_service,
-    });
+    if cfg.billing.enabled {
+        let stripe_client = stripe::Client::new(&cfg.billing.stripe.stripe_secret);
+        let google_play_client =
+            get_google_play_client(&cfg.billing.google.service_account_key).await;
+        let app_store_client = reqwest::Client::new();
+
+        let server_state = Arc::new(ServerState::new(
+            config,
+            index_db,
+            stripe_client,
+            google_play_client,
+            app_store_client,
+            document_service,
+        ));
+        spawn_compacter(&cfg, &server_state.index_db);
+
+        let routes = core_routes(&server_state)
+            .or(build_info())
+            .or(publications(&server_state))
+            .or(stripe_webhooks(&server_state))
+            .or(google_play_notification_webhooks(&server_state))
+            .or(app_store_notification_webhooks(&server_state));
 
-    let routes = core_routes(&server_state)
-        .or(build_info())
-        .or(stripe_webhooks(&server_state))
-        .or(google_play_notification_webhooks(&server_state))
-        .or(app_store_notification_webhooks(&server_state));
+        server_state.start_metrics_worker();
+        server_state.start_backup_worker();
+        serve(&cfg, routes).await;
+    } else {
+        info!("billing is disabled");
 
+        let server_state =
+            Arc::new(ServerState::new(config, index_db, Nop {}, Nop {}, Nop {}, document_service));
+        spawn_compacter(&cfg, &server_state.index_db);
+
+        let routes = core_routes(&server_state)
+            .or(build_info())
+            .or(publications(&server_state));
+
+        server_state.start_metrics_worker();
+        server_state.start_backup_worker();
+        serve(&cfg, routes).await;
+    }
+
+    Ok(())
+}
+
+async fn serve<F, R>(cfg: &Config, routes: F)
+where
+    F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
     let server = warp::serve(routes);
 
     error!("server started successfully");
 
-    server_state.start_metrics_worker();
-
     // metrics endpoint to be served anauthenticated, locally, only
     tokio::spawn(warp::serve(get_metrics()).run(([127, 0, 0, 1], 8080)));
 
     // *** How people can connect to this server ***
-    match (cfg.server.ssl_cert_location, cfg.server.ssl_private_key_location) {
+    match (&cfg.server.ssl_cert_location, &cfg.server.ssl_private_key_location) {
         (Some(cert), Some(key)) => {
             info!("binding to https://0.0.0.0:{}", cfg.server.port);
             server
                 .tls()
-                .cert_path(&cert)
-                .key_path(&key)
+                .cert_path(cert)
+                .key_path(key)
                 .run(([0, 0, 0, 0], cfg.server.port))
                 .await
         }
@@ -79,17 +140,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             server.run(([0, 0, 0, 0], cfg.server.port)).await
         }
     };
-
-    Ok(())
 }
 
-fn spawn_compacter(cfg: &Config, db: &Arc<Mutex<ServerV4>>) {
+fn spawn_compacter(cfg: &Config, db: &Arc<RwLock<ServerV4>>) {
     let cfg = cfg.clone();
     let db = db.clone();
     tokio::spawn(async move {
         loop {
             tokio::time::sleep(cfg.index_db.time_between_compacts).await;
-            if let Err(e) = db.lock().await.compact_log() {
+            if let Err(e) = db.write().await.compact_log() {
                 error!("failed to compact log: {e:?}");
             }
         }
@@ -11,7 +11,7 @@ use lb_rs::model::file_like::FileLike;
 use lb_rs::model::file_metadata::Owner;
 use lb_rs::model::server_tree::ServerTree;
 use lb_rs::model::tree_like::TreeLike;
-use prometheus::{register_int_gauge_vec, IntGaugeVec};
+use prometheus::{register_int_counter_vec, register_int_gauge_vec, IntCounterVec, IntGaugeVec};
 use prometheus_static_metric::make_static_metric;
 use std::fmt::Debug;
 use tracing::*;
@@ -38,6 +38,12 @@ make_static_metric! {
 }
 
 lazy_static! {
+    pub static ref METRICS_RATE_LIMITED_VEC: IntCounterVec = register_int_counter_vec!(
+        "lockbook_rate_limited_requests",
+        "Requests refused for exceeding a rate limit",
+        &["request", "limit"]
+    )
+    .unwrap();
     pub static ref METRICS_COUNTERS_VEC: IntGaugeVec = register_int_gauge_vec!(
         "lockbook_metrics_counters",
         "Lockbook's basic metrics of users and files derived from redis",
@@ -93,7 +99,7 @@ where
         loop {
             info!("Metrics refresh started");
 
-            let public_keys_and_usernames = self.index_db.lock().await.usernames.get().clone();
+            let public_keys_and_usernames = self.index_db.read().await.usernames.get().clone();
 
             let total_users_ever = public_keys_and_usernames.len() as i64;
             let mut total_documents = 0;
@@ -109,7 +115,7 @@ where
 
             for (username, owner) in public_keys_and_usernames {
                 {
-                    let mut db = self.index_db.lock().await;
+                    let mut db = self.index_db.write().await;
                     let maybe_user_info = Self::get_user_info(&mut db, owner)?;
 
                     let user_info = match maybe_user_info {
@@ -0,0 +1,158 @@
+use crate::billing::app_store_client::AppStoreClient;
+use crate::billing::google_play_client::GooglePlayClient;
+use crate::billing::stripe_client::StripeClient;
+use crate::document_service::DocumentService;
+use crate::{RequestContext, ServerError, ServerState};
+use lb_rs::model::api::{AwaitUpdatesError, AwaitUpdatesRequest, AwaitUpdatesResponse};
+use lb_rs::model::file_like::FileLike;
+use lb_rs::model::file_metadata::Owner;
+use lb_rs::model::tree_like::TreeLike;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+use uuid::Uuid;
+
+/// How long an await-updates request is held open before the client is told to ask again. Kept
+/// well below the idle timeouts of common proxies and load balancers.
+const AWAIT_UPDATES_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Tells clients blocked in [ServerState::await_updates] that some file they can see has a new
+/// version. Only owners someone is waiting on have a channel, so a change wakes just the people
+/// who can see it.
+#[derive(Clone, Default)]
+pub struct UpdateNotifier {
+    waiting: Arc<Mutex<HashMap<Owner, watch::Sender<()>>>>,
+}
+
+impl UpdateNotifier {
+    pub fn notify(&self, owners: HashSet<Owner>) {
+        let waiting = self.waiting.lock().unwrap();
+        for owner in owners {
+            if let Some(tx) = waiting.get(&owner) {
+                tx.send_replace(());
+            }
+        }
+    }
+
+    /// Changes notified after this returns wake the subscription
+    pub fn subscribe(&self, owner: Owner) -> UpdateSubscription {
+        let rx = self
+            .waiting
+            .lock()
+            .unwrap()
+            .entry(owner)
+            .or_insert_with(|| watch::channel(()).0)
+            .subscribe();
+        UpdateSubscription { owner, rx, waiting: self.waiting.clone() }
+    }
+}
+
+pub struct UpdateSubscription {
+    owner: Owner,
+    rx: watch::Receiver<()>,
+    waiting: Arc<Mutex<HashMap<Owner, watch::Sender<()>>>>,
+}
+
+impl UpdateSubscription {
+    pub async fn changed(&mut self) -> bool {
+        self.rx.changed().await.is_ok()
+    }
+}
+
+impl Drop for UpdateSubscription {
+    fn drop(&mut self) {
+        let mut waiting = self.waiting.lock().unwrap();
+        // the last one waiting on an owner takes their channel with them
+        if waiting
+            .get(&self.owner)
+            .is_some_and(|tx| tx.receiver_count() == 1)
+        {
+            waiting.remove(&self.owner);
+        }
+    }
+}
+
+/// Everyone who sees `id` in their tree: its owner and anyone it, or one of its ancestors, is
+/// shared with
+pub fn interested_owners<T: TreeLike>(tree: &T, id: &Uuid) -> HashSet<Owner> {
+    let mut owners = HashSet::new();
+    let mut current = Some(*id);
+    while let Some(id) = current {
+        let Some(file) = tree.maybe_find(&id) else { break };
+        owners.insert(file.owner());
+        owners.extend(
+            file.user_access_keys()
+                .iter()
+                .map(|k| Owner(k.encrypted_for)),
+        );
+        current = if file.is_root() { None } else { Some(*file.parent()) };
+    }
+    owners
+}
+
+impl<S, A, G, D> ServerState<S, A, G, D>
+where
+    S: StripeClient,
+    A: AppStoreClient,
+    G: GooglePlayClient,
+    D: DocumentService,
+{
+    /// Returns as soon as a file in the caller's tree has a version at or after
+    /// `since_metadata_version`, or after [AWAIT_UPDATES_TIMEOUT] if none does
+    pub async fn await_updates(
+        &self, context: RequestContext<AwaitUpdatesRequest>,
+    ) -> Result<AwaitUpdatesResponse, ServerError<AwaitUpdatesError>> {
+        let request = &context.request;
+        let owner = Owner(context.public_key);
+
+        // subscribe before checking so an update landing in between isn't missed
+        let mut subscription = self.update_notifier.subscribe(owner);
+
+        {
+            let db = self.index_db.read().await;
+            if !db.accounts.get().contains_key(&owner) {
+                return Err(ServerError::ClientError(AwaitUpdatesError::UserNotFound));
+            }
+            let version_index = self.version_index.lock()?;
+            if version_index
+                .since(&owner, request.since_metadata_version, None)
+                .next()
+                .is_some()
+            {
+                return Ok(AwaitUpdatesResponse { updated: true });
+            }
+        }
+
+        let updated = tokio::time::timeout(AWAIT_UPDATES_TIMEOUT, subscription.changed())
+            .await
+            .unwrap_or(false);
+        Ok(AwaitUpdatesResponse { updated })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lb_rs::model::account::Account;
+
+    #[tokio::test]
+    async fn notifies_only_owners_waiting() {
+        let owner = |name: &str| {
+            Owner(Account::new(name.to_string(), "http://localhost".to_string()).public_key())
+        };
+        let (waiting, other) = (owner("waiting"), owner("other"));
+        let notifier = UpdateNotifier::default();
+        let mut subscription = notifier.subscribe(waiting);
+
+        notifier.notify(HashSet::from([other]));
+        let woken = tokio::time::timeout(Duration::from_millis(50), subscription.changed()).await;
+        assert!(woken.is_err());
+
+        notifier.notify(HashSet::from([waiting]));
+        assert!(subscription.changed().await);
+
+        drop(subscription);
+        assert!(notifier.waiting.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,52 @@
+use lb_rs::model::file_metadata::Owner;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeSet;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use tokio::sync::{Mutex, MutexGuard};
+
+const SHARDS: usize = 256;
+
+/// Serializes changes to the same users' trees so they can be checked under a shared lock on the
+/// index db and applied afterwards, while changes to unrelated trees are checked in parallel.
+///
+/// Users hash into a fixed number of shards. A change locks the shard of every user whose tree it
+/// touches, in shard order so that overlapping changes can't deadlock. These locks are always taken
+/// before the index db lock, never while holding it.
+#[derive(Clone)]
+pub struct OwnerLocks {
+    shards: Arc<Vec<Mutex<()>>>,
+}
+
+impl Default for OwnerLocks {
+    fn default() -> Self {
+        Self { shards: Arc::new((0..SHARDS).map(|_| Mutex::new(())).collect()) }
+    }
+}
+
+impl OwnerLocks {
+    pub async fn lock(&self, owners: impl IntoIterator<Item = Owner>) -> Vec<MutexGuard<'_, ()>> {
+        let shards: BTreeSet<usize> = owners.into_iter().map(Self::shard).collect();
+
+        let mut guards = Vec::with_capacity(shards.len());
+        for shard in shards {
+            guards.push(self.shards[shard].lock().await);
+        }
+        guards
+    }
+
+    /// For changes that can touch anyone's tree, like deleting an account
+    pub async fn lock_all(&self) -> Vec<MutexGuard<'_, ()>> {
+        let mut guards = Vec::with_capacity(SHARDS);
+        for shard in self.shards.iter() {
+            guards.push(shard.lock().await);
+        }
+        guards
+    }
+
+    fn shard(owner: Owner) -> usize {
+        let mut hasher = DefaultHasher::new();
+        owner.hash(&mut hasher);
+        hasher.finish() as usize % SHARDS
+    }
+}
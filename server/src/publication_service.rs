@@ -0,0 +1,314 @@
+use crate::billing::app_store_client::AppStoreClient;
+use crate::billing::google_play_client::GooglePlayClient;
+use crate::billing::stripe_client::StripeClient;
+use crate::document_service::DocumentService;
+use crate::file_service::deleted_without_index;
+use crate::schema::{Publication, ServerDb};
+use crate::ServerError::ClientError;
+use crate::{RequestContext, ServerError, ServerState};
+use db_rs::{Db, DbError};
+use lb_rs::model::api::{
+    AccountState, EncryptedPublication, PublishError, PublishRequest, PublishResponse,
+    UnpublishError, UnpublishRequest,
+};
+use lb_rs::model::clock::get_time;
+use lb_rs::model::file_like::FileLike;
+use lb_rs::model::file_metadata::{DocumentHmac, Owner};
+use lb_rs::model::server_tree::ServerTreeView;
+use lb_rs::model::tree_like::TreeLike;
+use std::ops::DerefMut;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Publications are meant for a runbook or a handful of notes, not for hosting large files
+const MAX_PUBLICATION_SIZE: usize = 10 * 1024 * 1024;
+
+/// Caps how many live links one account can have; their bytes also count towards the account's
+/// data cap
+const MAX_PUBLICATIONS: usize = 100;
+
+/// Publication contents are kept by the document service under their link id, with this in place
+/// of a document's hmac
+pub const PUBLICATION_HMAC: DocumentHmac = [0; 32];
+
+impl<S, A, G, D> ServerState<S, A, G, D>
+where
+    S: StripeClient,
+    A: AppStoreClient,
+    G: GooglePlayClient,
+    D: DocumentService,
+{
+    pub async fn publish(
+        &self, context: RequestContext<PublishRequest>,
+    ) -> Result<PublishResponse, ServerError<PublishError>> {
+        let request = context.request;
+        let owner = Owner(context.public_key);
+
+        if request.content.value.len() > MAX_PUBLICATION_SIZE {
+            return Err(ClientError(PublishError::TooLarge));
+        }
+
+        // nothing changes the owner's files, usage or publications until this one is recorded
+        let _owner_guards = self.owner_locks.lock([owner]).await;
+
+        {
+            let db = self.index_db.read().await;
+
+            let metas = db.metas.get();
+            let meta = metas
+                .get(&request.file_id)
+                .ok_or(ClientError(PublishError::FileNonexistent))?;
+            if meta.owner() != owner {
+                return Err(ClientError(PublishError::NotPermissioned));
+            }
+            if deleted_without_index(metas, meta) {
+                return Err(ClientError(PublishError::FileNonexistent));
+            }
+
+            let replaced = db.published_files.get().get(&request.file_id).copied();
+            let (count, published_bytes) =
+                Self::publication_usage(&db, owner, get_time().0 as u64, replaced);
+            if count >= MAX_PUBLICATIONS {
+                return Err(ClientError(PublishError::TooMany));
+            }
+
+            let usage_cap = self
+                .get_cap(&db, &context.public_key)
+                .map_err(|err| internal!("{:?}", err))?;
+            let mut tree = ServerTreeView::new(
+                owner,
+                &db.owned_files,
+                &db.shared_files,
+                &db.file_children,
+                &db.metas,
+            )?
+            .to_lazy();
+            let file_usage = Self::get_usage_helper(&mut tree, db.sizes.get())
+                .map_err(|err| internal!("{:?}", err))?
+                .iter()
+                .map(|f| f.size_bytes)
+                .sum::<u64>();
+            let new_usage = file_usage + published_bytes + request.content.value.len() as u64;
+            if new_usage > usage_cap {
+                return Err(ClientError(PublishError::UsageIsOverDataCap));
+            }
+        }
+
+        let link_id = Uuid::new_v4();
+        self.document_service
+            .insert(&link_id, &PUBLICATION_HMAC, &request.content)
+            .await?;
+
+        let result = async {
+            let mut lock = self.index_db.write().await;
+            let db = lock.deref_mut();
+            let tx = db.begin_transaction()?;
+
+            // clean up after the owner's expired links while we're here
+            let now = get_time().0 as u64;
+            let mut removed: Vec<Uuid> = db
+                .owner_publications
+                .get()
+                .get(&owner)
+                .into_iter()
+                .flatten()
+                .filter(|link_id| {
+                    db.publications
+                        .get()
+                        .get(link_id)
+                        .is_some_and(|publication| expired(publication, now))
+                })
+                .copied()
+                .collect();
+            removed.extend(db.published_files.get().get(&request.file_id).copied());
+            for link_id in &removed {
+                unindex_publication(db, link_id)?;
+            }
+
+            db.publications.insert(
+                link_id,
+                Publication {
+                    owner,
+                    file_id: request.file_id,
+                    size: request.content.value.len() as u64,
+                    expires_at: request.expires_at,
+                },
+            )?;
+            db.owner_publications.insert(owner, link_id)?;
+            db.published_files.insert(request.file_id, link_id)?;
+
+            tx.drop_safely()?;
+            Ok(removed)
+        };
+
+        let removed = match result.await {
+            Ok(removed) => removed,
+            Err(err) => {
+                self.document_service
+                    .delete(&link_id, &PUBLICATION_HMAC)
+                    .await?;
+                return Err(err);
+            }
+        };
+        for link_id in removed {
+            self.document_service
+                .delete(&link_id, &PUBLICATION_HMAC)
+                .await?;
+        }
+
+        Ok(PublishResponse { link_id })
+    }
+
+    pub async fn unpublish(
+        &self, context: RequestContext<UnpublishRequest>,
+    ) -> Result<(), ServerError<UnpublishError>> {
+        let request = &context.request;
+
+        let link_id = {
+            let mut lock = self.index_db.write().await;
+            let db = lock.deref_mut();
+            let tx = db.begin_transaction()?;
+
+            let link_id = *db
+                .published_files
+                .get()
+                .get(&request.file_id)
+                .ok_or(ClientError(UnpublishError::NotPublished))?;
+            let published_by = db.publications.get().get(&link_id).map(|p| p.owner);
+            if published_by != Some(Owner(context.public_key)) {
+                return Err(ClientError(UnpublishError::NotPermissioned));
+            }
+
+            unindex_publication(db, &link_id)?;
+
+            tx.drop_safely()?;
+            link_id
+        };
+
+        self.document_service
+            .delete(&link_id, &PUBLICATION_HMAC)
+            .await?;
+        Ok(())
+    }
+
+    /// What's served for a publish link, unless it expired, the file it's a copy of was deleted, or
+    /// the account that published it was restricted
+    pub async fn get_publication(&self, link_id: Uuid) -> Option<EncryptedPublication> {
+        {
+            let db = self.index_db.read().await;
+            let publication = db.publications.get().get(&link_id)?;
+
+            if expired(publication, get_time().0 as u64) {
+                return None;
+            }
+            let state = db
+                .account_states
+                .get()
+                .get(&publication.owner)
+                .copied()
+                .unwrap_or_default();
+            if state != AccountState::Active {
+                return None;
+            }
+            let metas = db.metas.get();
+            match metas.get(&publication.file_id) {
+                Some(meta) if !deleted_without_index(metas, meta) => {}
+                _ => return None,
+            }
+        }
+
+        // the link may have been replaced since, in which case its contents are gone
+        match self
+            .document_service
+            .get::<()>(&link_id, &PUBLICATION_HMAC)
+            .await
+        {
+            Ok(content) => Some(content),
+            Err(err) => {
+                warn!(?link_id, ?err, "failed to read publication");
+                None
+            }
+        }
+    }
+
+    /// How many of `owner`'s links are live at `now`, leaving out `replaced`, and how many bytes
+    /// they take up
+    fn publication_usage(
+        db: &ServerDb, owner: Owner, now: u64, replaced: Option<Uuid>,
+    ) -> (usize, u64) {
+        db.owner_publications
+            .get()
+            .get(&owner)
+            .into_iter()
+            .flatten()
+            .filter(|link_id| Some(**link_id) != replaced)
+            .filter_map(|link_id| db.publications.get().get(link_id))
+            .filter(|publication| !expired(publication, now))
+            .fold((0, 0), |(count, bytes), publication| (count + 1, bytes + publication.size))
+    }
+}
+
+fn expired(publication: &Publication, now: u64) -> bool {
+    publication.expires_at.map(|at| at <= now) == Some(true)
+}
+
+/// Removes a publication from the index; its contents are left for the caller to delete once the
+/// change is committed
+pub fn unindex_publication(db: &mut ServerDb, link_id: &Uuid) -> Result<(), DbError> {
+    if let Some(publication) = db.publications.remove(link_id)? {
+        db.owner_publications.remove(&publication.owner, link_id)?;
+        if db.published_files.get().get(&publication.file_id) == Some(link_id) {
+            db.published_files.remove(&publication.file_id)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::PUBLICATION_HMAC;
+    use crate::document_service::DocumentService;
+    use crate::test_utils::{test_account, test_server};
+    use crate::RequestContext;
+    use lb_rs::model::api::{PublishRequest, UnpublishRequest};
+    use lb_rs::model::crypto::AESEncrypted;
+    use lb_rs::model::file_like::FileLike;
+
+    #[tokio::test]
+    async fn publications_are_stored_by_link() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = test_server(dir.path());
+        let account = test_account(&server).await;
+        let doc = account.create_doc(&server, "runbook.md", b"steps").await;
+        let public_key = account.account.public_key();
+        let publish = |content: &'static [u8]| {
+            server.publish(RequestContext {
+                request: PublishRequest {
+                    file_id: *doc.id(),
+                    content: AESEncrypted::new(content, vec![0; 12]),
+                    expires_at: None,
+                },
+                public_key,
+            })
+        };
+
+        let first = publish(b"first").await.unwrap().link_id;
+        assert_eq!(server.get_publication(first).await.unwrap().value, b"first");
+
+        // publishing again replaces the link, and the old contents go with it
+        let second = publish(b"second").await.unwrap().link_id;
+        assert!(server.get_publication(first).await.is_none());
+        assert!(!server.document_service.exists(&first, &PUBLICATION_HMAC));
+        assert_eq!(server.get_publication(second).await.unwrap().value, b"second");
+
+        server
+            .unpublish(RequestContext {
+                request: UnpublishRequest { file_id: *doc.id() },
+                public_key,
+            })
+            .await
+            .unwrap();
+        assert!(server.get_publication(second).await.is_none());
+        assert!(!server.document_service.exists(&second, &PUBLICATION_HMAC));
+    }
+}
@@ -0,0 +1,202 @@
+use crate::config::{RateLimit, RateLimitConfig};
+use lb_rs::model::api::{NewAccountRequest, Request};
+use lb_rs::model::file_metadata::Owner;
+use libsecp256k1::PublicKey;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Past this many tracked keys, keys whose buckets have refilled completely are dropped
+const EVICT_AT: usize = 100_000;
+
+/// A token bucket per key, tracked as the time at which the bucket will be full again (GCRA). A
+/// key may make `burst` requests at once and then one request per `refill`.
+pub struct RateLimiter<K> {
+    limit: Option<RateLimit>,
+    full_at: Mutex<HashMap<K, Instant>>,
+}
+
+impl<K: Eq + Hash> RateLimiter<K> {
+    pub fn new(limit: Option<RateLimit>) -> Self {
+        Self { limit, full_at: Default::default() }
+    }
+
+    /// Takes a token from `key`'s bucket, or returns how long until one is available
+    pub fn check(&self, key: K) -> Result<(), Duration> {
+        self.check_at(key, Instant::now())
+    }
+
+    fn check_at(&self, key: K, now: Instant) -> Result<(), Duration> {
+        let Some(limit) = self.limit else { return Ok(()) };
+        if limit.burst == 0 {
+            return Ok(());
+        }
+        let tolerance = limit.refill * (limit.burst - 1);
+
+        let mut full_at = self.full_at.lock().unwrap();
+        if full_at.len() >= EVICT_AT {
+            full_at.retain(|_, at| *at > now);
+        }
+
+        let at = full_at.get(&key).copied().unwrap_or(now).max(now);
+        let wait = at - now;
+        if wait > tolerance {
+            return Err(wait - tolerance);
+        }
+        full_at.insert(key, at + limit.refill);
+        Ok(())
+    }
+}
+
+pub struct RateLimits {
+    accounts: RateLimiter<Owner>,
+    new_accounts: RateLimiter<IpAddr>,
+    trusted_proxies: HashSet<IpAddr>,
+}
+
+impl RateLimits {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            accounts: RateLimiter::new(config.account),
+            new_accounts: RateLimiter::new(config.new_account),
+            trusted_proxies: config.trusted_proxies.clone(),
+        }
+    }
+
+    /// Account creation isn't attributable to an existing account, so it's limited per ip instead.
+    /// Every other request is limited per signing key. Returns the name of the exceeded limit and
+    /// how long the caller should wait.
+    pub fn check(
+        &self, route: &str, public_key: &PublicKey, remote: Option<SocketAddr>,
+        forwarded_for: Option<&str>,
+    ) -> Result<(), (&'static str, Duration)> {
+        if route == NewAccountRequest::ROUTE {
+            match self.client_ip(remote, forwarded_for) {
+                Some(ip) => self
+                    .new_accounts
+                    .check(ip)
+                    .map_err(|wait| ("new_account", wait)),
+                None => Ok(()),
+            }
+        } else {
+            self.accounts
+                .check(Owner(*public_key))
+                .map_err(|wait| ("account", wait))
+        }
+    }
+
+    /// Where a request came from. Each trusted proxy appends the address it got the request from
+    /// to `X-Forwarded-For`, so the header is read right to left for as long as the address so far
+    /// is one of our proxies; anything further left was written by the client.
+    fn client_ip(&self, remote: Option<SocketAddr>, forwarded_for: Option<&str>) -> Option<IpAddr> {
+        let mut ip = remote?.ip();
+        for hop in forwarded_for.unwrap_or_default().rsplit(',') {
+            if !self.trusted_proxies.contains(&ip) {
+                break;
+            }
+            match hop.trim().parse() {
+                Ok(hop) => ip = hop,
+                Err(_) => break,
+            }
+        }
+        Some(ip)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LIMIT: RateLimit = RateLimit { burst: 3, refill: Duration::from_secs(10) };
+
+    #[test]
+    fn bursts_then_waits() {
+        let limiter = RateLimiter::new(Some(LIMIT));
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert_eq!(limiter.check_at("key", now), Ok(()));
+        }
+        assert_eq!(limiter.check_at("key", now), Err(Duration::from_secs(10)));
+        assert_eq!(
+            limiter.check_at("key", now + Duration::from_secs(4)),
+            Err(Duration::from_secs(6))
+        );
+
+        // other keys have their own buckets
+        assert_eq!(limiter.check_at("other", now), Ok(()));
+    }
+
+    #[test]
+    fn refills_one_at_a_time() {
+        let limiter = RateLimiter::new(Some(LIMIT));
+        let now = Instant::now();
+        for _ in 0..3 {
+            limiter.check_at("key", now).unwrap();
+        }
+
+        let later = now + Duration::from_secs(10);
+        assert_eq!(limiter.check_at("key", later), Ok(()));
+        assert!(limiter.check_at("key", later).is_err());
+
+        // a bucket left alone long enough is full again, but no fuller
+        let much_later = now + Duration::from_secs(1000);
+        for _ in 0..3 {
+            assert_eq!(limiter.check_at("key", much_later), Ok(()));
+        }
+        assert!(limiter.check_at("key", much_later).is_err());
+    }
+
+    #[test]
+    fn evicts_full_buckets() {
+        let limiter = RateLimiter::new(Some(LIMIT));
+        let now = Instant::now();
+        for key in 0..EVICT_AT - 1 {
+            limiter.check_at(key, now).unwrap();
+        }
+        limiter
+            .check_at(EVICT_AT, now + Duration::from_secs(5))
+            .unwrap();
+
+        // the first keys refill by the time the next one is checked; the last is still refilling
+        limiter
+            .check_at(EVICT_AT + 1, now + Duration::from_secs(10))
+            .unwrap();
+        let tracked = limiter.full_at.lock().unwrap();
+        assert_eq!(tracked.len(), 2);
+        assert!(tracked.contains_key(&EVICT_AT));
+    }
+
+    #[test]
+    fn no_limit() {
+        let limiter = RateLimiter::new(None);
+        let now = Instant::now();
+        for _ in 0..100 {
+            assert_eq!(limiter.check_at("key", now), Ok(()));
+        }
+    }
+
+    #[test]
+    fn forwarded_for_is_only_read_from_trusted_proxies() {
+        let config = RateLimitConfig {
+            trusted_proxies: HashSet::from(["10.0.0.1".parse().unwrap()]),
+            ..Default::default()
+        };
+        let limits = RateLimits::new(&config);
+        let proxy = Some("10.0.0.1:443".parse().unwrap());
+        let direct = Some("203.0.113.9:443".parse().unwrap());
+
+        assert_eq!(
+            limits.client_ip(proxy, Some("1.1.1.1, 198.51.100.7")),
+            Some("198.51.100.7".parse().unwrap())
+        );
+        assert_eq!(limits.client_ip(proxy, None), Some("10.0.0.1".parse().unwrap()));
+        assert_eq!(
+            limits.client_ip(direct, Some("198.51.100.7")),
+            Some("203.0.113.9".parse().unwrap())
+        );
+        assert_eq!(limits.client_ip(None, Some("198.51.100.7")), None);
+    }
+}
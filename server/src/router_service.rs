@@ -18,6 +18,7 @@ use serde::Serialize;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tracing::*;
+use uuid::Uuid;
 use warp::http::{HeaderValue, Method, StatusCode};
 use warp::hyper::body::Bytes;
 use warp::{reject, Filter, Rejection};
@@ -40,10 +41,11 @@ lazy_static! {
 #[macro_export]
 macro_rules! core_req {
     ($Req: ty, $handler: path, $state: ident) => {{
-        use lb_rs::model::api::{ErrorWrapper, Request};
+        use lb_rs::model::api::{AuditOutcome, ErrorWrapper, Request};
         use lb_rs::model::file_metadata::Owner;
         use std::net::SocketAddr;
         use tracing::*;
+        use $crate::audit_service::AUDITED_ROUTES;
         use $crate::router_service::{self, deserialize_and_check, method};
         use $crate::{RequestContext, ServerError};
 
@@ -55,11 +57,13 @@ macro_rules! core_req {
             .and(warp::body::bytes())
             .and(warp::header::optional::<String>("Accept-Version"))
             .and(warp::filters::addr::remote())
+            .and(warp::header::optional::<String>("X-Forwarded-For"))
             .then(
                 |state: Arc<ServerState<S, A, G, D>>,
                  request: Bytes,
                  version: Option<String>,
-                 ip: Option<SocketAddr>| {
+                 ip: Option<SocketAddr>,
+                 forwarded_for: Option<String>| {
                     let span1 = span!(
                         Level::INFO,
                         "matched_request",
@@ -95,9 +99,28 @@ macro_rules! core_req {
 
                         debug!("request verified successfully");
                         let req_pk = request.signed_request.public_key;
-                        let username = {
-                            let db = state.index_db.lock().await;
-                            match db
+                        if let Err((limit, wait)) = state.rate_limits.check(
+                            <$Req>::ROUTE,
+                            &req_pk,
+                            ip,
+                            forwarded_for.as_deref(),
+                        ) {
+                            warn!("request rate limited: {limit}, retry after {wait:?}");
+                            $crate::metrics::METRICS_RATE_LIMITED_VEC
+                                .with_label_values(&[<$Req>::ROUTE, limit])
+                                .inc();
+                            return warp::reply::with_status(
+                                warp::reply::json::<Result<RequestWrapper<$Req>, _>>(&Err(
+                                    ErrorWrapper::<<$Req as Request>::Error>::RateLimited {
+                                        retry_after_ms: (wait.as_micros() as u64).div_ceil(1000),
+                                    },
+                                )),
+                                warp::http::StatusCode::TOO_MANY_REQUESTS,
+                            );
+                        }
+                        let (username, account_state) = {
+                            let db = state.index_db.read().await;
+                            let username = match db
                                 .accounts
                                 .get()
                                 .get(&Owner(req_pk))
@@ -105,8 +128,23 @@ macro_rules! core_req {
                             {
                                 Some(username) => username,
                                 None => "~unknown~".to_string(),
-                            }
+                            };
+                            let account_state = db
+                                .account_states
+                                .get()
+                                .get(&Owner(req_pk))
+                                .copied()
+                                .unwrap_or_default();
+                            (username, account_state)
                         };
+                        if let Err(err) = router_service::check_account_state::<$Req>(account_state)
+                        {
+                            warn!(?username, ?account_state, "request refused for account state");
+                            return warp::reply::with_status(
+                                warp::reply::json::<Result<RequestWrapper<$Req>, _>>(&Err(err)),
+                                warp::http::StatusCode::FORBIDDEN,
+                            );
+                        }
                         let req_pk = base64::encode(req_pk.serialize_compressed());
 
                         let span2 = span!(
@@ -119,15 +157,25 @@ macro_rules! core_req {
                             request: request.signed_request.timestamped_value.value,
                             public_key: request.signed_request.public_key,
                         };
+                        let audited = if AUDITED_ROUTES.contains(&<$Req>::ROUTE) {
+                            Some((
+                                rc.public_key,
+                                serde_json::to_value(&rc.request).unwrap_or_default(),
+                            ))
+                        } else {
+                            None
+                        };
 
                         async move {
                             let status;
                             let log;
+                            let outcome;
                             let mut level = tracing::Level::INFO;
                             let to_serialize = match $handler(state, rc).await {
                                 Ok(response) => {
                                     status = warp::http::StatusCode::OK;
                                     log = "request processed successfully".to_string();
+                                    outcome = AuditOutcome::Succeeded;
                                     Ok(response)
                                 }
                                 Err(ServerError::ClientError(e)) => {
@@ -135,15 +183,22 @@ macro_rules! core_req {
                                     level = tracing::Level::WARN;
                                     log =
                                         format!("request rejected due to a client error: {:?}", e);
+                                    outcome = AuditOutcome::Rejected(format!("{:?}", e));
                                     Err(ErrorWrapper::Endpoint(e))
                                 }
                                 Err(ServerError::InternalError(e)) => {
                                     status = warp::http::StatusCode::INTERNAL_SERVER_ERROR;
                                     level = tracing::Level::ERROR;
                                     log = format!("Internal error {}: {}", <$Req>::ROUTE, e);
+                                    outcome = AuditOutcome::Failed(e);
                                     Err(ErrorWrapper::InternalError)
                                 }
                             };
+                            if let Some((actor, request)) = audited {
+                                state
+                                    .journal(actor, username, <$Req>::ROUTE, request, outcome)
+                                    .await;
+                            }
                             let response =
                                 warp::reply::with_status(warp::reply::json(&to_serialize), status);
                             let latency = timer.stop_and_record();
@@ -207,6 +262,12 @@ where
         .or(core_req!(GetUsageRequest, ServerState::get_usage, server_state))
         .or(core_req!(GetFileIdsRequest, ServerState::get_file_ids, server_state))
         .or(core_req!(GetUpdatesRequest, ServerState::get_updates, server_state))
+        .or(core_req!(AwaitUpdatesRequest, ServerState::await_updates, server_state))
+        .or(core_req!(TakeoutRequest, ServerState::takeout, server_state))
+        .or(core_req!(PublishRequest, ServerState::publish, server_state))
+        .or(core_req!(UnpublishRequest, ServerState::unpublish, server_state))
+        .or(core_req!(PushCollabRequest, ServerState::push_collab, server_state))
+        .or(core_req!(PullCollabRequest, ServerState::pull_collab, server_state))
         .or(core_req!(
             UpgradeAccountGooglePlayRequest,
             ServerState::upgrade_account_google_play,
@@ -243,9 +304,17 @@ where
             server_state
         ))
         .or(core_req!(AdminValidateServerRequest, ServerState::admin_validate_server, server_state))
+        .or(core_req!(AdminRepairServerRequest, ServerState::admin_repair_server, server_state))
         .or(core_req!(AdminFileInfoRequest, ServerState::admin_file_info, server_state))
+        .or(core_req!(AdminGetAuditLogRequest, ServerState::admin_get_audit_log, server_state))
         .or(core_req!(AdminRebuildIndexRequest, ServerState::admin_rebuild_index, server_state))
         .or(core_req!(AdminSetUserTierRequest, ServerState::admin_set_user_tier, server_state))
+        .or(core_req!(AdminSetDataCapRequest, ServerState::admin_set_data_cap, server_state))
+        .or(core_req!(
+            AdminSetAccountStateRequest,
+            ServerState::admin_set_account_state,
+            server_state
+        ))
 }
 
 pub fn build_info() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
@@ -452,6 +521,73 @@ where
         })
 }
 
+static PUBLICATION_ROUTE: &str = "p";
+static PUBLICATION_PAGE: &str = include_str!("publication_page.html");
+
+#[derive(Serialize)]
+struct PublishedContent {
+    value: String,
+    nonce: String,
+}
+
+/// Publish links: `/p/{link_id}` is a page that fetches `/p/{link_id}/content` and decrypts it
+/// with the key in the link's fragment. Neither needs an account.
+pub fn publications<S, A, G, D>(
+    server_state: &Arc<ServerState<S, A, G, D>>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
+where
+    S: StripeClient,
+    A: AppStoreClient,
+    G: GooglePlayClient,
+    D: DocumentService,
+{
+    let cloned_state = server_state.clone();
+
+    let page = warp::get()
+        .and(warp::path(PUBLICATION_ROUTE))
+        .and(warp::path::param::<Uuid>())
+        .and(warp::path::end())
+        .map(|_link_id: Uuid| {
+            let reply = warp::reply::html(PUBLICATION_PAGE);
+            let reply = warp::reply::with_header(
+                reply,
+                "Content-Security-Policy",
+                "default-src 'none'; script-src 'unsafe-inline'; style-src 'unsafe-inline'; \
+                 connect-src 'self'",
+            );
+            warp::reply::with_header(reply, "Referrer-Policy", "no-referrer")
+        });
+
+    let content = warp::get()
+        .and(warp::path(PUBLICATION_ROUTE))
+        .and(warp::path::param::<Uuid>())
+        .and(warp::path("content"))
+        .and(warp::path::end())
+        .and(warp::any().map(move || cloned_state.clone()))
+        .then(|link_id: Uuid, state: Arc<ServerState<S, A, G, D>>| async move {
+            let span = span!(
+                Level::INFO,
+                "matched_request",
+                method = "GET",
+                route = format!("/{}", PUBLICATION_ROUTE).as_str()
+            );
+            let _enter = span.enter();
+
+            match state.get_publication(link_id).await {
+                Some(content) => warp::reply::with_status(
+                    warp::reply::json(&PublishedContent {
+                        value: base64::encode(&content.value),
+                        nonce: base64::encode(&content.nonce),
+                    }),
+                    StatusCode::OK,
+                ),
+                None => warp::reply::with_status(warp::reply::json(&()), StatusCode::NOT_FOUND),
+            }
+        });
+
+    page.or(content)
+}
+
 pub fn method(name: Method) -> impl Filter<Extract = (), Error = Rejection> + Clone {
     warp::method()
         .and(warp::any().map(move || name.clone()))
@@ -492,3 +628,23 @@ where
 
     Ok(request)
 }
+
+/// What suspended accounts may still do: pull down their files
+const EXPORT_ROUTES: [&str; 4] = [
+    GetUpdatesRequest::ROUTE,
+    GetDocRequest::ROUTE,
+    GetFileIdsRequest::ROUTE,
+    TakeoutRequest::ROUTE,
+];
+
+pub fn check_account_state<Req: Request>(
+    state: AccountState,
+) -> Result<(), ErrorWrapper<Req::Error>> {
+    match state {
+        AccountState::Active => Ok(()),
+        AccountState::ReadOnly if Req::METHOD == Method::GET => Ok(()),
+        AccountState::ReadOnly => Err(ErrorWrapper::AccountReadOnly),
+        AccountState::Suspended if EXPORT_ROUTES.contains(&Req::ROUTE) => Ok(()),
+        AccountState::Suspended => Err(ErrorWrapper::AccountSuspended),
+    }
+}
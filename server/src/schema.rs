@@ -1,6 +1,7 @@
 use crate::billing::billing_model::SubscriptionProfile;
-use db_rs::{LookupSet, LookupTable};
+use db_rs::{LookupSet, LookupTable, Single};
 use db_rs_derive::Schema;
+use lb_rs::model::api::{AccountState, AuditEntry, UnixTimeMillis};
 use lb_rs::model::file_metadata::Owner;
 use lb_rs::model::server_file::ServerFile;
 use serde::{Deserialize, Serialize};
@@ -15,6 +16,17 @@ pub struct Account {
     pub billing_info: SubscriptionProfile,
 }
 
+/// A copy of a file anyone with its link can read. Its contents are kept by the document service
+/// under the link id, encrypted with a key that only ever appears in the link's fragment, which
+/// browsers don't send to the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Publication {
+    pub owner: Owner,
+    pub file_id: Uuid,
+    pub size: u64,
+    pub expires_at: Option<UnixTimeMillis>,
+}
+
 pub type ServerDb = ServerV4;
 
 #[derive(Schema)]
@@ -31,4 +43,18 @@ pub struct ServerV4 {
     pub owned_files: LookupSet<Owner, Uuid>,
     pub shared_files: LookupSet<Owner, Uuid>,
     pub file_children: LookupSet<Uuid, Uuid>,
+    /// per-account data caps set by an admin, taking precedence over billing and defaults
+    pub data_caps: LookupTable<Owner, u64>,
+    /// data cap set by an admin for accounts without their own, when billing is disabled
+    pub global_data_cap: Single<u64>,
+    /// accounts an admin has restricted; everyone else is active
+    pub account_states: LookupTable<Owner, AccountState>,
+    /// by link id
+    pub publications: LookupTable<Uuid, Publication>,
+    /// the link id each published file is currently reachable by
+    pub published_files: LookupTable<Uuid, Uuid>,
+    /// link ids by the account that published them
+    pub owner_publications: LookupSet<Owner, Uuid>,
+    /// append-only, by seq
+    pub audit_log: LookupTable<u64, AuditEntry>,
 }
@@ -0,0 +1,128 @@
+//! Servers and accounts for unit tests, driven through [ServerState] rather than the network
+
+use crate::billing::Nop;
+use crate::config::Config;
+use crate::document_service::OnDiskDocuments;
+use crate::schema::ServerV4;
+use crate::{RequestContext, ServerState};
+use db_rs::Db;
+use lb_rs::model::account::Account;
+use lb_rs::model::api::{ChangeDocRequest, NewAccountRequest, UpsertRequest};
+use lb_rs::model::crypto::{AESEncrypted, AESKey};
+use lb_rs::model::file_like::FileLike;
+use lb_rs::model::file_metadata::{FileDiff, FileMetadata, FileType};
+use lb_rs::model::signed_file::SignedFile;
+use lb_rs::model::symkey;
+use std::fs;
+use std::path::Path;
+use uuid::Uuid;
+
+pub type TestServer = ServerState<Nop, Nop, Nop, OnDiskDocuments>;
+
+/// A self-hosted server keeping its index and documents under `dir`
+pub fn test_server(dir: &Path) -> TestServer {
+    test_server_with(test_config(dir))
+}
+
+/// Configures a self-hosted server to keep its index and documents under `dir`
+pub fn test_config(dir: &Path) -> Config {
+    fs::create_dir_all(dir).unwrap();
+    let config_path = dir.join("server.toml");
+    fs::write(
+        &config_path,
+        format!(
+            "[server]\nport = 0\nlog_path = '{dir}/logs'\n\n\
+             [index_db]\ndb_location = '{dir}/index'\n\n\
+             [files]\npath = '{dir}/files'\n",
+            dir = dir.display()
+        ),
+    )
+    .unwrap();
+    Config::from_file(&config_path).unwrap()
+}
+
+pub fn test_server_with(config: Config) -> TestServer {
+    let index = ServerV4::init(db_rs::Config::in_folder(&config.index_db.db_location)).unwrap();
+    let document_service = OnDiskDocuments::from(&config);
+    ServerState::new(config, index, Nop {}, Nop {}, Nop {}, document_service)
+}
+
+pub struct TestAccount {
+    pub account: Account,
+    pub root: SignedFile,
+    root_key: AESKey,
+}
+
+pub async fn test_account(server: &TestServer) -> TestAccount {
+    let username = format!("test{}", &Uuid::new_v4().simple().to_string()[..16]);
+    let account = Account::new(username, "http://localhost".to_string());
+    let root = FileMetadata::create_root(&account)
+        .unwrap()
+        .sign_with(&account)
+        .unwrap();
+    let root_key = root.user_access_keys()[0].decrypt(&account).unwrap();
+
+    server
+        .new_account(RequestContext {
+            request: NewAccountRequest::new(&account, &root),
+            public_key: account.public_key(),
+        })
+        .await
+        .unwrap();
+
+    TestAccount { account, root, root_key }
+}
+
+impl TestAccount {
+    /// Metadata for a new file in the account's root, not yet sent to the server
+    pub fn new_file(&self, name: &str, file_type: FileType) -> SignedFile {
+        FileMetadata::create(
+            Uuid::new_v4(),
+            symkey::generate_key(),
+            &self.account.public_key(),
+            *self.root.id(),
+            &self.root_key,
+            name,
+            file_type,
+        )
+        .unwrap()
+        .sign_with(&self.account)
+        .unwrap()
+    }
+
+    /// Creates a document in the account's root holding `content` as-is; the server never reads
+    /// it, so it doesn't need to be encrypted
+    pub async fn create_doc(&self, server: &TestServer, name: &str, content: &[u8]) -> SignedFile {
+        let public_key = self.account.public_key();
+        let created = self.new_file(name, FileType::Document);
+        server
+            .upsert_file_metadata(RequestContext {
+                request: UpsertRequest { updates: vec![FileDiff::new(&created)] },
+                public_key,
+            })
+            .await
+            .unwrap();
+
+        let meta = created.timestamped_value.value.clone();
+        let written = FileMetadata { document_hmac: Some(rand_hmac()), ..meta }
+            .sign_with(&self.account)
+            .unwrap();
+        server
+            .change_doc(RequestContext {
+                request: ChangeDocRequest {
+                    diff: FileDiff::edit(&created, &written),
+                    new_content: AESEncrypted::new(content, vec![0; 12]),
+                },
+                public_key,
+            })
+            .await
+            .unwrap();
+        written
+    }
+}
+
+pub fn rand_hmac() -> [u8; 32] {
+    let mut hmac = [0; 32];
+    hmac[..16].copy_from_slice(Uuid::new_v4().as_bytes());
+    hmac
+}
@@ -0,0 +1,299 @@
+use lb_rs::model::file_like::FileLike;
+use lb_rs::model::file_metadata::Owner;
+use lb_rs::model::server_file::ServerFile;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ops::Bound;
+use uuid::Uuid;
+
+/// Where every file is listed for everyone who can see it, ordered per viewer, so
+/// [crate::ServerState::get_updates] can find what changed without walking the caller's whole
+/// tree. A file is listed for its owner at its own version, and for someone it's shared with at
+/// the latest of its version and those of the ancestors shared with them, so everything in a
+/// folder is listed again when that folder is (re)shared. Like the sharee's tree, a share stops
+/// counting once it's rejected or the shared file is deleted. Kept in memory, built from the index
+/// db at startup, and updated while holding the index db's write lock.
+#[derive(Default)]
+pub struct VersionIndex {
+    by_viewer: HashMap<Owner, BTreeSet<(u64, Uuid)>>,
+    current: HashMap<Uuid, HashMap<Owner, u64>>,
+    latest: u64,
+}
+
+impl VersionIndex {
+    pub fn new(metas: &HashMap<Uuid, ServerFile>) -> Self {
+        let mut index = Self::default();
+        for id in metas.keys() {
+            index.index_file(metas, id);
+        }
+        index
+    }
+
+    /// Re-indexes `id` after it changed, along with its descendants if it's shared or deleted,
+    /// since where they're listed for sharees depends on it
+    pub fn record(
+        &mut self, metas: &HashMap<Uuid, ServerFile>, children: &HashMap<Uuid, HashSet<Uuid>>,
+        id: &Uuid,
+    ) {
+        let was_shared = self.is_shared(id);
+        self.index_file(metas, id);
+        let deleted = metas
+            .get(id)
+            .map(|file| file.explicitly_deleted())
+            .unwrap_or_default();
+        if !was_shared && !self.is_shared(id) && !deleted {
+            return;
+        }
+        let mut to_visit = vec![*id];
+        while let Some(id) = to_visit.pop() {
+            for child in children.get(&id).into_iter().flatten() {
+                self.index_file(metas, child);
+                to_visit.push(*child);
+            }
+        }
+    }
+
+    pub fn forget(&mut self, id: &Uuid) {
+        if let Some(positions) = self.current.remove(id) {
+            for (viewer, version) in positions {
+                if let Some(versions) = self.by_viewer.get_mut(&viewer) {
+                    versions.remove(&(version, *id));
+                }
+            }
+        }
+    }
+
+    /// The files `viewer` can see that are listed at `version` or later, oldest first, resuming
+    /// after `after` when paging
+    pub fn since(
+        &self, viewer: &Owner, version: u64, after: Option<(u64, Uuid)>,
+    ) -> impl Iterator<Item = (u64, Uuid)> + '_ {
+        let from = (version, Uuid::nil());
+        let start = match after {
+            Some(after) if after >= from => Bound::Excluded(after),
+            _ => Bound::Included(from),
+        };
+        self.by_viewer
+            .get(viewer)
+            .into_iter()
+            .flat_map(move |versions| versions.range((start, Bound::Unbounded)).copied())
+    }
+
+    /// The highest version recorded so far; no file is listed after it
+    pub fn latest(&self) -> u64 {
+        self.latest
+    }
+
+    /// Whether anyone besides its owner sees `id`
+    fn is_shared(&self, id: &Uuid) -> bool {
+        self.current
+            .get(id)
+            .map(|positions| positions.len() > 1)
+            .unwrap_or_default()
+    }
+
+    fn index_file(&mut self, metas: &HashMap<Uuid, ServerFile>, id: &Uuid) {
+        self.forget(id);
+        let Some(file) = metas.get(id) else { return };
+
+        let mut ancestors = vec![file];
+        while let Some(ancestor) = ancestors.last().filter(|ancestor| !ancestor.is_root()) {
+            match metas.get(ancestor.parent()) {
+                Some(parent) => ancestors.push(parent),
+                None => break,
+            }
+        }
+
+        let mut positions = HashMap::from([(file.owner(), file.version)]);
+        // from the root down, stopping at the first deleted file: it and everything under it were
+        // dropped from their sharees' shared files
+        for shared in ancestors.into_iter().rev() {
+            if shared.explicitly_deleted() {
+                break;
+            }
+            for key in shared.user_access_keys().iter().filter(|key| !key.deleted) {
+                let sharee = Owner(key.encrypted_for);
+                if sharee == file.owner() {
+                    continue;
+                }
+                let position = positions.entry(sharee).or_default();
+                *position = (*position).max(file.version).max(shared.version);
+            }
+        }
+
+        for (viewer, version) in &positions {
+            self.by_viewer
+                .entry(*viewer)
+                .or_default()
+                .insert((*version, *id));
+        }
+        self.latest = self.latest.max(file.version);
+        self.current.insert(*id, positions);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use lb_rs::model::access_info::{UserAccessInfo, UserAccessMode};
+    use lb_rs::model::account::Account;
+    use lb_rs::model::file_metadata::{FileMetadata, FileType};
+    use lb_rs::model::server_file::IntoServerFile;
+    use lb_rs::model::symkey;
+
+    #[test]
+    fn since_resumes_after_cursor() {
+        let account = Account::new("test".to_string(), "http://localhost".to_string());
+        let owner = Owner(account.public_key());
+        let mut index = VersionIndex::default();
+        let mut metas = HashMap::new();
+        let mut files = vec![];
+        for version in [1, 2, 2, 3] {
+            let file = FileMetadata::create_root(&account)
+                .unwrap()
+                .sign_with(&account)
+                .unwrap()
+                .add_time(version);
+            files.push((version, *file.id()));
+            metas.insert(*file.id(), file);
+        }
+        for (_, id) in &files {
+            index.record(&metas, &HashMap::new(), id);
+        }
+        files.sort();
+
+        let since = |after| index.since(&owner, 2, after).collect::<Vec<_>>();
+        assert_eq!(since(None), files[1..]);
+        assert_eq!(since(Some(files[1])), files[2..]);
+        assert!(since(Some(files[3])).is_empty());
+        // a cursor from before `version` doesn't reach back past it
+        assert_eq!(since(Some(files[0])), files[1..]);
+    }
+
+    #[test]
+    fn shared_files_are_listed_with_their_share() {
+        let account = Account::new("owner".to_string(), "http://localhost".to_string());
+        let sharee = Account::new("sharee".to_string(), "http://localhost".to_string());
+        let (owner_pk, sharee_pk) = (account.public_key(), sharee.public_key());
+
+        let root = FileMetadata::create_root(&account).unwrap();
+        let key = symkey::generate_key();
+        let mut folder = FileMetadata::create(
+            Uuid::new_v4(),
+            key,
+            &owner_pk,
+            root.id,
+            &key,
+            "folder",
+            FileType::Folder,
+        )
+        .unwrap();
+        let doc = FileMetadata::create(
+            Uuid::new_v4(),
+            key,
+            &owner_pk,
+            folder.id,
+            &key,
+            "doc",
+            FileType::Document,
+        )
+        .unwrap();
+        let (root_id, folder_id, doc_id) = (root.id, folder.id, doc.id);
+
+        let mut metas = HashMap::new();
+        let children = HashMap::from([
+            (root_id, HashSet::from([folder_id])),
+            (folder_id, HashSet::from([doc_id])),
+        ]);
+        let mut record = |index: &mut VersionIndex, file: FileMetadata, version| {
+            let id = file.id;
+            metas.insert(id, file.sign_with(&account).unwrap().add_time(version));
+            index.record(&metas, &children, &id);
+        };
+
+        let mut index = VersionIndex::default();
+        record(&mut index, root, 1);
+        record(&mut index, folder.clone(), 2);
+        record(&mut index, doc.clone(), 3);
+        assert!(index.since(&Owner(sharee_pk), 0, None).next().is_none());
+
+        folder.user_access_keys.push(
+            UserAccessInfo::encrypt(&account, &owner_pk, &sharee_pk, &key, UserAccessMode::Read)
+                .unwrap(),
+        );
+        record(&mut index, folder, 10);
+        let since = |index: &VersionIndex, viewer, version| {
+            index
+                .since(&Owner(viewer), version, None)
+                .collect::<Vec<_>>()
+        };
+        // the document hasn't changed, but the sharee hasn't seen it yet
+        assert_eq!(since(&index, owner_pk, 5), vec![(10, folder_id)]);
+        let mut shared = since(&index, sharee_pk, 5);
+        shared.sort_by_key(|(_, id)| *id);
+        let mut expected = vec![(10, folder_id), (10, doc_id)];
+        expected.sort_by_key(|(_, id)| *id);
+        assert_eq!(shared, expected);
+
+        record(&mut index, doc, 12);
+        assert_eq!(since(&index, sharee_pk, 11), vec![(12, doc_id)]);
+        assert_eq!(index.latest(), 12);
+    }
+
+    #[test]
+    fn deleting_an_unshared_folder_drops_shares_inside_it() {
+        let account = Account::new("owner".to_string(), "http://localhost".to_string());
+        let sharee = Account::new("sharee".to_string(), "http://localhost".to_string());
+        let (owner_pk, sharee_pk) = (account.public_key(), sharee.public_key());
+
+        let root = FileMetadata::create_root(&account).unwrap();
+        let key = symkey::generate_key();
+        let mut folder = FileMetadata::create(
+            Uuid::new_v4(),
+            key,
+            &owner_pk,
+            root.id,
+            &key,
+            "folder",
+            FileType::Folder,
+        )
+        .unwrap();
+        let mut doc = FileMetadata::create(
+            Uuid::new_v4(),
+            key,
+            &owner_pk,
+            folder.id,
+            &key,
+            "doc",
+            FileType::Document,
+        )
+        .unwrap();
+        doc.user_access_keys.push(
+            UserAccessInfo::encrypt(&account, &owner_pk, &sharee_pk, &key, UserAccessMode::Read)
+                .unwrap(),
+        );
+        let (root_id, folder_id, doc_id) = (root.id, folder.id, doc.id);
+
+        let mut metas = HashMap::new();
+        let children = HashMap::from([
+            (root_id, HashSet::from([folder_id])),
+            (folder_id, HashSet::from([doc_id])),
+        ]);
+        let mut record = |index: &mut VersionIndex, file: FileMetadata, version| {
+            let id = file.id;
+            metas.insert(id, file.sign_with(&account).unwrap().add_time(version));
+            index.record(&metas, &children, &id);
+        };
+
+        let mut index = VersionIndex::default();
+        record(&mut index, root, 1);
+        record(&mut index, folder.clone(), 2);
+        record(&mut index, doc, 3);
+        let since =
+            |index: &VersionIndex| index.since(&Owner(sharee_pk), 0, None).collect::<Vec<_>>();
+        assert_eq!(since(&index), vec![(3, doc_id)]);
+
+        folder.is_deleted = true;
+        record(&mut index, folder, 4);
+        assert!(since(&index).is_empty());
+    }
+}
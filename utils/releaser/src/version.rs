@@ -68,6 +68,7 @@ fn handle_cargo_tomls(version: &str) {
         "clients/linux",
         "clients/windows",
         "clients/admin",
+        "clients/git-remote-lockbook",
         "utils/dev-tool",
         "utils/releaser",
         "utils/winstaller",